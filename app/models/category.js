@@ -6,6 +6,7 @@ export default class Category extends Model {
   @attr description;
   @attr('date') created_at;
   @attr crates_cnt;
+  @attr subtree_crates_cnt;
 
   @attr subcategories;
   @attr parent_categories;
@@ -64,6 +64,22 @@ impl Bucket {
             .map_err(Into::into)
     }
 
+    pub fn get(&self, client: &Client, path: &str) -> Result<Response, Error> {
+        let path = path.strip_prefix('/').unwrap_or(path);
+        let date = Utc::now().to_rfc2822();
+        let auth = self.auth("GET", &date, path, "", "");
+        let url = self.url(path);
+
+        client
+            .get(url)
+            .header(header::DATE, date)
+            .header(header::AUTHORIZATION, auth)
+            .timeout(Duration::from_secs(30))
+            .send()?
+            .error_for_status()
+            .map_err(Into::into)
+    }
+
     pub fn delete(&self, client: &Client, path: &str) -> Result<Response, Error> {
         let path = path.strip_prefix('/').unwrap_or(path);
         let date = Utc::now().to_rfc2822();
@@ -79,6 +95,53 @@ impl Bucket {
             .map_err(Into::into)
     }
 
+    /// Lists the objects whose keys start with `prefix`, using the `ListObjectsV2` API.
+    ///
+    /// The response body is the XML document of the API, which contains at most 1000 objects.
+    /// If it is truncated, the `NextContinuationToken` from it can be passed as
+    /// `continuation_token` to list the following objects.
+    pub fn list(
+        &self,
+        client: &Client,
+        prefix: &str,
+        continuation_token: Option<&str>,
+    ) -> Result<Response, Error> {
+        let date = Utc::now().to_rfc2822();
+        let auth = self.auth("GET", &date, "", "", "");
+        let url = self.url("");
+
+        let mut query = vec![("list-type", "2"), ("prefix", prefix)];
+        if let Some(continuation_token) = continuation_token {
+            query.push(("continuation-token", continuation_token));
+        }
+
+        client
+            .get(url)
+            .query(&query)
+            .header(header::DATE, date)
+            .header(header::AUTHORIZATION, auth)
+            .timeout(Duration::from_secs(60))
+            .send()?
+            .error_for_status()
+            .map_err(Into::into)
+    }
+
+    /// Checks that the bucket exists and is accessible with the configured credentials.
+    pub fn head_bucket(&self, client: &Client) -> Result<Response, Error> {
+        let date = Utc::now().to_rfc2822();
+        let auth = self.auth("HEAD", &date, "", "", "");
+        let url = self.url("");
+
+        client
+            .head(url)
+            .header(header::DATE, date)
+            .header(header::AUTHORIZATION, auth)
+            .timeout(Duration::from_secs(5))
+            .send()?
+            .error_for_status()
+            .map_err(Into::into)
+    }
+
     pub fn host(&self) -> String {
         format!(
             "{}.s3{}.amazonaws.com",
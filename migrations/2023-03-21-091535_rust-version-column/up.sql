@@ -0,0 +1,2 @@
+ALTER TABLE versions
+    ADD COLUMN rust_version VARCHAR NULL;
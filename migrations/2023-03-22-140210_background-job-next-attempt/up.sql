@@ -0,0 +1,9 @@
+-- A `NULL` value means that the job has exhausted its retries and will not be
+-- run again.
+ALTER TABLE background_jobs
+    ADD COLUMN next_attempt_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP;
+
+-- Preserve the schedule of jobs that have previously failed.
+UPDATE background_jobs
+    SET next_attempt_at = last_retry + INTERVAL '1 minute' * power(2, retries)
+    WHERE retries > 0;
@@ -0,0 +1,6 @@
+-- Jobs with the same key do the same work, so enqueueing a job while another
+-- one with the same key is still waiting to run is a no-op.
+ALTER TABLE background_jobs
+    ADD COLUMN dedup_key TEXT NULL;
+
+CREATE INDEX background_jobs_dedup_key_index ON background_jobs (dedup_key);
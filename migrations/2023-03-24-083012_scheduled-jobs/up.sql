@@ -0,0 +1,6 @@
+-- Tracks when each recurring background job was last enqueued, so that
+-- restarting the background worker doesn't enqueue it twice or skip it.
+CREATE TABLE scheduled_jobs (
+    name VARCHAR PRIMARY KEY,
+    last_run_at TIMESTAMP NOT NULL
+);
@@ -0,0 +1,2 @@
+ALTER TABLE api_tokens
+    ADD COLUMN expired_at TIMESTAMP NULL;
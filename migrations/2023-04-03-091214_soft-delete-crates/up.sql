@@ -0,0 +1,5 @@
+-- A non-`NULL` value means that the crate was deleted by its owner and will be
+-- removed permanently once the grace period has passed.
+ALTER TABLE crates ADD COLUMN deleted_at TIMESTAMP;
+
+CREATE INDEX index_crates_deleted_at ON crates (deleted_at) WHERE deleted_at IS NOT NULL;
@@ -0,0 +1,12 @@
+CREATE TABLE crate_webhooks (
+    id SERIAL PRIMARY KEY,
+    crate_id INTEGER NOT NULL REFERENCES crates (id) ON DELETE CASCADE,
+    url VARCHAR NOT NULL,
+    secret VARCHAR NOT NULL,
+    -- A bitmask of the events that the webhook is subscribed to, see
+    -- `models::crate_webhook::WebhookEvent`.
+    events INTEGER NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX index_crate_webhooks_crate_id ON crate_webhooks (crate_id);
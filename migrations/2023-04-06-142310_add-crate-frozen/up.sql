@@ -0,0 +1,3 @@
+-- Frozen crates remain visible, but can't be published to, yanked or deleted
+-- until an admin unfreezes them, e.g. while they are under legal review.
+ALTER TABLE crates ADD COLUMN crate_frozen BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,14 @@
+-- The responses to requests with an `Idempotency-Key` header, which are replayed when a client
+-- retries the request with the same key. Rows older than the configured expiration are ignored
+-- and may be deleted at any time.
+CREATE TABLE idempotency_keys (
+    user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    key VARCHAR NOT NULL,
+    request VARCHAR NOT NULL,
+    response_status INTEGER NOT NULL,
+    response_body JSONB NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT now(),
+    PRIMARY KEY (user_id, key)
+);
+
+CREATE INDEX index_idempotency_keys_created_at ON idempotency_keys (created_at);
@@ -0,0 +1,8 @@
+-- Translations of `categories.description`. The description in `categories`
+-- is the English one, which is used for locales without a translation.
+CREATE TABLE category_descriptions (
+    category_id INTEGER NOT NULL REFERENCES categories (id) ON DELETE CASCADE,
+    locale VARCHAR NOT NULL CHECK (locale = lower(locale)),
+    description VARCHAR NOT NULL,
+    PRIMARY KEY (category_id, locale)
+);
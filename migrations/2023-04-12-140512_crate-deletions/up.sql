@@ -0,0 +1,12 @@
+-- A record of every crate that was deleted by its owners, which is kept after the crate is
+-- removed permanently so that admins can review the deletions.
+CREATE TABLE crate_deletions (
+    id SERIAL PRIMARY KEY,
+    crate_name VARCHAR NOT NULL,
+    deleted_by INTEGER REFERENCES users (id) ON DELETE SET NULL,
+    deleted_at TIMESTAMP NOT NULL DEFAULT now(),
+    -- `owner` if a user owner deleted the crate, `team` if a team member deleted a new crate
+    path VARCHAR NOT NULL
+);
+
+CREATE INDEX index_crate_deletions_deleted_at ON crate_deletions (deleted_at);
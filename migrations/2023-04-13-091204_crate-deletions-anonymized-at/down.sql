@@ -0,0 +1 @@
+ALTER TABLE crate_deletions DROP COLUMN anonymized_at;
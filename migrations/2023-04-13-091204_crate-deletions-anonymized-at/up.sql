@@ -0,0 +1,3 @@
+-- When the user that deleted the crate was removed from the record because the retention period
+-- of the audit trail has passed
+ALTER TABLE crate_deletions ADD COLUMN anonymized_at TIMESTAMP;
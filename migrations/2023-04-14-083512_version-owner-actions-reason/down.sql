@@ -0,0 +1 @@
+ALTER TABLE version_owner_actions DROP COLUMN reason;
@@ -0,0 +1,2 @@
+-- Why an admin yanked a version of a crate that they don't own
+ALTER TABLE version_owner_actions ADD COLUMN reason VARCHAR;
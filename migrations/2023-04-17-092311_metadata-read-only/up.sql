@@ -0,0 +1,3 @@
+-- Whether an admin put crates.io into read-only mode, which is shared by all instances of the
+-- application
+ALTER TABLE metadata ADD COLUMN read_only BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1 @@
+ALTER TABLE crate_owner_invitations DROP COLUMN is_transfer;
@@ -0,0 +1,3 @@
+-- Whether accepting the invitation also removes the user who sent it from the owners of the
+-- crate, as requested through `POST /api/v1/crates/:crate_id/transfer`
+ALTER TABLE crate_owner_invitations ADD COLUMN is_transfer BOOLEAN NOT NULL DEFAULT FALSE;
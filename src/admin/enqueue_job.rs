@@ -23,6 +23,38 @@ pub enum Command {
         #[arg(long = "dry-run")]
         dry_run: bool,
     },
+    PruneExpiredTokens,
+    AnonymizeCrateDeletions,
+    PurgeExpiredInvitations,
+    SyncCratesFeeds,
+    VerifyDownloadTotals {
+        /// How many crates are checked by each job.
+        #[arg(long, default_value_t = 1000)]
+        batch_size: i64,
+        /// Differences of up to this many downloads are ignored.
+        #[arg(long, default_value_t = 0)]
+        threshold: i64,
+        /// Correct the download counts of crates that don't match their versions.
+        #[arg(long)]
+        fix: bool,
+    },
+    SweepOrphanedStorage {
+        /// Files that were modified less than this many hours ago are kept, since they might
+        /// belong to a version that is being published.
+        #[arg(long, default_value_t = 24)]
+        min_age_hours: i64,
+        /// Only log the orphaned files instead of removing them.
+        #[arg(long = "dry-run")]
+        dry_run: bool,
+    },
+    VerifyIndexConsistency {
+        /// How many crates are checked by each job.
+        #[arg(long, default_value_t = 1000)]
+        batch_size: i64,
+        /// Resync the index files of crates that don't match the database.
+        #[arg(long)]
+        fix: bool,
+    },
 }
 
 pub fn run(command: Command) -> Result<()> {
@@ -51,5 +83,21 @@ pub fn run(command: Command) -> Result<()> {
         Command::DailyDbMaintenance => Ok(worker::daily_db_maintenance().enqueue(conn)?),
         Command::SquashIndex => Ok(worker::squash_index().enqueue(conn)?),
         Command::NormalizeIndex { dry_run } => Ok(worker::normalize_index(dry_run).enqueue(conn)?),
+        Command::PruneExpiredTokens => Ok(worker::prune_expired_tokens().enqueue(conn)?),
+        Command::AnonymizeCrateDeletions => Ok(worker::anonymize_crate_deletions().enqueue(conn)?),
+        Command::PurgeExpiredInvitations => Ok(worker::purge_expired_invitations().enqueue(conn)?),
+        Command::SyncCratesFeeds => Ok(worker::sync_crates_feeds().enqueue(conn)?),
+        Command::VerifyDownloadTotals {
+            batch_size,
+            threshold,
+            fix,
+        } => Ok(worker::verify_download_totals(batch_size, threshold, fix).enqueue(conn)?),
+        Command::SweepOrphanedStorage {
+            min_age_hours,
+            dry_run,
+        } => Ok(worker::sweep_orphaned_storage(min_age_hours, dry_run).enqueue(conn)?),
+        Command::VerifyIndexConsistency { batch_size, fix } => {
+            Ok(worker::verify_index_consistency(batch_size, fix).enqueue(conn)?)
+        }
     }
 }
@@ -145,7 +145,7 @@ fn get_readme(
 
     let location = match uploader {
         Uploader::S3 { .. } => location,
-        Uploader::Local => format!("http://localhost:8888/{location}"),
+        Uploader::Local { .. } => format!("http://localhost:8888/{location}"),
     };
 
     let mut extra_headers = header::HeaderMap::new();
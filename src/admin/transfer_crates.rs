@@ -66,9 +66,10 @@ fn transfer(opts: Opts, conn: &mut PgConnection) {
         .load(conn)
         .unwrap();
 
+    let crate_ids = crates.iter().map(|krate| krate.id).collect::<Vec<_>>();
+    let owners = Crate::owners_for_many(&crate_ids, conn).unwrap();
     for krate in crates {
-        let owners = krate.owners(conn).unwrap();
-        if owners.len() != 1 {
+        if owners[&krate.id].len() != 1 {
             println!("warning: not exactly one owner for {}", krate.name);
         }
     }
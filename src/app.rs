@@ -4,16 +4,21 @@ use crate::db::{ConnectionConfig, DieselPool, DieselPooledConn, PoolError};
 use crate::{config, Env};
 use std::ops::Deref;
 use std::sync::atomic::AtomicUsize;
+use std::time::Instant;
 use std::{sync::Arc, time::Duration};
 
 use crate::downloads_counter::DownloadsCounter;
 use crate::email::Emails;
 use crate::github::{GitHubClient, RealGitHubClient};
 use crate::metrics::{InstanceMetrics, ServiceMetrics};
+use crate::rate_limiter::RateLimiter;
+use crate::schema::metadata;
 use axum::extract::{FromRef, FromRequestParts, State};
+use diesel::prelude::*;
 use diesel::r2d2;
 use moka::future::{Cache, CacheBuilder};
 use oauth2::basic::BasicClient;
+use parking_lot::Mutex;
 use reqwest::blocking::Client;
 use scheduled_thread_pool::ScheduledThreadPool;
 
@@ -45,7 +50,7 @@ pub struct App {
     pub downloads_counter: DownloadsCounter,
 
     /// Backend used to send emails
-    pub emails: Emails,
+    pub emails: Arc<Emails>,
 
     /// Metrics related to the service as a whole
     pub service_metrics: ServiceMetrics,
@@ -65,8 +70,18 @@ pub struct App {
 
     /// In-flight request counters for the `balance_capacity` middleware.
     pub balance_capacity: BalanceCapacityState,
+
+    /// Limits how often users can perform certain actions
+    pub rate_limiter: RateLimiter,
+
+    /// The last known value of the read-only mode that admins can enable through the admin API,
+    /// and when it was loaded from the database
+    admin_read_only_mode: Mutex<Option<(bool, Instant)>>,
 }
 
+/// How long each instance uses the read-only mode of the admins before loading it again.
+const ADMIN_READ_ONLY_MODE_CACHE_TTL: Duration = Duration::from_secs(5);
+
 impl App {
     /// Creates a new `App` with a given `Config` and an optional HTTP `Client`
     ///
@@ -182,12 +197,14 @@ impl App {
             github_oauth,
             version_id_cacher,
             downloads_counter: DownloadsCounter::new(),
-            emails: Emails::from_environment(&config),
+            emails: Arc::new(Emails::from_environment(&config)),
             service_metrics: ServiceMetrics::new().expect("could not initialize service metrics"),
             instance_metrics,
             http_client,
             fastboot_client,
             balance_capacity: Default::default(),
+            rate_limiter: RateLimiter::new(config.rate_limiter.clone()),
+            admin_read_only_mode: Mutex::new(None),
             config,
         }
     }
@@ -207,6 +224,59 @@ impl App {
             .expect("No HTTP client is configured.  In tests, use `TestApp::with_proxy()`.")
     }
 
+    /// Returns whether the application is in read-only mode, either because all database pools
+    /// are configured to be read-only, or because an admin enabled it.
+    ///
+    /// This can query the database, so it must not be called from async code.
+    pub fn is_read_only(&self) -> bool {
+        self.config.db.are_all_read_only() || self.is_admin_read_only()
+    }
+
+    /// Returns whether an admin enabled read-only mode.
+    ///
+    /// The flag is stored in the `metadata` table, so that it applies to all instances and
+    /// survives restarts, and is cached for a few seconds. If it can't be loaded, e.g. because
+    /// the database is unavailable, the last known value is used and cached just the same, so
+    /// that an unavailable database isn't queried on every request.
+    pub fn is_admin_read_only(&self) -> bool {
+        // The lock isn't held while the flag is loaded, so that concurrent requests don't wait
+        // for each other's database connections
+        let cached = *self.admin_read_only_mode.lock();
+        if let Some((read_only, loaded_at)) = cached {
+            if loaded_at.elapsed() < ADMIN_READ_ONLY_MODE_CACHE_TTL {
+                return read_only;
+            }
+        }
+
+        let started_at = Instant::now();
+        let loaded = self.db_read_prefer_primary().ok().and_then(|mut conn| {
+            metadata::table
+                .select(metadata::read_only)
+                .first(&mut *conn)
+                .ok()
+        });
+        let read_only = loaded.unwrap_or_else(|| cached.map_or(false, |(read_only, _)| read_only));
+
+        // Don't overwrite a value that was set by `set_admin_read_only` in the meantime
+        let mut cached = self.admin_read_only_mode.lock();
+        match *cached {
+            Some((read_only, loaded_at)) if loaded_at > started_at => read_only,
+            _ => {
+                *cached = Some((read_only, Instant::now()));
+                read_only
+            }
+        }
+    }
+
+    /// Enables or disables the read-only mode of the admins for all instances.
+    pub fn set_admin_read_only(&self, conn: &mut PgConnection, read_only: bool) -> QueryResult<()> {
+        diesel::update(metadata::table)
+            .set(metadata::read_only.eq(read_only))
+            .execute(conn)?;
+        *self.admin_read_only_mode.lock() = Some((read_only, Instant::now()));
+        Ok(())
+    }
+
     /// A unique key to generate signed cookies
     pub fn session_key(&self) -> &cookie::Key {
         &self.config.session_key
@@ -5,53 +5,119 @@ use crate::middleware::session::RequestSession;
 use crate::models::token::{CrateScope, EndpointScope};
 use crate::models::{ApiToken, User};
 use crate::util::errors::{
-    account_locked, forbidden, internal, AppError, AppResult, InsecurelyGeneratedTokenRevoked,
+    account_locked, forbidden, internal, recent_auth_required, token_only, website_only, AppError,
+    AppResult, BoxedAppError, ExpiredApiToken, InsecurelyGeneratedTokenRevoked,
 };
-use chrono::Utc;
+use chrono::{Duration, NaiveDateTime, Utc};
 use diesel::PgConnection;
 use http::header;
 
 #[derive(Debug, Clone)]
 pub struct AuthCheck {
+    allow_cookie: bool,
     allow_token: bool,
     endpoint_scope: Option<EndpointScope>,
     crate_name: Option<String>,
+    max_auth_age: Option<Duration>,
+    require_admin: bool,
 }
 
 impl AuthCheck {
+    /// Same as `cookie_or_token`.
     #[must_use]
     // #[must_use] can't be applied in the `Default` trait impl
     #[allow(clippy::should_implement_trait)]
     pub fn default() -> Self {
+        Self::cookie_or_token()
+    }
+
+    /// Allows both the session cookie of the website and API tokens.
+    #[must_use]
+    pub fn cookie_or_token() -> Self {
         Self {
+            allow_cookie: true,
             allow_token: true,
             endpoint_scope: None,
             crate_name: None,
+            max_auth_age: None,
+            require_admin: false,
         }
     }
 
+    /// Only allows the session cookie of the website, API tokens are rejected.
     #[must_use]
     pub fn only_cookie() -> Self {
         Self {
+            allow_cookie: true,
             allow_token: false,
             endpoint_scope: None,
             crate_name: None,
+            max_auth_age: None,
+            require_admin: false,
+        }
+    }
+
+    /// Only allows API tokens, the session cookie of the website is rejected.
+    ///
+    /// Requests with both are still authenticated by their cookie, like for the other modes.
+    #[must_use]
+    pub fn only_token() -> Self {
+        Self {
+            allow_cookie: false,
+            allow_token: true,
+            endpoint_scope: None,
+            crate_name: None,
+            max_auth_age: None,
+            require_admin: false,
         }
     }
 
     pub fn with_endpoint_scope(&self, endpoint_scope: EndpointScope) -> Self {
         Self {
+            allow_cookie: self.allow_cookie,
             allow_token: self.allow_token,
             endpoint_scope: Some(endpoint_scope),
             crate_name: self.crate_name.clone(),
+            max_auth_age: self.max_auth_age,
+            require_admin: self.require_admin,
         }
     }
 
     pub fn for_crate(&self, crate_name: &str) -> Self {
         Self {
+            allow_cookie: self.allow_cookie,
             allow_token: self.allow_token,
             endpoint_scope: self.endpoint_scope,
             crate_name: Some(crate_name.to_string()),
+            max_auth_age: self.max_auth_age,
+            require_admin: self.require_admin,
+        }
+    }
+
+    /// Requires cookie sessions to have been established within `max_age`, so that users have to
+    /// log in again before performing particularly dangerous actions.
+    ///
+    /// This does not affect API tokens, which have no session.
+    pub fn require_recent_auth(&self, max_age: Duration) -> Self {
+        Self {
+            allow_cookie: self.allow_cookie,
+            allow_token: self.allow_token,
+            endpoint_scope: self.endpoint_scope,
+            crate_name: self.crate_name.clone(),
+            max_auth_age: Some(max_age),
+            require_admin: self.require_admin,
+        }
+    }
+
+    /// Only allows users that are crates.io administrators.
+    pub fn require_admin(&self) -> Self {
+        Self {
+            allow_cookie: self.allow_cookie,
+            allow_token: self.allow_token,
+            endpoint_scope: self.endpoint_scope,
+            crate_name: self.crate_name.clone(),
+            max_auth_age: self.max_auth_age,
+            require_admin: true,
         }
     }
 
@@ -60,23 +126,78 @@ impl AuthCheck {
         request: &T,
         conn: &mut PgConnection,
     ) -> AppResult<Authentication> {
+        self.check_inner(request, conn)
+            .map_err(AuthError::into_app_error)
+    }
+
+    /// Like `check()`, but returns `None` instead of an error if the request isn't authenticated
+    /// in a way that this check allows.
+    ///
+    /// Failures that aren't caused by the credentials of the request, like database errors, are
+    /// still returned.
+    pub fn check_optional<T: RequestPartsExt>(
+        &self,
+        request: &T,
+        conn: &mut PgConnection,
+    ) -> AppResult<Option<Authentication>> {
+        match self.check_inner(request, conn) {
+            Ok(auth) => Ok(Some(auth)),
+            Err(AuthError::Rejected(_)) => Ok(None),
+            Err(AuthError::Failed(error)) => Err(error),
+        }
+    }
+
+    fn check_inner<T: RequestPartsExt>(
+        &self,
+        request: &T,
+        conn: &mut PgConnection,
+    ) -> Result<Authentication, AuthError> {
         let auth = authenticate(request, conn)?;
 
+        if let Authentication::Cookie(cookie) = &auth {
+            if !self.allow_cookie {
+                let error_message = "Cookie authentication was explicitly disallowed for this API";
+                return Err(AuthError::Rejected(
+                    internal(error_message).chain(token_only()),
+                ));
+            }
+
+            if !self.auth_age_matches(cookie.authenticated_at, Utc::now().naive_utc()) {
+                let error_message = "Session is not recent enough";
+                return Err(AuthError::Rejected(
+                    internal(error_message).chain(recent_auth_required()),
+                ));
+            }
+        }
+
+        if self.require_admin && !auth.user().is_admin {
+            let error_message = "User is not an admin";
+            return Err(AuthError::Rejected(
+                internal(error_message).chain(forbidden()),
+            ));
+        }
+
         if let Some(token) = auth.api_token() {
             if !self.allow_token {
                 let error_message =
                     "API Token authentication was explicitly disallowed for this API";
-                return Err(internal(error_message).chain(forbidden()));
+                return Err(AuthError::Rejected(
+                    internal(error_message).chain(website_only()),
+                ));
             }
 
             if !self.endpoint_scope_matches(token.endpoint_scopes.as_ref()) {
                 let error_message = "Endpoint scope mismatch";
-                return Err(internal(error_message).chain(forbidden()));
+                return Err(AuthError::Rejected(
+                    internal(error_message).chain(forbidden()),
+                ));
             }
 
             if !self.crate_scope_matches(token.crate_scopes.as_ref()) {
                 let error_message = "Crate scope mismatch";
-                return Err(internal(error_message).chain(forbidden()));
+                return Err(AuthError::Rejected(
+                    internal(error_message).chain(forbidden()),
+                ));
             }
         }
 
@@ -96,6 +217,22 @@ impl AuthCheck {
         }
     }
 
+    fn auth_age_matches(
+        &self,
+        authenticated_at: Option<NaiveDateTime>,
+        now: NaiveDateTime,
+    ) -> bool {
+        match (self.max_auth_age, authenticated_at) {
+            // The endpoint does not care about the age of the session.
+            (None, _) => true,
+
+            // The session was created before we started recording the login time.
+            (Some(_), None) => false,
+
+            (Some(max_age), Some(authenticated_at)) => now - authenticated_at <= max_age,
+        }
+    }
+
     fn crate_scope_matches(&self, token_scopes: Option<&Vec<CrateScope>>) -> bool {
         match (&token_scopes, &self.crate_name) {
             // The token is a legacy token.
@@ -115,6 +252,34 @@ impl AuthCheck {
     }
 }
 
+/// Why `AuthCheck::check_inner()` failed, so that `AuthCheck::check_optional()` can tell missing
+/// or unacceptable credentials apart from other failures.
+enum AuthError {
+    /// The request isn't authenticated, or not in a way that the check allows.
+    Rejected(BoxedAppError),
+    /// Something else went wrong while authenticating the request, like a database query.
+    Failed(BoxedAppError),
+}
+
+impl AuthError {
+    fn into_app_error(self) -> BoxedAppError {
+        match self {
+            AuthError::Rejected(error) | AuthError::Failed(error) => error,
+        }
+    }
+
+    /// Users that can't be found are rejected, any other failure to load them is returned as is.
+    fn user_not_loaded(error: diesel::result::Error, message: &'static str) -> Self {
+        let not_found = matches!(error, diesel::result::Error::NotFound);
+        let error = error.chain(internal(message));
+        if not_found {
+            AuthError::Rejected(error)
+        } else {
+            AuthError::Failed(error)
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum Authentication {
     Cookie(CookieAuthentication),
@@ -124,6 +289,8 @@ pub enum Authentication {
 #[derive(Debug)]
 pub struct CookieAuthentication {
     user: User,
+    /// When the user logged in, or `None` for sessions that predate recording it.
+    authenticated_at: Option<NaiveDateTime>,
 }
 
 #[derive(Debug)]
@@ -159,7 +326,7 @@ impl Authentication {
 fn authenticate_via_cookie<T: RequestPartsExt>(
     req: &T,
     conn: &mut PgConnection,
-) -> AppResult<Option<CookieAuthentication>> {
+) -> Result<Option<CookieAuthentication>, AuthError> {
     let user_id_from_session = req
         .session()
         .get("user_id")
@@ -167,20 +334,30 @@ fn authenticate_via_cookie<T: RequestPartsExt>(
 
     let Some(id) = user_id_from_session else { return Ok(None) };
 
-    let user = User::find(conn, id)
-        .map_err(|err| err.chain(internal("user_id from cookie not found in database")))?;
+    let user = User::find(conn, id).map_err(|err| {
+        AuthError::user_not_loaded(err, "user_id from cookie not found in database")
+    })?;
 
-    ensure_not_locked(&user)?;
+    ensure_not_locked(&user).map_err(AuthError::Rejected)?;
 
     req.request_log().add("uid", id);
 
-    Ok(Some(CookieAuthentication { user }))
+    let authenticated_at = req
+        .session()
+        .get("authenticated_at")
+        .and_then(|s| s.parse::<i64>().ok())
+        .and_then(|timestamp| NaiveDateTime::from_timestamp_opt(timestamp, 0));
+
+    Ok(Some(CookieAuthentication {
+        user,
+        authenticated_at,
+    }))
 }
 
 fn authenticate_via_token<T: RequestPartsExt>(
     req: &T,
     conn: &mut PgConnection,
-) -> AppResult<Option<TokenAuthentication>> {
+) -> Result<Option<TokenAuthentication>, AuthError> {
     let maybe_authorization = req
         .headers()
         .get(header::AUTHORIZATION)
@@ -190,16 +367,21 @@ fn authenticate_via_token<T: RequestPartsExt>(
 
     let token = ApiToken::find_by_api_token(conn, header_value).map_err(|e| {
         if e.is::<InsecurelyGeneratedTokenRevoked>() {
-            e
+            AuthError::Rejected(e)
         } else {
-            e.chain(internal("invalid token")).chain(forbidden())
+            AuthError::Rejected(e.chain(internal("invalid token")).chain(forbidden()))
         }
     })?;
 
-    let user = User::find(conn, token.user_id)
-        .map_err(|err| err.chain(internal("user_id from token not found in database")))?;
+    if token.is_expired() {
+        return Err(AuthError::Rejected(ExpiredApiToken::boxed()));
+    }
 
-    ensure_not_locked(&user)?;
+    let user = User::find(conn, token.user_id).map_err(|err| {
+        AuthError::user_not_loaded(err, "user_id from token not found in database")
+    })?;
+
+    ensure_not_locked(&user).map_err(AuthError::Rejected)?;
 
     req.request_log().add("uid", token.user_id);
     req.request_log().add("tokenid", token.id);
@@ -207,8 +389,11 @@ fn authenticate_via_token<T: RequestPartsExt>(
     Ok(Some(TokenAuthentication { user, token }))
 }
 
-fn authenticate<T: RequestPartsExt>(req: &T, conn: &mut PgConnection) -> AppResult<Authentication> {
-    controllers::util::verify_origin(req)?;
+fn authenticate<T: RequestPartsExt>(
+    req: &T,
+    conn: &mut PgConnection,
+) -> Result<Authentication, AuthError> {
+    controllers::util::verify_origin(req).map_err(AuthError::Rejected)?;
 
     match authenticate_via_cookie(req, conn) {
         Ok(None) => {}
@@ -223,7 +408,8 @@ fn authenticate<T: RequestPartsExt>(req: &T, conn: &mut PgConnection) -> AppResu
     }
 
     // Unable to authenticate the user
-    return Err(internal("no cookie session or auth header found").chain(forbidden()));
+    let error = internal("no cookie session or auth header found").chain(forbidden());
+    Err(AuthError::Rejected(error))
 }
 
 fn ensure_not_locked(user: &User) -> AppResult<()> {
@@ -339,4 +525,19 @@ mod tests {
         assert!(!auth_check.crate_scope_matches(Some(&vec![cs("anyhow")])));
         assert!(!auth_check.crate_scope_matches(Some(&vec![cs("actix-*")])));
     }
+
+    #[test]
+    fn recent_auth() {
+        let now = Utc::now().naive_utc();
+        let auth_check = AuthCheck::only_cookie().require_recent_auth(Duration::minutes(10));
+
+        assert!(auth_check.auth_age_matches(Some(now), now));
+        assert!(auth_check.auth_age_matches(Some(now - Duration::minutes(10)), now));
+        assert!(!auth_check.auth_age_matches(Some(now - Duration::minutes(11)), now));
+        assert!(!auth_check.auth_age_matches(None, now));
+
+        let auth_check = AuthCheck::only_cookie();
+        assert!(auth_check.auth_age_matches(Some(now - Duration::days(30)), now));
+        assert!(auth_check.auth_age_matches(None, now));
+    }
 }
@@ -1,27 +1,54 @@
+use chrono::NaiveDateTime;
 use diesel::prelude::*;
 use diesel::r2d2::{ConnectionManager, PooledConnection};
+use hex::ToHex;
 use reqwest::blocking::Client;
+use sha2::{Digest, Sha256};
+use std::net::SocketAddr;
 use std::panic::AssertUnwindSafe;
 use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
 
+use crate::config::{FeedConfig, MirrorConfig};
 use crate::db::ConnectionPool;
+use crate::email::Emails;
+use crate::models::WebhookEvent;
 use crate::swirl::errors::EnqueueError;
-use crate::swirl::PerformError;
+use crate::swirl::{Backoff, PerformError, RetryPolicy};
 use crate::uploaders::Uploader;
+use crate::util::public_url;
 use crate::worker;
 use crate::worker::cloudfront::CloudFront;
 use cargo_registry_index::Repository;
+use url::{Host, Url};
 
 pub enum Job {
+    AnonymizeCrateDeletions,
     DailyDbMaintenance,
+    DeleteVersionFromStorage(DeleteVersionFromStorageJob),
+    DispatchCrateWebhook(DispatchCrateWebhookJob),
     DumpDb(DumpDbJob),
     IndexAddCrate(IndexAddCrateJob),
+    IndexRemoveCrate(IndexRemoveCrateJob),
     IndexSquash,
+    IndexSyncToGit(IndexSyncToGitJob),
     IndexSyncToHttp(IndexSyncToHttpJob),
     IndexUpdateYanked(IndexUpdateYankedJob),
     NormalizeIndex(NormalizeIndexJob),
+    NotifyMirrorOfDeletion(NotifyMirrorOfDeletionJob),
+    NotifyMirrorOfRestoration(NotifyMirrorOfRestorationJob),
+    PruneExpiredTokens,
+    PurgeDeletedCrates,
+    PurgeExpiredInvitations,
     RenderAndUploadReadme(RenderAndUploadReadmeJob),
+    SendOwnershipTransferEmails(SendOwnershipTransferEmailsJob),
+    SweepOrphanedStorage(SweepOrphanedStorageJob),
+    SyncCategoryFeed(SyncCategoryFeedJob),
+    SyncCratesFeeds,
+    SyncUserFeed(SyncUserFeedJob),
     UpdateDownloads,
+    VerifyDownloadTotals(VerifyDownloadTotalsJob),
+    VerifyIndexConsistency(VerifyIndexConsistencyJob),
 }
 
 /// Database state that is passed to `Job::perform()`.
@@ -39,52 +66,174 @@ pub(crate) struct PerformState<'a> {
 }
 
 impl Job {
+    const ANONYMIZE_CRATE_DELETIONS: &str = "anonymize_crate_deletions";
     const DAILY_DB_MAINTENANCE: &str = "daily_db_maintenance";
+    const DELETE_VERSION_FROM_STORAGE: &str = "delete_version_from_storage";
+    const DISPATCH_CRATE_WEBHOOK: &str = "dispatch_crate_webhook";
     const DUMP_DB: &str = "dump_db";
     const INDEX_ADD_CRATE: &str = "add_crate";
+    const INDEX_REMOVE_CRATE: &str = "remove_crate";
     const INDEX_SQUASH: &str = "squash_index";
+    const INDEX_SYNC_TO_GIT: &str = "sync_to_git_index";
     const INDEX_SYNC_TO_HTTP: &str = "update_crate_index";
     const INDEX_UPDATE_YANKED: &str = "sync_yanked";
     const NORMALIZE_INDEX: &str = "normalize_index";
+    const NOTIFY_MIRROR_OF_DELETION: &str = "notify_mirror_of_deletion";
+    const NOTIFY_MIRROR_OF_RESTORATION: &str = "notify_mirror_of_restoration";
+    const PRUNE_EXPIRED_TOKENS: &str = "prune_expired_tokens";
+    const PURGE_DELETED_CRATES: &str = "purge_deleted_crates";
+    const PURGE_EXPIRED_INVITATIONS: &str = "purge_expired_invitations";
     const RENDER_AND_UPLOAD_README: &str = "render_and_upload_readme";
+    const SEND_OWNERSHIP_TRANSFER_EMAILS: &str = "send_ownership_transfer_emails";
+    const SWEEP_ORPHANED_STORAGE: &str = "sweep_orphaned_storage";
+    const SYNC_CATEGORY_FEED: &str = "sync_category_feed";
+    const SYNC_CRATES_FEEDS: &str = "sync_crates_feeds";
+    const SYNC_USER_FEED: &str = "sync_user_feed";
     const UPDATE_DOWNLOADS: &str = "update_downloads";
+    const VERIFY_DOWNLOAD_TOTALS: &str = "verify_download_totals";
+    const VERIFY_INDEX_CONSISTENCY: &str = "verify_index_consistency";
 
     fn as_type_str(&self) -> &'static str {
         match self {
+            Job::AnonymizeCrateDeletions => Self::ANONYMIZE_CRATE_DELETIONS,
             Job::DailyDbMaintenance => Self::DAILY_DB_MAINTENANCE,
+            Job::DeleteVersionFromStorage(_) => Self::DELETE_VERSION_FROM_STORAGE,
+            Job::DispatchCrateWebhook(_) => Self::DISPATCH_CRATE_WEBHOOK,
             Job::DumpDb(_) => Self::DUMP_DB,
             Job::IndexAddCrate(_) => Self::INDEX_ADD_CRATE,
+            Job::IndexRemoveCrate(_) => Self::INDEX_REMOVE_CRATE,
             Job::IndexSquash => Self::INDEX_SQUASH,
+            Job::IndexSyncToGit(_) => Self::INDEX_SYNC_TO_GIT,
             Job::IndexSyncToHttp(_) => Self::INDEX_SYNC_TO_HTTP,
             Job::IndexUpdateYanked(_) => Self::INDEX_UPDATE_YANKED,
             Job::NormalizeIndex(_) => Self::NORMALIZE_INDEX,
+            Job::NotifyMirrorOfDeletion(_) => Self::NOTIFY_MIRROR_OF_DELETION,
+            Job::NotifyMirrorOfRestoration(_) => Self::NOTIFY_MIRROR_OF_RESTORATION,
+            Job::PruneExpiredTokens => Self::PRUNE_EXPIRED_TOKENS,
+            Job::PurgeDeletedCrates => Self::PURGE_DELETED_CRATES,
+            Job::PurgeExpiredInvitations => Self::PURGE_EXPIRED_INVITATIONS,
             Job::RenderAndUploadReadme(_) => Self::RENDER_AND_UPLOAD_README,
+            Job::SendOwnershipTransferEmails(_) => Self::SEND_OWNERSHIP_TRANSFER_EMAILS,
+            Job::SweepOrphanedStorage(_) => Self::SWEEP_ORPHANED_STORAGE,
+            Job::SyncCategoryFeed(_) => Self::SYNC_CATEGORY_FEED,
+            Job::SyncCratesFeeds => Self::SYNC_CRATES_FEEDS,
+            Job::SyncUserFeed(_) => Self::SYNC_USER_FEED,
             Job::UpdateDownloads => Self::UPDATE_DOWNLOADS,
+            Job::VerifyDownloadTotals(_) => Self::VERIFY_DOWNLOAD_TOTALS,
+            Job::VerifyIndexConsistency(_) => Self::VERIFY_INDEX_CONSISTENCY,
         }
     }
 
     fn to_value(&self) -> serde_json::Result<serde_json::Value> {
         match self {
+            Job::AnonymizeCrateDeletions => Ok(serde_json::Value::Null),
             Job::DailyDbMaintenance => Ok(serde_json::Value::Null),
+            Job::DeleteVersionFromStorage(inner) => serde_json::to_value(inner),
+            Job::DispatchCrateWebhook(inner) => serde_json::to_value(inner),
             Job::DumpDb(inner) => serde_json::to_value(inner),
             Job::IndexAddCrate(inner) => serde_json::to_value(inner),
+            Job::IndexRemoveCrate(inner) => serde_json::to_value(inner),
             Job::IndexSquash => Ok(serde_json::Value::Null),
+            Job::IndexSyncToGit(inner) => serde_json::to_value(inner),
             Job::IndexSyncToHttp(inner) => serde_json::to_value(inner),
             Job::IndexUpdateYanked(inner) => serde_json::to_value(inner),
             Job::NormalizeIndex(inner) => serde_json::to_value(inner),
+            Job::NotifyMirrorOfDeletion(inner) => serde_json::to_value(inner),
+            Job::NotifyMirrorOfRestoration(inner) => serde_json::to_value(inner),
+            Job::PruneExpiredTokens => Ok(serde_json::Value::Null),
+            Job::PurgeDeletedCrates => Ok(serde_json::Value::Null),
+            Job::PurgeExpiredInvitations => Ok(serde_json::Value::Null),
             Job::RenderAndUploadReadme(inner) => serde_json::to_value(inner),
+            Job::SendOwnershipTransferEmails(inner) => serde_json::to_value(inner),
+            Job::SweepOrphanedStorage(inner) => serde_json::to_value(inner),
+            Job::SyncCategoryFeed(inner) => serde_json::to_value(inner),
+            Job::SyncCratesFeeds => Ok(serde_json::Value::Null),
+            Job::SyncUserFeed(inner) => serde_json::to_value(inner),
             Job::UpdateDownloads => Ok(serde_json::Value::Null),
+            Job::VerifyDownloadTotals(inner) => serde_json::to_value(inner),
+            Job::VerifyIndexConsistency(inner) => serde_json::to_value(inner),
         }
     }
 
+    /// Returns how a failed job of the given type is retried.
+    ///
+    /// This is based on the job type instead of the job itself, since the job data might not be
+    /// deserializable anymore after a failure.
+    pub(super) fn retry_policy(job_type: &str) -> RetryPolicy {
+        match job_type {
+            // S3 blips are common, but hopefully short-lived.
+            Self::DELETE_VERSION_FROM_STORAGE => RetryPolicy {
+                max_retries: Some(5),
+                backoff: Backoff::Exponential {
+                    base: Duration::from_secs(30),
+                    jitter: true,
+                },
+            },
+            // Webhook receivers might be down for a while, but shouldn't be notified forever.
+            Self::DISPATCH_CRATE_WEBHOOK
+            | Self::NOTIFY_MIRROR_OF_DELETION
+            | Self::NOTIFY_MIRROR_OF_RESTORATION => RetryPolicy {
+                max_retries: Some(10),
+                backoff: Backoff::Exponential {
+                    base: Duration::from_secs(60),
+                    jitter: true,
+                },
+            },
+            _ => RetryPolicy::default(),
+        }
+    }
+
+    /// Returns the key used to coalesce jobs doing the same work.
+    ///
+    /// By default this is derived from the job type and its payload, so only identical jobs are
+    /// coalesced.
+    fn dedup_key(&self, job_data: &serde_json::Value) -> String {
+        match self {
+            Job::IndexSyncToHttp(inner) => format!("{}:{}", self.as_type_str(), inner.crate_name),
+            _ => {
+                let digest: String = Sha256::digest(job_data.to_string()).encode_hex();
+                format!("{}:{digest}", self.as_type_str())
+            }
+        }
+    }
+
+    /// Adds the job to the queue, unless a job with the same `dedup_key` is already waiting to
+    /// be run.
     pub fn enqueue(&self, conn: &mut PgConnection) -> Result<(), EnqueueError> {
+        self.enqueue_returning_id(conn).map(|_| ())
+    }
+
+    /// Like `enqueue()`, but returns the ID of the new job, or of the pending job it was
+    /// coalesced with.
+    pub fn enqueue_returning_id(&self, conn: &mut PgConnection) -> Result<i64, EnqueueError> {
         use crate::schema::background_jobs::dsl::*;
 
         let job_data = self.to_value()?;
-        diesel::insert_into(background_jobs)
-            .values((job_type.eq(self.as_type_str()), data.eq(job_data)))
-            .execute(conn)?;
-        Ok(())
+        let key = self.dedup_key(&job_data);
+
+        // Jobs that are currently running are locked by the runner and skipped here, since they
+        // might have already read the state that the new job is supposed to pick up.
+        let pending_id = background_jobs
+            .select(id)
+            .filter(dedup_key.eq(&key))
+            .filter(retries.eq(0))
+            .for_update()
+            .skip_locked()
+            .first::<i64>(conn)
+            .optional()?;
+        if let Some(pending_id) = pending_id {
+            return Ok(pending_id);
+        }
+
+        let job_id = diesel::insert_into(background_jobs)
+            .values((
+                job_type.eq(self.as_type_str()),
+                data.eq(job_data),
+                dedup_key.eq(key),
+            ))
+            .returning(id)
+            .get_result(conn)?;
+        Ok(job_id)
     }
 
     pub(super) fn from_value(
@@ -93,15 +242,36 @@ impl Job {
     ) -> Result<Self, PerformError> {
         use serde_json::from_value;
         Ok(match job_type {
+            Self::ANONYMIZE_CRATE_DELETIONS => Job::AnonymizeCrateDeletions,
             Self::DAILY_DB_MAINTENANCE => Job::DailyDbMaintenance,
+            Self::DELETE_VERSION_FROM_STORAGE => Job::DeleteVersionFromStorage(from_value(value)?),
+            Self::DISPATCH_CRATE_WEBHOOK => Job::DispatchCrateWebhook(from_value(value)?),
             Self::DUMP_DB => Job::DumpDb(from_value(value)?),
             Self::INDEX_ADD_CRATE => Job::IndexAddCrate(from_value(value)?),
+            Self::INDEX_REMOVE_CRATE => Job::IndexRemoveCrate(from_value(value)?),
             Self::INDEX_SQUASH => Job::IndexSquash,
+            Self::INDEX_SYNC_TO_GIT => Job::IndexSyncToGit(from_value(value)?),
             Self::INDEX_SYNC_TO_HTTP => Job::IndexSyncToHttp(from_value(value)?),
             Self::INDEX_UPDATE_YANKED => Job::IndexUpdateYanked(from_value(value)?),
             Self::NORMALIZE_INDEX => Job::NormalizeIndex(from_value(value)?),
+            Self::NOTIFY_MIRROR_OF_DELETION => Job::NotifyMirrorOfDeletion(from_value(value)?),
+            Self::NOTIFY_MIRROR_OF_RESTORATION => {
+                Job::NotifyMirrorOfRestoration(from_value(value)?)
+            }
+            Self::PRUNE_EXPIRED_TOKENS => Job::PruneExpiredTokens,
+            Self::PURGE_DELETED_CRATES => Job::PurgeDeletedCrates,
+            Self::PURGE_EXPIRED_INVITATIONS => Job::PurgeExpiredInvitations,
             Self::RENDER_AND_UPLOAD_README => Job::RenderAndUploadReadme(from_value(value)?),
+            Self::SEND_OWNERSHIP_TRANSFER_EMAILS => {
+                Job::SendOwnershipTransferEmails(from_value(value)?)
+            }
+            Self::SWEEP_ORPHANED_STORAGE => Job::SweepOrphanedStorage(from_value(value)?),
+            Self::SYNC_CATEGORY_FEED => Job::SyncCategoryFeed(from_value(value)?),
+            Self::SYNC_CRATES_FEEDS => Job::SyncCratesFeeds,
+            Self::SYNC_USER_FEED => Job::SyncUserFeed(from_value(value)?),
             Self::UPDATE_DOWNLOADS => Job::UpdateDownloads,
+            Self::VERIFY_DOWNLOAD_TOTALS => Job::VerifyDownloadTotals(from_value(value)?),
+            Self::VERIFY_INDEX_CONSISTENCY => Job::VerifyIndexConsistency(from_value(value)?),
             job_type => Err(PerformError::from(format!("Unknown job type {job_type}")))?,
         })
     }
@@ -116,17 +286,48 @@ impl Job {
             .as_ref()
             .expect("Application should configure a background runner environment");
         match self {
+            Job::AnonymizeCrateDeletions => worker::perform_anonymize_crate_deletions(env, conn),
             Job::DailyDbMaintenance => {
                 worker::perform_daily_db_maintenance(&mut *fresh_connection(pool)?)
             }
+            Job::DeleteVersionFromStorage(args) => {
+                worker::perform_delete_version_from_storage(env, &args.crate_name, &args.version)
+            }
+            Job::DispatchCrateWebhook(args) => worker::perform_dispatch_crate_webhook(
+                env,
+                conn,
+                args.webhook_id,
+                args.event,
+                &args.payload,
+            ),
             Job::DumpDb(args) => worker::perform_dump_db(env, args.database_url, args.target_name),
             Job::IndexAddCrate(args) => worker::perform_index_add_crate(env, conn, &args.krate),
+            Job::IndexRemoveCrate(args) => {
+                worker::perform_index_remove_crate(env, conn, &args.crate_name)
+            }
             Job::IndexSquash => worker::perform_index_squash(env),
-            Job::IndexSyncToHttp(args) => worker::perform_index_sync_to_http(env, args.crate_name),
+            Job::IndexSyncToGit(args) => {
+                worker::perform_index_sync_to_git(env, conn, &args.crate_name)
+            }
+            Job::IndexSyncToHttp(args) => {
+                worker::perform_index_sync_to_http(env, conn, args.crate_name)
+            }
             Job::IndexUpdateYanked(args) => {
                 worker::perform_index_update_yanked(env, conn, &args.krate, &args.version_num)
             }
             Job::NormalizeIndex(args) => worker::perform_normalize_index(env, args),
+            Job::NotifyMirrorOfDeletion(args) => worker::perform_notify_mirror_of_deletion(
+                env,
+                &args.crate_name,
+                args.deleted_at,
+                &args.reason,
+            ),
+            Job::NotifyMirrorOfRestoration(args) => {
+                worker::perform_notify_mirror_of_restoration(env, &args.crate_name, args.restored_at)
+            }
+            Job::PruneExpiredTokens => worker::perform_prune_expired_tokens(conn),
+            Job::PurgeDeletedCrates => worker::perform_purge_deleted_crates(env, conn),
+            Job::PurgeExpiredInvitations => worker::perform_purge_expired_invitations(env, conn),
             Job::RenderAndUploadReadme(args) => worker::perform_render_and_upload_readme(
                 conn,
                 env,
@@ -136,7 +337,28 @@ impl Job {
                 args.base_url.as_deref(),
                 args.pkg_path_in_vcs.as_deref(),
             ),
+            Job::SendOwnershipTransferEmails(args) => {
+                worker::perform_send_ownership_transfer_emails(
+                    env,
+                    conn,
+                    &args.crate_name,
+                    args.previous_owner_id,
+                    args.new_owner_id,
+                )
+            }
+            Job::SweepOrphanedStorage(args) => {
+                worker::perform_sweep_orphaned_storage(env, conn, &args)
+            }
+            Job::SyncCategoryFeed(args) => {
+                worker::perform_sync_category_feed(env, conn, &args.slug)
+            }
+            Job::SyncCratesFeeds => worker::perform_sync_crates_feeds(env, conn),
+            Job::SyncUserFeed(args) => worker::perform_sync_user_feed(env, conn, args.user_id),
             Job::UpdateDownloads => worker::perform_update_downloads(&mut *fresh_connection(pool)?),
+            Job::VerifyDownloadTotals(args) => worker::perform_verify_download_totals(conn, &args),
+            Job::VerifyIndexConsistency(args) => {
+                worker::perform_verify_index_consistency(env, conn, &args)
+            }
         }
     }
 }
@@ -156,6 +378,19 @@ fn fresh_connection(
     Ok(pool.get()?)
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct DeleteVersionFromStorageJob {
+    pub(super) crate_name: String,
+    pub(super) version: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DispatchCrateWebhookJob {
+    pub(super) webhook_id: i32,
+    pub(super) event: WebhookEvent,
+    pub(super) payload: serde_json::Value,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct DumpDbJob {
     pub(super) database_url: String,
@@ -167,6 +402,16 @@ pub struct IndexAddCrateJob {
     pub(super) krate: cargo_registry_index::Crate,
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct IndexRemoveCrateJob {
+    pub(super) crate_name: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct IndexSyncToGitJob {
+    pub(super) crate_name: String,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct IndexSyncToHttpJob {
     pub(super) crate_name: String,
@@ -183,6 +428,19 @@ pub struct NormalizeIndexJob {
     pub dry_run: bool,
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct NotifyMirrorOfDeletionJob {
+    pub(super) crate_name: String,
+    pub(super) deleted_at: NaiveDateTime,
+    pub(super) reason: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct NotifyMirrorOfRestorationJob {
+    pub(super) crate_name: String,
+    pub(super) restored_at: NaiveDateTime,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct RenderAndUploadReadmeJob {
     pub(super) version_id: i32,
@@ -192,11 +450,56 @@ pub struct RenderAndUploadReadmeJob {
     pub(super) pkg_path_in_vcs: Option<String>,
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct SendOwnershipTransferEmailsJob {
+    pub(super) crate_name: String,
+    pub(super) previous_owner_id: i32,
+    pub(super) new_owner_id: i32,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct SweepOrphanedStorageJob {
+    pub(super) min_age_hours: i64,
+    pub(super) dry_run: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SyncCategoryFeedJob {
+    pub(super) slug: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SyncUserFeedJob {
+    pub(super) user_id: i32,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct VerifyDownloadTotalsJob {
+    pub(super) after_crate_id: i32,
+    pub(super) batch_size: i64,
+    pub(super) threshold: i64,
+    pub(super) fix: bool,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct VerifyIndexConsistencyJob {
+    pub(super) after_crate_id: i32,
+    pub(super) batch_size: i64,
+    pub(super) fix: bool,
+}
+
 pub struct Environment {
     index: Arc<Mutex<Repository>>,
     pub uploader: Uploader,
     http_client: AssertUnwindSafe<Client>,
     cloudfront: Option<CloudFront>,
+    feeds: FeedConfig,
+    emails: Arc<Emails>,
+    crate_deletion_grace_period: Duration,
+    crate_deletion_audit_retention: Duration,
+    ownership_invitations_expiration_days: u64,
+    mirror: Option<MirrorConfig>,
+    send_webhooks_with_http_client: bool,
 }
 
 impl Clone for Environment {
@@ -206,39 +509,80 @@ impl Clone for Environment {
             uploader: self.uploader.clone(),
             http_client: AssertUnwindSafe(self.http_client.0.clone()),
             cloudfront: self.cloudfront.clone(),
+            feeds: self.feeds.clone(),
+            emails: self.emails.clone(),
+            crate_deletion_grace_period: self.crate_deletion_grace_period,
+            crate_deletion_audit_retention: self.crate_deletion_audit_retention,
+            ownership_invitations_expiration_days: self.ownership_invitations_expiration_days,
+            mirror: self.mirror.clone(),
+            send_webhooks_with_http_client: self.send_webhooks_with_http_client,
         }
     }
 }
 
 impl Environment {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         index: Repository,
         uploader: Uploader,
         http_client: Client,
         cloudfront: Option<CloudFront>,
+        feeds: FeedConfig,
+        emails: Arc<Emails>,
+        crate_deletion_grace_period: Duration,
+        crate_deletion_audit_retention: Duration,
+        ownership_invitations_expiration_days: u64,
+        mirror: Option<MirrorConfig>,
     ) -> Self {
         Self::new_shared(
             Arc::new(Mutex::new(index)),
             uploader,
             http_client,
             cloudfront,
+            feeds,
+            emails,
+            crate_deletion_grace_period,
+            crate_deletion_audit_retention,
+            ownership_invitations_expiration_days,
+            mirror,
         )
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn new_shared(
         index: Arc<Mutex<Repository>>,
         uploader: Uploader,
         http_client: Client,
         cloudfront: Option<CloudFront>,
+        feeds: FeedConfig,
+        emails: Arc<Emails>,
+        crate_deletion_grace_period: Duration,
+        crate_deletion_audit_retention: Duration,
+        ownership_invitations_expiration_days: u64,
+        mirror: Option<MirrorConfig>,
     ) -> Self {
         Self {
             index,
             uploader,
             http_client: AssertUnwindSafe(http_client),
             cloudfront,
+            feeds,
+            emails,
+            crate_deletion_grace_period,
+            crate_deletion_audit_retention,
+            ownership_invitations_expiration_days,
+            mirror,
+            send_webhooks_with_http_client: false,
         }
     }
 
+    /// Sends webhooks with the regular HTTP client, without pinning the addresses of their
+    /// hosts. This is used in tests, where the client goes through the recording proxy.
+    pub fn send_webhooks_with_http_client(mut self) -> Self {
+        self.send_webhooks_with_http_client = true;
+        self
+    }
+
     pub fn lock_index(&self) -> Result<MutexGuard<'_, Repository>, PerformError> {
         let repo = self.index.lock().unwrap_or_else(PoisonError::into_inner);
         repo.reset_head()?;
@@ -250,7 +594,53 @@ impl Environment {
         &self.http_client
     }
 
+    /// Returns a client for sending a webhook to `url`, which only connects to `addrs`.
+    ///
+    /// The addresses must have been checked with `public_url::public_addrs()`, so that the
+    /// hostname can't resolve to a private address in between.
+    pub(crate) fn webhook_client(&self, url: &Url, addrs: &[SocketAddr]) -> reqwest::Result<Client> {
+        if self.send_webhooks_with_http_client {
+            return Ok(self.http_client.0.clone());
+        }
+
+        let mut builder = Client::builder()
+            .timeout(Duration::from_secs(45))
+            .redirect(public_url::redirect_policy());
+        if let Some(Host::Domain(domain)) = url.host() {
+            builder = builder.resolve_to_addrs(domain, addrs);
+        }
+        builder.build()
+    }
+
     pub(crate) fn cloudfront(&self) -> Option<&CloudFront> {
         self.cloudfront.as_ref()
     }
+
+    pub(crate) fn feeds(&self) -> &FeedConfig {
+        &self.feeds
+    }
+
+    pub(crate) fn emails(&self) -> &Emails {
+        &self.emails
+    }
+
+    /// Returns how long deleted crates are kept around before they are removed permanently.
+    pub(crate) fn crate_deletion_grace_period(&self) -> Duration {
+        self.crate_deletion_grace_period
+    }
+
+    /// Returns how long the user that deleted a crate is kept in the record of the deletion.
+    pub(crate) fn crate_deletion_audit_retention(&self) -> Duration {
+        self.crate_deletion_audit_retention
+    }
+
+    /// Returns for how many days ownership invitations can be accepted.
+    pub(crate) fn ownership_invitations_expiration_days(&self) -> u64 {
+        self.ownership_invitations_expiration_days
+    }
+
+    /// Returns the mirror that is notified about deleted crates, if there is one.
+    pub(crate) fn mirror(&self) -> Option<&MirrorConfig> {
+        self.mirror.as_ref()
+    }
 }
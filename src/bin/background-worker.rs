@@ -1,7 +1,8 @@
 //! Runs enqueued background jobs
 //!
-//! This binary will loop until interrupted. It will run all jobs in the
-//! background queue, sleeping for 1 second whenever the queue is empty. If we
+//! This binary will loop until interrupted. It will enqueue any scheduled jobs
+//! that are due and run all jobs in the background queue, sleeping for 1 second
+//! whenever the queue is empty. If we
 //! are unable to spawn workers to run jobs (either because we couldn't connect
 //! to the DB, an error occurred while loading, or we just never heard back from
 //! the worker thread), we will rebuild the runner and try again up to 5 times.
@@ -16,15 +17,21 @@
 extern crate tracing;
 
 use cargo_registry::config;
+use cargo_registry::email::Emails;
+use cargo_registry::metrics::LogEncoder;
 use cargo_registry::worker::cloudfront::CloudFront;
-use cargo_registry::{background_jobs::*, db, ssh};
+use cargo_registry::{background_jobs::*, db, env_optional, ssh, worker};
 use cargo_registry_index::{Repository, RepositoryConfig};
+use chrono::Utc;
+use prometheus::Encoder;
 use reqwest::blocking::Client;
+use std::io::Write;
 use std::sync::{Arc, Mutex};
 use std::thread::sleep;
 use std::time::{Duration, Instant};
 
 use cargo_registry::swirl;
+use cargo_registry::swirl::{ConcurrencyLimits, ScheduledJob, Scheduler};
 
 fn main() {
     let _sentry = cargo_registry::sentry::init();
@@ -36,6 +43,7 @@ fn main() {
 
     let config = config::Server::default();
     let uploader = config.base.uploader();
+    let emails = Arc::new(Emails::from_environment(&config));
 
     if config.db.are_all_read_only() {
         loop {
@@ -54,6 +62,16 @@ fn main() {
         .parse()
         .expect("Invalid value for `BACKGROUND_JOB_TIMEOUT`");
 
+    let slow_job_threshold = dotenv::var("BACKGROUND_JOB_SLOW_THRESHOLD")
+        .unwrap_or_else(|_| "60".into())
+        .parse()
+        .expect("Invalid value for `BACKGROUND_JOB_SLOW_THRESHOLD`");
+
+    let concurrency_limits = ConcurrencyLimits::from_environment();
+
+    let metrics_log_interval =
+        env_optional("WORKER_METRICS_LOG_EVERY_SECONDS").map(Duration::from_secs);
+
     info!("Cloning index");
 
     if dotenv::var("HEROKU").is_ok() {
@@ -81,16 +99,66 @@ fn main() {
             uploader.clone(),
             client,
             cloudfront.clone(),
+            config.feeds.clone(),
+            emails.clone(),
+            config.crate_deletion_grace_period,
+            config.crate_deletion_audit_retention,
+            config.ownership_invitations_expiration_days,
+            config.mirror.clone(),
         );
-        swirl::Runner::production_runner(environment, db_url.clone(), job_start_timeout)
+        swirl::Runner::production_runner(
+            environment,
+            db_url.clone(),
+            job_start_timeout,
+            slow_job_threshold,
+            &concurrency_limits,
+        )
     };
     let mut runner = build_runner();
 
+    let scheduler = Scheduler::new(vec![
+        ScheduledJob::new(
+            "anonymize_crate_deletions",
+            "0 45 3 * * *",
+            worker::anonymize_crate_deletions,
+        ),
+        ScheduledJob::new(
+            "daily_db_maintenance",
+            "0 0 3 * * *",
+            worker::daily_db_maintenance,
+        ),
+        ScheduledJob::new(
+            "prune_expired_tokens",
+            "0 30 3 * * *",
+            worker::prune_expired_tokens,
+        ),
+        ScheduledJob::new(
+            "purge_expired_invitations",
+            "0 40 3 * * *",
+            worker::purge_expired_invitations,
+        ),
+        ScheduledJob::new(
+            "purge_deleted_crates",
+            "0 15 * * * *",
+            worker::purge_deleted_crates,
+        ),
+        ScheduledJob::new(
+            "sync_crates_feeds",
+            "0 */5 * * * *",
+            worker::sync_crates_feeds,
+        ),
+    ]);
+
     info!("Runner booted, running jobs");
 
     let mut failure_count = 0;
+    let mut metrics_logged_at = Instant::now();
 
     loop {
+        if let Err(err) = runner.enqueue_scheduled_jobs(&scheduler, Utc::now()) {
+            warn!(?err, "Failed to enqueue scheduled jobs");
+        }
+
         if let Err(e) = runner.run_all_pending_jobs() {
             failure_count += 1;
             if failure_count < 5 {
@@ -100,6 +168,26 @@ fn main() {
                 panic!("Failed to begin running jobs 5 times. Restarting the process");
             }
         }
+
+        if let Some(interval) = metrics_log_interval {
+            if metrics_logged_at.elapsed() >= interval {
+                if let Err(err) = log_metrics(&runner) {
+                    error!(?err, "log_metrics error");
+                }
+                metrics_logged_at = Instant::now();
+            }
+        }
+
         sleep(Duration::from_secs(1));
     }
 }
+
+fn log_metrics(runner: &swirl::Runner) -> prometheus::Result<()> {
+    let families = runner.metrics().gather();
+
+    let mut stdout = std::io::stdout();
+    LogEncoder::new().encode(&families, &mut stdout)?;
+    stdout.flush()?;
+
+    Ok(())
+}
@@ -2,22 +2,37 @@ use anyhow::{anyhow, Context};
 use ipnetwork::IpNetwork;
 use oauth2::{ClientId, ClientSecret};
 
-use crate::publish_rate_limit::PublishRateLimit;
+use crate::models::category::MAX_CATEGORIES;
+use crate::models::krate::MAX_NAME_LENGTH;
+use crate::rate_limiter::RateLimiterConfigs;
 use crate::{env, env_optional, uploaders::Uploader, Env};
 
 mod balance_capacity;
 mod base;
 mod database_pools;
+mod feeds;
+mod mirror;
 
 pub use self::base::Base;
 pub use self::database_pools::{DatabasePools, DbPoolConfig};
 pub use crate::config::balance_capacity::BalanceCapacityConfig;
-use http::HeaderValue;
+pub use crate::config::feeds::FeedConfig;
+pub use crate::config::mirror::MirrorConfig;
+use http::{HeaderName, HeaderValue};
 use std::collections::HashSet;
 use std::time::Duration;
 
 const DEFAULT_VERSION_ID_CACHE_SIZE: u64 = 10_000;
 const DEFAULT_VERSION_ID_CACHE_TTL: u64 = 5 * 60; // 5 minutes
+const DEFAULT_CRATE_DELETION_GRACE_PERIOD_HOURS: u64 = 24;
+const DEFAULT_CRATE_DELETION_CONFIRMATION_MINUTES: u64 = 5;
+const DEFAULT_CRATE_DELETION_AUDIT_RETENTION_DAYS: u64 = 365;
+const DEFAULT_CRATE_DELETION_MAX_AUTH_AGE_MINUTES: u64 = 60;
+const DEFAULT_CRATE_DELETION_TEAM_WINDOW_HOURS: u64 = 24;
+const DEFAULT_IDEMPOTENCY_KEY_EXPIRATION_HOURS: u64 = 24;
+const DEFAULT_MAX_ACCOUNT_LOCK_DAYS: u64 = 90;
+const DEFAULT_MAX_BATCH_CRATES: usize = 100;
+const DEFAULT_OWNERSHIP_INVITATIONS_EXPIRATION_DAYS: u64 = 30;
 
 pub struct Server {
     pub base: Base,
@@ -27,7 +42,7 @@ pub struct Server {
     pub gh_client_secret: ClientSecret,
     pub max_upload_size: u64,
     pub max_unpack_size: u64,
-    pub publish_rate_limit: PublishRateLimit,
+    pub rate_limiter: RateLimiterConfigs,
     pub new_version_rate_limit: Option<u32>,
     pub blocked_traffic: Vec<(String, Vec<String>)>,
     pub max_allowed_page_offset: u32,
@@ -47,6 +62,21 @@ pub struct Server {
     pub version_id_cache_ttl: Duration,
     pub cdn_user_agent: String,
     pub balance_capacity: BalanceCapacityConfig,
+    pub feeds: FeedConfig,
+    pub crate_deletion_grace_period: Duration,
+    pub crate_deletion_confirmation_expiration: Duration,
+    pub crate_deletion_max_auth_age: Duration,
+    pub crate_deletion_audit_retention: Duration,
+    pub crate_deletion_team_window: Duration,
+    pub crate_deletion_allow_unused: bool,
+    pub max_crate_name_length: usize,
+    pub mirror: Option<MirrorConfig>,
+    pub idempotency_key_expiration: Duration,
+    pub max_categories_per_crate: usize,
+    pub max_account_lock_duration: Duration,
+    pub max_batch_crates: usize,
+    pub anonymous_rate_limited_routes: HashSet<String>,
+    pub client_ip_header: HeaderName,
 }
 
 impl Default for Server {
@@ -80,8 +110,54 @@ impl Default for Server {
     ///   If the environment variable is not present instance metrics are not logged.
     /// - `FORCE_UNCONDITIONAL_REDIRECTS`: Whether to force unconditional redirects in the download
     ///   endpoint even with a healthy database pool.
+    /// - `OWNERSHIP_INVITATIONS_EXPIRATION_DAYS`: How long ownership invitations can be accepted.
+    ///   Defaults to 30.
+    /// - `RATE_LIMITER_{ACTION}_RATE_SECONDS`, `RATE_LIMITER_{ACTION}_BURST`: Override the rate
+    ///   limit of a `LimitedAction` (e.g. `RATE_LIMITER_PUBLISH_NEW_BURST`). Actions without
+    ///   these variables use the defaults of `LimitedAction`. The publish limit can also still be
+    ///   set with the older `WEB_NEW_PKG_RATE_LIMIT_RATE_MINUTES` and `WEB_NEW_PKG_RATE_LIMIT_BURST`.
+    /// - `RATE_LIMITER_{ACTION}_VERIFIED_RATE_SECONDS`, `RATE_LIMITER_{ACTION}_VERIFIED_BURST`:
+    ///   Override the rate limit of a `LimitedAction` for users with a verified email address.
+    ///   If neither these nor the variables of unverified users are set, the verified defaults
+    ///   of `LimitedAction` are used.
     /// - `BLOCKED_ROUTES`: A comma separated list of HTTP route patterns that are manually blocked
     ///   by an operator (e.g. `/crates/:crate_id/:version/download`).
+    /// - `FEED_CRATES_LENGTH`, `FEED_UPDATES_LENGTH`: The number of items in the feeds of new
+    ///   crates and of recently published versions. Default to 25, and are capped at 1000.
+    /// - `FEED_USER_LENGTH`: The number of versions listed in each user's RSS feed. Defaults to 25,
+    ///   setting it to 0 disables user feeds.
+    /// - `FEED_INCLUDE_YANKED`: Whether to list yanked versions in the feed of recently published
+    ///   versions. They are excluded by default.
+    /// - `CRATE_DELETION_GRACE_PERIOD_HOURS`: How long deleted crates can still be restored by an
+    ///   admin before they are removed permanently. Defaults to 24 hours.
+    /// - `CRATE_DELETION_TEAM_WINDOW_HOURS`: How long after their creation crates can be deleted
+    ///   by members of an owning team. Defaults to 24 hours.
+    /// - `CRATE_DELETION_ALLOW_UNUSED`: Whether members of an owning team can delete crates that
+    ///   were never downloaded and have no reverse dependencies after that window as well.
+    /// - `CRATE_DELETION_CONFIRMATION_MINUTES`: How long the token that confirms the deletion of
+    ///   a crate can be used for. Defaults to 5 minutes.
+    /// - `CRATE_DELETION_MAX_AUTH_AGE_MINUTES`: How recently users must have logged in to delete
+    ///   a crate. Defaults to 60 minutes.
+    /// - `MAX_CRATE_NAME_LENGTH`: The maximum number of characters in the name of a newly
+    ///   published crate. Defaults to 64.
+    /// - `MIRROR_DELETION_WEBHOOK_URL`, `MIRROR_WEBHOOK_SECRET`: Where to send a signed webhook
+    ///   when a crate is deleted, and the secret to sign it with. No webhooks are sent if the URL
+    ///   is not set.
+    /// - `IDEMPOTENCY_KEY_EXPIRATION_HOURS`: How long the response to a request with an
+    ///   `Idempotency-Key` header is replayed for requests with the same key. Defaults to 24 hours.
+    ///   Values that reach further back than the earliest representable date are rejected.
+    /// - `MAX_CATEGORIES_PER_CRATE`: The maximum number of categories that a crate can be in.
+    ///   Defaults to 5.
+    /// - `MAX_ACCOUNT_LOCK_DAYS`: How far in the future admins can set the end of an account lock,
+    ///   unless they explicitly lock the account permanently. Defaults to 90 days.
+    /// - `MAX_BATCH_CRATES`: The maximum number of crates that can be requested at once from
+    ///   `POST /api/private/crates/batch`. Defaults to 100.
+    /// - `ANONYMOUS_RATE_LIMITED_ROUTES`: A comma separated list of HTTP route patterns (e.g.
+    ///   `/api/v1/crates/:crate_id/reverse_dependencies`) on which requests without a user are
+    ///   rate limited per IP address. The limit can be changed through
+    ///   `RATE_LIMITER_ANONYMOUS_READ_RATE_SECONDS` and `RATE_LIMITER_ANONYMOUS_READ_BURST`.
+    /// - `CLIENT_IP_HEADER`: The header that the trusted proxy in front of the application puts
+    ///   the IP address of the client in. Defaults to `X-Real-Ip`.
     ///
     /// # Panics
     ///
@@ -119,7 +195,7 @@ impl Default for Server {
             gh_client_secret: ClientSecret::new(env("GH_CLIENT_SECRET")),
             max_upload_size: 10 * 1024 * 1024, // 10 MB default file upload size limit
             max_unpack_size: 512 * 1024 * 1024, // 512 MB max when decompressed
-            publish_rate_limit: Default::default(),
+            rate_limiter: crate::rate_limiter::config_from_environment(),
             new_version_rate_limit: env_optional("MAX_NEW_VERSIONS_DAILY"),
             blocked_traffic: blocked_traffic(),
             max_allowed_page_offset: env_optional("WEB_MAX_ALLOWED_PAGE_OFFSET").unwrap_or(200),
@@ -135,7 +211,10 @@ impl Default for Server {
                         .expect("invalid DOWNLOADS_PERSIST_INTERVAL_MS")
                 })
                 .unwrap_or(60_000), // 1 minute
-            ownership_invitations_expiration_days: 30,
+            ownership_invitations_expiration_days: env_optional(
+                "OWNERSHIP_INVITATIONS_EXPIRATION_DAYS",
+            )
+            .unwrap_or(DEFAULT_OWNERSHIP_INVITATIONS_EXPIRATION_DAYS),
             metrics_authorization_token: dotenv::var("METRICS_AUTHORIZATION_TOKEN").ok(),
             use_test_database_pool: false,
             instance_metrics_log_every_seconds: env_optional("INSTANCE_METRICS_LOG_EVERY_SECONDS"),
@@ -151,6 +230,58 @@ impl Default for Server {
             cdn_user_agent: dotenv::var("WEB_CDN_USER_AGENT")
                 .unwrap_or_else(|_| "Amazon CloudFront".into()),
             balance_capacity: BalanceCapacityConfig::from_environment(),
+            feeds: FeedConfig::from_environment(),
+            crate_deletion_grace_period: Duration::from_secs(
+                env_optional("CRATE_DELETION_GRACE_PERIOD_HOURS")
+                    .unwrap_or(DEFAULT_CRATE_DELETION_GRACE_PERIOD_HOURS)
+                    * 60
+                    * 60,
+            ),
+            crate_deletion_confirmation_expiration: Duration::from_secs(
+                env_optional("CRATE_DELETION_CONFIRMATION_MINUTES")
+                    .unwrap_or(DEFAULT_CRATE_DELETION_CONFIRMATION_MINUTES)
+                    * 60,
+            ),
+            crate_deletion_max_auth_age: Duration::from_secs(
+                env_optional("CRATE_DELETION_MAX_AUTH_AGE_MINUTES")
+                    .unwrap_or(DEFAULT_CRATE_DELETION_MAX_AUTH_AGE_MINUTES)
+                    * 60,
+            ),
+            crate_deletion_audit_retention: Duration::from_secs(
+                env_optional("CRATE_DELETION_AUDIT_RETENTION_DAYS")
+                    .unwrap_or(DEFAULT_CRATE_DELETION_AUDIT_RETENTION_DAYS)
+                    * 24
+                    * 60
+                    * 60,
+            ),
+            crate_deletion_team_window: Duration::from_secs(
+                env_optional("CRATE_DELETION_TEAM_WINDOW_HOURS")
+                    .unwrap_or(DEFAULT_CRATE_DELETION_TEAM_WINDOW_HOURS)
+                    * 60
+                    * 60,
+            ),
+            crate_deletion_allow_unused: dotenv::var("CRATE_DELETION_ALLOW_UNUSED").is_ok(),
+            max_crate_name_length: env_optional("MAX_CRATE_NAME_LENGTH").unwrap_or(MAX_NAME_LENGTH),
+            mirror: MirrorConfig::from_environment(),
+            idempotency_key_expiration: parse_idempotency_key_expiration(
+                env_optional("IDEMPOTENCY_KEY_EXPIRATION_HOURS")
+                    .unwrap_or(DEFAULT_IDEMPOTENCY_KEY_EXPIRATION_HOURS),
+            )
+            .expect("invalid IDEMPOTENCY_KEY_EXPIRATION_HOURS"),
+            max_categories_per_crate: env_optional("MAX_CATEGORIES_PER_CRATE")
+                .unwrap_or(MAX_CATEGORIES),
+            max_account_lock_duration: Duration::from_secs(
+                env_optional("MAX_ACCOUNT_LOCK_DAYS").unwrap_or(DEFAULT_MAX_ACCOUNT_LOCK_DAYS)
+                    * 24
+                    * 60
+                    * 60,
+            ),
+            max_batch_crates: env_optional("MAX_BATCH_CRATES").unwrap_or(DEFAULT_MAX_BATCH_CRATES),
+            anonymous_rate_limited_routes: env_optional("ANONYMOUS_RATE_LIMITED_ROUTES")
+                .map(|routes: String| routes.split(',').map(|s| s.into()).collect())
+                .unwrap_or_else(HashSet::new),
+            client_ip_header: env_optional("CLIENT_IP_HEADER")
+                .unwrap_or_else(|| HeaderName::from_static("x-real-ip")),
         }
     }
 }
@@ -195,6 +326,17 @@ fn parse_cidr_block(block: &str) -> anyhow::Result<IpNetwork> {
     Ok(cidr)
 }
 
+/// Converts the expiration of idempotency keys to a `Duration`, as long as it can be subtracted
+/// from the current time to find the expired keys.
+fn parse_idempotency_key_expiration(hours: u64) -> Option<Duration> {
+    let expiration = Duration::from_secs(hours.checked_mul(60 * 60)?);
+    let signed_expiration = chrono::Duration::from_std(expiration).ok()?;
+    chrono::Utc::now()
+        .naive_utc()
+        .checked_sub_signed(signed_expiration)?;
+    Some(expiration)
+}
+
 fn blocked_traffic() -> Vec<(String, Vec<String>)> {
     let pattern_list = dotenv::var("BLOCKED_TRAFFIC").unwrap_or_default();
     parse_traffic_patterns(&pattern_list)
@@ -250,6 +392,18 @@ fn parse_traffic_patterns_splits_on_comma_and_looks_for_equal_sign() {
     assert_none!(parse_traffic_patterns(pattern_string_3).next());
 }
 
+#[test]
+fn parse_idempotency_key_expiration_rejects_unrepresentable_values() {
+    assert_some_eq!(
+        parse_idempotency_key_expiration(24),
+        Duration::from_secs(24 * 60 * 60)
+    );
+    assert_some_eq!(parse_idempotency_key_expiration(0), Duration::ZERO);
+    assert_none!(parse_idempotency_key_expiration(u64::MAX));
+    assert_none!(parse_idempotency_key_expiration(u64::MAX / (60 * 60)));
+    assert_none!(parse_idempotency_key_expiration(24 * 365 * 1_000_000));
+}
+
 #[test]
 fn parse_cidr_block_list_successfully() {
     assert_ok_eq!(
@@ -7,8 +7,13 @@
 //! - `AWS_ACCESS_KEY`: The access key to interact with S3. Optional if running a mirror.
 //! - `AWS_SECRET_KEY`: The secret key to interact with S3. Optional if running a mirror.
 //! - `S3_CDN`: Optional CDN configuration for building public facing URLs.
+//! - `STORAGE_BACKEND`: `s3` or `local`, to select where crate files are stored. Defaults to `s3`
+//!    in production, and during development to `s3` if `S3_BUCKET` is set and `local` otherwise.
+//! - `LOCAL_UPLOADS_PATH`: The directory used by the `local` storage backend. Defaults to
+//!    `local_uploads` in the working directory.
 
 use crate::{env, uploaders::Uploader, Env};
+use std::path::PathBuf;
 
 pub struct Base {
     pub env: Env,
@@ -24,14 +29,23 @@ impl Base {
             Env::Development
         };
 
-        let uploader = match env {
-            Env::Production => {
+        let storage_backend = dotenv::var("STORAGE_BACKEND").ok();
+        let uploader = match (env, storage_backend.as_deref()) {
+            (_, Some("local")) => Self::local(),
+            (_, Some(backend)) if backend != "s3" => {
+                panic!("Invalid value for `STORAGE_BACKEND`: {backend:?}, expected `s3` or `local`")
+            }
+            (Env::Production, _) => {
                 // `env` panics if these vars are not set, and in production for a primary instance,
                 // that's what we want since we don't want to be able to start the server if the
                 // server doesn't know where to upload crates.
                 Self::s3_panic_if_missing_keys()
             }
             // In Development mode, either running as a primary instance or a read-only mirror
+            (_, Some("s3")) => {
+                info!("Using S3 uploader");
+                Self::s3_maybe_read_only()
+            }
             _ => {
                 if dotenv::var("S3_BUCKET").is_ok() {
                     // If we've set the `S3_BUCKET` variable to any value, use all of the values
@@ -41,13 +55,10 @@ impl Base {
                     info!("Using S3 uploader");
                     Self::s3_maybe_read_only()
                 } else {
-                    // If we don't set the `S3_BUCKET` variable, we'll use a development-only
-                    // uploader that makes it possible to run and publish to a locally-running
-                    // crates.io instance without needing to set up an account and a bucket in S3.
-                    info!(
-                        "Using local uploader, crate files will be in the local_uploads directory"
-                    );
-                    Uploader::Local
+                    // If we don't set the `S3_BUCKET` variable, we'll use the local uploader that
+                    // makes it possible to run and publish to a locally-running crates.io
+                    // instance without needing to set up an account and a bucket in S3.
+                    Self::local()
                 }
             }
         };
@@ -83,10 +94,24 @@ impl Base {
         }
     }
 
+    /// Like `test`, but with the `local` storage backend storing the files in `root`.
+    pub fn test_with_local_storage(root: PathBuf) -> Self {
+        Self {
+            env: Env::Test,
+            uploader: Uploader::Local { root },
+        }
+    }
+
     pub fn uploader(&self) -> &Uploader {
         &self.uploader
     }
 
+    fn local() -> Uploader {
+        let root = dotenv::var("LOCAL_UPLOADS_PATH").unwrap_or_else(|_| "local_uploads".into());
+        info!("Using local uploader, crate files will be in the {root} directory");
+        Uploader::Local { root: root.into() }
+    }
+
     fn s3_panic_if_missing_keys() -> Uploader {
         let index_bucket = match dotenv::var("S3_INDEX_BUCKET") {
             Ok(name) => Some(Box::new(s3::Bucket::new(
@@ -0,0 +1,52 @@
+use crate::env_optional;
+use std::env;
+
+const DEFAULT_FEED_LENGTH: i64 = 25;
+
+/// The maximum number of items in a feed, to keep the feed jobs and files reasonably small.
+const MAX_FEED_LENGTH: i64 = 1000;
+
+#[derive(Clone, Debug)]
+pub struct FeedConfig {
+    /// The number of crates listed in the `crates` feed of newly published crates.
+    pub crates_feed_length: i64,
+    /// The number of versions listed in the `updates` feed of recently published versions.
+    pub updates_feed_length: i64,
+    /// The number of versions listed in the feed of each user. Setting this to `0` disables the
+    /// generation of user feeds.
+    pub user_feed_length: i64,
+    /// Whether yanked versions are listed in the `updates` feed.
+    pub include_yanked: bool,
+}
+
+impl FeedConfig {
+    pub fn from_environment() -> Self {
+        Self {
+            crates_feed_length: feed_length_from_environment("FEED_CRATES_LENGTH"),
+            updates_feed_length: feed_length_from_environment("FEED_UPDATES_LENGTH"),
+            user_feed_length: feed_length_from_environment("FEED_USER_LENGTH"),
+            include_yanked: env::var("FEED_INCLUDE_YANKED").is_ok(),
+        }
+    }
+
+    /// User feeds are disabled, since they are regenerated on every publish and the publish
+    /// times they include would make the recorded HTTP interactions of most publishing tests
+    /// nondeterministic. The feed tests enable them explicitly instead.
+    pub fn for_testing() -> Self {
+        Self {
+            crates_feed_length: DEFAULT_FEED_LENGTH,
+            updates_feed_length: DEFAULT_FEED_LENGTH,
+            user_feed_length: 0,
+            include_yanked: false,
+        }
+    }
+}
+
+fn feed_length_from_environment(name: &str) -> i64 {
+    let length = env_optional(name).unwrap_or(DEFAULT_FEED_LENGTH);
+    if length > MAX_FEED_LENGTH {
+        warn!("`{name}` is larger than the maximum of {MAX_FEED_LENGTH}, using the maximum");
+        return MAX_FEED_LENGTH;
+    }
+    length.max(0)
+}
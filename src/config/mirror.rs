@@ -0,0 +1,28 @@
+use crate::env;
+
+/// An external mirror that is notified about deleted crates, so that it can remove its copy, and
+/// about deleted crates that were restored.
+#[derive(Clone, Debug)]
+pub struct MirrorConfig {
+    /// The URL that deletion and restoration webhooks are `POST`ed to. They are told apart by
+    /// the `X-CratesIo-Event` header.
+    pub deletion_webhook_url: String,
+    /// The secret used to sign the webhook payloads.
+    pub webhook_secret: String,
+}
+
+impl MirrorConfig {
+    /// Returns `None` if `MIRROR_DELETION_WEBHOOK_URL` isn't set.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the webhook URL is set, but `MIRROR_WEBHOOK_SECRET` is not.
+    pub fn from_environment() -> Option<Self> {
+        let deletion_webhook_url = dotenv::var("MIRROR_DELETION_WEBHOOK_URL").ok()?;
+
+        Some(Self {
+            deletion_webhook_url,
+            webhook_secret: env("MIRROR_WEBHOOK_SECRET"),
+        })
+    }
+}
@@ -64,11 +64,13 @@ mod prelude {
 pub mod helpers;
 pub mod util;
 
+pub mod admin;
 pub mod category;
-mod conduit_axum;
+pub(crate) mod conduit_axum;
 pub mod crate_owner_invitation;
 pub mod git;
 pub mod github;
+pub mod health;
 pub mod keyword;
 pub mod krate;
 pub mod metrics;
@@ -0,0 +1,752 @@
+//! Endpoints that are only available to crates.io administrators.
+
+use crate::auth::AuthCheck;
+
+use crate::controllers::frontend_prelude::*;
+use crate::controllers::helpers::pagination::{Paginated, PaginationOptions};
+use crate::controllers::helpers::Paginate;
+use crate::controllers::version::yank::perform_version_yank;
+use crate::models::krate::ALL_COLUMNS;
+use crate::models::{
+    Crate, CrateDeletion, CrateOwner, CrateWebhook, OwnerKind, User, WebhookEvent,
+};
+use crate::schema::{
+    api_tokens, crate_deletions, crate_owners, crates, emails, metadata, users, version_downloads,
+    versions,
+};
+use crate::sql::lower;
+use crate::util::errors::LockTooLong;
+use crate::views::EncodableAdminUser;
+use crate::worker;
+
+#[derive(Deserialize)]
+struct ReadOnlyModeUpdate {
+    read_only: bool,
+}
+
+/// Handles the `PUT /api/v1/admin/read_only` route.
+///
+/// While read-only mode is enabled, all requests that could write to the database are rejected
+/// with a `503 Service Unavailable`. The flag is stored in the database, so it applies to all
+/// instances, although the other instances may only notice the change after a few seconds.
+pub async fn update_read_only_mode(app: AppState, req: BytesRequest) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        let update: ReadOnlyModeUpdate =
+            serde_json::from_slice(req.body()).map_err(|_| bad_request("invalid json request"))?;
+
+        let conn = &mut *app.db_write()?;
+        let user = AuthCheck::only_cookie().require_admin().check(&req, conn)?;
+
+        app.set_admin_read_only(conn, update.read_only)?;
+        info!(
+            admin = user.user().gh_login,
+            read_only = update.read_only,
+            "Read-only mode was changed by an admin"
+        );
+
+        Ok(Json(json!({ "read_only": app.is_read_only() })))
+    })
+    .await
+}
+
+/// Handles the `PUT /api/v1/admin/crates/:crate_id/restore` route.
+///
+/// Restores a crate that was deleted by its owners, as long as it hasn't been removed
+/// permanently yet.
+pub async fn restore_crate(
+    app: AppState,
+    Path(crate_name): Path<String>,
+    req: Parts,
+) -> AppResult<Response> {
+    conduit_compat(move || {
+        let conn = &mut *app.db_write()?;
+        let user = AuthCheck::only_cookie().require_admin().check(&req, conn)?;
+
+        conn.transaction(|conn| {
+            let krate: Crate = diesel::update(crates::table)
+                .filter(Crate::with_name(&crate_name))
+                .filter(crates::deleted_at.is_not_null())
+                .set(crates::deleted_at.eq(None::<chrono::NaiveDateTime>))
+                .returning(ALL_COLUMNS)
+                .get_result(conn)?;
+
+            info!(
+                admin = user.user().gh_login,
+                krate.name = krate.name,
+                "Deleted crate was restored by an admin"
+            );
+
+            // Undo everything that `DELETE /crates/:crate_id` told others about the deletion
+            worker::update_crate_index(krate.name.clone()).enqueue(conn)?;
+            CrateWebhook::enqueue_dispatch(conn, &krate, WebhookEvent::Restored, json!({}))?;
+            if app.config.mirror.is_some() {
+                let restored_at = chrono::Utc::now().naive_utc();
+                worker::notify_mirror_of_restoration(krate.name.clone(), restored_at)
+                    .enqueue(conn)?;
+            }
+            worker::sync_crates_feeds().enqueue(conn)?;
+            worker::sync_category_feeds_of_crate(conn, krate.id)?;
+
+            ok_true()
+        })
+    })
+    .await
+}
+
+#[derive(Deserialize)]
+struct FrozenUpdate {
+    frozen: bool,
+}
+
+/// Handles the `PUT /api/v1/admin/crates/:crate_id/frozen` route.
+///
+/// Frozen crates stay visible, but publishing, yanking and deleting are rejected with a
+/// `403 Forbidden` until the crate is unfrozen again.
+pub async fn update_crate_frozen(
+    app: AppState,
+    Path(crate_name): Path<String>,
+    req: BytesRequest,
+) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        let update: FrozenUpdate =
+            serde_json::from_slice(req.body()).map_err(|_| bad_request("invalid json request"))?;
+
+        let conn = &mut *app.db_write()?;
+        let user = AuthCheck::only_cookie().require_admin().check(&req, conn)?;
+
+        let name: String = diesel::update(crates::table)
+            .filter(Crate::with_name(&crate_name))
+            .set(crates::crate_frozen.eq(update.frozen))
+            .returning(crates::name)
+            .get_result(conn)?;
+
+        info!(
+            admin = user.user().gh_login,
+            krate.name = name,
+            frozen = update.frozen,
+            "Frozen state of the crate was changed by an admin"
+        );
+
+        Ok(Json(json!({ "frozen": update.frozen })))
+    })
+    .await
+}
+
+/// Handles the `POST /api/v1/admin/crates/:crate_id/reset_downloads` route.
+///
+/// Removes all downloads of the crate and its versions, e.g. after they were inflated by fake
+/// downloads. The global download count is reduced accordingly, while the recent downloads are
+/// only updated by the next run of the `update_downloads` background job.
+pub async fn reset_crate_downloads(
+    app: AppState,
+    Path(crate_name): Path<String>,
+    req: Parts,
+) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        let conn = &mut *app.db_write()?;
+        let user = AuthCheck::only_cookie().require_admin().check(&req, conn)?;
+
+        conn.transaction(|conn| {
+            let krate: Crate = Crate::by_name(&crate_name).for_update().first(conn)?;
+            let version_ids = versions::table
+                .filter(versions::crate_id.eq(krate.id))
+                .select(versions::id);
+
+            // Downloads that weren't counted yet by the `update_downloads` background job are
+            // only recorded in this table, so they are removed without updating the totals
+            diesel::delete(version_downloads::table)
+                .filter(version_downloads::version_id.eq_any(version_ids))
+                .execute(conn)?;
+
+            diesel::update(versions::table)
+                .filter(versions::crate_id.eq(krate.id))
+                .set(versions::downloads.eq(0))
+                .execute(conn)?;
+
+            let downloads: i32 = diesel::update(&krate)
+                .set(crates::downloads.eq(0))
+                .returning(crates::downloads)
+                .get_result(conn)?;
+
+            diesel::update(metadata::table)
+                .set(
+                    metadata::total_downloads
+                        .eq(metadata::total_downloads - i64::from(krate.downloads)),
+                )
+                .execute(conn)?;
+
+            info!(
+                admin = user.user().gh_login,
+                krate.name = krate.name,
+                downloads = krate.downloads,
+                "Downloads of the crate were reset by an admin"
+            );
+
+            Ok(Json(json!({ "downloads": downloads })))
+        })
+    })
+    .await
+}
+
+/// Handles the `POST /api/v1/admin/crates/:crate_id/resync_index` route.
+///
+/// Rewrites the git index file of the crate from the database and uploads it to the HTTP-based
+/// index, e.g. after the index drifted from the database. Crates that were removed permanently
+/// are removed from the index again.
+pub async fn resync_crate_index(
+    app: AppState,
+    Path(crate_name): Path<String>,
+    req: Parts,
+) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        let conn = &mut *app.db_write()?;
+        let user = AuthCheck::only_cookie().require_admin().check(&req, conn)?;
+
+        let name: String = crates::table
+            .filter(Crate::with_name(&crate_name))
+            .select(crates::name)
+            .first(conn)
+            .optional()?
+            .unwrap_or(crate_name);
+
+        info!(
+            admin = user.user().gh_login,
+            krate.name = name,
+            "Index resync of the crate was requested by an admin"
+        );
+
+        // The git index is synced first, so that the HTTP-based index picks up its changes
+        let git_job_id = worker::sync_to_git_index(name.clone()).enqueue_returning_id(conn)?;
+        let http_job_id = worker::update_crate_index(name).enqueue_returning_id(conn)?;
+
+        Ok(Json(json!({ "job_ids": [git_job_id, http_job_id] })))
+    })
+    .await
+}
+
+#[derive(Default, Deserialize)]
+struct ForceYank {
+    reason: Option<String>,
+}
+
+/// Handles the `PUT /api/v1/admin/crates/:crate_id/:version/yank` route.
+///
+/// Yanks the version without checking the ownership of the crate, e.g. for versions that are
+/// malicious. Frozen crates can be yanked as well. The optional `reason` is recorded together
+/// with the yank in the audit actions of the version, but it isn't shown publicly.
+pub async fn force_yank(
+    app: AppState,
+    Path((crate_name, version)): Path<(String, String)>,
+    req: BytesRequest,
+) -> AppResult<Response> {
+    conduit_compat(move || {
+        let update: ForceYank = if req.body().is_empty() {
+            ForceYank::default()
+        } else {
+            serde_json::from_slice(req.body()).map_err(|_| bad_request("invalid json request"))?
+        };
+
+        let conn = &mut *app.db_write()?;
+        let user = AuthCheck::only_cookie().require_admin().check(&req, conn)?;
+        let user = user.user();
+
+        let krate: Crate = Crate::by_name(&crate_name).first(conn)?;
+        let version = krate.find_version(conn, &version)?;
+
+        info!(
+            admin = user.gh_login,
+            krate.name = krate.name,
+            version = version.num,
+            reason = update.reason,
+            "Version of the crate was yanked by an admin"
+        );
+
+        let reason = update.reason.as_deref();
+        perform_version_yank(conn, &krate, &version, user.id, None, reason, true)?;
+
+        ok_true()
+    })
+    .await
+}
+
+/// Handles the `GET /api/v1/crates/:crate_id/storage_manifest` route.
+///
+/// Lists the files in storage that are removed once the crate is deleted permanently, without
+/// changing anything. Crates that were deleted, but not removed yet, are included.
+pub async fn storage_manifest(
+    app: AppState,
+    Path(crate_name): Path<String>,
+    req: Parts,
+) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        let conn = &mut *app.db_read_prefer_primary()?;
+        AuthCheck::only_cookie().require_admin().check(&req, conn)?;
+
+        let (crate_id, name): (i32, String) = crates::table
+            .filter(Crate::with_name(&crate_name))
+            .select((crates::id, crates::name))
+            .first(conn)?;
+
+        let version_nums: Vec<String> = versions::table
+            .filter(versions::crate_id.eq(crate_id))
+            .order(versions::id)
+            .select(versions::num)
+            .load(conn)?;
+
+        let files = app
+            .config
+            .uploader()
+            .crate_files(&name, &version_nums)
+            .into_iter()
+            .map(|(bucket, path)| json!({ "bucket": bucket, "path": path }))
+            .collect::<Vec<_>>();
+
+        Ok(Json(json!({ "files": files })))
+    })
+    .await
+}
+
+/// Handles the `GET /api/v1/admin/deletions` route.
+///
+/// Lists the crates that were deleted by their owners, newest first, including crates that were
+/// removed permanently since. With `since`, only deletions at or after that time are included.
+pub async fn deletions(app: AppState, req: Parts) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        let since = match req.query().get("since") {
+            Some(since) => Some(since.parse::<chrono::NaiveDateTime>().map_err(|_| {
+                bad_request("`since` must be a date and time like 2023-04-12T10:00:00")
+            })?),
+            None => None,
+        };
+
+        let conn = &mut *app.db_read_prefer_primary()?;
+        AuthCheck::only_cookie().require_admin().check(&req, conn)?;
+
+        let mut query = crate_deletions::table
+            .left_join(users::table)
+            .select((crate_deletions::all_columns, users::gh_login.nullable()))
+            .order((
+                crate_deletions::deleted_at.desc(),
+                crate_deletions::id.desc(),
+            ))
+            .into_boxed();
+
+        if let Some(since) = since {
+            query = query.filter(crate_deletions::deleted_at.ge(since));
+        }
+
+        let query = query.pages_pagination(PaginationOptions::builder().gather(&req)?);
+        let data: Paginated<(CrateDeletion, Option<String>)> = query.load(conn)?;
+        let total = data.total();
+        let deletions = data
+            .into_iter()
+            .map(|(deletion, login)| {
+                json!({
+                    "crate_name": deletion.crate_name,
+                    "deleted_by": login,
+                    "deleted_at": deletion.deleted_at,
+                    "path": deletion.path,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        Ok(Json(json!({
+            "deletions": deletions,
+            "meta": { "total": total },
+        })))
+    })
+    .await
+}
+
+#[derive(Deserialize)]
+struct BulkLock {
+    logins: Vec<String>,
+    reason: String,
+    until: Option<chrono::NaiveDateTime>,
+    #[serde(default)]
+    permanent: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum LockResult {
+    Locked,
+    NotFound,
+    SkippedBecauseAdmin,
+}
+
+/// Handles the `POST /api/v1/admin/users/lock_bulk` route.
+///
+/// Locks the accounts of all the given users at once, e.g. during a spam wave. Admins are never
+/// locked, which also keeps admins from locking themselves out.
+pub async fn lock_users_bulk(app: AppState, req: BytesRequest) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        let update: BulkLock =
+            serde_json::from_slice(req.body()).map_err(|_| bad_request("invalid json request"))?;
+
+        if update.reason.trim().is_empty() {
+            return Err(bad_request("a reason for the lock is required"));
+        }
+
+        let conn = &mut *app.db_write()?;
+        let admin = AuthCheck::only_cookie().require_admin().check(&req, conn)?;
+        check_lock_duration(&app, update.until, update.permanent)?;
+
+        conn.transaction(|conn| {
+            let mut results = Vec::with_capacity(update.logins.len());
+            for login in &update.logins {
+                let user: Option<User> = users::table
+                    .filter(lower(users::gh_login).eq(login.to_lowercase()))
+                    .order(users::id.desc())
+                    .first(conn)
+                    .optional()?;
+
+                let result = match user {
+                    None => LockResult::NotFound,
+                    Some(user) if user.is_admin => LockResult::SkippedBecauseAdmin,
+                    Some(user) => {
+                        user.lock(conn, &update.reason, update.until)?;
+                        info!(
+                            admin = admin.user().gh_login,
+                            user = user.gh_login,
+                            reason = update.reason,
+                            "Account was locked by an admin"
+                        );
+                        LockResult::Locked
+                    }
+                };
+
+                results.push(json!({ "login": login, "result": result }));
+            }
+
+            Ok(Json(json!({ "results": results })))
+        })
+    })
+    .await
+}
+
+/// The longest period that `GET /api/v1/admin/locks/expiring` looks ahead, one year.
+const MAX_EXPIRING_WITHIN_HOURS: i64 = 24 * 365;
+
+/// Handles the `GET /api/v1/admin/locks/expiring` route.
+///
+/// Lists the users whose temporary account lock ends within the next `within_hours` hours
+/// (24 by default), so that the locks can be reviewed before they expire. Locks without an end
+/// date and locks that already expired are not included.
+pub async fn expiring_locks(app: AppState, req: Parts) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        let within_hours = match req.query().get("within_hours") {
+            Some(hours) => hours
+                .parse::<i64>()
+                .ok()
+                .filter(|hours| (1..=MAX_EXPIRING_WITHIN_HOURS).contains(hours))
+                .ok_or_else(|| {
+                    bad_request(&format!(
+                        "`within_hours` must be a number between 1 and {MAX_EXPIRING_WITHIN_HOURS}"
+                    ))
+                })?,
+            None => 24,
+        };
+
+        let conn = &mut *app.db_read_prefer_primary()?;
+        AuthCheck::only_cookie().require_admin().check(&req, conn)?;
+
+        let now = chrono::Utc::now().naive_utc();
+        let users: Vec<(User, Option<String>)> = users::table
+            .left_join(emails::table)
+            .filter(users::account_lock_reason.is_not_null())
+            .filter(users::account_lock_until.gt(now))
+            .filter(users::account_lock_until.le(now + chrono::Duration::hours(within_hours)))
+            .order((users::account_lock_until.asc(), users::id.asc()))
+            .select((users::all_columns, emails::email.nullable()))
+            .load(conn)?;
+
+        let users = users
+            .into_iter()
+            .map(|(user, email)| EncodableAdminUser::from(user, email))
+            .collect::<Vec<_>>();
+
+        Ok(Json(json!({ "users": users })))
+    })
+    .await
+}
+
+#[derive(Deserialize)]
+struct LockUpdate {
+    /// `None` if the request doesn't contain `until`, and `Some(None)` if it is `null`.
+    #[serde(default, deserialize_with = "deserialize_present")]
+    until: Option<Option<chrono::NaiveDateTime>>,
+    append_reason: Option<String>,
+    #[serde(default)]
+    permanent: bool,
+}
+
+/// Handles the `PATCH /api/v1/users/:user_id/lock` route.
+///
+/// Changes when the lock of an account ends, with `null` locking it indefinitely and a missing
+/// `until` keeping the current end date. The reason of the lock is kept, but more details can be
+/// added to it with `append_reason`.
+pub async fn update_user_lock(
+    app: AppState,
+    Path(login): Path<String>,
+    req: BytesRequest,
+) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        let update: LockUpdate =
+            serde_json::from_slice(req.body()).map_err(|_| bad_request("invalid json request"))?;
+
+        let conn = &mut *app.db_write()?;
+        let admin = AuthCheck::only_cookie().require_admin().check(&req, conn)?;
+        if let Some(until) = update.until {
+            check_lock_duration(&app, until, update.permanent)?;
+        }
+
+        conn.transaction(|conn| {
+            let user: User = users::table
+                .filter(lower(users::gh_login).eq(login.to_lowercase()))
+                .order(users::id.desc())
+                .for_update()
+                .first(conn)?;
+
+            let Some(reason) = &user.account_lock_reason else {
+                return Err(bad_request("the account is not locked"));
+            };
+
+            let reason = match update.append_reason.as_deref().map(str::trim) {
+                Some(appended) if !appended.is_empty() => format!("{reason}; {appended}"),
+                _ => reason.clone(),
+            };
+
+            let until = update.until.unwrap_or(user.account_lock_until);
+            user.lock(conn, &reason, until)?;
+            info!(
+                admin = admin.user().gh_login,
+                user = user.gh_login,
+                until = ?until,
+                "Account lock was changed by an admin"
+            );
+
+            let (user, email): (User, Option<String>) = users::table
+                .find(user.id)
+                .left_join(emails::table)
+                .select((users::all_columns, emails::email.nullable()))
+                .first(conn)?;
+
+            Ok(Json(json!({
+                "user": EncodableAdminUser::from(user, email)
+            })))
+        })
+    })
+    .await
+}
+
+#[derive(Deserialize)]
+struct UserMerge {
+    source_login: String,
+    target_login: String,
+}
+
+/// Handles the `POST /api/v1/admin/users/merge` route.
+///
+/// Moves the crate ownerships and API tokens of the `source_login` account to the
+/// `target_login` account, e.g. after a user ended up with two accounts. Crates that both
+/// accounts own are only owned by the target afterwards. The email address is only moved if the
+/// target doesn't have one yet. The source account is locked indefinitely, but kept, so that
+/// the versions it published still have a publisher.
+pub async fn merge_users(app: AppState, req: BytesRequest) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        let merge: UserMerge =
+            serde_json::from_slice(req.body()).map_err(|_| bad_request("invalid json request"))?;
+
+        if merge.source_login.eq_ignore_ascii_case(&merge.target_login) {
+            return Err(bad_request("an account can't be merged into itself"));
+        }
+
+        let conn = &mut *app.db_write()?;
+        let admin = AuthCheck::only_cookie().require_admin().check(&req, conn)?;
+
+        conn.transaction(|conn| {
+            let find_user = |conn: &mut PgConnection, login: &str| -> QueryResult<User> {
+                users::table
+                    .filter(lower(users::gh_login).eq(login.to_lowercase()))
+                    .order(users::id.desc())
+                    .for_update()
+                    .first(conn)
+            };
+            let source = find_user(conn, &merge.source_login)?;
+            let target = find_user(conn, &merge.target_login)?;
+
+            let crate_ids: Vec<i32> = CrateOwner::by_owner_kind(OwnerKind::User)
+                .filter(crate_owners::owner_id.eq(source.id))
+                .select(crate_owners::crate_id)
+                .load(conn)?;
+
+            let new_owners = crate_ids
+                .iter()
+                .map(|&crate_id| {
+                    (
+                        crate_owners::crate_id.eq(crate_id),
+                        crate_owners::owner_id.eq(target.id),
+                        crate_owners::owner_kind.eq(OwnerKind::User as i32),
+                        crate_owners::created_by.eq(admin.user().id),
+                    )
+                })
+                .collect::<Vec<_>>();
+
+            // Crates that the target already owns, or owned before, keep their existing row
+            diesel::insert_into(crate_owners::table)
+                .values(&new_owners)
+                .on_conflict((
+                    crate_owners::crate_id,
+                    crate_owners::owner_id,
+                    crate_owners::owner_kind,
+                ))
+                .do_update()
+                .set(crate_owners::deleted.eq(false))
+                .execute(conn)?;
+
+            diesel::update(crate_owners::table)
+                .filter(crate_owners::owner_id.eq(source.id))
+                .filter(crate_owners::owner_kind.eq(OwnerKind::User as i32))
+                .set(crate_owners::deleted.eq(true))
+                .execute(conn)?;
+
+            let api_tokens = diesel::update(api_tokens::table)
+                .filter(api_tokens::user_id.eq(source.id))
+                .set(api_tokens::user_id.eq(target.id))
+                .execute(conn)?;
+
+            let target_has_email = diesel::select(diesel::dsl::exists(
+                emails::table.filter(emails::user_id.eq(target.id)),
+            ))
+            .get_result::<bool>(conn)?;
+
+            let email_moved = !target_has_email
+                && diesel::update(emails::table)
+                    .filter(emails::user_id.eq(source.id))
+                    .set(emails::user_id.eq(target.id))
+                    .execute(conn)?
+                    > 0;
+
+            source.lock(conn, &format!("merged into {}", target.gh_login), None)?;
+
+            info!(
+                admin = admin.user().gh_login,
+                source = source.gh_login,
+                target = target.gh_login,
+                crates = crate_ids.len(),
+                api_tokens,
+                email_moved,
+                "Accounts were merged by an admin"
+            );
+
+            Ok(Json(json!({
+                "crates": crate_ids.len(),
+                "api_tokens": api_tokens,
+                "email_moved": email_moved,
+            })))
+        })
+    })
+    .await
+}
+
+/// Handles the `POST /api/v1/admin/admins/:login` route.
+pub async fn add_admin(
+    app: AppState,
+    Path(login): Path<String>,
+    req: Parts,
+) -> AppResult<Json<Value>> {
+    conduit_compat(move || update_admin(&app, &login, &req, true)).await
+}
+
+/// Handles the `DELETE /api/v1/admin/admins/:login` route.
+///
+/// The last remaining admin can't be removed, so that admins can't lock themselves out.
+pub async fn remove_admin(
+    app: AppState,
+    Path(login): Path<String>,
+    req: Parts,
+) -> AppResult<Json<Value>> {
+    conduit_compat(move || update_admin(&app, &login, &req, false)).await
+}
+
+fn update_admin(
+    app: &AppState,
+    login: &str,
+    req: &Parts,
+    is_admin: bool,
+) -> AppResult<Json<Value>> {
+    let conn = &mut *app.db_write()?;
+    let admin = AuthCheck::only_cookie().require_admin().check(req, conn)?;
+
+    conn.transaction(|conn| {
+        // Locking all admins makes concurrent removals wait for each other, so that they can't
+        // remove the last two admins at the same time.
+        let admin_ids: Vec<i32> = users::table
+            .filter(users::is_admin.eq(true))
+            .select(users::id)
+            .for_update()
+            .load(conn)?;
+
+        let user: User = users::table
+            .filter(lower(users::gh_login).eq(login.to_lowercase()))
+            .order(users::id.desc())
+            .first(conn)?;
+
+        if !is_admin && admin_ids == [user.id] {
+            return Err(bad_request("the last admin can't be removed"));
+        }
+
+        diesel::update(&user)
+            .set(users::is_admin.eq(is_admin))
+            .execute(conn)?;
+
+        info!(
+            admin = admin.user().gh_login,
+            user = user.gh_login,
+            is_admin,
+            "Admin permissions were changed by an admin"
+        );
+
+        Ok(Json(
+            json!({ "login": user.gh_login, "is_admin": is_admin }),
+        ))
+    })
+}
+
+/// Rejects account locks that end later than `max_account_lock_duration` from now, or that
+/// don't end at all, unless they are explicitly marked as `permanent`.
+/// Deserializes a field that may be `null` as `Some(None)`, so that it can be told apart from a
+/// missing field with `#[serde(default)]`.
+fn deserialize_present<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: serde::Deserialize<'de>,
+{
+    serde::Deserialize::deserialize(deserializer).map(Some)
+}
+
+fn check_lock_duration(
+    app: &AppState,
+    until: Option<chrono::NaiveDateTime>,
+    permanent: bool,
+) -> AppResult<()> {
+    if permanent {
+        return Ok(());
+    }
+
+    let max = app.config.max_account_lock_duration;
+    let latest = chrono::Duration::from_std(max)
+        .ok()
+        .and_then(|max| chrono::Utc::now().naive_utc().checked_add_signed(max));
+
+    match (until, latest) {
+        (Some(until), Some(latest)) if until > latest => {}
+        (Some(_), _) => return Ok(()),
+        (None, _) => {}
+    }
+
+    Err(Box::new(LockTooLong {
+        max_days: max.as_secs() / (24 * 60 * 60),
+    }))
+}
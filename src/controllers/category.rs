@@ -1,54 +1,168 @@
+pub mod export;
+
+use super::helpers::locale::{requested_locales, Localized};
 use super::helpers::pagination::*;
 use super::prelude::*;
 
-use crate::models::Category;
+use crate::models::{Category, CategoryCursor};
 use crate::schema::categories;
-use crate::views::{EncodableCategory, EncodableCategoryWithSubcategories};
+use crate::util::errors::bad_request;
+use crate::views::{
+    EncodableAssignableCategory, EncodableCategory, EncodableCategoryWithSubcategories,
+};
 
 /// Handles the `GET /categories` route.
-pub async fn index(app: AppState, req: Parts) -> AppResult<Json<Value>> {
+///
+/// Like the other category endpoints, this translates the descriptions of the categories into
+/// the locale requested through the `locale` query parameter or the `Accept-Language` header,
+/// if there is a translation for it.
+///
+/// Requests with a `page` query parameter are paginated by offset. Otherwise the categories are
+/// paginated by keyset: `meta.next_cursor` can be passed back as the `seek` query parameter,
+/// together with the same `sort`, to get the next page.
+///
+/// `crates_cnt` only counts the crates directly in each category, while `subtree_crates_cnt`
+/// includes the crates of all subcategories. Sorting by `crates` uses the latter.
+pub async fn index(app: AppState, req: Parts) -> AppResult<Localized<Json<Value>>> {
     conduit_compat(move || {
         let query = req.query();
         // FIXME: There are 69 categories, 47 top level. This isn't going to
         // grow by an OoM. We need a limit for /summary, but we don't need
         // to paginate this.
-        let options = PaginationOptions::builder().gather(&req)?;
-        let offset = options.offset().unwrap_or_default();
+        let options = PaginationOptions::builder()
+            .enable_seek(true)
+            .gather(&req)?;
         let sort = query.get("sort").map_or("alpha", String::as_str);
 
         let conn = &mut app.db_read()?;
-        let categories = Category::toplevel(conn, sort, options.per_page, offset)?;
+        let (mut categories, next_cursor) = match &options.page {
+            Page::Numeric(_) => {
+                let offset = options.offset().unwrap_or_default();
+                let categories = Category::toplevel(conn, sort, options.per_page, offset)?;
+                (categories, None)
+            }
+            page => {
+                let cursor = match page {
+                    Page::Seek(seek) => Some(seek.decode::<CategoryCursor>()?),
+                    _ => None,
+                };
+                if let Some(cursor) = cursor.as_ref().filter(|c| !c.matches_sort(sort)) {
+                    let msg = format!("the `seek` cursor is for `sort={}`", cursor.sort());
+                    return Err(bad_request(&msg));
+                }
+
+                // Load one more category than requested to find out if there's a next page
+                let mut categories =
+                    Category::toplevel_after(conn, sort, cursor.as_ref(), options.per_page + 1)?;
+                let next_cursor = if categories.len() as i64 > options.per_page {
+                    categories.pop();
+                    let last = categories.last().map(|c| CategoryCursor::after(c, sort));
+                    last.map(encode_seek).transpose()?
+                } else {
+                    None
+                };
+                (categories, next_cursor)
+            }
+        };
+        let locales = requested_locales(&req);
+        let toplevel = categories.iter_mut().map(|c| &mut c.category);
+        Category::localize_descriptions(conn, toplevel, &locales)?;
         let categories = categories
             .into_iter()
-            .map(Category::into)
-            .collect::<Vec<EncodableCategory>>();
+            .map(EncodableCategory::from)
+            .collect::<Vec<_>>();
 
         // Query for the total count of categories
         let total = Category::count_toplevel(conn)?;
 
-        Ok(Json(json!({
+        Ok(Localized(Json(json!({
+            "categories": categories,
+            "meta": { "total": total, "next_cursor": next_cursor },
+        }))))
+    })
+    .await
+}
+
+/// Handles the `GET /categories/search` route.
+///
+/// Finds categories by their name instead of their slug, see `Category::search` for how the
+/// results are ordered.
+pub async fn search(app: AppState, req: Parts) -> AppResult<Localized<Json<Value>>> {
+    conduit_compat(move || {
+        let query = req.query();
+        let q = query
+            .get("q")
+            .map(|q| q.trim())
+            .filter(|q| !q.is_empty())
+            .ok_or_else(|| bad_request("missing or empty `q` query parameter"))?;
+
+        let options = PaginationOptions::builder().gather(&req)?;
+        let offset = options.offset().unwrap_or_default();
+
+        let conn = &mut app.db_read()?;
+        let mut categories = Category::search(conn, q, options.per_page, offset)?;
+        let found = categories.iter_mut().map(|c| &mut c.category);
+        Category::localize_descriptions(conn, found, &requested_locales(&req))?;
+        let categories = categories
+            .into_iter()
+            .map(EncodableCategory::from)
+            .collect::<Vec<_>>();
+
+        let total = Category::count_search(conn, q)?;
+
+        Ok(Localized(Json(json!({
             "categories": categories,
             "meta": { "total": total },
-        })))
+        }))))
+    })
+    .await
+}
+
+/// Handles the `GET /categories/assignable` route.
+///
+/// Returns all categories that crates can be added to, ordered so that each category comes right
+/// before its subcategories. Together with the `depth` of each category, this is enough to render
+/// them as an indented list. `subtree_crates_cnt` includes the crates of all subcategories.
+pub async fn assignable(app: AppState, req: Parts) -> AppResult<Localized<Json<Value>>> {
+    conduit_compat(move || {
+        let conn = &mut app.db_read()?;
+        let mut categories = Category::assignable(conn)?;
+        let locales = requested_locales(&req);
+        let all = categories.iter_mut().map(|c| &mut c.category);
+        Category::localize_descriptions(conn, all, &locales)?;
+        let categories = categories
+            .into_iter()
+            .map(EncodableAssignableCategory::from)
+            .collect::<Vec<_>>();
+
+        Ok(Localized(Json(json!({ "categories": categories }))))
     })
     .await
 }
 
 /// Handles the `GET /categories/:category_id` route.
-pub async fn show(state: AppState, Path(slug): Path<String>) -> AppResult<Json<Value>> {
+pub async fn show(
+    state: AppState,
+    Path(slug): Path<String>,
+    req: Parts,
+) -> AppResult<Localized<Json<Value>>> {
     conduit_compat(move || {
         let conn = &mut *state.db_read()?;
-        let cat: Category = Category::by_slug(&slug).first(conn)?;
-        let subcats = cat
-            .subcategories(conn)?
-            .into_iter()
-            .map(Category::into)
-            .collect();
-        let parents = cat
-            .parent_categories(conn)?
-            .into_iter()
-            .map(Category::into)
-            .collect();
+        let locales = requested_locales(&req);
+        let query = req.query();
+        let sort = query.get("sort").map_or("alpha", String::as_str);
+
+        let mut cat: Category = Category::by_slug(&slug).first(conn)?;
+        let mut subcats = cat.subcategories(conn, sort)?;
+        let mut parents = cat.parent_categories(conn)?;
+        Category::localize_descriptions(conn, std::slice::from_mut(&mut cat), &locales)?;
+        let subcategories = subcats.iter_mut().map(|c| &mut c.category);
+        Category::localize_descriptions(conn, subcategories, &locales)?;
+        let parent_categories = parents.iter_mut().map(|c| &mut c.category);
+        Category::localize_descriptions(conn, parent_categories, &locales)?;
+
+        let subcats = subcats.into_iter().map(EncodableCategory::from).collect();
+        let parents = parents.into_iter().map(EncodableCategory::from).collect();
 
         let cat = EncodableCategory::from(cat);
         let cat_with_subcats = EncodableCategoryWithSubcategories {
@@ -62,28 +176,35 @@ pub async fn show(state: AppState, Path(slug): Path<String>) -> AppResult<Json<V
             parent_categories: parents,
         };
 
-        Ok(Json(json!({ "category": cat_with_subcats })))
+        Ok(Localized(Json(json!({ "category": cat_with_subcats }))))
     })
     .await
 }
 
 /// Handles the `GET /category_slugs` route.
-pub async fn slugs(state: AppState) -> AppResult<Json<Value>> {
+pub async fn slugs(state: AppState, req: Parts) -> AppResult<Localized<Json<Value>>> {
     conduit_compat(move || {
         let conn = &mut *state.db_read()?;
-        let slugs: Vec<Slug> = categories::table
-            .select((categories::slug, categories::slug, categories::description))
-            .order(categories::slug)
-            .load(conn)?;
+        let mut categories: Vec<Category> = categories::table.order(categories::slug).load(conn)?;
+        Category::localize_descriptions(conn, &mut categories, &requested_locales(&req))?;
 
-        #[derive(Serialize, Queryable)]
+        #[derive(Serialize)]
         struct Slug {
             id: String,
             slug: String,
             description: String,
         }
 
-        Ok(Json(json!({ "category_slugs": slugs })))
+        let slugs = categories
+            .into_iter()
+            .map(|category| Slug {
+                id: category.slug.clone(),
+                slug: category.slug,
+                description: category.description,
+            })
+            .collect::<Vec<_>>();
+
+        Ok(Localized(Json(json!({ "category_slugs": slugs }))))
     })
     .await
 }
@@ -0,0 +1,178 @@
+//! Endpoint for exporting all categories at once, e.g. for mirrors
+
+use crate::controllers::conduit_axum::spawn_blocking;
+use crate::controllers::frontend_prelude::*;
+use crate::schema::categories;
+use axum::body::boxed;
+use hyper::body::{Body, Sender};
+use tokio::runtime::Handle;
+
+/// The number of categories that are loaded from the database at a time.
+const BATCH_SIZE: i64 = 1000;
+
+#[derive(Clone, Copy)]
+enum Format {
+    /// One line per category, in the order in which they were created.
+    Flat,
+    /// One line per top-level category, with its subcategories nested inside of it.
+    Tree,
+}
+
+type ExportedColumns = (
+    categories::id,
+    categories::category,
+    categories::slug,
+    categories::description,
+    categories::crates_cnt,
+);
+
+const EXPORTED_COLUMNS: ExportedColumns = (
+    categories::id,
+    categories::category,
+    categories::slug,
+    categories::description,
+    categories::crates_cnt,
+);
+
+#[derive(Queryable, Serialize)]
+struct ExportedCategory {
+    id: i32,
+    category: String,
+    slug: String,
+    description: String,
+    crates_cnt: i32,
+}
+
+#[derive(Serialize)]
+struct ExportedTree {
+    #[serde(flatten)]
+    category: ExportedCategory,
+    subcategories: Vec<ExportedTree>,
+}
+
+/// Handles the `GET /categories/export` route.
+///
+/// Responds with one JSON object per line for each category. With `format=tree`, there is one
+/// line per top-level category instead, which includes its subcategories in `subcategories`.
+/// `crates_cnt` only counts the crates directly in each category in both formats.
+///
+/// Like `GET /crates/:crate_id/versions/export`, the categories are loaded in batches while the
+/// response is being sent, and the response is aborted if the export fails halfway through.
+pub async fn export(app: AppState, req: Parts) -> AppResult<Response> {
+    let format = match req.query().get("format").map(String::as_str) {
+        None | Some("flat") => Format::Flat,
+        Some("tree") => Format::Tree,
+        Some(_) => return Err(bad_request("`format` must be `flat` or `tree`")),
+    };
+
+    let (sender, body) = Body::channel();
+    let handle = Handle::current();
+    spawn_blocking(move || {
+        if let Err(error) = send_categories(&app, &handle, format, sender) {
+            warn!(%error, "Failed to export the categories");
+        }
+    });
+
+    let content_type = [(header::CONTENT_TYPE, "application/x-ndjson")];
+    Ok((content_type, boxed(body)).into_response())
+}
+
+fn send_categories(
+    app: &AppState,
+    handle: &Handle,
+    format: Format,
+    mut sender: Sender,
+) -> AppResult<()> {
+    let result = (|| {
+        let conn = &mut *app.db_read()?;
+
+        let mut last_id = 0;
+        loop {
+            let mut query = categories::table
+                .filter(categories::id.gt(last_id))
+                .select(EXPORTED_COLUMNS)
+                .order(categories::id)
+                .limit(BATCH_SIZE)
+                .into_boxed();
+
+            if let Format::Tree = format {
+                query = query.filter(categories::slug.not_like("%::%"));
+            }
+
+            let batch: Vec<ExportedCategory> = query.load(conn)?;
+            let Some(last) = batch.last() else {
+                return Ok(());
+            };
+            last_id = last.id;
+
+            let mut chunk = Vec::new();
+            for category in batch {
+                match format {
+                    Format::Flat => serde_json::to_writer(&mut chunk, &category)?,
+                    Format::Tree => {
+                        let tree = load_tree(conn, category)?;
+                        serde_json::to_writer(&mut chunk, &tree)?;
+                    }
+                }
+                chunk.push(b'\n');
+            }
+
+            // The client went away, so there is nobody left to send the remaining categories to
+            if handle.block_on(sender.send_data(chunk.into())).is_err() {
+                return Ok(());
+            }
+        }
+    })();
+
+    if result.is_err() {
+        sender.abort();
+    }
+    result
+}
+
+/// Loads all subcategories of the top-level category `root` and nests them by their slugs.
+fn load_tree(conn: &mut PgConnection, root: ExportedCategory) -> AppResult<ExportedTree> {
+    let prefix = format!("{}::", root.slug);
+    let mut descendants: Vec<ExportedCategory> = categories::table
+        .filter(categories::slug.like(format!("{prefix}%")))
+        .select(EXPORTED_COLUMNS)
+        .order(categories::slug)
+        .load(conn)?;
+
+    // `_` in a slug matches any character in `LIKE`, so only keep the actual descendants
+    descendants.retain(|category| category.slug.starts_with(&prefix));
+
+    Ok(nest(root, &mut descendants))
+}
+
+/// Moves the direct children of `parent` out of `descendants`, together with their own
+/// descendants.
+fn nest(parent: ExportedCategory, descendants: &mut Vec<ExportedCategory>) -> ExportedTree {
+    let prefix = format!("{}::", parent.slug);
+    let is_child = |category: &ExportedCategory| {
+        category
+            .slug
+            .strip_prefix(&prefix)
+            .map_or(false, |rest| !rest.contains("::"))
+    };
+
+    let mut children = Vec::new();
+    let mut i = 0;
+    while i < descendants.len() {
+        if is_child(&descendants[i]) {
+            children.push(descendants.remove(i));
+        } else {
+            i += 1;
+        }
+    }
+
+    let subcategories = children
+        .into_iter()
+        .map(|child| nest(child, descendants))
+        .collect();
+
+    ExportedTree {
+        category: parent,
+        subcategories,
+    }
+}
@@ -5,7 +5,7 @@ use tokio::task::JoinHandle;
 
 /// Just like [tokio::task::spawn_blocking], but automatically runs the passed
 /// in function in the context of the current Sentry hub.
-fn spawn_blocking<F, R>(f: F) -> JoinHandle<R>
+pub(crate) fn spawn_blocking<F, R>(f: F) -> JoinHandle<R>
 where
     F: FnOnce() -> R + Send + 'static,
     R: Send + 'static,
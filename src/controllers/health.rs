@@ -0,0 +1,57 @@
+//! Endpoints for load balancer health checks.
+
+use crate::controllers::frontend_prelude::*;
+
+use crate::util::errors::DependenciesUnavailable;
+
+/// Handles the `GET /api/v1/health` route.
+///
+/// This only checks that the process is able to serve requests. Use the readiness endpoint to
+/// also check the services that the application depends on.
+pub async fn health() -> AppResult<Response> {
+    ok_true()
+}
+
+/// Handles the `GET /api/v1/ready` route.
+///
+/// Responds with a `503 Service Unavailable` listing the failed dependencies if the database or
+/// the storage backend can't be reached.
+pub async fn ready(app: AppState) -> AppResult<Response> {
+    conduit_compat(move || {
+        let mut unavailable = Vec::new();
+
+        let database = app
+            .db_read_prefer_primary()
+            .map_err(|err| err.to_string())
+            .and_then(|mut conn| {
+                diesel::sql_query("SELECT 1")
+                    .execute(&mut *conn)
+                    .map_err(|err| err.to_string())
+            });
+        if let Err(error) = database {
+            warn!(%error, "Readiness check failed for the database");
+            unavailable.push("database");
+        }
+
+        // In read-only mode there is no write pool that could be unavailable
+        if !app.config.db.are_all_read_only() {
+            if let Err(error) = app.db_write() {
+                warn!(%error, "Readiness check failed for the primary database");
+                unavailable.push("primary database");
+            }
+        }
+
+        let uploader = app.config.uploader();
+        if let Err(error) = uploader.check_reachability(app.http_client()) {
+            warn!(%error, "Readiness check failed for the storage backend");
+            unavailable.push("storage");
+        }
+
+        if !unavailable.is_empty() {
+            return Err(Box::new(DependenciesUnavailable(unavailable)));
+        }
+
+        ok_true()
+    })
+    .await
+}
@@ -2,6 +2,9 @@ use crate::controllers::cargo_prelude::{AppResult, Response};
 use axum::response::IntoResponse;
 use axum::Json;
 
+pub(crate) mod etag;
+pub(crate) mod idempotency;
+pub(crate) mod locale;
 pub(crate) mod pagination;
 
 pub(crate) use self::pagination::Paginate;
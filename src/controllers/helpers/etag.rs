@@ -0,0 +1,82 @@
+use crate::controllers::prelude::*;
+use crate::util::HeaderMapExt;
+
+use http::{HeaderMap, HeaderValue};
+use std::fmt::Display;
+
+/// A weak entity tag, as used in the `ETag` and `If-None-Match` headers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct WeakEtag(String);
+
+impl WeakEtag {
+    /// Creates a weak entity tag from a value identifying the state of a resource.
+    ///
+    /// The value must not contain double quotes.
+    pub(crate) fn new(value: impl Display) -> Self {
+        Self(format!("W/\"{value}\""))
+    }
+
+    /// Returns whether the `If-None-Match` header in `headers` matches this tag.
+    ///
+    /// This uses the weak comparison function from RFC 7232, so the tags of the header match
+    /// regardless of whether they are marked as weak.
+    fn matches(&self, headers: &HeaderMap) -> bool {
+        let opaque_tag = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+        let own_tag = opaque_tag(&self.0);
+
+        headers
+            .get_str_or_default(header::IF_NONE_MATCH)
+            .split(',')
+            .map(opaque_tag)
+            .any(|tag| tag == "*" || tag == own_tag)
+    }
+}
+
+/// Responds with `304 Not Modified` if the request's `If-None-Match` header matches `etag`, or
+/// with the response returned by `f` otherwise.
+///
+/// `f` is only called if the client doesn't already have the current representation, so
+/// expensive work should happen inside of it. Both responses include the `ETag` header.
+pub(crate) fn conditional_response<R: IntoResponse>(
+    req: &Parts,
+    etag: WeakEtag,
+    f: impl FnOnce() -> AppResult<R>,
+) -> AppResult<Response> {
+    let mut response = if etag.matches(&req.headers) {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        f()?.into_response()
+    };
+
+    let value = HeaderValue::try_from(etag.0).map_err(http::Error::from)?;
+    response.headers_mut().insert(header::ETAG, value);
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(if_none_match: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::IF_NONE_MATCH,
+            HeaderValue::from_static(if_none_match),
+        );
+        headers
+    }
+
+    #[test]
+    fn weak_etag_matching() {
+        let etag = WeakEtag::new("1680000000-3");
+        assert_eq!(etag.0, r#"W/"1680000000-3""#);
+
+        assert!(etag.matches(&headers(r#"W/"1680000000-3""#)));
+        assert!(etag.matches(&headers(r#""1680000000-3""#)));
+        assert!(etag.matches(&headers(r#"W/"abc", W/"1680000000-3""#)));
+        assert!(etag.matches(&headers("*")));
+
+        assert!(!etag.matches(&HeaderMap::new()));
+        assert!(!etag.matches(&headers(r#"W/"1680000000-4""#)));
+    }
+}
@@ -0,0 +1,63 @@
+use crate::controllers::frontend_prelude::*;
+use crate::models::{IdempotencyKey, NewIdempotencyKey};
+use crate::util::HeaderMapExt;
+
+/// The header that clients can send so that retries of a request return the original response
+/// instead of being processed again.
+pub(crate) const IDEMPOTENCY_KEY: &str = "idempotency-key";
+
+/// The maximum length of an idempotency key.
+const MAX_KEY_LENGTH: usize = 255;
+
+/// Runs `f`, unless the request has an `Idempotency-Key` header that was already used by `user_id`
+/// within the expiration window, in which case the recorded response is returned instead.
+///
+/// Only successful responses are recorded, so that requests which failed can be retried with
+/// the same key. Reusing a key for a different request is rejected. `conn` must be inside of a
+/// transaction, so that the response is only recorded if the changes made by `f` are committed,
+/// and so that concurrent requests with the same key wait for each other.
+pub(crate) fn idempotent(
+    app: &AppState,
+    conn: &mut PgConnection,
+    req: &Parts,
+    user_id: i32,
+    f: impl FnOnce(&mut PgConnection) -> AppResult<Value>,
+) -> AppResult<Response> {
+    let key = req.headers.get_str_or_default(IDEMPOTENCY_KEY);
+    if key.is_empty() {
+        return Ok(Json(f(conn)?).into_response());
+    }
+    if key.len() > MAX_KEY_LENGTH {
+        return Err(bad_request(&format_args!(
+            "the `Idempotency-Key` header can have at most {MAX_KEY_LENGTH} characters"
+        )));
+    }
+
+    let request = format!("{} {}", req.method, req.uri.path());
+    let expiration = app.config.idempotency_key_expiration;
+
+    IdempotencyKey::lock(conn, user_id, key)?;
+    if let Some(recorded) = IdempotencyKey::find(conn, user_id, key, expiration)? {
+        if recorded.request != request {
+            return Err(bad_request(
+                "the `Idempotency-Key` header was already used for a different request",
+            ));
+        }
+
+        let status = StatusCode::from_u16(recorded.response_status as u16)
+            .map_err(|_| server_error("invalid recorded response status"))?;
+        return Ok((status, Json(recorded.response_body)).into_response());
+    }
+
+    let response_body = f(conn)?;
+    let new_key = NewIdempotencyKey {
+        user_id,
+        key,
+        request: &request,
+        response_status: StatusCode::OK.as_u16() as i32,
+        response_body: &response_body,
+    };
+    IdempotencyKey::record(conn, new_key, expiration)?;
+
+    Ok(Json(response_body).into_response())
+}
@@ -0,0 +1,83 @@
+use crate::controllers::prelude::*;
+use crate::util::HeaderMapExt;
+use std::cmp::Ordering;
+
+/// Returns the locales requested by the client, most preferred first and in lowercase.
+///
+/// The `locale` query parameter takes precedence over the `Accept-Language` header. Locales for
+/// a region, like `pt-br`, are followed by their language, so that a translation that isn't
+/// specific to the region can be used as a fallback.
+pub(crate) fn requested_locales(req: &Parts) -> Vec<String> {
+    let tags = match req.query().get("locale") {
+        Some(locale) => vec![locale.to_lowercase()],
+        None => accept_language(req.headers.get_str_or_default(header::ACCEPT_LANGUAGE)),
+    };
+
+    let mut locales = Vec::new();
+    for tag in tags {
+        let language = tag.split('-').next().unwrap_or_default().to_string();
+        for locale in [tag, language] {
+            if !locale.is_empty() && !locales.contains(&locale) {
+                locales.push(locale);
+            }
+        }
+    }
+    locales
+}
+
+/// A response that was localized with `requested_locales()`.
+///
+/// It includes a `Vary: Accept-Language` header, so that caches don't serve a response in one
+/// language to clients that asked for another one.
+pub struct Localized<T>(pub T);
+
+impl<T: IntoResponse> IntoResponse for Localized<T> {
+    fn into_response(self) -> Response {
+        ([(header::VARY, "Accept-Language")], self.0).into_response()
+    }
+}
+
+/// Parses the language tags of an `Accept-Language` header, sorted by their quality values.
+fn accept_language(header: &str) -> Vec<String> {
+    let mut tags = header
+        .split(',')
+        .filter_map(|entry| {
+            let mut parts = entry.split(';');
+            let tag = parts.next()?.trim().to_lowercase();
+            let quality = parts
+                .find_map(|param| param.trim().strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.trim().parse::<f32>().ok())?;
+
+            (!tag.is_empty() && tag != "*" && quality > 0.0).then_some((tag, quality))
+        })
+        .collect::<Vec<_>>();
+
+    // The sort is stable, so tags with the same quality keep their order
+    tags.sort_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap_or(Ordering::Equal));
+    tags.into_iter().map(|(tag, _)| tag).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn locales(uri: &str, accept_language: Option<&str>) -> Vec<String> {
+        let mut request = Request::get(uri);
+        if let Some(accept_language) = accept_language {
+            request = request.header(header::ACCEPT_LANGUAGE, accept_language);
+        }
+        requested_locales(&request.body(()).unwrap().into_parts().0)
+    }
+
+    #[test]
+    fn requested_locales_are_sorted_by_preference() {
+        assert_eq!(locales("/", None), Vec::<String>::new());
+        assert_eq!(locales("/", Some("de")), ["de"]);
+        assert_eq!(
+            locales("/", Some("fr;q=0.5, pt-BR, pt;q=0.8, *;q=0.1, en;q=0")),
+            ["pt-br", "pt", "fr"]
+        );
+        assert_eq!(locales("/", Some("de-CH, de-AT")), ["de-ch", "de", "de-at"]);
+        assert_eq!(locales("/?locale=ZH-hant", Some("de")), ["zh-hant", "zh"]);
+    }
+}
@@ -1,6 +1,9 @@
+pub mod delete;
 pub mod downloads;
+pub mod export;
 pub mod follow;
 pub mod metadata;
 pub mod owners;
 pub mod publish;
 pub mod search;
+pub mod webhooks;
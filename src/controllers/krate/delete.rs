@@ -0,0 +1,316 @@
+//! Endpoint for deleting a crate
+
+use crate::app::App;
+use crate::auth::AuthCheck;
+use crate::controllers::cargo_prelude::*;
+use crate::controllers::helpers::idempotency::idempotent;
+use crate::models::{Crate, CrateWebhook, NewCrateDeletion, Rights, WebhookEvent};
+use crate::schema::crates;
+use crate::swirl::errors::EnqueueError;
+use crate::util::errors::{server_error, BoxedAppError, CrateFrozen, DeletionNotConfirmed};
+use crate::util::HeaderMapExt;
+use crate::worker;
+use chrono::{NaiveDateTime, Utc};
+use diesel::dsl::now;
+use ring::hmac;
+use std::time::Duration;
+
+/// The reason sent to mirrors for crates deleted through this endpoint.
+const DELETED_BY_OWNER: &str = "deleted by an owner";
+
+/// The header containing the token returned by `confirm`, which is required to delete a crate.
+pub const CONFIRMATION_HEADER: &str = "x-crates-io-delete-confirmation";
+
+/// Handles the `POST /crates/:crate_id/delete/confirm` route.
+///
+/// Returns a short-lived token that has to be sent in the `X-Crates-Io-Delete-Confirmation`
+/// header when deleting the crate, so that a stale tab can't delete a crate by accident. The
+/// token is signed with the session key and only valid for this crate and user, so nothing has
+/// to be stored.
+pub async fn confirm(
+    app: AppState,
+    Path(crate_name): Path<String>,
+    req: Parts,
+) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        let conn = &mut *app.db_read_prefer_primary()?;
+        let auth = AuthCheck::only_cookie()
+            .require_recent_auth(max_auth_age(&app))
+            .check(&req, conn)?;
+        let krate: Crate = Crate::by_name(&crate_name).first(conn)?;
+
+        let expiration = app.config.crate_deletion_confirmation_expiration.as_secs();
+        let expires_at = Utc::now().timestamp() + expiration as i64;
+        let token = confirmation_token(&app, &krate.name, auth.user().id, expires_at);
+
+        Ok(Json(json!({
+            "token": token,
+            "expires_at": NaiveDateTime::from_timestamp_opt(expires_at, 0),
+        })))
+    })
+    .await
+}
+
+/// Handles the `DELETE /crates/:crate_id` route.
+///
+/// The crate is only marked as deleted here, which hides it from the API and from the index.
+/// An admin can restore it until the grace period has passed, after which the
+/// `purge_deleted_crates` background job removes it permanently.
+///
+/// Team members can only delete crates that were created less than the grace period ago, e.g.
+/// right after publishing one by mistake. Older crates can only be deleted by user owners,
+/// unless `crate_deletion_allow_unused` is enabled and the crate was never downloaded and has
+/// no reverse dependencies.
+///
+/// Every deletion is recorded in the `crate_deletions` table, which admins can review with
+/// `GET /api/v1/admin/deletions`, even after the crate was removed permanently.
+///
+/// The request has to include a token from `confirm` in the `X-Crates-Io-Delete-Confirmation`
+/// header, and like `confirm` it is rejected if the user logged in longer than
+/// `crate_deletion_max_auth_age` ago.
+///
+/// Clients can send an `Idempotency-Key` header, so that retrying a deletion that succeeded
+/// returns the original response instead of a `404 Not Found`.
+pub async fn delete(
+    app: AppState,
+    Path(crate_name): Path<String>,
+    req: Parts,
+) -> AppResult<Response> {
+    conduit_compat(move || {
+        let conn = &mut *app.db_write()?;
+        let auth = AuthCheck::only_cookie()
+            .require_recent_auth(max_auth_age(&app))
+            .check(&req, conn)?;
+        let user = auth.user();
+        let outcomes = &app.instance_metrics.crate_deletions_total;
+
+        conn.transaction(|conn| {
+            idempotent(&app, conn, &req, user.id, |conn| {
+                // Locking the row makes concurrent deletions of the same crate wait for each
+                // other, so that all but the first one find the crate already deleted.
+                let krate: Crate = Crate::by_name(&crate_name)
+                    .for_update()
+                    .first(conn)
+                    .optional()?
+                    .ok_or_else(|| Crate::not_found(conn, &crate_name))?;
+
+                let token = req.headers.get_str_or_default(CONFIRMATION_HEADER);
+                if !is_valid_confirmation(&app, token, &krate.name, user.id) {
+                    outcomes.with_label_values(&["blocked_unconfirmed"]).inc();
+                    return Err(Box::new(DeletionNotConfirmed {
+                        crate_name: krate.name,
+                    }));
+                }
+
+                let owners = krate.owners(conn)?;
+                let rights = user.rights(&app, &owners)?;
+
+                let blockers = deletion_blockers(&app, conn, &krate, rights)?;
+                if let Some(blocker) = blockers.first() {
+                    outcomes.with_label_values(&[blocker.outcome()]).inc();
+                    return Err(blocker.error(&app, &krate));
+                }
+
+                let path = match rights {
+                    Rights::Full => "owner",
+                    _ => "team",
+                };
+
+                let deleted_at: Option<NaiveDateTime> = diesel::update(&krate)
+                    .set(crates::deleted_at.eq(now.nullable()))
+                    .returning(crates::deleted_at)
+                    .get_result(conn)?;
+
+                NewCrateDeletion {
+                    crate_name: &krate.name,
+                    deleted_by: user.id,
+                    path,
+                }
+                .insert(conn)?;
+
+                info!(
+                    krate.name = krate.name,
+                    user = user.gh_login,
+                    "Crate was deleted by its owner"
+                );
+
+                // Removes the crate from the HTTP-based index right away. The git index is only
+                // updated once the crate is removed permanently.
+                worker::update_crate_index(krate.name.clone())
+                    .enqueue(conn)
+                    .map_err(|error| enqueue_failed(&krate.name, "update_crate_index", error))?;
+                CrateWebhook::enqueue_dispatch(conn, &krate, WebhookEvent::Deleted, json!({}))
+                    .map_err(|error| {
+                        enqueue_failed(&krate.name, "dispatch_crate_webhook", error)
+                    })?;
+
+                if app.config.mirror.is_some() {
+                    let deleted_at = deleted_at.expect("`deleted_at` was just set");
+                    let reason = DELETED_BY_OWNER.to_string();
+                    worker::notify_mirror_of_deletion(krate.name.clone(), deleted_at, reason)
+                        .enqueue(conn)
+                        .map_err(|error| {
+                            enqueue_failed(&krate.name, "notify_mirror_of_deletion", error)
+                        })?;
+                }
+
+                // Deleted crates are left out of the category feeds, so they have to be
+                // regenerated. The `crates` and `updates` feeds are regenerated periodically.
+                worker::sync_category_feeds_of_crate(conn, krate.id)
+                    .map_err(|error| enqueue_failed(&krate.name, "sync_category_feed", error))?;
+
+                outcomes.with_label_values(&["success"]).inc();
+                Ok(json!({ "ok": true }))
+            })
+        })
+    })
+    .await
+}
+
+/// A reason why a user can't delete a crate.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeletionBlocker {
+    /// The user is neither an owner nor a member of an owning team.
+    NotOwner,
+    /// The user is a member of an owning team, but the crate is older than the grace period and
+    /// not unused.
+    TeamGracePeriodPassed,
+    /// An admin froze the crate.
+    Frozen,
+}
+
+impl DeletionBlocker {
+    /// The `outcome` label of the `crate_deletions_total` metric for deletions blocked by this.
+    fn outcome(self) -> &'static str {
+        match self {
+            Self::NotOwner => "blocked_not_owner",
+            Self::TeamGracePeriodPassed => "blocked_team",
+            Self::Frozen => "blocked_frozen",
+        }
+    }
+
+    fn error(self, app: &App, krate: &Crate) -> BoxedAppError {
+        match self {
+            Self::NotOwner => cargo_err("only owners have permission to delete crates"),
+            Self::TeamGracePeriodPassed => {
+                let hours = app.config.crate_deletion_team_window.as_secs() / 60 / 60;
+                if app.config.crate_deletion_allow_unused {
+                    cargo_err(&format_args!(
+                        "team members can only delete crates within {hours} hours \
+                        of their creation, or crates that were never downloaded and have no \
+                        reverse dependencies, ask a user owner to delete this crate"
+                    ))
+                } else {
+                    cargo_err(&format_args!(
+                        "team members can only delete crates within {hours} hours \
+                        of their creation, ask a user owner to delete this crate"
+                    ))
+                }
+            }
+            Self::Frozen => Box::new(CrateFrozen {
+                crate_name: krate.name.clone(),
+            }),
+        }
+    }
+}
+
+/// Returns why a user with `rights` to the crate can't delete it, or nothing if they can.
+///
+/// The blockers are ordered by how they are checked by `delete`, which fails with the first one.
+/// This doesn't check the confirmation token, which is only needed for the deletion itself.
+pub(crate) fn deletion_blockers(
+    app: &App,
+    conn: &mut PgConnection,
+    krate: &Crate,
+    rights: Rights,
+) -> AppResult<Vec<DeletionBlocker>> {
+    let mut blockers = Vec::new();
+    match rights {
+        Rights::Full => {}
+        // Team members can delete crates that were just published by mistake
+        Rights::Publish if !is_old(krate, app.config.crate_deletion_team_window) => {}
+        Rights::Publish if app.config.crate_deletion_allow_unused && is_unused(conn, krate)? => {}
+        Rights::Publish => blockers.push(DeletionBlocker::TeamGracePeriodPassed),
+        Rights::None => blockers.push(DeletionBlocker::NotOwner),
+    }
+
+    if krate.is_frozen(conn)? {
+        blockers.push(DeletionBlocker::Frozen);
+    }
+
+    Ok(blockers)
+}
+
+/// Returns an error that names the background `job` that couldn't be enqueued, since the error of
+/// the database alone doesn't tell which step of the deletion failed.
+///
+/// The whole deletion is rolled back in that case, so the crate is still available.
+fn enqueue_failed(crate_name: &str, job: &str, error: EnqueueError) -> BoxedAppError {
+    error!(
+        krate.name = crate_name,
+        job,
+        %error,
+        "Failed to enqueue a background job for a deleted crate"
+    );
+    let message = format!(
+        "the deletion of `{crate_name}` failed because the `{job}` background job could not be \
+        enqueued, the crate was not deleted"
+    );
+    BoxedAppError::from(error).chain(server_error(&message))
+}
+
+/// How recently users must have logged in to delete a crate.
+///
+/// Values too large for `chrono::Duration` don't limit the age of the session at all.
+fn max_auth_age(app: &App) -> chrono::Duration {
+    chrono::Duration::from_std(app.config.crate_deletion_max_auth_age)
+        .unwrap_or_else(|_| chrono::Duration::max_value())
+}
+
+/// Returns whether the crate was created longer than `window` ago.
+fn is_old(krate: &Crate, window: Duration) -> bool {
+    let age = Utc::now().naive_utc() - krate.created_at;
+    age.to_std().map_or(false, |age| age > window)
+}
+
+/// Returns whether the crate was never downloaded and no other crates depend on it.
+fn is_unused(conn: &mut PgConnection, krate: &Crate) -> QueryResult<bool> {
+    if krate.downloads != 0 {
+        return Ok(false);
+    }
+
+    let (crates, _) = krate.reverse_dependencies_count(conn)?;
+    Ok(crates == 0)
+}
+
+/// Signs the deletion of `crate_name` by `user_id` until `expires_at`, a Unix timestamp.
+///
+/// The token is the expiration followed by a `.` and the hex encoded HMAC-SHA256 of the crate
+/// name, the user and the expiration.
+fn confirmation_token(app: &App, crate_name: &str, user_id: i32, expires_at: i64) -> String {
+    let message = confirmation_message(crate_name, user_id, expires_at);
+    let tag = hmac::sign(&confirmation_key(app), message.as_bytes());
+    format!("{expires_at}.{}", hex::encode(tag))
+}
+
+/// Returns whether `token` was returned by `confirm` for this crate and user, and is not
+/// expired yet.
+fn is_valid_confirmation(app: &App, token: &str, crate_name: &str, user_id: i32) -> bool {
+    let Some((expires_at, signature)) = token.split_once('.') else { return false };
+    let Ok(expires_at) = expires_at.parse::<i64>() else { return false };
+    let Ok(signature) = hex::decode(signature) else { return false };
+
+    let message = confirmation_message(crate_name, user_id, expires_at);
+    let is_signed = hmac::verify(&confirmation_key(app), message.as_bytes(), &signature).is_ok();
+    is_signed && Utc::now().timestamp() < expires_at
+}
+
+fn confirmation_key(app: &App) -> hmac::Key {
+    hmac::Key::new(hmac::HMAC_SHA256, app.session_key().signing())
+}
+
+/// Crate names can't contain `:`, so the parts of the message can't be shifted around.
+fn confirmation_message(crate_name: &str, user_id: i32, expires_at: i64) -> String {
+    format!("delete-crate:{crate_name}:{user_id}:{expires_at}")
+}
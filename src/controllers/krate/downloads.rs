@@ -4,6 +4,9 @@
 //! download counts are located in `version::downloads`.
 
 use std::cmp;
+use std::collections::HashMap;
+
+use chrono::{Duration, NaiveDate, Utc};
 
 use crate::controllers::frontend_prelude::*;
 
@@ -12,12 +15,37 @@ use crate::schema::version_downloads;
 use crate::sql::to_char;
 use crate::views::EncodableVersionDownload;
 
+/// The number of days that the `GET /crates/:crate_id/downloads` route covers by default.
+const DEFAULT_DOWNLOADS_DAYS: i64 = 90;
+
+/// The maximum number of days that can be requested from the `GET /crates/:crate_id/downloads`
+/// route at once.
+const MAX_DOWNLOADS_DAYS: i64 = 366;
+
 /// Handles the `GET /crates/:crate_id/downloads` route.
-pub async fn downloads(state: AppState, Path(crate_name): Path<String>) -> AppResult<Json<Value>> {
+///
+/// The downloads of the five latest versions are returned per version and day, and the downloads
+/// of all other versions are summed up per day in `meta.extra_downloads`. `meta.daily_downloads`
+/// has the daily totals over all versions.
+///
+/// The `from` and `to` query parameters select the days to return (inclusive, `YYYY-MM-DD`).
+/// Without them, the last 90 days are returned.
+pub async fn downloads(
+    state: AppState,
+    Path(crate_name): Path<String>,
+    req: Parts,
+) -> AppResult<Json<Value>> {
     conduit_compat(move || {
         use diesel::dsl::*;
         use diesel::sql_types::BigInt;
 
+        let query = req.query();
+        let (from, to) = downloads_range(
+            query.get("from").map(String::as_str),
+            query.get("to").map(String::as_str),
+            Utc::now().date_naive(),
+        )?;
+
         let conn = &mut *state.db_read()?;
         let krate: Crate = Crate::by_name(&crate_name).first(conn)?;
 
@@ -27,26 +55,36 @@ pub async fn downloads(state: AppState, Path(crate_name): Path<String>) -> AppRe
         let (latest_five, rest) = versions.split_at(cmp::min(5, versions.len()));
 
         let downloads = VersionDownload::belonging_to(latest_five)
-            .filter(version_downloads::date.gt(date(now - 90.days())))
+            .filter(version_downloads::date.between(from, to))
             .order(version_downloads::date.asc())
             .load(conn)?
             .into_iter()
             .map(VersionDownload::into)
             .collect::<Vec<EncodableVersionDownload>>();
 
-        let sum_downloads = sql::<BigInt>("SUM(version_downloads.downloads)");
-        let extra: Vec<ExtraDownload> = VersionDownload::belonging_to(rest)
+        let sum_downloads = || sql::<BigInt>("SUM(version_downloads.downloads)");
+        let extra: Vec<DateDownloads> = VersionDownload::belonging_to(rest)
             .select((
                 to_char(version_downloads::date, "YYYY-MM-DD"),
-                sum_downloads,
+                sum_downloads(),
             ))
-            .filter(version_downloads::date.gt(date(now - 90.days())))
+            .filter(version_downloads::date.between(from, to))
+            .group_by(version_downloads::date)
+            .order(version_downloads::date.asc())
+            .load(conn)?;
+
+        let daily: Vec<DateDownloads> = VersionDownload::belonging_to(&versions)
+            .select((
+                to_char(version_downloads::date, "YYYY-MM-DD"),
+                sum_downloads(),
+            ))
+            .filter(version_downloads::date.between(from, to))
             .group_by(version_downloads::date)
             .order(version_downloads::date.asc())
             .load(conn)?;
 
         #[derive(Serialize, Queryable)]
-        struct ExtraDownload {
+        struct DateDownloads {
             date: String,
             downloads: i64,
         }
@@ -55,8 +93,176 @@ pub async fn downloads(state: AppState, Path(crate_name): Path<String>) -> AppRe
             "version_downloads": downloads,
             "meta": {
                 "extra_downloads": extra,
+                "daily_downloads": daily,
+                "from": from,
+                "to": to,
             },
         })))
     })
     .await
 }
+
+/// Returns the first and last day for the `from` and `to` query parameters, defaulting to the
+/// last `DEFAULT_DOWNLOADS_DAYS` days up to `today`.
+fn downloads_range(
+    from: Option<&str>,
+    to: Option<&str>,
+    today: NaiveDate,
+) -> AppResult<(NaiveDate, NaiveDate)> {
+    let parse = |name, date: &str| {
+        NaiveDate::parse_from_str(date, "%F")
+            .map_err(|_| bad_request(&format!("invalid `{name}` date, expected `YYYY-MM-DD`")))
+    };
+
+    let default_days = Duration::days(DEFAULT_DOWNLOADS_DAYS - 1);
+    let (from, to) = match (from, to) {
+        (Some(from), Some(to)) => (parse("from", from)?, parse("to", to)?),
+        (Some(from), None) => (parse("from", from)?, today),
+        (None, Some(to)) => {
+            let to = parse("to", to)?;
+            let from = to
+                .checked_sub_signed(default_days)
+                .ok_or_else(|| bad_request("`to` is too early, use `from` to set the range"))?;
+            (from, to)
+        }
+        (None, None) => (today - default_days, today),
+    };
+
+    if from > to {
+        return Err(bad_request("`from` must not be after `to`"));
+    }
+    if (to - from).num_days() >= MAX_DOWNLOADS_DAYS {
+        return Err(bad_request(&format!(
+            "at most {MAX_DOWNLOADS_DAYS} days of downloads can be requested at once"
+        )));
+    }
+
+    Ok((from, to))
+}
+
+/// Handles the `GET /crates/:crate_id/downloads_by_version` route.
+///
+/// Returns the total number of downloads of each version of the crate, optionally restricted
+/// to a `date_range` query parameter in the form of `YYYY-MM-DD..YYYY-MM-DD` (inclusive).
+/// Yanked versions are included, but flagged as such.
+pub async fn downloads_by_version(
+    state: AppState,
+    Path(crate_name): Path<String>,
+    req: Parts,
+) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        use diesel::dsl::*;
+        use diesel::sql_types::BigInt;
+
+        let date_range = req
+            .query()
+            .get("date_range")
+            .map(|range| {
+                parse_date_range(range).ok_or_else(|| {
+                    bad_request("invalid date_range, expected `YYYY-MM-DD..YYYY-MM-DD`")
+                })
+            })
+            .transpose()?;
+
+        let conn = &mut *state.db_read()?;
+        let krate: Crate = Crate::by_name(&crate_name).first(conn)?;
+
+        let mut versions: Vec<Version> = krate.all_versions().load(conn)?;
+        versions
+            .sort_by_cached_key(|version| cmp::Reverse(semver::Version::parse(&version.num).ok()));
+
+        let sum_downloads = sql::<BigInt>("SUM(version_downloads.downloads)");
+        let mut query = VersionDownload::belonging_to(&versions)
+            .select((version_downloads::version_id, sum_downloads))
+            .group_by(version_downloads::version_id)
+            .into_boxed();
+
+        if let Some((start, end)) = date_range {
+            query = query.filter(version_downloads::date.between(start, end));
+        }
+
+        let totals: HashMap<i32, i64> = query.load(conn)?.into_iter().collect();
+
+        #[derive(Serialize)]
+        struct VersionDownloads {
+            version: String,
+            downloads: i64,
+            yanked: bool,
+        }
+
+        let versions = versions
+            .into_iter()
+            .map(|version| VersionDownloads {
+                downloads: totals.get(&version.id).copied().unwrap_or(0),
+                version: version.num,
+                yanked: version.yanked,
+            })
+            .collect::<Vec<_>>();
+
+        Ok(Json(json!({ "versions": versions })))
+    })
+    .await
+}
+
+/// Parses a `YYYY-MM-DD..YYYY-MM-DD` date range, returning `None` if it is malformed or the
+/// start of the range is after its end.
+fn parse_date_range(range: &str) -> Option<(NaiveDate, NaiveDate)> {
+    let (start, end) = range.split_once("..")?;
+    let start = NaiveDate::parse_from_str(start, "%F").ok()?;
+    let end = NaiveDate::parse_from_str(end, "%F").ok()?;
+    (start <= end).then_some((start, end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{downloads_range, parse_date_range};
+    use chrono::NaiveDate;
+
+    #[test]
+    fn date_range_parsing() {
+        let date = |s| NaiveDate::parse_from_str(s, "%F").unwrap();
+
+        assert_eq!(
+            parse_date_range("2023-01-01..2023-01-31"),
+            Some((date("2023-01-01"), date("2023-01-31")))
+        );
+        assert_eq!(
+            parse_date_range("2023-01-01..2023-01-01"),
+            Some((date("2023-01-01"), date("2023-01-01")))
+        );
+        assert_none!(parse_date_range("2023-01-31..2023-01-01"));
+        assert_none!(parse_date_range("2023-01-01"));
+        assert_none!(parse_date_range("2023-01-01..yesterday"));
+        assert_none!(parse_date_range(""));
+    }
+
+    #[test]
+    fn downloads_range_defaults() {
+        let date = |s| NaiveDate::parse_from_str(s, "%F").unwrap();
+        let today = date("2023-06-30");
+
+        let range = |from, to| downloads_range(from, to, today).ok();
+        assert_eq!(range(None, None), Some((date("2023-04-02"), today)));
+        assert_eq!(
+            range(Some("2023-06-01"), None),
+            Some((date("2023-06-01"), today))
+        );
+        assert_eq!(
+            range(None, Some("2023-01-31")),
+            Some((date("2022-11-03"), date("2023-01-31")))
+        );
+        assert_eq!(
+            range(Some("2022-01-01"), Some("2023-01-01")),
+            Some((date("2022-01-01"), date("2023-01-01")))
+        );
+        // The default range can't start before the earliest representable date
+        assert_none!(range(None, Some("-262144-01-01")));
+        assert_eq!(
+            range(Some("-262144-01-01"), Some("-262144-01-01")),
+            Some((NaiveDate::MIN, NaiveDate::MIN))
+        );
+        assert_none!(range(Some("2022-01-01"), Some("2023-01-02")));
+        assert_none!(range(Some("2023-01-02"), Some("2023-01-01")));
+        assert_none!(range(Some("yesterday"), None));
+    }
+}
@@ -0,0 +1,110 @@
+//! Endpoint for exporting all versions of a crate at once
+
+use crate::controllers::conduit_axum::spawn_blocking;
+use crate::controllers::frontend_prelude::*;
+use crate::models::Crate;
+use crate::schema::versions;
+use crate::util::rfc3339;
+use axum::body::boxed;
+use chrono::NaiveDateTime;
+use hyper::body::{Body, Sender};
+use tokio::runtime::Handle;
+
+/// The number of versions that are loaded from the database at a time.
+const BATCH_SIZE: i64 = 1000;
+
+#[derive(Queryable, Serialize)]
+struct ExportedVersion {
+    #[serde(skip)]
+    id: i32,
+    num: String,
+    #[serde(with = "rfc3339")]
+    created_at: NaiveDateTime,
+    yanked: bool,
+    checksum: String,
+    crate_size: Option<i32>,
+}
+
+/// Handles the `GET /crates/:crate_id/versions/export` route.
+///
+/// Responds with one JSON object per line for each version of the crate, in the order in which
+/// they were published. The versions are loaded in batches while the response is being sent, so
+/// crates with many versions don't need to be kept in memory as a whole.
+///
+/// If the export fails halfway through, the response is aborted instead of being ended normally,
+/// so that clients don't mistake it for a complete export.
+pub async fn export(app: AppState, Path(crate_name): Path<String>) -> AppResult<Response> {
+    let crate_id = conduit_compat({
+        let app = app.clone();
+        move || {
+            let conn = &mut *app.db_read()?;
+            let krate: Crate = Crate::by_name(&crate_name).first(conn)?;
+            Ok(krate.id)
+        }
+    })
+    .await?;
+
+    let (sender, body) = Body::channel();
+    let handle = Handle::current();
+    spawn_blocking(move || {
+        if let Err(error) = send_versions(&app, &handle, crate_id, sender) {
+            warn!(%error, crate_id, "Failed to export the versions of a crate");
+        }
+    });
+
+    let content_type = [(header::CONTENT_TYPE, "application/x-ndjson")];
+    Ok((content_type, boxed(body)).into_response())
+}
+
+fn send_versions(
+    app: &AppState,
+    handle: &Handle,
+    crate_id: i32,
+    mut sender: Sender,
+) -> AppResult<()> {
+    let result = (|| {
+        let mut last_id = 0;
+        loop {
+            // The connection is returned to the pool before the batch is sent, so that slow
+            // clients can't exhaust the pool
+            let batch: Vec<ExportedVersion> = {
+                let conn = &mut *app.db_read()?;
+                versions::table
+                    .filter(versions::crate_id.eq(crate_id))
+                    .filter(versions::id.gt(last_id))
+                    .select((
+                        versions::id,
+                        versions::num,
+                        versions::created_at,
+                        versions::yanked,
+                        versions::checksum,
+                        versions::crate_size,
+                    ))
+                    .order(versions::id)
+                    .limit(BATCH_SIZE)
+                    .load(conn)?
+            };
+
+            let Some(last) = batch.last() else {
+                return Ok(());
+            };
+            last_id = last.id;
+
+            let mut chunk = Vec::new();
+            for version in &batch {
+                serde_json::to_writer(&mut chunk, version)?;
+                chunk.push(b'\n');
+            }
+
+            // The client went away, so there is nobody left to send the remaining versions to
+            if handle.block_on(sender.send_data(chunk.into())).is_err() {
+                return Ok(());
+            }
+        }
+    })();
+
+    if result.is_err() {
+        sender.abort();
+    }
+    result
+}
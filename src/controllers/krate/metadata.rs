@@ -4,19 +4,28 @@
 //! index or cached metadata which was extracted (client side) from the
 //! `Cargo.toml` file.
 
+use chrono::NaiveDateTime;
 use std::cmp::Reverse;
+use std::collections::HashMap;
 use std::str::FromStr;
 
+use crate::auth::AuthCheck;
 use crate::controllers::frontend_prelude::*;
+use crate::controllers::helpers::etag::{conditional_response, WeakEtag};
+use crate::controllers::helpers::locale::{requested_locales, Localized};
 use crate::controllers::helpers::pagination::PaginationOptions;
+use crate::controllers::krate::delete::{deletion_blockers, DeletionBlocker};
 
 use crate::models::{
-    Category, Crate, CrateCategory, CrateKeyword, CrateVersions, Keyword, RecentCrateDownloads,
-    TopVersions, User, Version, VersionOwnerAction,
+    Category, Crate, CrateCategory, CrateKeyword, CrateVersions, Keyword, Owner,
+    RecentCrateDownloads, Rights, TopVersions, User, Version, VersionOwnerAction,
 };
 use crate::schema::*;
+use crate::sql::canon_crate_name;
+use crate::util::errors::not_found;
 use crate::views::{
-    EncodableCategory, EncodableCrate, EncodableDependency, EncodableKeyword, EncodableVersion,
+    EncodableCategory, EncodableCrate, EncodableCrateCategory, EncodableDependency,
+    EncodableKeyword, EncodableOwner, EncodableVersion,
 };
 
 use crate::models::krate::ALL_COLUMNS;
@@ -29,7 +38,10 @@ pub async fn summary(state: AppState) -> AppResult<Json<Value>> {
         let config = &state.config;
 
         let conn = &mut *state.db_read()?;
-        let num_crates: i64 = crates.count().get_result(conn)?;
+        let num_crates: i64 = crates
+            .filter(deleted_at.is_null())
+            .count()
+            .get_result(conn)?;
         let num_downloads: i64 = metadata::table
             .select(metadata::total_downloads)
             .get_result(conn)?;
@@ -65,20 +77,24 @@ pub async fn summary(state: AppState) -> AppResult<Json<Value>> {
 
         let new_crates = crates
             .left_join(recent_crate_downloads::table)
+            .filter(deleted_at.is_null())
             .order(created_at.desc())
             .select(selection)
             .limit(10)
             .load(conn)?;
         let just_updated = crates
             .left_join(recent_crate_downloads::table)
+            .filter(deleted_at.is_null())
             .filter(updated_at.ne(created_at))
             .order(updated_at.desc())
             .select(selection)
             .limit(10)
             .load(conn)?;
 
-        let mut most_downloaded_query =
-            crates.left_join(recent_crate_downloads::table).into_boxed();
+        let mut most_downloaded_query = crates
+            .left_join(recent_crate_downloads::table)
+            .filter(deleted_at.is_null())
+            .into_boxed();
         if !config.excluded_crate_names.is_empty() {
             most_downloaded_query =
                 most_downloaded_query.filter(name.ne_all(&config.excluded_crate_names));
@@ -91,6 +107,7 @@ pub async fn summary(state: AppState) -> AppResult<Json<Value>> {
 
         let mut most_recently_downloaded_query = crates
             .inner_join(recent_crate_downloads::table)
+            .filter(deleted_at.is_null())
             .into_boxed();
         if !config.excluded_crate_names.is_empty() {
             most_recently_downloaded_query =
@@ -112,8 +129,8 @@ pub async fn summary(state: AppState) -> AppResult<Json<Value>> {
 
         let popular_categories = Category::toplevel(conn, "crates", 10, 0)?
             .into_iter()
-            .map(Category::into)
-            .collect::<Vec<EncodableCategory>>();
+            .map(EncodableCategory::from)
+            .collect::<Vec<_>>();
 
         Ok(Json(json!({
             "num_downloads": num_downloads,
@@ -130,7 +147,18 @@ pub async fn summary(state: AppState) -> AppResult<Json<Value>> {
 }
 
 /// Handles the `GET /crates/:crate_id` route.
-pub async fn show(app: AppState, Path(name): Path<String>, req: Parts) -> AppResult<Json<Value>> {
+///
+/// The response has a weak `ETag` based on when the crate was last updated and how many versions
+/// it has, so that polling clients can use `If-None-Match` to avoid downloading it again.
+///
+/// If the crate doesn't exist, the `code` of the error is `crate_deleted` together with a
+/// `deleted_at` timestamp if its owners deleted it, and `crate_not_found` otherwise.
+///
+/// With `include=delete_status`, the response also has a `delete_status` with the reasons why the
+/// caller can't delete the crate, which are the same checks as `DELETE /crates/:crate_id`.
+/// Callers without a valid website session can't delete crates, so they are treated like users
+/// that don't own the crate. Other failures while authenticating them are returned as errors.
+pub async fn show(app: AppState, Path(name): Path<String>, req: Parts) -> AppResult<Response> {
     conduit_compat(move || {
         let include = req
             .query()
@@ -140,105 +168,229 @@ pub async fn show(app: AppState, Path(name): Path<String>, req: Parts) -> AppRes
             .unwrap_or_default();
 
         let conn = &mut *app.db_read()?;
-        let krate: Crate = Crate::by_name(&name).first(conn)?;
-
-        let versions_publishers_and_audit_actions = if include.versions {
-            let mut versions_and_publishers: Vec<(Version, Option<User>)> = krate
-                .all_versions()
-                .left_outer_join(users::table)
-                .select((versions::all_columns, users::all_columns.nullable()))
-                .load(conn)?;
-            versions_and_publishers.sort_by_cached_key(|(version, _)| {
-                Reverse(semver::Version::parse(&version.num).ok())
-            });
-
-            let versions = versions_and_publishers
-                .iter()
-                .map(|(v, _)| v)
-                .cloned()
-                .collect::<Vec<_>>();
-            Some(
-                versions_and_publishers
-                    .into_iter()
-                    .zip(VersionOwnerAction::for_versions(conn, &versions)?.into_iter())
-                    .map(|((v, pb), aas)| (v, pb, aas))
-                    .collect::<Vec<_>>(),
-            )
-        } else {
-            None
-        };
-        let ids = versions_publishers_and_audit_actions
-            .as_ref()
-            .map(|vps| vps.iter().map(|v| v.0.id).collect());
-
-        let kws = if include.keywords {
-            Some(
-                CrateKeyword::belonging_to(&krate)
-                    .inner_join(keywords::table)
-                    .select(keywords::all_columns)
-                    .load(conn)?,
-            )
-        } else {
-            None
-        };
-        let cats = if include.categories {
-            Some(
-                CrateCategory::belonging_to(&krate)
-                    .inner_join(categories::table)
-                    .select(categories::all_columns)
-                    .load(conn)?,
-            )
-        } else {
-            None
-        };
-        let recent_downloads = if include.downloads {
-            RecentCrateDownloads::belonging_to(&krate)
-                .select(recent_crate_downloads::downloads)
-                .get_result(conn)
-                .optional()?
+        let krate: Crate = Crate::by_name(&name)
+            .first(conn)
+            .optional()?
+            .ok_or_else(|| Crate::not_found(conn, &name))?;
+
+        let delete_status = if include.delete_status {
+            let rights = match AuthCheck::only_cookie().check_optional(&req, conn)? {
+                Some(auth) => auth.user().rights(&app, &krate.owners(conn)?)?,
+                None => Rights::None,
+            };
+            Some(deletion_blockers(&app, conn, &krate, rights)?)
         } else {
             None
         };
 
-        let badges = if include.badges { Some(vec![]) } else { None };
+        let num_versions: i64 = krate.all_versions().count().get_result(conn)?;
+        let updated_at = krate.updated_at.timestamp_nanos();
+        // The deletion status depends on the caller, so it has to be part of the tag as well
+        let blockers = delete_status
+            .iter()
+            .flatten()
+            .map(|blocker| format!("-{blocker:?}"))
+            .collect::<String>();
+        let etag = WeakEtag::new(format_args!("{updated_at}-{num_versions}{blockers}"));
+
+        conditional_response(&req, etag, || {
+            let versions_publishers_and_audit_actions = if include.versions {
+                let mut versions_and_publishers: Vec<(Version, Option<User>)> = krate
+                    .all_versions()
+                    .left_outer_join(users::table)
+                    .select((versions::all_columns, users::all_columns.nullable()))
+                    .load(conn)?;
+                versions_and_publishers.sort_by_cached_key(|(version, _)| {
+                    Reverse(semver::Version::parse(&version.num).ok())
+                });
+
+                let versions = versions_and_publishers
+                    .iter()
+                    .map(|(v, _)| v)
+                    .cloned()
+                    .collect::<Vec<_>>();
+                Some(
+                    versions_and_publishers
+                        .into_iter()
+                        .zip(VersionOwnerAction::for_versions(conn, &versions)?.into_iter())
+                        .map(|((v, pb), aas)| (v, pb, aas))
+                        .collect::<Vec<_>>(),
+                )
+            } else {
+                None
+            };
+            let ids = versions_publishers_and_audit_actions
+                .as_ref()
+                .map(|vps| vps.iter().map(|v| v.0.id).collect());
+
+            let kws = if include.keywords {
+                Some(
+                    CrateKeyword::belonging_to(&krate)
+                        .inner_join(keywords::table)
+                        .select(keywords::all_columns)
+                        .load(conn)?,
+                )
+            } else {
+                None
+            };
+            let cats = if include.categories {
+                Some(
+                    CrateCategory::belonging_to(&krate)
+                        .inner_join(categories::table)
+                        .select(categories::all_columns)
+                        .load(conn)?,
+                )
+            } else {
+                None
+            };
+            let recent_downloads = if include.downloads {
+                RecentCrateDownloads::belonging_to(&krate)
+                    .select(recent_crate_downloads::downloads)
+                    .get_result(conn)
+                    .optional()?
+            } else {
+                None
+            };
+
+            let badges = if include.badges { Some(vec![]) } else { None };
+
+            let top_versions = if include.versions {
+                Some(krate.top_versions(conn)?)
+            } else {
+                None
+            };
+
+            let encodable_crate = EncodableCrate::from(
+                krate.clone(),
+                top_versions.as_ref(),
+                ids,
+                kws.as_deref(),
+                cats.as_deref(),
+                badges,
+                false,
+                recent_downloads,
+            );
+            let encodable_versions = versions_publishers_and_audit_actions.map(|vpa| {
+                vpa.into_iter()
+                    .map(|(v, pb, aas)| EncodableVersion::from(v, &krate.name, pb, aas))
+                    .collect::<Vec<_>>()
+            });
+            let encodable_keywords = kws.map(|kws| {
+                kws.into_iter()
+                    .map(Keyword::into)
+                    .collect::<Vec<EncodableKeyword>>()
+            });
+            let encodable_cats = cats.map(|cats| {
+                cats.into_iter()
+                    .map(Category::into)
+                    .collect::<Vec<EncodableCategory>>()
+            });
+            let mut response = json!({
+                "crate": encodable_crate,
+                "versions": encodable_versions,
+                "keywords": encodable_keywords,
+                "categories": encodable_cats,
+            });
+            if let Some(blockers) = delete_status {
+                response["delete_status"] = encode_delete_status(blockers);
+            }
+            Ok(Json(response))
+        })
+    })
+    .await
+}
+
+fn encode_delete_status(blockers: Vec<DeletionBlocker>) -> Value {
+    json!({
+        "eligible": blockers.is_empty(),
+        "blockers": blockers,
+    })
+}
 
-        let top_versions = if include.versions {
-            Some(krate.top_versions(conn)?)
-        } else {
-            None
-        };
+#[derive(Deserialize)]
+struct BatchRequest {
+    names: Vec<String>,
+}
 
-        let encodable_crate = EncodableCrate::from(
-            krate.clone(),
-            top_versions.as_ref(),
-            ids,
-            kws.as_deref(),
-            cats.as_deref(),
-            badges,
-            false,
-            recent_downloads,
-        );
-        let encodable_versions = versions_publishers_and_audit_actions.map(|vpa| {
-            vpa.into_iter()
-                .map(|(v, pb, aas)| EncodableVersion::from(v, &krate.name, pb, aas))
-                .collect::<Vec<_>>()
-        });
-        let encodable_keywords = kws.map(|kws| {
-            kws.into_iter()
-                .map(Keyword::into)
-                .collect::<Vec<EncodableKeyword>>()
-        });
-        let encodable_cats = cats.map(|cats| {
-            cats.into_iter()
-                .map(Category::into)
-                .collect::<Vec<EncodableCategory>>()
-        });
-        Ok(Json(json!({
-            "crate": encodable_crate,
-            "versions": encodable_versions,
-            "keywords": encodable_keywords,
-            "categories": encodable_cats,
-        })))
+/// Handles the `POST /api/private/crates/batch` route.
+///
+/// Returns the metadata and owners of several crates at once, keyed by the requested names.
+/// Names of crates that don't exist are listed in `missing` instead of failing the request.
+pub async fn batch(app: AppState, req: BytesRequest) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        let request: BatchRequest =
+            serde_json::from_slice(req.body()).map_err(|_| bad_request("invalid json request"))?;
+
+        let mut names = request.names;
+        names.sort();
+        names.dedup();
+
+        let max = app.config.max_batch_crates;
+        if names.len() > max {
+            return Err(bad_request(&format!(
+                "at most {max} crates can be requested at once"
+            )));
+        }
+
+        // Names are matched like `Crate::by_name()` does, see the `canon_crate_name` function
+        let canonical_name = |name: &str| name.to_lowercase().replace('-', "_");
+        let canonical_names = names
+            .iter()
+            .map(|name| canonical_name(name))
+            .collect::<Vec<_>>();
+
+        let conn = &mut *app.db_read()?;
+        let data: Vec<(Crate, Option<i64>)> = crates::table
+            .left_join(recent_crate_downloads::table)
+            .filter(crates::deleted_at.is_null())
+            .filter(canon_crate_name(crates::name).eq_any(&canonical_names))
+            .select((ALL_COLUMNS, recent_crate_downloads::downloads.nullable()))
+            .load(conn)?;
+
+        let recent_downloads = data.iter().map(|&(_, s)| s).collect::<Vec<_>>();
+        let krates = data.into_iter().map(|(c, _)| c).collect::<Vec<_>>();
+
+        let crate_ids = krates.iter().map(|krate| krate.id).collect::<Vec<_>>();
+        let mut owners = Crate::owners_for_many(&crate_ids, conn)?;
+
+        let versions: Vec<Version> = krates.versions().load(conn)?;
+        let mut crates_by_name = versions
+            .grouped_by(&krates)
+            .into_iter()
+            .map(TopVersions::from_versions)
+            .zip(krates)
+            .zip(recent_downloads)
+            .map(|((top_versions, krate), recent_downloads)| {
+                let owners = owners
+                    .remove(&krate.id)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(Owner::into)
+                    .collect::<Vec<EncodableOwner>>();
+                let name = canonical_name(&krate.name);
+                let krate = EncodableCrate::from_minimal(
+                    krate,
+                    Some(&top_versions),
+                    None,
+                    false,
+                    recent_downloads,
+                );
+                (name, json!({ "crate": krate, "owners": owners }))
+            })
+            .collect::<HashMap<_, _>>();
+
+        let mut found = serde_json::Map::new();
+        let mut missing = Vec::new();
+        for (name, canonical_name) in names.into_iter().zip(canonical_names) {
+            match crates_by_name.remove(&canonical_name) {
+                Some(krate) => {
+                    found.insert(name, krate);
+                }
+                None => missing.push(name),
+            }
+        }
+
+        Ok(Json(json!({ "crates": found, "missing": missing })))
     })
     .await
 }
@@ -250,6 +402,8 @@ struct ShowIncludeMode {
     categories: bool,
     badges: bool,
     downloads: bool,
+    /// Not part of `full`, since it depends on the caller.
+    delete_status: bool,
 }
 
 impl Default for ShowIncludeMode {
@@ -261,13 +415,14 @@ impl Default for ShowIncludeMode {
             categories: true,
             badges: true,
             downloads: true,
+            delete_status: false,
         }
     }
 }
 
 impl ShowIncludeMode {
     const INVALID_COMPONENT: &'static str =
-        "invalid component for ?include= (expected 'versions', 'keywords', 'categories', 'badges', 'downloads', or 'full')";
+        "invalid component for ?include= (expected 'versions', 'keywords', 'categories', 'badges', 'downloads', 'delete_status', or 'full')";
 }
 
 impl FromStr for ShowIncludeMode {
@@ -280,6 +435,7 @@ impl FromStr for ShowIncludeMode {
             categories: false,
             badges: false,
             downloads: false,
+            delete_status: false,
         };
         for component in s.split(',') {
             match component {
@@ -291,6 +447,7 @@ impl FromStr for ShowIncludeMode {
                         categories: true,
                         badges: true,
                         downloads: true,
+                        delete_status: mode.delete_status,
                     }
                 }
                 "versions" => mode.versions = true,
@@ -298,6 +455,7 @@ impl FromStr for ShowIncludeMode {
                 "categories" => mode.categories = true,
                 "badges" => mode.badges = true,
                 "downloads" => mode.downloads = true,
+                "delete_status" => mode.delete_status = true,
                 _ => return Err(bad_request(Self::INVALID_COMPONENT)),
             }
         }
@@ -306,31 +464,91 @@ impl FromStr for ShowIncludeMode {
 }
 
 /// Handles the `GET /crates/:crate_id/:version/readme` route.
+///
+/// JSON requests get the URL of the rendered README, which is what the frontend uses. All other
+/// requests get the rendered HTML itself, or a `404 Not Found` if no README was rendered for the
+/// version. The `ETag` of the HTML changes whenever the README is rendered again.
 pub async fn readme(
     app: AppState,
     Path((crate_name, version)): Path<(String, String)>,
     req: Parts,
-) -> Response {
-    let redirect_url = app.config.uploader().readme_location(&crate_name, &version);
+) -> AppResult<Response> {
     if req.wants_json() {
-        Json(json!({ "url": redirect_url })).into_response()
-    } else {
-        redirect(redirect_url)
+        let url = app.config.uploader().readme_location(&crate_name, &version);
+        return Ok(Json(json!({ "url": url })).into_response());
     }
+
+    conduit_compat(move || {
+        let conn = &mut *app.db_read()?;
+        let krate: Crate = Crate::by_name(&crate_name)
+            .first(conn)
+            .optional()?
+            .ok_or_else(|| Crate::not_found(conn, &crate_name))?;
+
+        let rendered_at: NaiveDateTime = readme_renderings::table
+            .inner_join(versions::table)
+            .filter(versions::crate_id.eq(krate.id))
+            .filter(versions::num.eq(&version))
+            .select(readme_renderings::rendered_at)
+            .first(conn)?;
+
+        let etag = WeakEtag::new(rendered_at.timestamp_nanos());
+        conditional_response(&req, etag, || {
+            let html = app
+                .config
+                .uploader()
+                .download_readme(app.http_client(), &krate.name, &version)?
+                .ok_or_else(not_found)?;
+
+            let headers = [
+                (header::CONTENT_TYPE, "text/html; charset=utf-8"),
+                // READMEs are replaced when they are rendered again, so caches have to
+                // revalidate the HTML with its `ETag` instead of keeping it for a while
+                (header::CACHE_CONTROL, "no-cache"),
+                // The HTML is sanitized when rendering, but it still shouldn't run with the
+                // origin of the API
+                (header::CONTENT_SECURITY_POLICY, "sandbox"),
+            ];
+            Ok((headers, html))
+        })
+    })
+    .await
 }
 
 /// Handles the `GET /crates/:crate_id/versions` route.
+///
+/// The versions are sorted by their semver precedence, highest first. `include_yanked` selects
+/// whether yanked versions are listed, see `YankedFilter`.
 // FIXME: Not sure why this is necessary since /crates/:crate_id returns
 // this information already, but ember is definitely requesting it
-pub async fn versions(state: AppState, Path(crate_name): Path<String>) -> AppResult<Json<Value>> {
+pub async fn versions(
+    state: AppState,
+    Path(crate_name): Path<String>,
+    req: Parts,
+) -> AppResult<Json<Value>> {
     conduit_compat(move || {
+        let yanked_filter = req
+            .query()
+            .get("include_yanked")
+            .map(|filter| YankedFilter::from_str(filter))
+            .transpose()?
+            .unwrap_or_default();
+
         let conn = &mut *state.db_read()?;
         let krate: Crate = Crate::by_name(&crate_name).first(conn)?;
-        let mut versions_and_publishers: Vec<(Version, Option<User>)> = krate
-            .all_versions()
+        let mut query = versions::table
             .left_outer_join(users::table)
+            .filter(versions::crate_id.eq(krate.id))
             .select((versions::all_columns, users::all_columns.nullable()))
-            .load(conn)?;
+            .into_boxed();
+
+        query = match yanked_filter {
+            YankedFilter::Include => query,
+            YankedFilter::Exclude => query.filter(versions::yanked.eq(false)),
+            YankedFilter::Only => query.filter(versions::yanked.eq(true)),
+        };
+
+        let mut versions_and_publishers: Vec<(Version, Option<User>)> = query.load(conn)?;
 
         versions_and_publishers
             .sort_by_cached_key(|(version, _)| Reverse(semver::Version::parse(&version.num).ok()));
@@ -351,6 +569,94 @@ pub async fn versions(state: AppState, Path(crate_name): Path<String>) -> AppRes
     .await
 }
 
+/// The `include_yanked` parameter of `GET /crates/:crate_id/versions`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum YankedFilter {
+    /// `true`: All versions are listed, which is what clients got before the parameter existed.
+    #[default]
+    Include,
+    /// `false`: Only versions that can be installed are listed.
+    Exclude,
+    /// `only`: Only yanked versions are listed.
+    Only,
+}
+
+impl FromStr for YankedFilter {
+    type Err = BoxedAppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "true" => Ok(Self::Include),
+            "false" => Ok(Self::Exclude),
+            "only" => Ok(Self::Only),
+            _ => Err(bad_request(
+                "invalid value for ?include_yanked= (expected 'true', 'false', or 'only')",
+            )),
+        }
+    }
+}
+
+/// Handles the `GET /crates/:crate_id/categories` route.
+///
+/// Unlike the `categories` of `GET /crates/:crate_id`, each category includes the crates of its
+/// subcategories in `subtree_crates_cnt`, and its parent categories are listed from the
+/// top-level category down.
+pub async fn categories(
+    app: AppState,
+    Path(name): Path<String>,
+    req: Parts,
+) -> AppResult<Localized<Json<Value>>> {
+    conduit_compat(move || {
+        let conn = &mut *app.db_read()?;
+        let krate: Crate = Crate::by_name(&name).first(conn)?;
+
+        let locales = requested_locales(&req);
+        let mut categories = Category::for_crate(conn, krate.id)?;
+        let crate_categories = categories.iter_mut().map(|c| &mut c.category);
+        Category::localize_descriptions(conn, crate_categories, &locales)?;
+
+        let categories = categories
+            .into_iter()
+            .map(|category| {
+                let mut parents = category.category.parent_categories(conn)?;
+                let parent_categories = parents.iter_mut().map(|c| &mut c.category);
+                Category::localize_descriptions(conn, parent_categories, &locales)?;
+
+                let subtree_crates_cnt = category.subtree_crates_cnt;
+                let category = EncodableCategory::from(category.category);
+                Ok(EncodableCrateCategory {
+                    id: category.id,
+                    category: category.category,
+                    slug: category.slug,
+                    description: category.description,
+                    created_at: category.created_at,
+                    crates_cnt: category.crates_cnt,
+                    subtree_crates_cnt,
+                    parent_categories: parents.into_iter().map(EncodableCategory::from).collect(),
+                })
+            })
+            .collect::<QueryResult<Vec<_>>>()?;
+
+        Ok(Localized(Json(json!({ "categories": categories }))))
+    })
+    .await
+}
+
+/// Handles the `GET /crates/:crate_id/reverse_dependencies/count` route.
+pub async fn reverse_dependencies_count(
+    app: AppState,
+    Path(name): Path<String>,
+) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        let conn = &mut *app.db_read()?;
+        let krate: Crate = Crate::by_name(&name).first(conn)?;
+        let (crates, versions) = krate.reverse_dependencies_count(conn)?;
+
+        Ok(Json(json!({ "crates": crates, "versions": versions })))
+    })
+    .await
+}
+
 /// Handles the `GET /crates/:crate_id/reverse_dependencies` route.
 pub async fn reverse_dependencies(
     app: AppState,
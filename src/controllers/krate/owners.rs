@@ -1,10 +1,14 @@
 //! All routes related to managing owners of a crate
 
 use crate::auth::AuthCheck;
+use crate::controllers::krate::delete::deletion_blockers;
 use crate::controllers::prelude::*;
 use crate::models::token::EndpointScope;
-use crate::models::{Crate, Owner, Rights, Team, User};
-use crate::views::EncodableOwner;
+use crate::models::{
+    Crate, CrateOwnerInvitation, NewCrateOwnerInvitationOutcome, Owner, Rights, Team, User,
+};
+use crate::schema::crate_owner_invitations;
+use crate::views::{EncodableCratePermissions, EncodableOwner};
 use axum::body::Bytes;
 use http::Request;
 
@@ -24,6 +28,31 @@ pub async fn owners(state: AppState, Path(crate_name): Path<String>) -> AppResul
     .await
 }
 
+/// Handles the `GET /crates/:crate_id/me/permissions` route.
+///
+/// Returns the rights of the authenticated user on the crate, and which actions they allow.
+pub async fn permissions(
+    app: AppState,
+    Path(crate_name): Path<String>,
+    req: Parts,
+) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        let conn = &mut *app.db_read_prefer_primary()?;
+        let auth = AuthCheck::only_cookie().check(&req, conn)?;
+
+        let krate: Crate = Crate::by_name(&crate_name).first(conn)?;
+        let owners = krate.owners(conn)?;
+        let rights = auth.user().rights(&app, &owners)?;
+        // The same checks as for `DELETE /crates/:crate_id`, which include the age of the crate
+        let can_delete = deletion_blockers(&app, conn, &krate, rights)?.is_empty();
+
+        Ok(Json(json!({
+            "permissions": EncodableCratePermissions::new(rights, can_delete),
+        })))
+    })
+    .await
+}
+
 /// Handles the `GET /crates/:crate_id/owner_team` route.
 pub async fn owner_team(state: AppState, Path(crate_name): Path<String>) -> AppResult<Json<Value>> {
     conduit_compat(move || {
@@ -103,7 +132,7 @@ fn modify_owners(
     let logins = parse_owners_request(req)?;
 
     let conn = &mut *app.db_write()?;
-    let auth = AuthCheck::default()
+    let auth = AuthCheck::cookie_or_token()
         .with_endpoint_scope(EndpointScope::ChangeOwners)
         .for_crate(crate_name)
         .check(req, conn)?;
@@ -156,3 +185,93 @@ fn modify_owners(
         Ok(Json(json!({ "ok": true, "msg": comma_sep_msg })))
     })
 }
+
+/// Handles the `POST /crates/:crate_id/transfer` route.
+///
+/// Invites the given user to take over the crate. Unlike the invitations of
+/// `PUT /crates/:crate_id/owners`, accepting this one also removes the authenticated user from the
+/// owners of the crate, in the same transaction that adds the new owner. The format of the request
+/// body is:
+///
+/// ```json
+/// {"owner": "username"}
+/// ```
+pub async fn transfer_ownership(
+    app: AppState,
+    Path(crate_name): Path<String>,
+    req: BytesRequest,
+) -> AppResult<Json<Value>> {
+    #[derive(Deserialize)]
+    struct TransferRequest {
+        owner: String,
+    }
+
+    conduit_compat(move || {
+        let login = serde_json::from_slice::<TransferRequest>(req.body())
+            .map_err(|_| cargo_err("invalid json request"))?
+            .owner;
+
+        let conn = &mut *app.db_write()?;
+        let auth = AuthCheck::cookie_or_token()
+            .with_endpoint_scope(EndpointScope::ChangeOwners)
+            .for_crate(&crate_name)
+            .check(&req, conn)?;
+
+        let user = auth.user();
+
+        conn.transaction(|conn| {
+            let krate: Crate = Crate::by_name(&crate_name).first(conn)?;
+            let owners = krate.owners(conn)?;
+
+            match user.rights(&app, &owners)? {
+                Rights::Full => {}
+                Rights::Publish => {
+                    return Err(cargo_err(
+                        "team members don't have permission to transfer crates",
+                    ));
+                }
+                Rights::None => {
+                    return Err(cargo_err("only owners have permission to transfer crates"));
+                }
+            }
+
+            // Teams can't be the only remaining owner, since their members can't modify owners
+            if login.contains(':') {
+                return Err(cargo_err("crates can only be transferred to users"));
+            }
+            let new_owner = match Owner::find_or_create_by_login(&app, conn, user, &login)? {
+                Owner::User(new_owner) => new_owner,
+                Owner::Team(_) => unreachable!("team logins are rejected above"),
+            };
+            if new_owner.id == user.id {
+                return Err(cargo_err("cannot transfer a crate to yourself"));
+            }
+
+            // A pending invitation to become a regular owner is replaced by the transfer
+            diesel::delete(crate_owner_invitations::table.find((new_owner.id, krate.id)))
+                .execute(conn)?;
+
+            let config = &app.config;
+            let outcome =
+                CrateOwnerInvitation::create(new_owner.id, user.id, krate.id, true, conn, config)?;
+            if let NewCrateOwnerInvitationOutcome::InviteCreated { plaintext_token } = outcome {
+                if let Ok(Some(email)) = new_owner.verified_email(conn) {
+                    // Swallow any error, the invitation is listed on the website as well
+                    let _ = app.emails.send_ownership_transfer_invite(
+                        &email,
+                        &user.gh_login,
+                        &krate.name,
+                        &plaintext_token,
+                    );
+                }
+            }
+
+            let msg = format!(
+                "user {} has been invited to take over crate {}",
+                new_owner.gh_login, krate.name
+            );
+            Ok(Json(json!({ "ok": true, "msg": msg })))
+        })
+    })
+    .await
+}
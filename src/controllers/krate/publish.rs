@@ -6,23 +6,25 @@ use flate2::read::GzDecoder;
 use hex::ToHex;
 use hyper::body::Buf;
 use sha2::{Digest, Sha256};
-use std::collections::BTreeMap;
 use std::io::Read;
 use std::path::Path;
 
 use crate::controllers::cargo_prelude::*;
 use crate::controllers::util::RequestPartsExt;
 use crate::models::{
-    insert_version_owner_action, Category, Crate, DependencyKind, Keyword, NewCrate, NewVersion,
-    Rights, VersionAction,
+    insert_version_owner_action, Category, Crate, CrateCategory, CrateWebhook, DependencyKind,
+    Keyword, NewCrate, NewVersion, VersionAction, WebhookEvent,
 };
 use crate::worker;
 
 use crate::middleware::log_request::RequestLogExt;
+use crate::middleware::rate_limit::RequestRateLimiterExt;
+use crate::models::krate::{split_index_features, validate_crate_name, validate_dependency_name};
 use crate::models::token::EndpointScope;
+use crate::rate_limiter::LimitedAction;
 use crate::schema::*;
 use crate::util::errors::{cargo_err, AppResult};
-use crate::util::{CargoVcsInfo, LimitErrorReader, Maximums};
+use crate::util::{CargoVcsInfo, LimitErrorReader, Manifest, Maximums};
 use crate::views::{
     EncodableCrate, EncodableCrateDependency, EncodableCrateUpload, GoodCrate, PublishWarnings,
 };
@@ -90,7 +92,11 @@ pub async fn publish(app: AppState, req: BytesRequest) -> AppResult<Json<GoodCra
             None => EndpointScope::PublishNew,
         };
 
-        let auth = AuthCheck::default()
+        if existing_crate.is_none() {
+            validate_crate_name(conn, &new_crate.name, app.config.max_crate_name_length)?;
+        }
+
+        let auth = AuthCheck::cookie_or_token()
             .with_endpoint_scope(endpoint_scope)
             .for_crate(&new_crate.name)
             .check(&req, conn)?;
@@ -143,14 +149,15 @@ pub async fn publish(app: AppState, req: BytesRequest) -> AppResult<Json<GoodCra
             };
 
             let license_file = new_crate.license_file.as_deref();
-            let krate =
-                persist.create_or_update(conn, user.id, Some(&app.config.publish_rate_limit))?;
+            let krate = persist.create_or_update(conn, user.id, Some(req.rate_limiter()))?;
 
             let owners = krate.owners(conn)?;
-            if user.rights(&app, &owners)? < Rights::Publish {
+            if !user.rights(&app, &owners)?.can_publish() {
                 return Err(cargo_err(MISSING_RIGHTS_ERROR_MESSAGE));
             }
 
+            krate.ensure_not_frozen(conn)?;
+
             if krate.name != *name {
                 return Err(cargo_err(&format_args!(
                     "crate was previously named `{}`",
@@ -188,6 +195,10 @@ pub async fn publish(app: AppState, req: BytesRequest) -> AppResult<Json<GoodCra
             // Read tarball from request
             let hex_cksum: String = Sha256::digest(&tarball_bytes).encode_hex();
 
+            let pkg_name = format!("{}-{}", krate.name, vers);
+            let tarball_info = verify_tarball(&pkg_name, &tarball_bytes, maximums.max_unpack_size)?;
+            let rust_version = tarball_info.manifest.rust_version().map(String::from);
+
             // Persist the new version of this crate
             let version = NewVersion::new(
                 krate.id,
@@ -201,6 +212,7 @@ pub async fn publish(app: AppState, req: BytesRequest) -> AppResult<Json<GoodCra
                 user.id,
                 hex_cksum.clone(),
                 links.clone(),
+                rust_version,
             )?
             .save(conn, &verified_email_address)?;
 
@@ -210,24 +222,65 @@ pub async fn publish(app: AppState, req: BytesRequest) -> AppResult<Json<GoodCra
                 user.id,
                 api_token_id,
                 VersionAction::Publish,
+                None,
             )?;
 
             // Link this new version to all dependencies
-            let git_deps = add_dependencies(conn, &new_crate.deps, version.id)?;
+            let git_deps = add_dependencies(
+                conn,
+                &new_crate.deps,
+                version.id,
+                app.config.max_crate_name_length,
+            )?;
 
             // Update all keywords for this crate
             Keyword::update_crate(conn, &krate, &keywords)?;
 
+            let crate_categories: Vec<Category> = categories::table
+                .filter(categories::slug.eq_any(&categories))
+                .order(categories::slug)
+                .load(conn)?;
+
+            // Replacing the categories of a crate rewrites all of its `crates_categories` rows,
+            // so changing them is rate limited. Publishing with unchanged categories is not.
+            let current_categories: Vec<String> = CrateCategory::belonging_to(&krate)
+                .inner_join(categories::table)
+                .order(categories::slug)
+                .select(categories::slug)
+                .load(conn)?;
+            if !current_categories
+                .iter()
+                .eq(crate_categories.iter().map(|category| &category.slug))
+            {
+                req.rate_limiter().check_rate_limit(
+                    user.id,
+                    LimitedAction::UpdateCategories,
+                    conn,
+                )?;
+            }
+
             // Update all categories for this crate, collecting any invalid categories
             // in order to be able to warn about them
-            let ignored_invalid_categories = Category::update_crate(conn, &krate, &categories)?;
+            let max_categories = app.config.max_categories_per_crate;
+            let ignored_invalid_categories =
+                Category::update_crate(conn, &krate, &categories, max_categories)?;
+
+            // Regenerate the feeds of all categories the new version shows up in, which
+            // includes the parents of this crate's categories
+            for category in crate_categories {
+                for parent in category.parent_categories(conn)? {
+                    worker::sync_category_feed(parent.category.slug).enqueue(conn)?;
+                }
+                worker::sync_category_feed(category.slug).enqueue(conn)?;
+            }
+
+            if app.config.feeds.user_feed_length > 0 {
+                worker::sync_user_feed(user.id).enqueue(conn)?;
+            }
 
             let top_versions = krate.top_versions(conn)?;
 
-            let pkg_name = format!("{}-{}", krate.name, vers);
-            let cargo_vcs_info =
-                verify_tarball(&pkg_name, &tarball_bytes, maximums.max_unpack_size)?;
-            let pkg_path_in_vcs = cargo_vcs_info.map(|info| info.path_in_vcs);
+            let pkg_path_in_vcs = tarball_info.vcs_info.map(|info| info.path_in_vcs);
 
             if let Some(readme) = new_crate.readme {
                 worker::render_and_upload_readme(
@@ -247,17 +300,7 @@ pub async fn publish(app: AppState, req: BytesRequest) -> AppResult<Json<GoodCra
                 .uploader()
                 .upload_crate(app.http_client(), tarball_bytes, &krate, vers)?;
 
-            let (features, features2): (BTreeMap<_, _>, BTreeMap<_, _>) =
-                features.into_iter().partition(|(_k, vals)| {
-                    !vals
-                        .iter()
-                        .any(|v| v.starts_with("dep:") || v.contains("?/"))
-                });
-            let (features2, v) = if features2.is_empty() {
-                (None, None)
-            } else {
-                (Some(features2), Some(2))
-            };
+            let (features, features2, v) = split_index_features(features);
 
             // Register this crate in our local git repo.
             let git_crate = cargo_registry_index::Crate {
@@ -273,6 +316,9 @@ pub async fn publish(app: AppState, req: BytesRequest) -> AppResult<Json<GoodCra
             };
             worker::add_crate(git_crate).enqueue(conn)?;
 
+            let data = json!({ "version": vers });
+            CrateWebhook::enqueue_dispatch(conn, &krate, WebhookEvent::Published, data)?;
+
             // The `other` field on `PublishWarnings` was introduced to handle a temporary warning
             // that is no longer needed. As such, crates.io currently does not return any `other`
             // warnings at this time, but if we need to, the field is available.
@@ -349,6 +395,7 @@ pub fn add_dependencies(
     conn: &mut PgConnection,
     deps: &[EncodableCrateDependency],
     target_version_id: i32,
+    max_name_length: usize,
 ) -> AppResult<Vec<cargo_registry_index::Dependency>> {
     use self::dependencies::dsl::*;
     use diesel::insert_into;
@@ -362,6 +409,10 @@ pub fn add_dependencies(
                 }
             }
 
+            if let Some(explicit_name_in_toml) = &dep.explicit_name_in_toml {
+                validate_dependency_name(explicit_name_in_toml, max_name_length)?;
+            }
+
             // Match only identical names to ensure the index always references the original crate name
             let krate:Crate = Crate::by_exact_name(&dep.name)
                 .first(conn)
@@ -419,11 +470,14 @@ pub fn add_dependencies(
     Ok(git_deps)
 }
 
-fn verify_tarball(
-    pkg_name: &str,
-    tarball: &[u8],
-    max_unpack: u64,
-) -> AppResult<Option<CargoVcsInfo>> {
+/// The relevant metadata extracted from an uploaded crate tarball
+#[derive(Debug, Default)]
+struct TarballInfo {
+    vcs_info: Option<CargoVcsInfo>,
+    manifest: Manifest,
+}
+
+fn verify_tarball(pkg_name: &str, tarball: &[u8], max_unpack: u64) -> AppResult<TarballInfo> {
     // All our data is currently encoded with gzip
     let decoder = GzDecoder::new(tarball);
 
@@ -435,7 +489,8 @@ fn verify_tarball(
     let mut archive = tar::Archive::new(decoder);
 
     let vcs_info_path = Path::new(&pkg_name).join(".cargo_vcs_info.json");
-    let mut vcs_info = None;
+    let manifest_path = Path::new(&pkg_name).join("Cargo.toml");
+    let mut info = TarballInfo::default();
 
     for entry in archive.entries()? {
         let mut entry = entry.map_err(|err| {
@@ -456,7 +511,11 @@ fn verify_tarball(
         if entry_path == vcs_info_path {
             let mut contents = String::new();
             entry.read_to_string(&mut contents)?;
-            vcs_info = CargoVcsInfo::from_contents(&contents).ok();
+            info.vcs_info = CargoVcsInfo::from_contents(&contents).ok();
+        } else if entry_path == manifest_path {
+            let mut contents = String::new();
+            entry.read_to_string(&mut contents)?;
+            info.manifest = Manifest::from_contents(&contents).unwrap_or_default();
         }
 
         // Historical versions of the `tar` crate which Cargo uses internally
@@ -469,7 +528,7 @@ fn verify_tarball(
             return Err(cargo_err("invalid tarball uploaded"));
         }
     }
-    Ok(vcs_info)
+    Ok(info)
 }
 
 #[cfg(test)]
@@ -496,9 +555,10 @@ mod tests {
             .unwrap();
 
         let limit = 512 * 1024 * 1024;
-        assert_eq!(
-            verify_tarball("foo-0.0.1", &serialized_archive, limit).unwrap(),
-            None
+        assert_none!(
+            verify_tarball("foo-0.0.1", &serialized_archive, limit)
+                .unwrap()
+                .vcs_info
         );
         assert_err!(verify_tarball("bar-0.0.1", &serialized_archive, limit));
     }
@@ -519,6 +579,7 @@ mod tests {
         let limit = 512 * 1024 * 1024;
         let vcs_info = verify_tarball("foo-0.0.1", &serialized_archive, limit)
             .unwrap()
+            .vcs_info
             .unwrap();
         assert_eq!(vcs_info.path_in_vcs, "");
     }
@@ -539,7 +600,25 @@ mod tests {
         let limit = 512 * 1024 * 1024;
         let vcs_info = verify_tarball("foo-0.0.1", &serialized_archive, limit)
             .unwrap()
+            .vcs_info
             .unwrap();
         assert_eq!(vcs_info.path_in_vcs, "path/in/vcs");
     }
+
+    #[test]
+    fn verify_tarball_test_manifest() {
+        let mut pkg = tar::Builder::new(vec![]);
+        add_file(
+            &mut pkg,
+            "foo-0.0.1/Cargo.toml",
+            b"[package]\nname = \"foo\"\nrust-version = \"1.59\"\n",
+        );
+        let mut serialized_archive = vec![];
+        GzEncoder::new(pkg.into_inner().unwrap().as_slice(), Default::default())
+            .read_to_end(&mut serialized_archive)
+            .unwrap();
+        let limit = 512 * 1024 * 1024;
+        let info = verify_tarball("foo-0.0.1", &serialized_archive, limit).unwrap();
+        assert_eq!(info.manifest.rust_version(), Some("1.59"));
+    }
 }
@@ -61,6 +61,7 @@ pub async fn search(app: AppState, req: Parts) -> AppResult<Json<Value>> {
         );
         let mut query = crates::table
             .left_join(recent_crate_downloads::table)
+            .filter(crates::deleted_at.is_null())
             .select(selection)
             .into_boxed();
 
@@ -190,7 +191,7 @@ pub async fn search(app: AppState, req: Parts) -> AppResult<Json<Value>> {
             // Calculating the total number of results with filters is not supported yet.
             supports_seek = false;
 
-            let user_id = AuthCheck::default().check(&req, conn)?.user_id();
+            let user_id = AuthCheck::cookie_or_token().check(&req, conn)?.user_id();
 
             query = query.filter(
                 crates::id.eq_any(
@@ -280,7 +281,10 @@ pub async fn search(app: AppState, req: Parts) -> AppResult<Json<Value>> {
             //
             // If this becomes a problem in the future the crates count could be denormalized, at least
             // for the filterless happy path.
-            let total: i64 = crates::table.count().get_result(conn)?;
+            let total: i64 = crates::table
+                .filter(crates::deleted_at.is_null())
+                .count()
+                .get_result(conn)?;
 
             let results: Vec<(Crate, bool, Option<i64>)> = query.load(conn)?;
 
@@ -0,0 +1,168 @@
+//! Endpoints for owners to manage the webhooks of a crate
+
+use crate::auth::AuthCheck;
+use crate::controllers::frontend_prelude::*;
+use crate::models::{Crate, CrateWebhook, NewCrateWebhook, Rights, User, WebhookEvent};
+use crate::schema::crate_webhooks;
+use crate::util::errors::not_found;
+use crate::util::public_url;
+use crate::views::EncodableCrateWebhook;
+use url::Url;
+
+#[derive(Deserialize)]
+struct WebhookRequest {
+    url: String,
+    secret: String,
+    events: Vec<WebhookEvent>,
+}
+
+impl WebhookRequest {
+    fn from_body(body: &[u8]) -> AppResult<Self> {
+        let request: WebhookRequest = serde_json::from_slice(body)
+            .map_err(|err| bad_request(&format_args!("invalid json request: {err}")))?;
+
+        let url = Url::parse(&request.url)
+            .ok()
+            .filter(|url| matches!(url.scheme(), "http" | "https"))
+            .ok_or_else(|| bad_request("the webhook url must be an http or https url"))?;
+        // The hostname is resolved again before each delivery, since its addresses can change
+        if !public_url::is_public_url(&url) {
+            return Err(bad_request("the webhook url must point to a public host"));
+        }
+        if request.secret.is_empty() {
+            return Err(bad_request("the webhook secret must not be empty"));
+        }
+        if request.events.is_empty() {
+            return Err(bad_request(
+                "a webhook must subscribe to at least one event",
+            ));
+        }
+
+        Ok(request)
+    }
+
+    fn as_new_webhook(&self, crate_id: i32) -> NewCrateWebhook<'_> {
+        NewCrateWebhook {
+            crate_id,
+            url: &self.url,
+            secret: &self.secret,
+            events: WebhookEvent::mask(&self.events),
+        }
+    }
+}
+
+/// Loads the crate and checks that `user` is allowed to manage its webhooks.
+///
+/// Webhook URLs and secrets are only visible to owners, since they could be used to
+/// impersonate crates.io towards the receiver.
+fn crate_managed_by(
+    app: &AppState,
+    conn: &mut PgConnection,
+    user: &User,
+    crate_name: &str,
+) -> AppResult<Crate> {
+    let krate: Crate = Crate::by_name(crate_name).first(conn)?;
+    let owners = krate.owners(conn)?;
+
+    match user.rights(app, &owners)? {
+        Rights::Full => Ok(krate),
+        Rights::Publish => Err(cargo_err(
+            "team members don't have permission to manage webhooks",
+        )),
+        Rights::None => Err(cargo_err("only owners have permission to manage webhooks")),
+    }
+}
+
+/// Handles the `GET /crates/:crate_id/webhooks` route.
+pub async fn list(
+    app: AppState,
+    Path(crate_name): Path<String>,
+    req: Parts,
+) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        let conn = &mut *app.db_read_prefer_primary()?;
+        let auth = AuthCheck::only_cookie().check(&req, conn)?;
+        let krate = crate_managed_by(&app, conn, auth.user(), &crate_name)?;
+
+        let webhooks = CrateWebhook::belonging_to(&krate)
+            .order(crate_webhooks::id)
+            .load::<CrateWebhook>(conn)?
+            .into_iter()
+            .map(EncodableCrateWebhook::from)
+            .collect::<Vec<_>>();
+
+        Ok(Json(json!({ "webhooks": webhooks })))
+    })
+    .await
+}
+
+/// Handles the `POST /crates/:crate_id/webhooks` route.
+pub async fn create(
+    app: AppState,
+    Path(crate_name): Path<String>,
+    req: BytesRequest,
+) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        let request = WebhookRequest::from_body(req.body())?;
+
+        let conn = &mut *app.db_write()?;
+        let auth = AuthCheck::only_cookie().check(&req, conn)?;
+        let krate = crate_managed_by(&app, conn, auth.user(), &crate_name)?;
+
+        let webhook: CrateWebhook = diesel::insert_into(crate_webhooks::table)
+            .values(request.as_new_webhook(krate.id))
+            .get_result(conn)?;
+
+        Ok(Json(json!({
+            "webhook": EncodableCrateWebhook::from(webhook)
+        })))
+    })
+    .await
+}
+
+/// Handles the `PUT /crates/:crate_id/webhooks/:webhook_id` route.
+pub async fn update(
+    app: AppState,
+    Path((crate_name, webhook_id)): Path<(String, i32)>,
+    req: BytesRequest,
+) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        let request = WebhookRequest::from_body(req.body())?;
+
+        let conn = &mut *app.db_write()?;
+        let auth = AuthCheck::only_cookie().check(&req, conn)?;
+        let krate = crate_managed_by(&app, conn, auth.user(), &crate_name)?;
+
+        let webhook: CrateWebhook =
+            diesel::update(CrateWebhook::belonging_to(&krate).find(webhook_id))
+                .set(request.as_new_webhook(krate.id))
+                .get_result(conn)?;
+
+        Ok(Json(json!({
+            "webhook": EncodableCrateWebhook::from(webhook)
+        })))
+    })
+    .await
+}
+
+/// Handles the `DELETE /crates/:crate_id/webhooks/:webhook_id` route.
+pub async fn delete(
+    app: AppState,
+    Path((crate_name, webhook_id)): Path<(String, i32)>,
+    req: Parts,
+) -> AppResult<Response> {
+    conduit_compat(move || {
+        let conn = &mut *app.db_write()?;
+        let auth = AuthCheck::only_cookie().check(&req, conn)?;
+        let krate = crate_managed_by(&app, conn, auth.user(), &crate_name)?;
+
+        let deleted =
+            diesel::delete(CrateWebhook::belonging_to(&krate).find(webhook_id)).execute(conn)?;
+        if deleted == 0 {
+            return Err(not_found());
+        }
+
+        ok_true()
+    })
+    .await
+}
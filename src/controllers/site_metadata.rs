@@ -1,13 +1,17 @@
 use crate::app::AppState;
+use crate::controllers::conduit_axum::spawn_blocking;
 use axum::response::IntoResponse;
 use axum::Json;
+use std::panic::resume_unwind;
 
 /// Returns the JSON representation of the current deployed commit sha.
 ///
 /// The sha is contained within the `HEROKU_SLUG_COMMIT` environment variable.
 /// If `HEROKU_SLUG_COMMIT` is not set, returns `"unknown"`.
 pub async fn show_deployed_sha(state: AppState) -> impl IntoResponse {
-    let read_only = state.config.db.are_all_read_only();
+    let read_only = spawn_blocking(move || state.is_read_only())
+        .await
+        .unwrap_or_else(|error| resume_unwind(error.into_panic()));
 
     let deployed_sha =
         dotenv::var("HEROKU_SLUG_COMMIT").unwrap_or_else(|_| String::from("unknown"));
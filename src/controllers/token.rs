@@ -7,6 +7,7 @@ use crate::views::EncodableApiTokenWithToken;
 use crate::auth::AuthCheck;
 use crate::models::token::{CrateScope, EndpointScope};
 use axum::response::IntoResponse;
+use chrono::{Duration, Utc};
 use serde_json as json;
 
 /// Handles the `GET /me/tokens` route.
@@ -35,6 +36,7 @@ pub async fn new(app: AppState, req: BytesRequest) -> AppResult<Json<Value>> {
             name: String,
             crate_scopes: Option<Vec<String>>,
             endpoint_scopes: Option<Vec<String>>,
+            expires_in_days: Option<u32>,
         }
 
         /// The incoming serialization format for the `ApiToken` model.
@@ -53,7 +55,7 @@ pub async fn new(app: AppState, req: BytesRequest) -> AppResult<Json<Value>> {
 
         let conn = &mut *app.db_write()?;
 
-        let auth = AuthCheck::default().check(&req, conn)?;
+        let auth = AuthCheck::cookie_or_token().check(&req, conn)?;
         if auth.api_token_id().is_some() {
             return Err(bad_request(
                 "cannot use an API token to create a new API token",
@@ -94,8 +96,26 @@ pub async fn new(app: AppState, req: BytesRequest) -> AppResult<Json<Value>> {
             .transpose()
             .map_err(|_err| bad_request("invalid endpoint scope"))?;
 
-        let api_token =
-            ApiToken::insert_with_scopes(conn, user.id, name, crate_scopes, endpoint_scopes)?;
+        let expired_at = match new.api_token.expires_in_days {
+            Some(0) => return Err(bad_request("expires_in_days must be at least 1")),
+            Some(days) => {
+                let expired_at = Utc::now()
+                    .naive_utc()
+                    .checked_add_signed(Duration::days(days.into()))
+                    .ok_or_else(|| bad_request("expires_in_days is too large"))?;
+                Some(expired_at)
+            }
+            None => None,
+        };
+
+        let api_token = ApiToken::insert_with_scopes(
+            conn,
+            user.id,
+            name,
+            crate_scopes,
+            endpoint_scopes,
+            expired_at,
+        )?;
         let api_token = EncodableApiTokenWithToken::from(api_token);
 
         Ok(Json(json!({ "api_token": api_token })))
@@ -107,7 +127,7 @@ pub async fn new(app: AppState, req: BytesRequest) -> AppResult<Json<Value>> {
 pub async fn revoke(app: AppState, Path(id): Path<i32>, req: Parts) -> AppResult<Json<Value>> {
     conduit_compat(move || {
         let conn = &mut *app.db_write()?;
-        let auth = AuthCheck::default().check(&req, conn)?;
+        let auth = AuthCheck::cookie_or_token().check(&req, conn)?;
         let user = auth.user();
         diesel::update(ApiToken::belonging_to(user).find(id))
             .set(api_tokens::revoked.eq(true))
@@ -122,7 +142,7 @@ pub async fn revoke(app: AppState, Path(id): Path<i32>, req: Parts) -> AppResult
 pub async fn revoke_current(app: AppState, req: Parts) -> AppResult<Response> {
     conduit_compat(move || {
         let conn = &mut *app.db_write()?;
-        let auth = AuthCheck::default().check(&req, conn)?;
+        let auth = AuthCheck::only_token().check(&req, conn)?;
         let api_token_id = auth
             .api_token_id()
             .ok_or_else(|| bad_request("token not provided"))?;
@@ -1,3 +1,4 @@
+pub mod export;
 pub mod me;
 pub mod other;
 pub mod session;
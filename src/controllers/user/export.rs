@@ -0,0 +1,223 @@
+//! Endpoint for exporting the metadata of all crates of a user at once, e.g. for backups
+
+use crate::auth::AuthCheck;
+use crate::controllers::conduit_axum::spawn_blocking;
+use crate::controllers::frontend_prelude::*;
+use crate::middleware::rate_limit::RequestRateLimiterExt;
+use crate::models::{Crate, CrateOwner, Owner, OwnerKind};
+use crate::rate_limiter::LimitedAction;
+use crate::schema::{categories, crate_owners, crates, crates_categories, crates_keywords};
+use crate::schema::{keywords, versions};
+use crate::util::rfc3339;
+use axum::body::{boxed, Bytes};
+use chrono::NaiveDateTime;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use hyper::body::{Body, Sender};
+use std::io::{self, Write};
+use tokio::runtime::Handle;
+
+#[derive(Serialize)]
+struct ExportedCrate {
+    name: String,
+    description: Option<String>,
+    homepage: Option<String>,
+    documentation: Option<String>,
+    repository: Option<String>,
+    #[serde(with = "rfc3339")]
+    created_at: NaiveDateTime,
+    #[serde(with = "rfc3339")]
+    updated_at: NaiveDateTime,
+    versions: Vec<ExportedVersion>,
+    categories: Vec<String>,
+    keywords: Vec<String>,
+    owners: Vec<ExportedOwner>,
+}
+
+#[derive(Queryable, Serialize)]
+struct ExportedVersion {
+    num: String,
+    #[serde(with = "rfc3339")]
+    created_at: NaiveDateTime,
+    yanked: bool,
+    license: Option<String>,
+    checksum: String,
+    crate_size: Option<i32>,
+}
+
+#[derive(Serialize)]
+struct ExportedOwner {
+    login: String,
+    kind: &'static str,
+}
+
+/// Handles the `GET /me/crates/export` route.
+///
+/// Responds with a `.tar.gz` archive that contains a `{crate}.json` file for each crate that the
+/// user owns directly, with its versions, categories, keywords and owners. The `.crate` files
+/// are not included.
+///
+/// Like `GET /crates/:crate_id/versions/export`, the crates are loaded one by one while the
+/// archive is being sent, without holding on to a database connection in between, and the
+/// response is aborted if the export fails halfway through.
+pub async fn export(app: AppState, req: Parts) -> AppResult<Response> {
+    let user_id = conduit_compat({
+        let app = app.clone();
+        move || {
+            let conn = &mut *app.db_write()?;
+            let user_id = AuthCheck::only_cookie().check(&req, conn)?.user_id();
+
+            req.rate_limiter()
+                .check_rate_limit(user_id, LimitedAction::ExportCrates, conn)?;
+
+            Ok(user_id)
+        }
+    })
+    .await?;
+
+    let (sender, body) = Body::channel();
+    let handle = Handle::current();
+    spawn_blocking(move || {
+        if let Err(error) = send_archive(&app, &handle, user_id, sender) {
+            warn!(%error, user_id, "Failed to export the crates of a user");
+        }
+    });
+
+    let headers = [
+        (header::CONTENT_TYPE, "application/gzip"),
+        (
+            header::CONTENT_DISPOSITION,
+            "attachment; filename=\"crates.tar.gz\"",
+        ),
+    ];
+    Ok((headers, boxed(body)).into_response())
+}
+
+fn send_archive(
+    app: &AppState,
+    handle: &Handle,
+    user_id: i32,
+    mut sender: Sender,
+) -> AppResult<()> {
+    let result = (|| {
+        let krates: Vec<Crate> = {
+            let conn = &mut *app.db_read()?;
+
+            let owned_crates = CrateOwner::by_owner_kind(OwnerKind::User)
+                .filter(crate_owners::owner_id.eq(user_id))
+                .select(crate_owners::crate_id);
+
+            Crate::all()
+                .filter(crates::id.eq_any(owned_crates))
+                .order(crates::name)
+                .load(conn)?
+        };
+
+        let writer = BodyWriter {
+            handle,
+            sender: &mut sender,
+        };
+        let mut archive = tar::Builder::new(GzEncoder::new(writer, Compression::default()));
+
+        for krate in krates {
+            let mtime = krate.updated_at.timestamp();
+            let path = format!("{}.json", krate.name);
+            // The connection is returned to the pool before the crate is sent, so that slow
+            // clients can't exhaust the pool
+            let json = {
+                let conn = &mut *app.db_read()?;
+                serde_json::to_vec_pretty(&load_crate(conn, krate)?)?
+            };
+
+            let mut header = tar::Header::new_gnu();
+            header.set_size(json.len() as u64);
+            header.set_mode(0o644);
+            header.set_mtime(mtime as u64);
+            archive.append_data(&mut header, path, &*json)?;
+        }
+
+        archive.into_inner()?.finish()?;
+        Ok(())
+    })();
+
+    if result.is_err() {
+        sender.abort();
+    }
+    result
+}
+
+fn load_crate(conn: &mut PgConnection, krate: Crate) -> AppResult<ExportedCrate> {
+    let versions = versions::table
+        .filter(versions::crate_id.eq(krate.id))
+        .select((
+            versions::num,
+            versions::created_at,
+            versions::yanked,
+            versions::license,
+            versions::checksum,
+            versions::crate_size,
+        ))
+        .order(versions::id)
+        .load(conn)?;
+
+    let categories = crates_categories::table
+        .filter(crates_categories::crate_id.eq(krate.id))
+        .inner_join(categories::table)
+        .select(categories::slug)
+        .order(categories::slug)
+        .load(conn)?;
+
+    let keywords = crates_keywords::table
+        .filter(crates_keywords::crate_id.eq(krate.id))
+        .inner_join(keywords::table)
+        .select(keywords::keyword)
+        .order(keywords::keyword)
+        .load(conn)?;
+
+    let owners = krate
+        .owners(conn)?
+        .into_iter()
+        .map(|owner| {
+            let kind = match owner {
+                Owner::User(_) => "user",
+                Owner::Team(_) => "team",
+            };
+            let login = owner.login().to_string();
+            ExportedOwner { login, kind }
+        })
+        .collect();
+
+    Ok(ExportedCrate {
+        name: krate.name,
+        description: krate.description,
+        homepage: krate.homepage,
+        documentation: krate.documentation,
+        repository: krate.repository,
+        created_at: krate.created_at,
+        updated_at: krate.updated_at,
+        versions,
+        categories,
+        keywords,
+        owners,
+    })
+}
+
+/// Sends everything that is written to it as part of the response body.
+struct BodyWriter<'a> {
+    handle: &'a Handle,
+    sender: &'a mut Sender,
+}
+
+impl Write for BodyWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let chunk = Bytes::copy_from_slice(buf);
+        self.handle
+            .block_on(self.sender.send_data(chunk))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "the client went away"))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -33,6 +33,7 @@ pub async fn me(app: AppState, req: Parts) -> AppResult<Json<EncodableMe>> {
         let owned_crates = CrateOwner::by_owner_kind(OwnerKind::User)
             .inner_join(crates::table)
             .filter(crate_owners::owner_id.eq(user_id))
+            .filter(crates::deleted_at.is_null())
             .select((crates::id, crates::name, crate_owners::email_notifications))
             .order(crates::name.asc())
             .load(conn)?
@@ -66,6 +67,7 @@ pub async fn updates(app: AppState, req: Parts) -> AppResult<Json<Value>> {
             .inner_join(crates::table)
             .left_outer_join(users::table)
             .filter(crates::id.eq_any(followed_crates))
+            .filter(crates::deleted_at.is_null())
             .order(versions::created_at.desc())
             .select((
                 versions::all_columns,
@@ -109,7 +111,7 @@ pub async fn update_user(
         let state = app.clone();
         let conn = &mut state.db_write()?;
 
-        let auth = AuthCheck::default().check(&req, conn)?;
+        let auth = AuthCheck::cookie_or_token().check(&req, conn)?;
         let user = auth.user();
 
         // need to check if current user matches user to be updated
@@ -202,7 +204,7 @@ pub async fn regenerate_token_and_send(
 
         let conn = &mut state.db_write()?;
 
-        let auth = AuthCheck::default().check(&req, conn)?;
+        let auth = AuthCheck::cookie_or_token().check(&req, conn)?;
         let user = auth.user();
 
         // need to check if current user matches user to be updated
@@ -246,7 +248,7 @@ pub async fn update_email_notifications(app: AppState, req: BytesRequest) -> App
                 .collect();
 
         let conn = &mut *app.db_write()?;
-        let user_id = AuthCheck::default().check(&req, conn)?.user_id();
+        let user_id = AuthCheck::cookie_or_token().check(&req, conn)?.user_id();
 
         // Build inserts from existing crates belonging to the current user
         let to_insert = CrateOwner::by_owner_kind(OwnerKind::User)
@@ -1,7 +1,7 @@
 use crate::controllers::frontend_prelude::*;
 
-use crate::models::{CrateOwner, OwnerKind, User};
-use crate::schema::{crate_owners, crates, users};
+use crate::models::{Crate, CrateOwner, OwnerKind, User};
+use crate::schema::{crate_owners, crates, teams, users};
 use crate::sql::lower;
 use crate::views::EncodablePublicUser;
 
@@ -22,6 +22,68 @@ pub async fn show(state: AppState, Path(user_name): Path<String>) -> AppResult<J
     .await
 }
 
+/// Handles the `GET /users/:user_id/crates` route.
+///
+/// Lists the crates that a user or a team owns, given its login, and whether it's the only
+/// owner of each of them. This helps with moving crates over to new owners.
+pub async fn crates(state: AppState, Path(login): Path<String>) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        let conn = &mut *state.db_read_prefer_primary()?;
+        let login = login.to_lowercase();
+
+        let user_id: Option<i32> = users::table
+            .filter(lower(users::gh_login).eq(&login))
+            .order(users::id.desc())
+            .select(users::id)
+            .first(conn)
+            .optional()?;
+        // Team logins look like `github:org:team`, so they can't clash with user logins
+        let (owner_kind, owner_id) = match user_id {
+            Some(user_id) => (OwnerKind::User, user_id),
+            None => {
+                let team_id = teams::table
+                    .filter(lower(teams::login).eq(&login))
+                    .select(teams::id)
+                    .first(conn)?;
+                (OwnerKind::Team, team_id)
+            }
+        };
+
+        let krates: Vec<(i32, String)> = CrateOwner::by_owner_kind(owner_kind)
+            .inner_join(crates::table)
+            .filter(crate_owners::owner_id.eq(owner_id))
+            .filter(crates::deleted_at.is_null())
+            .order(crates::name)
+            .select((crates::id, crates::name))
+            .load(conn)?;
+
+        let crate_ids = krates.iter().map(|&(id, _)| id).collect::<Vec<_>>();
+        let owners = Crate::owners_for_many(&crate_ids, conn)?;
+
+        #[derive(Serialize)]
+        struct OwnedCrate {
+            name: String,
+            owner_kind: &'static str,
+            sole_owner: bool,
+        }
+
+        let crates = krates
+            .into_iter()
+            .map(|(id, name)| OwnedCrate {
+                name,
+                owner_kind: match owner_kind {
+                    OwnerKind::User => "user",
+                    OwnerKind::Team => "team",
+                },
+                sole_owner: owners.get(&id).map_or(false, |owners| owners.len() == 1),
+            })
+            .collect::<Vec<_>>();
+
+        Ok(Json(json!({ "crates": crates })))
+    })
+    .await
+}
+
 /// Handles the `GET /users/:user_id/stats` route.
 pub async fn stats(state: AppState, Path(user_id): Path<i32>) -> AppResult<Json<Value>> {
     conduit_compat(move || {
@@ -1,5 +1,6 @@
 use crate::controllers::frontend_prelude::*;
 
+use chrono::Utc;
 use oauth2::reqwest::http_client;
 use oauth2::{AuthorizationCode, Scope, TokenResponse};
 
@@ -106,6 +107,10 @@ pub async fn authorize(
 
         // Log in by setting a cookie and the middleware authentication
         session.insert("user_id".to_string(), user.id.to_string());
+        session.insert(
+            "authenticated_at".to_string(),
+            Utc::now().timestamp().to_string(),
+        );
 
         Ok(req)
     })
@@ -147,6 +152,7 @@ fn save_user_to_database(
 /// Handles the `DELETE /api/private/session` route.
 pub async fn logout(session: SessionExtension) -> Json<bool> {
     session.remove("user_id");
+    session.remove("authenticated_at");
     Json(true)
 }
 
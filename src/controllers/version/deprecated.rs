@@ -31,6 +31,7 @@ pub async fn index(app: AppState, req: Parts) -> AppResult<Json<Value>> {
                 users::all_columns.nullable(),
             ))
             .filter(versions::id.eq_any(ids))
+            .filter(crates::deleted_at.is_null())
             .load(conn)?;
         let versions = versions_and_publishers
             .iter()
@@ -60,6 +61,7 @@ pub async fn show_by_id(state: AppState, Path(id): Path<i32>) -> AppResult<Json<
             .find(id)
             .inner_join(crates::table)
             .left_outer_join(users::table)
+            .filter(crates::deleted_at.is_null())
             .select((
                 versions::all_columns,
                 crate::models::krate::ALL_COLUMNS,
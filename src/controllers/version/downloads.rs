@@ -61,6 +61,7 @@ pub async fn download(
                             .inner_join(crates::table)
                             .select((id, crates::name))
                             .filter(Crate::with_name(&crate_name))
+                            .filter(crates::deleted_at.is_null())
                             .filter(num.eq(&version))
                             .first::<(i32, String)>(&mut *conn)
                     })?;
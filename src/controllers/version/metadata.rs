@@ -4,13 +4,21 @@
 //! index or cached metadata which was extracted (client side) from the
 //! `Cargo.toml` file.
 
+use std::collections::HashMap;
+
 use crate::controllers::frontend_prelude::*;
 
-use crate::models::VersionOwnerAction;
+use crate::models::{Crate, CrateVersions, Version, VersionOwnerAction};
+use crate::schema::{crates, versions};
+use crate::util::errors::not_found;
 use crate::views::{EncodableDependency, EncodableVersion};
 
 use super::version_and_crate;
 
+/// The depth of the dependency graph, if the request doesn't specify `max_depth`.
+const DEFAULT_DEPENDENCY_GRAPH_DEPTH: i32 = 10;
+const MAX_DEPENDENCY_GRAPH_DEPTH: i32 = 25;
+
 /// Handles the `GET /crates/:crate_id/:version/dependencies` route.
 ///
 /// This information can be obtained directly from the index.
@@ -40,6 +48,89 @@ pub async fn dependencies(
     .await
 }
 
+/// Handles the `GET /crates/:crate_id/:version/dependency_graph` route.
+///
+/// Returns the transitive normal and build dependencies of a version, with each dependency
+/// resolved to the highest non-yanked version of its crate that matches its version requirement.
+/// See `Version::dependency_graph()` for how this differs from Cargo's resolution. Versions that
+/// are depended on several times are only listed once. The `max_depth` query parameter limits how deep the graph is
+/// walked, and `meta.truncated` is set if any dependencies were left out because of it.
+pub async fn dependency_graph(
+    state: AppState,
+    Path((crate_name, version)): Path<(String, String)>,
+    req: Parts,
+) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        if semver::Version::parse(&version).is_err() {
+            return Err(cargo_err(&format_args!("invalid semver: {version}")));
+        }
+
+        let max_depth = match req.query().get("max_depth") {
+            Some(max_depth) => max_depth
+                .parse()
+                .ok()
+                .filter(|max_depth| (1..=MAX_DEPENDENCY_GRAPH_DEPTH).contains(max_depth))
+                .ok_or_else(|| {
+                    bad_request(&format_args!(
+                        "max_depth must be between 1 and {MAX_DEPENDENCY_GRAPH_DEPTH}"
+                    ))
+                })?,
+            None => DEFAULT_DEPENDENCY_GRAPH_DEPTH,
+        };
+
+        let conn = &mut *state.db_read()?;
+        let (version, _) = version_and_crate(conn, &crate_name, &version)?;
+
+        let (edges, truncated_edges): (Vec<_>, Vec<_>) = version
+            .dependency_graph(conn, max_depth)?
+            .into_iter()
+            .partition(|edge| edge.from_depth < max_depth);
+
+        // The edges are ordered by depth, so the first edge leading to a version is from the
+        // shallowest path
+        let mut depths = HashMap::from([(version.id, 0)]);
+        for edge in &edges {
+            depths
+                .entry(edge.to_version_id)
+                .or_insert(edge.from_depth + 1);
+        }
+
+        let mut nodes: Vec<(i32, String, String)> = versions::table
+            .inner_join(crates::table)
+            .filter(versions::id.eq_any(depths.keys()))
+            .filter(crates::deleted_at.is_null())
+            .select((versions::id, crates::name, versions::num))
+            .load(conn)?;
+        nodes.sort_by_cached_key(|(id, name, num)| (depths[id], name.clone(), num.clone()));
+
+        let nodes = nodes
+            .into_iter()
+            .map(|(id, name, num)| {
+                json!({ "version_id": id, "crate": name, "num": num, "depth": depths[&id] })
+            })
+            .collect::<Vec<_>>();
+        let edges = edges
+            .into_iter()
+            .map(|edge| {
+                json!({
+                    "from": edge.from_version_id,
+                    "to": edge.to_version_id,
+                    "req": edge.req,
+                    "kind": edge.kind,
+                    "optional": edge.optional,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        Ok(Json(json!({
+            "nodes": nodes,
+            "edges": edges,
+            "meta": { "max_depth": max_depth, "truncated": !truncated_edges.is_empty() },
+        })))
+    })
+    .await
+}
+
 /// Handles the `GET /crates/:crate_id/:version/authors` route.
 pub async fn authors() -> Json<Value> {
     // Currently we return the empty list.
@@ -74,3 +165,45 @@ pub async fn show(
     })
     .await
 }
+
+/// Handles the `GET /crates/:crate_id/latest` route.
+///
+/// Returns the highest version of the crate in semver order that isn't yanked. Pre-releases are
+/// only considered with `?include_prerelease=yes`.
+pub async fn latest(
+    state: AppState,
+    Path(crate_name): Path<String>,
+    req: Parts,
+) -> AppResult<Json<Value>> {
+    conduit_compat(move || {
+        let include_prerelease = req
+            .query()
+            .get("include_prerelease")
+            .map(|s| s == "yes")
+            .unwrap_or(false);
+
+        let conn = &mut *state.db_read()?;
+        let krate: Crate = Crate::by_name(&crate_name).first(conn)?;
+        let versions: Vec<Version> = krate
+            .all_versions()
+            .filter(versions::yanked.eq(false))
+            .load(conn)?;
+
+        let version = versions
+            .into_iter()
+            .filter_map(|version| {
+                let num = semver::Version::parse(&version.num).ok()?;
+                (include_prerelease || num.pre.is_empty()).then_some((num, version))
+            })
+            .max_by(|(a, _), (b, _)| a.cmp(b))
+            .map(|(_, version)| version)
+            .ok_or_else(not_found)?;
+
+        let published_by = version.published_by(conn);
+        let actions = VersionOwnerAction::by_version(conn, &version)?;
+
+        let version = EncodableVersion::from(version, &krate.name, published_by, actions);
+        Ok(Json(json!({ "version": version })))
+    })
+    .await
+}
@@ -5,8 +5,8 @@ use crate::auth::AuthCheck;
 use super::version_and_crate;
 use crate::controllers::cargo_prelude::*;
 use crate::models::token::EndpointScope;
-use crate::models::Rights;
 use crate::models::{insert_version_owner_action, VersionAction};
+use crate::models::{Crate, CrateWebhook, Version, WebhookEvent};
 use crate::schema::versions;
 use crate::worker;
 
@@ -53,7 +53,7 @@ fn modify_yank(
 
     let conn = &mut *state.db_write()?;
 
-    let auth = AuthCheck::default()
+    let auth = AuthCheck::cookie_or_token()
         .with_endpoint_scope(EndpointScope::Yank)
         .for_crate(crate_name)
         .check(req, conn)?;
@@ -63,16 +63,36 @@ fn modify_yank(
     let user = auth.user();
     let owners = krate.owners(conn)?;
 
-    if user.rights(state, &owners)? < Rights::Publish {
+    if !user.rights(state, &owners)?.can_yank() {
         return Err(cargo_err("must already be an owner to yank or unyank"));
     }
 
+    krate.ensure_not_frozen(conn)?;
+
+    perform_version_yank(conn, &krate, &version, user.id, api_token_id, None, yanked)?;
+
+    ok_true()
+}
+
+/// Sets the `yanked` flag of the version, records the change in the audit actions and
+/// enqueues the index and webhook updates.
+///
+/// No permission checks are done here, which is up to the caller.
+pub(crate) fn perform_version_yank(
+    conn: &mut PgConnection,
+    krate: &Crate,
+    version: &Version,
+    user_id: i32,
+    api_token_id: Option<i32>,
+    reason: Option<&str>,
+    yanked: bool,
+) -> AppResult<()> {
     if version.yanked == yanked {
         // The crate is already in the state requested, nothing to do
-        return ok_true();
+        return Ok(());
     }
 
-    diesel::update(&version)
+    diesel::update(version)
         .set(versions::yanked.eq(yanked))
         .execute(conn)?;
 
@@ -82,9 +102,14 @@ fn modify_yank(
         VersionAction::Unyank
     };
 
-    insert_version_owner_action(conn, version.id, user.id, api_token_id, action)?;
+    insert_version_owner_action(conn, version.id, user_id, api_token_id, action, reason)?;
 
-    worker::sync_yanked(krate.name, version.num).enqueue(conn)?;
+    if yanked {
+        let data = json!({ "version": version.num });
+        CrateWebhook::enqueue_dispatch(conn, krate, WebhookEvent::Yanked, data)?;
+    }
 
-    ok_true()
+    worker::sync_yanked(krate.name.clone(), version.num.clone()).enqueue(conn)?;
+
+    Ok(())
 }
@@ -91,6 +91,47 @@ or go to https://{domain}/me/pending-invites to manage all of your crate ownersh
         self.send(email, subject, &body)
     }
 
+    /// Attempts to send an invitation to take over the ownership of a crate.
+    pub fn send_ownership_transfer_invite(
+        &self,
+        email: &str,
+        user_name: &str,
+        crate_name: &str,
+        token: &str,
+    ) -> AppResult<()> {
+        let subject = "Crate ownership transfer";
+        let body = format!(
+            "{user_name} wants to transfer the crate {crate_name} to you!
+Accepting this invitation makes you an owner of the crate, and removes {user_name} from its owners.\n
+Visit https://{domain}/accept-invite/{token} to accept this invitation,
+or go to https://{domain}/me/pending-invites to manage all of your crate ownership invitations.",
+            domain = crate::config::domain_name()
+        );
+
+        self.send(email, subject, &body)
+    }
+
+    /// Attempts to send a notification that the ownership of a crate was transferred.
+    ///
+    /// The same notification is sent to both the previous and the new owner.
+    pub fn send_ownership_transfer(
+        &self,
+        email: &str,
+        crate_name: &str,
+        previous_owner: &str,
+        new_owner: &str,
+    ) -> AppResult<()> {
+        let subject = "Crate ownership transferred";
+        let body = format!(
+            "{previous_owner} has transferred their ownership of the crate {crate_name}
+to {new_owner}.\n
+Visit https://{domain}/crates/{crate_name} to see the current owners of the crate.",
+            domain = crate::config::domain_name()
+        );
+
+        self.send(email, subject, &body)
+    }
+
     /// Attempts to send an API token exposure notification email
     pub fn send_token_exposed_notification(
         &self,
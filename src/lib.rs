@@ -8,6 +8,8 @@
 // `diesel` macros are currently generating code that breaks this rule, so
 // we have to disable it for now.
 #![allow(clippy::extra_unused_lifetimes)]
+// The `table!` macro for `crates` exceeds the default limit since it has many documented columns
+#![recursion_limit = "256"]
 
 #[cfg(test)]
 #[macro_use]
@@ -46,7 +48,7 @@ pub mod github;
 pub mod headers;
 pub mod metrics;
 pub mod middleware;
-mod publish_rate_limit;
+pub mod rate_limiter;
 pub mod schema;
 pub mod sql;
 pub mod ssh;
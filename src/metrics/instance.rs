@@ -56,6 +56,9 @@ metrics! {
         pub version_id_cache_hits: IntCounter,
         /// Number of version ID cache misses on the download endpoint.
         pub version_id_cache_misses: IntCounter,
+
+        /// Number of crate deletion requests, by whether they succeeded or what blocked them
+        pub crate_deletions_total: IntCounterVec["outcome"],
     }
 
     // All instance metrics will be prefixed with this namespace.
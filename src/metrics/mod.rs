@@ -1,6 +1,7 @@
 pub use self::instance::InstanceMetrics;
 pub use self::log_encoder::LogEncoder;
 pub use self::service::ServiceMetrics;
+pub use self::worker::WorkerMetrics;
 
 #[macro_use]
 mod macros;
@@ -8,3 +9,4 @@ mod macros;
 mod instance;
 mod log_encoder;
 mod service;
+mod worker;
@@ -0,0 +1,27 @@
+//! This module defines all the background worker metrics of crates.io.
+//!
+//! Worker metrics are collected separately for each background worker process, and are updated
+//! by the job runner every time a job finishes running. Individual jobs should not need to update
+//! them themselves.
+
+use prometheus::{proto::MetricFamily, HistogramVec, IntCounterVec};
+
+metrics! {
+    pub struct WorkerMetrics {
+        /// Time it took to run background jobs
+        pub job_duration: HistogramVec["job_type"],
+        /// Number of background jobs that ran successfully
+        pub jobs_succeeded_total: IntCounterVec["job_type"],
+        /// Number of background jobs that failed
+        pub jobs_failed_total: IntCounterVec["job_type"],
+    }
+
+    // All worker metrics will be prefixed with this namespace.
+    namespace: "cratesio_worker",
+}
+
+impl WorkerMetrics {
+    pub fn gather(&self) -> Vec<MetricFamily> {
+        self.registry.gather()
+    }
+}
@@ -2,10 +2,13 @@ pub mod app;
 mod balance_capacity;
 mod block_traffic;
 mod debug;
+pub mod deprecation;
 mod ember_html;
 mod head;
 pub mod log_request;
 pub mod normalize_path;
+mod prefer;
+pub mod rate_limit;
 mod require_user_agent;
 mod sentry;
 pub mod session;
@@ -65,9 +68,18 @@ pub fn apply_axum_middleware(state: AppState, router: Router) -> Router {
             state.clone(),
             block_traffic::block_routes,
         ))
+        .layer(from_fn_with_state(
+            state.clone(),
+            block_traffic::block_writes_in_read_only_mode,
+        ))
         .layer(from_fn(head::support_head_requests))
-        .layer(conditional_layer(env == Env::Development, || {
-            from_fn(static_or_continue::serve_local_uploads)
+        .layer(from_fn(prefer::apply_return_preference))
+        .layer(from_fn_with_state(
+            state.clone(),
+            rate_limit::add_rate_limit_headers,
+        ))
+        .layer(conditional_layer(env != Env::Test, || {
+            from_fn_with_state(state.clone(), static_or_continue::serve_local_uploads)
         }))
         // Serve the static files in the *dist* directory, which are the frontend assets.
         // Not needed for the backend tests.
@@ -86,8 +98,14 @@ pub fn apply_axum_middleware(state: AppState, router: Router) -> Router {
         // Because such a large portion of production traffic is for download requests (which update
         // download counts), we consider only the primary pool here.
         .layer(conditional_layer(capacity >= 10, || {
-            from_fn_with_state(state, balance_capacity::balance_capacity)
-        }));
+            from_fn_with_state(state.clone(), balance_capacity::balance_capacity)
+        }))
+        // Authenticating the request to check whether it is anonymous requires the app state
+        // extension and a database connection, so this runs after the middleware above.
+        .layer(from_fn_with_state(
+            state,
+            rate_limit::limit_anonymous_requests,
+        ));
 
     router.layer(middleware)
 }
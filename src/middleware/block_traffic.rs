@@ -9,12 +9,14 @@
 //! examples). Values of the headers must match exactly.
 
 use crate::app::AppState;
+use crate::controllers::conduit_axum::spawn_blocking;
 use crate::middleware::log_request::RequestLogExt;
-use crate::util::errors::RouteBlocked;
+use crate::util::errors::{AppError, ReadOnlyMode, RouteBlocked};
 use axum::extract::MatchedPath;
 use axum::middleware::Next;
 use axum::response::IntoResponse;
 use http::StatusCode;
+use std::panic::resume_unwind;
 
 pub async fn block_traffic<B>(
     state: AppState,
@@ -71,3 +73,33 @@ pub async fn block_routes<B>(
 
     next.run(req).await
 }
+
+/// Rejects all requests that could write to the database while an admin enabled read-only mode,
+/// except for the request that disables it again.
+pub async fn block_writes_in_read_only_mode<B>(
+    matched_path: Option<MatchedPath>,
+    state: AppState,
+    req: http::Request<B>,
+    next: Next<B>,
+) -> axum::response::Response {
+    let is_safe_method = matches!(
+        *req.method(),
+        http::Method::GET | http::Method::HEAD | http::Method::OPTIONS
+    );
+    let is_toggle = matched_path.map_or(false, |path| path.as_str() == "/api/v1/admin/read_only");
+
+    if is_safe_method || is_toggle {
+        return next.run(req).await;
+    }
+
+    let is_admin_read_only = spawn_blocking(move || state.is_admin_read_only())
+        .await
+        .unwrap_or_else(|error| resume_unwind(error.into_panic()));
+    if is_admin_read_only {
+        req.request_log()
+            .add("cause", "read-only mode enabled by an admin");
+        return ReadOnlyMode.response();
+    }
+
+    next.run(req).await
+}
@@ -0,0 +1,63 @@
+//! Middleware that marks the responses of deprecated routes with the `Deprecation`, `Sunset`
+//! (RFC 8594) and `Link` headers, so that clients can migrate before the route is removed.
+
+use axum::extract::State;
+use axum::middleware::Next;
+use axum::response::Response;
+use chrono::NaiveDate;
+use http::{header, HeaderValue, Request};
+
+/// A deprecated route, with the day after which it might be removed and the route replacing it.
+///
+/// It is applied to a route with
+/// `get(handler).layer(from_fn_with_state(deprecation, add_deprecation_headers))`.
+#[derive(Clone, Debug)]
+pub struct Deprecation {
+    sunset: HeaderValue,
+    link: HeaderValue,
+}
+
+impl Deprecation {
+    pub fn new(sunset: NaiveDate, successor: &str) -> Self {
+        let sunset = sunset
+            .and_hms_opt(0, 0, 0)
+            .expect("midnight is a valid time")
+            .format("%a, %d %b %Y %H:%M:%S GMT")
+            .to_string();
+        let link = format!("<{successor}>; rel=\"successor-version\"");
+
+        Self {
+            sunset: HeaderValue::try_from(sunset).expect("HTTP dates are valid header values"),
+            link: HeaderValue::try_from(link).expect("invalid successor route"),
+        }
+    }
+}
+
+pub async fn add_deprecation_headers<B>(
+    State(deprecation): State<Deprecation>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let mut response = next.run(request).await;
+
+    let headers = response.headers_mut();
+    headers.insert("deprecation", HeaderValue::from_static("true"));
+    headers.insert("sunset", deprecation.sunset);
+    headers.append(header::LINK, deprecation.link);
+
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Deprecation;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn headers_are_formatted() {
+        let sunset = NaiveDate::from_ymd_opt(2027, 6, 30).unwrap();
+        let deprecation = Deprecation::new(sunset, "/api/v2/foo");
+        assert_eq!(deprecation.sunset, "Wed, 30 Jun 2027 00:00:00 GMT");
+        assert_eq!(deprecation.link, "</api/v2/foo>; rel=\"successor-version\"");
+    }
+}
@@ -0,0 +1,72 @@
+//! Middleware that honors the `Prefer: return=minimal` header of RFC 7240
+//!
+//! Clients that don't need the JSON body of a successful write request can send this header to
+//! get a `204 No Content` instead. `Prefer: return=representation`, or no preference at all, keeps
+//! the full body.
+//!
+//! The preference is ignored for requests that only read, like `GET`, whose body is the whole
+//! point of the request, and for the failures of `cargo_err` and `cargo_errs`, which have a status
+//! of 200.
+
+use axum::body::BoxBody;
+use axum::middleware::Next;
+use axum::response::Response;
+use http::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use http::{HeaderMap, HeaderValue, Method, Request, StatusCode};
+
+use crate::util::errors::CargoErrorResponse;
+
+const PREFERENCE_APPLIED: &str = "preference-applied";
+
+pub async fn apply_return_preference<B>(req: Request<B>, next: Next<B>) -> Response {
+    let minimal = is_write(req.method()) && prefers_minimal_return(req.headers());
+
+    let mut response = next.run(req).await;
+    if minimal && is_successful(&response) && is_json(response.headers()) {
+        *response.status_mut() = StatusCode::NO_CONTENT;
+        *response.body_mut() = BoxBody::default();
+
+        let headers = response.headers_mut();
+        headers.remove(CONTENT_TYPE);
+        headers.remove(CONTENT_LENGTH);
+        headers.insert(
+            PREFERENCE_APPLIED,
+            HeaderValue::from_static("return=minimal"),
+        );
+    }
+    response
+}
+
+/// Returns whether the first `return` preference in the `Prefer` headers is `minimal`.
+fn prefers_minimal_return(headers: &HeaderMap) -> bool {
+    headers
+        .get_all("prefer")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        // Parameters of a preference, like `; foo=bar`, don't matter here
+        .filter_map(|preference| preference.split(';').next())
+        .filter_map(|preference| preference.split_once('='))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("return"))
+        .map_or(false, |(_, value)| {
+            value
+                .trim()
+                .trim_matches('"')
+                .eq_ignore_ascii_case("minimal")
+        })
+}
+
+fn is_write(method: &Method) -> bool {
+    !matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+}
+
+fn is_successful(response: &Response) -> bool {
+    response.status().is_success() && response.extensions().get::<CargoErrorResponse>().is_none()
+}
+
+fn is_json(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map_or(false, |value| value.starts_with("application/json"))
+}
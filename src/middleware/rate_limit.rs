@@ -0,0 +1,163 @@
+//! Reports the state of the rate limits that were checked during a request in the
+//! `X-RateLimit-*` response headers, and limits anonymous requests to expensive routes.
+
+use axum::extract::{ConnectInfo, MatchedPath, State};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use diesel::PgConnection;
+use http::{header, HeaderValue, Request};
+use parking_lot::Mutex;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::panic::resume_unwind;
+use std::sync::Arc;
+
+use crate::app::AppState;
+use crate::auth::AuthCheck;
+use crate::controllers::conduit_axum::spawn_blocking;
+use crate::controllers::util::RequestPartsExt;
+use crate::middleware::session::SessionExtension;
+use crate::rate_limiter::{LimitedAction, RateLimitStatus};
+use crate::util::errors::AppResult;
+
+/// The rate limiter, as seen by a single request.
+///
+/// Handlers should check rate limits through this type instead of `App::rate_limiter`, so that
+/// the remaining tokens are included in the response.
+#[derive(Clone)]
+pub struct RequestRateLimiter {
+    app: AppState,
+    status: Arc<Mutex<Option<RateLimitStatus>>>,
+}
+
+impl RequestRateLimiter {
+    pub fn check_rate_limit(
+        &self,
+        user_id: i32,
+        action: LimitedAction,
+        conn: &mut PgConnection,
+    ) -> AppResult<()> {
+        let status = self
+            .app
+            .rate_limiter
+            .check_rate_limit(user_id, action, conn)?;
+        *self.status.lock() = Some(status);
+        Ok(())
+    }
+}
+
+pub async fn add_rate_limit_headers<B>(
+    State(app): State<AppState>,
+    mut request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let status = Arc::new(Mutex::new(None));
+    request.extensions_mut().insert(RequestRateLimiter {
+        app,
+        status: status.clone(),
+    });
+
+    let mut response = next.run(request).await;
+
+    // Failed requests roll back their transaction, including the token that was taken
+    let status = *status.lock();
+    if let Some(status) = status.filter(|_| response.status().is_success()) {
+        let headers = response.headers_mut();
+        headers.insert("x-ratelimit-limit", HeaderValue::from(status.limit));
+        headers.insert("x-ratelimit-remaining", HeaderValue::from(status.remaining));
+        headers.insert(
+            "x-ratelimit-reset",
+            HeaderValue::from(status.reset.timestamp()),
+        );
+    }
+
+    response
+}
+
+/// The key of the bucket that is shared by all anonymous clients whose address is unknown.
+const UNKNOWN_CLIENT_IP: IpAddr = IpAddr::V4(Ipv4Addr::UNSPECIFIED);
+
+/// Limits the anonymous requests to the routes in `Server::anonymous_rate_limited_routes` per
+/// IP address of the client.
+///
+/// The IP address is read from the `Server::client_ip_header` set by the proxy in front of the
+/// application. Requests without a valid header are limited by the address of the peer instead,
+/// or share a single bucket if that isn't known either. Requests by authenticated users are not
+/// limited.
+pub async fn limit_anonymous_requests<B>(
+    matched_path: Option<MatchedPath>,
+    State(app): State<AppState>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let is_limited_route = matched_path.map_or(false, |path| {
+        app.config
+            .anonymous_rate_limited_routes
+            .contains(path.as_str())
+    });
+    if !is_limited_route {
+        return next.run(request).await;
+    }
+
+    let client_ip = request
+        .headers()
+        .get(&app.config.client_ip_header)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<IpAddr>().ok())
+        .or_else(|| {
+            let connect_info = request.extensions().get::<ConnectInfo<SocketAddr>>();
+            connect_info.map(|ConnectInfo(addr)| addr.ip())
+        })
+        .unwrap_or(UNKNOWN_CLIENT_IP);
+
+    let (request, is_authenticated) = is_authenticated(&app, request).await;
+    if !is_authenticated {
+        if let Err(error) = app.rate_limiter.check_anonymous_rate_limit(client_ip) {
+            return error.into_response();
+        }
+    }
+
+    next.run(request).await
+}
+
+/// Checks whether the request is authenticated by a session cookie or an API token.
+///
+/// Requests without either are considered anonymous without querying the database.
+async fn is_authenticated<B>(app: &AppState, request: Request<B>) -> (Request<B>, bool) {
+    let has_session_user = request
+        .extensions()
+        .get::<SessionExtension>()
+        .map_or(false, |session| session.get("user_id").is_some());
+    let has_token = request.headers().contains_key(header::AUTHORIZATION);
+    if !has_session_user && !has_token {
+        return (request, false);
+    }
+
+    let (parts, body) = request.into_parts();
+    let app = app.clone();
+    let result = spawn_blocking(move || {
+        // Requests are let through if the database is unavailable, like for the other routes
+        let is_authenticated = app.db_read_prefer_primary().map_or(true, |mut conn| {
+            AuthCheck::cookie_or_token()
+                .check(&parts, &mut conn)
+                .is_ok()
+        });
+        (parts, is_authenticated)
+    })
+    .await;
+
+    let (parts, is_authenticated) =
+        result.unwrap_or_else(|error| resume_unwind(error.into_panic()));
+    (Request::from_parts(parts, body), is_authenticated)
+}
+
+pub trait RequestRateLimiterExt {
+    fn rate_limiter(&self) -> &RequestRateLimiter;
+}
+
+impl<T: RequestPartsExt> RequestRateLimiterExt for T {
+    fn rate_limiter(&self) -> &RequestRateLimiter {
+        self.extensions()
+            .get::<RequestRateLimiter>()
+            .expect("Failed to find `RequestRateLimiter` request extension")
+    }
+}
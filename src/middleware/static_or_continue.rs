@@ -1,6 +1,8 @@
 //! This module implements middleware to serve static files from the
 //! specified directory.
 
+use crate::app::AppState;
+use crate::uploaders::Uploader;
 use axum::middleware::Next;
 use axum::response::Response;
 use http::{Method, Request, StatusCode};
@@ -8,8 +10,16 @@ use std::path::Path;
 use tower::ServiceExt;
 use tower_http::services::ServeDir;
 
-pub async fn serve_local_uploads<B>(request: Request<B>, next: Next<B>) -> Response {
-    serve("local_uploads", request, next).await
+/// Serves the files of the local storage backend, if it is configured.
+pub async fn serve_local_uploads<B>(
+    state: AppState,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    match state.config.uploader() {
+        Uploader::Local { root } => serve(root, request, next).await,
+        Uploader::S3 { .. } => next.run(request).await,
+    }
 }
 
 pub async fn serve_dist<B>(request: Request<B>, next: Next<B>) -> Response {
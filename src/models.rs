@@ -1,10 +1,15 @@
 pub use self::action::{insert_version_owner_action, VersionAction, VersionOwnerAction};
-pub use self::category::{Category, CrateCategory, NewCategory};
+pub use self::category::{
+    AssignableCategory, Category, CategoryCursor, CrateCategory, NewCategory, CategoryWithSubtree,
+};
+pub use self::crate_deletion::{CrateDeletion, NewCrateDeletion};
 pub use self::crate_owner_invitation::{CrateOwnerInvitation, NewCrateOwnerInvitationOutcome};
-pub use self::dependency::{Dependency, DependencyKind, ReverseDependency};
+pub use self::crate_webhook::{CrateWebhook, NewCrateWebhook, WebhookEvent};
+pub use self::dependency::{Dependency, DependencyGraphEdge, DependencyKind, ReverseDependency};
 pub use self::download::VersionDownload;
 pub use self::email::{Email, NewEmail};
 pub use self::follow::Follow;
+pub use self::idempotency_key::{IdempotencyKey, NewIdempotencyKey};
 pub use self::keyword::{CrateKeyword, Keyword};
 pub use self::krate::{Crate, CrateVersions, NewCrate, RecentCrateDownloads};
 pub use self::owner::{CrateOwner, Owner, OwnerKind};
@@ -18,11 +23,14 @@ pub mod helpers;
 
 mod action;
 pub mod category;
+mod crate_deletion;
 mod crate_owner_invitation;
+mod crate_webhook;
 pub mod dependency;
 mod download;
 mod email;
 mod follow;
+mod idempotency_key;
 mod keyword;
 pub mod krate;
 mod owner;
@@ -54,7 +54,7 @@ impl ToSql<Integer, Pg> for VersionAction {
     }
 }
 
-#[derive(Debug, Clone, Copy, Queryable, Identifiable, Associations)]
+#[derive(Debug, Clone, Queryable, Identifiable, Associations)]
 #[diesel(belongs_to(Version))]
 #[diesel(belongs_to(User, foreign_key = user_id))]
 #[diesel(belongs_to(ApiToken, foreign_key = api_token_id))]
@@ -66,6 +66,8 @@ pub struct VersionOwnerAction {
     pub api_token_id: Option<i32>,
     pub action: VersionAction,
     pub time: NaiveDateTime,
+    /// Only set for versions that were yanked by an admin.
+    pub reason: Option<String>,
 }
 
 impl VersionOwnerAction {
@@ -104,8 +106,9 @@ pub fn insert_version_owner_action(
     user_id_: i32,
     api_token_id_: Option<i32>,
     action_: VersionAction,
+    reason_: Option<&str>,
 ) -> QueryResult<VersionOwnerAction> {
-    use version_owner_actions::dsl::{action, api_token_id, user_id, version_id};
+    use version_owner_actions::dsl::{action, api_token_id, reason, user_id, version_id};
 
     diesel::insert_into(version_owner_actions::table)
         .values((
@@ -113,6 +116,7 @@ pub fn insert_version_owner_action(
             user_id.eq(user_id_),
             api_token_id.eq(api_token_id_),
             action.eq(action_),
+            reason.eq(reason_),
         ))
         .get_result(conn)
 }
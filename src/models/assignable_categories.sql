@@ -0,0 +1,14 @@
+SELECT
+  c.id,
+  c.category,
+  c.slug,
+  c.description,
+  c.crates_cnt,
+  sum(c2.crates_cnt)::int as subtree_crates_cnt,
+  c.created_at
+FROM categories as c
+INNER JOIN categories c2
+  ON c2.slug = c.slug
+  -- Not `LIKE`, since `_` in a slug would match any character
+  OR left(c2.slug, length(c.slug) + 2) = c.slug || '::'
+GROUP BY c.id
@@ -3,6 +3,10 @@ use diesel::{self, *};
 
 use crate::models::Crate;
 use crate::schema::*;
+use crate::util::errors::{bad_request, AppResult, TooManyCategories};
+
+/// The default for the maximum number of categories that a crate can be in.
+pub const MAX_CATEGORIES: usize = 5;
 
 #[derive(Clone, Identifiable, Queryable, QueryableByName, Debug)]
 #[diesel(table_name = categories)]
@@ -15,6 +19,36 @@ pub struct Category {
     pub created_at: NaiveDateTime,
 }
 
+/// A category together with the crates of its subcategories, as returned by all queries of
+/// `Category` that list categories, like `Category::toplevel`.
+#[derive(Clone, QueryableByName, Debug)]
+pub struct CategoryWithSubtree {
+    /// The category itself, with only its own crates in `crates_cnt`.
+    #[diesel(embed)]
+    pub category: Category,
+    /// The number of crates in the category and all of its subcategories.
+    #[diesel(sql_type = diesel::sql_types::Integer)]
+    pub subtree_crates_cnt: i32,
+}
+
+/// A category that crates can be added to, as returned by `Category::assignable`.
+#[derive(Clone, QueryableByName, Debug)]
+pub struct AssignableCategory {
+    /// The category itself, with only its own crates in `crates_cnt`.
+    #[diesel(embed)]
+    pub category: Category,
+    /// The number of crates in the category and all of its subcategories.
+    #[diesel(sql_type = diesel::sql_types::Integer)]
+    pub subtree_crates_cnt: i32,
+}
+
+impl AssignableCategory {
+    /// Returns how deeply the category is nested, which is `0` for top-level categories.
+    pub fn depth(&self) -> usize {
+        self.category.slug.matches("::").count()
+    }
+}
+
 type WithSlug<'a> = diesel::dsl::Eq<categories::slug, crate::sql::lower::HelperType<&'a str>>;
 type BySlug<'a> = diesel::dsl::Filter<categories::table, WithSlug<'a>>;
 
@@ -28,6 +62,18 @@ pub struct CrateCategory {
     category_id: i32,
 }
 
+impl CrateCategory {
+    fn for_crate(krate: &Crate, categories: &[Category]) -> Vec<CrateCategory> {
+        categories
+            .iter()
+            .map(|c| CrateCategory {
+                category_id: c.id,
+                crate_id: krate.id,
+            })
+            .collect()
+    }
+}
+
 impl Category {
     pub fn with_slug(slug: &str) -> WithSlug<'_> {
         categories::slug.eq(crate::sql::lower(slug))
@@ -37,28 +83,22 @@ impl Category {
         categories::table.filter(Self::with_slug(slug))
     }
 
+    /// Replaces the categories of the crate with the ones in `slugs`, and returns the slugs
+    /// that don't belong to any category.
+    ///
+    /// Fails without changing anything if the crate would be in more than `max_categories`
+    /// categories.
     pub fn update_crate(
         conn: &mut PgConnection,
         krate: &Crate,
         slugs: &[&str],
-    ) -> QueryResult<Vec<String>> {
+        max_categories: usize,
+    ) -> AppResult<Vec<String>> {
         conn.transaction(|conn| {
-            let categories: Vec<Category> = categories::table
-                .filter(categories::slug.eq_any(slugs))
-                .load(conn)?;
-            let invalid_categories = slugs
-                .iter()
-                .cloned()
-                .filter(|s| !categories.iter().any(|c| c.slug == *s))
-                .map(ToString::to_string)
-                .collect();
-            let crate_categories = categories
-                .iter()
-                .map(|c| CrateCategory {
-                    category_id: c.id,
-                    crate_id: krate.id,
-                })
-                .collect::<Vec<_>>();
+            lock_crate(conn, krate)?;
+            let (categories, invalid_categories) = Self::find_slugs(conn, slugs)?;
+            ensure_within_limit(categories.len(), max_categories)?;
+            let crate_categories = CrateCategory::for_crate(krate, &categories);
 
             delete(CrateCategory::belonging_to(krate)).execute(conn)?;
             insert_into(crates_categories::table)
@@ -68,6 +108,110 @@ impl Category {
         })
     }
 
+    /// Adds the categories in `slugs` to the crate, keeping the ones it already has.
+    ///
+    /// Like `update_crate`, this returns the slugs that don't belong to any category, and fails
+    /// if the crate would be in more than `max_categories` categories afterwards.
+    pub fn add_categories(
+        conn: &mut PgConnection,
+        krate: &Crate,
+        slugs: &[&str],
+        max_categories: usize,
+    ) -> AppResult<Vec<String>> {
+        conn.transaction(|conn| {
+            lock_crate(conn, krate)?;
+            let (mut categories, invalid_categories) = Self::find_slugs(conn, slugs)?;
+
+            // The trigger that updates `crates_cnt` runs before conflicts are detected, so
+            // categories that the crate already has must not be inserted again
+            let existing: Vec<i32> = CrateCategory::belonging_to(krate)
+                .select(crates_categories::category_id)
+                .load(conn)?;
+            categories.retain(|c| !existing.contains(&c.id));
+            ensure_within_limit(existing.len() + categories.len(), max_categories)?;
+
+            insert_into(crates_categories::table)
+                .values(&CrateCategory::for_crate(krate, &categories))
+                .on_conflict_do_nothing()
+                .execute(conn)?;
+            Ok(invalid_categories)
+        })
+    }
+
+    /// Removes the categories in `slugs` from the crate, keeping all other ones.
+    ///
+    /// Like `update_crate`, this returns the slugs that don't belong to any category.
+    pub fn remove_categories(
+        conn: &mut PgConnection,
+        krate: &Crate,
+        slugs: &[&str],
+    ) -> QueryResult<Vec<String>> {
+        let (categories, invalid_categories) = Self::find_slugs(conn, slugs)?;
+        let category_ids = categories.iter().map(|c| c.id).collect::<Vec<_>>();
+
+        delete(CrateCategory::belonging_to(krate))
+            .filter(crates_categories::category_id.eq_any(category_ids))
+            .execute(conn)?;
+        Ok(invalid_categories)
+    }
+
+    /// Loads the categories with the given slugs, and returns them together with the slugs that
+    /// don't belong to any category.
+    fn find_slugs(
+        conn: &mut PgConnection,
+        slugs: &[&str],
+    ) -> QueryResult<(Vec<Category>, Vec<String>)> {
+        let categories: Vec<Category> = categories::table
+            .filter(categories::slug.eq_any(slugs))
+            .load(conn)?;
+        let invalid_categories = slugs
+            .iter()
+            .cloned()
+            .filter(|s| !categories.iter().any(|c| c.slug == *s))
+            .map(ToString::to_string)
+            .collect();
+        Ok((categories, invalid_categories))
+    }
+
+    /// Replaces the descriptions of `categories` with their translation into the first of
+    /// `locales` that one exists for, which should be sorted by preference and lowercase.
+    ///
+    /// Categories without a matching translation keep their default English description.
+    pub fn localize_descriptions<'a>(
+        conn: &mut PgConnection,
+        categories: impl IntoIterator<Item = &'a mut Category>,
+        locales: &[String],
+    ) -> QueryResult<()> {
+        let categories = categories.into_iter().collect::<Vec<_>>();
+        if categories.is_empty() || locales.is_empty() {
+            return Ok(());
+        }
+
+        let ids = categories.iter().map(|c| c.id).collect::<Vec<_>>();
+        let translations: Vec<(i32, String, String)> = category_descriptions::table
+            .filter(category_descriptions::category_id.eq_any(ids))
+            .filter(category_descriptions::locale.eq_any(locales))
+            .select((
+                category_descriptions::category_id,
+                category_descriptions::locale,
+                category_descriptions::description,
+            ))
+            .load(conn)?;
+
+        for category in categories {
+            let translation = locales.iter().find_map(|locale| {
+                translations
+                    .iter()
+                    .find(|(id, l, _)| *id == category.id && l == locale)
+            });
+            if let Some((_, _, description)) = translation {
+                category.description = description.clone();
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn count_toplevel(conn: &mut PgConnection) -> QueryResult<i64> {
         use self::categories::dsl::*;
 
@@ -77,40 +221,160 @@ impl Category {
             .get_result(conn)
     }
 
+    /// Returns all categories, with the crates of their subcategories summed up in
+    /// `subtree_crates_cnt`.
+    ///
+    /// The categories are ordered like a tree: each category comes right before its
+    /// subcategories, and siblings are sorted by their slugs.
+    pub fn assignable(conn: &mut PgConnection) -> QueryResult<Vec<AssignableCategory>> {
+        let mut categories: Vec<AssignableCategory> =
+            sql_query(include_str!("assignable_categories.sql")).load(conn)?;
+
+        // Sorting the whole slugs would put `foo-bar` between `foo` and `foo::bar`
+        categories.sort_by(|a, b| {
+            let a = a.category.slug.split("::");
+            let b = b.category.slug.split("::");
+            a.cmp(b)
+        });
+        Ok(categories)
+    }
+
+    /// Returns the top-level categories, with the crates of their subcategories summed up in
+    /// `subtree_crates_cnt`.
+    ///
+    /// Sorting by `"crates"` puts the categories with the largest `subtree_crates_cnt` first.
     pub fn toplevel(
         conn: &mut PgConnection,
         sort: &str,
         limit: i64,
         offset: i64,
-    ) -> QueryResult<Vec<Category>> {
+    ) -> QueryResult<Vec<CategoryWithSubtree>> {
         use diesel::sql_types::Int8;
 
-        let sort_sql = match sort {
-            "crates" => "ORDER BY crates_cnt DESC",
-            _ => "ORDER BY category ASC",
+        sql_query(format!(include_str!("toplevel.sql"), sort_sql(sort)))
+            .bind::<Int8, _>(limit)
+            .bind::<Int8, _>(offset)
+            .load(conn)
+    }
+
+    /// Like `toplevel`, but returns the categories that come after `cursor` instead of skipping
+    /// over a number of them, so that categories created or removed between two pages don't
+    /// shift the following pages.
+    ///
+    /// Ties in the sort order are broken by id. A `cursor` for a different sort order than
+    /// `sort` is treated as if there was no cursor; callers should reject it first.
+    pub fn toplevel_after(
+        conn: &mut PgConnection,
+        sort: &str,
+        cursor: Option<&CategoryCursor>,
+        limit: i64,
+    ) -> QueryResult<Vec<CategoryWithSubtree>> {
+        use diesel::sql_types::{Int4, Int8, Text, Timestamp};
+
+        let cursor = cursor.filter(|cursor| cursor.matches_sort(sort));
+        let sql = |condition: &str| {
+            let clauses = format!("{condition} {}", seek_order_sql(sort));
+            format!(include_str!("toplevel_after.sql"), clauses)
         };
 
-        // Collect all the top-level categories and sum up the crates_cnt of
-        // the crates in all subcategories
-        sql_query(format!(include_str!("toplevel.sql"), sort_sql))
+        match cursor {
+            None => sql_query(sql("")).bind::<Int8, _>(limit).load(conn),
+            Some(CategoryCursor::Alpha(category, id)) => {
+                sql_query(sql("WHERE (category, id) > ($2, $3)"))
+                    .bind::<Int8, _>(limit)
+                    .bind::<Text, _>(category)
+                    .bind::<Int4, _>(id)
+                    .load(conn)
+            }
+            Some(CategoryCursor::Crates(subtree_crates_cnt, id)) => sql_query(sql(
+                "WHERE subtree_crates_cnt < $2 OR (subtree_crates_cnt = $2 AND id > $3)",
+            ))
+            .bind::<Int8, _>(limit)
+            .bind::<Int4, _>(subtree_crates_cnt)
+            .bind::<Int4, _>(id)
+            .load(conn),
+            Some(CategoryCursor::Recent(created_at, id)) => sql_query(sql(
+                "WHERE created_at < $2 OR (created_at = $2 AND id > $3)",
+            ))
+            .bind::<Int8, _>(limit)
+            .bind::<Timestamp, _>(created_at)
+            .bind::<Int4, _>(id)
+            .load(conn),
+        }
+    }
+
+    /// Returns the categories whose name contains `query`, ignoring case, with the crates of
+    /// their subcategories summed up in `subtree_crates_cnt`.
+    ///
+    /// Exact matches come first, followed by names that start with `query`, and then all other
+    /// matches. Categories are sorted by name within each of those groups.
+    pub fn search(
+        conn: &mut PgConnection,
+        query: &str,
+        limit: i64,
+        offset: i64,
+    ) -> QueryResult<Vec<CategoryWithSubtree>> {
+        use diesel::sql_types::{Int8, Text};
+
+        sql_query(include_str!("category_search.sql"))
+            .bind::<Text, _>(query)
             .bind::<Int8, _>(limit)
             .bind::<Int8, _>(offset)
             .load(conn)
     }
 
-    pub fn subcategories(&self, conn: &mut PgConnection) -> QueryResult<Vec<Category>> {
-        use diesel::sql_types::Text;
+    /// Returns the total number of categories that `search` finds for `query`.
+    pub fn count_search(conn: &mut PgConnection, query: &str) -> QueryResult<i64> {
+        use crate::sql::{lower, strpos};
+
+        categories::table
+            .filter(strpos(lower(categories::category), lower(query)).gt(0))
+            .count()
+            .get_result(conn)
+    }
+
+    /// Returns the categories of the crate sorted by name, with the crates of their
+    /// subcategories summed up in `subtree_crates_cnt` like for `toplevel`.
+    pub fn for_crate(
+        conn: &mut PgConnection,
+        crate_id: i32,
+    ) -> QueryResult<Vec<CategoryWithSubtree>> {
+        use diesel::sql_types::Int4;
 
-        sql_query(include_str!("../subcategories.sql"))
-            .bind::<Text, _>(&self.category)
+        sql_query(include_str!("crate_categories.sql"))
+            .bind::<Int4, _>(crate_id)
             .load(conn)
     }
 
+    /// Returns the direct subcategories of this category, with the crates of their own
+    /// subcategories summed up in `subtree_crates_cnt`.
+    ///
+    /// Like for `toplevel`, `sort` can be `"crates"` to put the subcategories with the largest
+    /// `subtree_crates_cnt` first. They are sorted by name otherwise.
+    pub fn subcategories(
+        &self,
+        conn: &mut PgConnection,
+        sort: &str,
+    ) -> QueryResult<Vec<CategoryWithSubtree>> {
+        use diesel::sql_types::Text;
+
+        sql_query(format!(
+            include_str!("../subcategories.sql"),
+            sort_sql(sort)
+        ))
+        .bind::<Text, _>(&self.category)
+        .load(conn)
+    }
+
     /// Gathers the parent categories from the top-level Category to the direct parent of this Category.
     /// Returns categories as a Vector in order of traversal, not including this Category.
     /// The intention is to be able to have slugs or parent categories arrayed in order, to
     /// offer the frontend, for examples, slugs to create links to each parent category in turn.
-    pub fn parent_categories(&self, conn: &mut PgConnection) -> QueryResult<Vec<Category>> {
+    /// The crates of all subcategories of each parent are summed up in `subtree_crates_cnt`.
+    pub fn parent_categories(
+        &self,
+        conn: &mut PgConnection,
+    ) -> QueryResult<Vec<CategoryWithSubtree>> {
         use diesel::sql_types::Text;
 
         sql_query(include_str!("../parent_categories.sql"))
@@ -119,6 +383,90 @@ impl Category {
     }
 }
 
+/// Locks the row of the crate until the end of the current transaction, so that concurrent
+/// changes to its categories can't each stay within the limit while exceeding it together.
+fn lock_crate(conn: &mut PgConnection, krate: &Crate) -> QueryResult<()> {
+    crates::table
+        .find(krate.id)
+        .select(crates::id)
+        .for_update()
+        .execute(conn)?;
+    Ok(())
+}
+
+fn ensure_within_limit(num_categories: usize, max_categories: usize) -> AppResult<()> {
+    if num_categories > max_categories {
+        return Err(Box::new(TooManyCategories {
+            max: max_categories,
+        }));
+    }
+    Ok(())
+}
+
+/// The `ORDER BY` clause for the results of `toplevel.sql` and `subcategories.sql`, which are
+/// sorted by the crates of their whole subtree.
+fn sort_sql(sort: &str) -> &'static str {
+    match sort {
+        "crates" => "ORDER BY subtree_crates_cnt DESC",
+        "recent" => "ORDER BY created_at DESC",
+        _ => "ORDER BY category ASC",
+    }
+}
+
+/// Like `sort_sql`, but with the id as a tie breaker so that `toplevel_after` can
+/// resume exactly where the previous page stopped.
+fn seek_order_sql(sort: &str) -> &'static str {
+    match seek_sort(sort) {
+        "crates" => "ORDER BY subtree_crates_cnt DESC, id ASC",
+        "recent" => "ORDER BY created_at DESC, id ASC",
+        _ => "ORDER BY category ASC, id ASC",
+    }
+}
+
+/// The sort orders that `toplevel_after` supports, with anything else falling back to `"alpha"`.
+fn seek_sort(sort: &str) -> &'static str {
+    match sort {
+        "crates" => "crates",
+        "recent" => "recent",
+        _ => "alpha",
+    }
+}
+
+/// The position of a category in the results of `Category::toplevel_after`, holding the
+/// sort key for one sort order and the id of the category.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CategoryCursor {
+    Alpha(String, i32),
+    Crates(i32, i32),
+    Recent(NaiveDateTime, i32),
+}
+
+impl CategoryCursor {
+    /// Returns the cursor for the page that follows `category` when sorting by `sort`.
+    pub fn after(toplevel: &CategoryWithSubtree, sort: &str) -> Self {
+        let category = &toplevel.category;
+        match seek_sort(sort) {
+            "crates" => Self::Crates(toplevel.subtree_crates_cnt, category.id),
+            "recent" => Self::Recent(category.created_at, category.id),
+            _ => Self::Alpha(category.category.clone(), category.id),
+        }
+    }
+
+    /// Returns the sort order that this cursor was created for.
+    pub fn sort(&self) -> &'static str {
+        match self {
+            Self::Alpha(..) => "alpha",
+            Self::Crates(..) => "crates",
+            Self::Recent(..) => "recent",
+        }
+    }
+
+    /// Returns whether this cursor can be used to continue a listing sorted by `sort`.
+    pub fn matches_sort(&self, sort: &str) -> bool {
+        self.sort() == seek_sort(sort)
+    }
+}
+
 /// Struct for inserting categories; only used in tests. Actual categories are inserted
 /// in src/boot/categories.rs.
 #[derive(Insertable, AsChangeset, Default, Debug)]
@@ -131,15 +479,40 @@ pub struct NewCategory<'a> {
 
 impl<'a> NewCategory<'a> {
     /// Inserts the category into the database, or updates an existing one.
-    pub fn create_or_update(&self, conn: &mut PgConnection) -> QueryResult<Category> {
+    ///
+    /// The hierarchy of the categories is derived from the `::` separated segments of their
+    /// slugs, so a subcategory is rejected unless all of its parents exist already, e.g. `a` and
+    /// `a::b` for `a::b::c`.
+    pub fn create_or_update(&self, conn: &mut PgConnection) -> AppResult<Category> {
         use crate::schema::categories::dsl::*;
 
+        let parents = self.parent_slugs();
+        let existing: Vec<String> = categories
+            .filter(slug.eq_any(&parents))
+            .select(slug)
+            .load(conn)?;
+        if let Some(missing) = parents.iter().find(|parent| !existing.contains(parent)) {
+            return Err(bad_request(&format_args!(
+                "the parent category `{missing}` of `{}` does not exist",
+                self.slug
+            )));
+        }
+
         insert_into(categories)
             .values(self)
             .on_conflict(slug)
             .do_update()
             .set(self)
             .get_result(conn)
+            .map_err(Into::into)
+    }
+
+    /// Returns the slugs of all parents of the category, starting with its top-level category.
+    fn parent_slugs(&self) -> Vec<String> {
+        let segments = self.slug.split("::").collect::<Vec<_>>();
+        (1..segments.len())
+            .map(|len| segments[..len].join("::"))
+            .collect()
     }
 }
 
@@ -149,6 +522,41 @@ mod tests {
     use crate::test_util::pg_connection_no_transaction;
     use diesel::connection::SimpleConnection;
 
+    fn new_category(slug: &str) -> NewCategory<'_> {
+        NewCategory {
+            category: slug,
+            slug,
+            description: "",
+        }
+    }
+
+    #[test]
+    fn parent_slugs_of_nested_categories() {
+        assert!(new_category("a").parent_slugs().is_empty());
+        assert_eq!(new_category("a::b").parent_slugs(), ["a"]);
+        assert_eq!(new_category("a::b::c").parent_slugs(), ["a", "a::b"]);
+    }
+
+    #[test]
+    fn subcategories_without_parent_are_rejected() {
+        let conn = &mut pg_connection();
+
+        let error = new_category("a::b").create_or_update(conn).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "the parent category `a` of `a::b` does not exist"
+        );
+
+        new_category("a").create_or_update(conn).unwrap();
+        new_category("a::b").create_or_update(conn).unwrap();
+
+        let error = new_category("a::c::d").create_or_update(conn).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "the parent category `a::c` of `a::c::d` does not exist"
+        );
+    }
+
     fn pg_connection() -> PgConnection {
         let mut conn = pg_connection_no_transaction();
         // These tests deadlock if run concurrently
@@ -173,7 +581,7 @@ mod tests {
         let cats = Category::toplevel(conn, "", 10, 0)
             .unwrap()
             .into_iter()
-            .map(|c| c.category)
+            .map(|c| c.category.category)
             .collect::<Vec<_>>();
         let expected = vec!["Cat 1".to_string(), "Cat 2".to_string()];
         assert_eq!(expected, cats);
@@ -195,7 +603,7 @@ mod tests {
         let cats = Category::toplevel(conn, "crates", 10, 0)
             .unwrap()
             .into_iter()
-            .map(|c| c.category)
+            .map(|c| c.category.category)
             .collect::<Vec<_>>();
         let expected = vec![
             "Cat 2".to_string(),
@@ -220,7 +628,7 @@ mod tests {
         let cats = Category::toplevel(conn, "", 1, 0)
             .unwrap()
             .into_iter()
-            .map(|c| c.category)
+            .map(|c| c.category.category)
             .collect::<Vec<_>>();
         let expected = vec!["Cat 1".to_string()];
         assert_eq!(expected, cats);
@@ -228,7 +636,7 @@ mod tests {
         let cats = Category::toplevel(conn, "", 1, 1)
             .unwrap()
             .into_iter()
-            .map(|c| c.category)
+            .map(|c| c.category.category)
             .collect::<Vec<_>>();
         let expected = vec!["Cat 2".to_string()];
         assert_eq!(expected, cats);
@@ -265,12 +673,18 @@ mod tests {
         let cats = Category::toplevel(conn, "crates", 10, 0)
             .unwrap()
             .into_iter()
-            .map(|c| (c.category, c.crates_cnt))
+            .map(|c| {
+                (
+                    c.category.category,
+                    c.category.crates_cnt,
+                    c.subtree_crates_cnt,
+                )
+            })
             .collect::<Vec<_>>();
         let expected = vec![
-            ("Cat 2".to_string(), 12),
-            ("Cat 3".to_string(), 6),
-            ("Cat 1".to_string(), 3),
+            ("Cat 2".to_string(), 3, 12),
+            ("Cat 3".to_string(), 6, 6),
+            ("Cat 1".to_string(), 1, 3),
         ];
         assert_eq!(expected, cats);
     }
@@ -306,17 +720,29 @@ mod tests {
         let cats = Category::toplevel(conn, "crates", 2, 0)
             .unwrap()
             .into_iter()
-            .map(|c| (c.category, c.crates_cnt))
+            .map(|c| {
+                (
+                    c.category.category,
+                    c.category.crates_cnt,
+                    c.subtree_crates_cnt,
+                )
+            })
             .collect::<Vec<_>>();
-        let expected = vec![("Cat 2".to_string(), 12), ("Cat 3".to_string(), 6)];
+        let expected = vec![("Cat 2".to_string(), 3, 12), ("Cat 3".to_string(), 6, 6)];
         assert_eq!(expected, cats);
 
         let cats = Category::toplevel(conn, "crates", 2, 1)
             .unwrap()
             .into_iter()
-            .map(|c| (c.category, c.crates_cnt))
+            .map(|c| {
+                (
+                    c.category.category,
+                    c.category.crates_cnt,
+                    c.subtree_crates_cnt,
+                )
+            })
             .collect::<Vec<_>>();
-        let expected = vec![("Cat 3".to_string(), 6), ("Cat 1".to_string(), 3)];
+        let expected = vec![("Cat 3".to_string(), 6, 6), ("Cat 1".to_string(), 1, 3)];
         assert_eq!(expected, cats);
     }
 
@@ -359,13 +785,79 @@ mod tests {
             .unwrap();
 
         let cat: Category = Category::by_slug("cat1::sub1").first(conn).unwrap();
-        let subcats = cat.subcategories(conn).unwrap();
+        let subcats = cat.subcategories(conn, "").unwrap();
         let parents = cat.parent_categories(conn).unwrap();
 
         assert_eq!(parents.len(), 1);
-        assert_eq!(parents[0].slug, "cat1");
-        assert_eq!(parents[0].crates_cnt, 7);
+        assert_eq!(parents[0].category.slug, "cat1");
+        assert_eq!(parents[0].category.crates_cnt, 1);
+        assert_eq!(parents[0].subtree_crates_cnt, 7);
         assert_eq!(subcats.len(), 1);
-        assert_eq!(subcats[0].slug, "cat1::sub1::subsub1");
+        assert_eq!(subcats[0].category.slug, "cat1::sub1::subsub1");
+    }
+
+    #[test]
+    fn category_subcategories_can_be_sorted_by_crates_count() {
+        use self::categories::dsl::*;
+        let conn = &mut pg_connection();
+        insert_into(categories)
+            .values(&vec![
+                (category.eq("Cat 1"), slug.eq("cat1"), crates_cnt.eq(1)),
+                (
+                    category.eq("Cat 1::Sub A"),
+                    slug.eq("cat1::sub-a"),
+                    crates_cnt.eq(2),
+                ),
+                (
+                    category.eq("Cat 1::Sub B"),
+                    slug.eq("cat1::sub-b"),
+                    crates_cnt.eq(1),
+                ),
+                (
+                    category.eq("Cat 1::Sub B::Sub C"),
+                    slug.eq("cat1::sub-b::sub-c"),
+                    crates_cnt.eq(5),
+                ),
+            ])
+            .execute(conn)
+            .unwrap();
+
+        let cat: Category = Category::by_slug("cat1").first(conn).unwrap();
+
+        let subcats = cat
+            .subcategories(conn, "crates")
+            .unwrap()
+            .into_iter()
+            .map(|c| {
+                (
+                    c.category.category,
+                    c.category.crates_cnt,
+                    c.subtree_crates_cnt,
+                )
+            })
+            .collect::<Vec<_>>();
+        let expected = vec![
+            ("Cat 1::Sub B".to_string(), 1, 6),
+            ("Cat 1::Sub A".to_string(), 2, 2),
+        ];
+        assert_eq!(expected, subcats);
+
+        let subcats = cat
+            .subcategories(conn, "alpha")
+            .unwrap()
+            .into_iter()
+            .map(|c| {
+                (
+                    c.category.category,
+                    c.category.crates_cnt,
+                    c.subtree_crates_cnt,
+                )
+            })
+            .collect::<Vec<_>>();
+        let expected = vec![
+            ("Cat 1::Sub A".to_string(), 2, 2),
+            ("Cat 1::Sub B".to_string(), 1, 6),
+        ];
+        assert_eq!(expected, subcats);
     }
 }
@@ -0,0 +1,20 @@
+SELECT
+  c.id,
+  c.category,
+  c.slug,
+  c.description,
+  c.crates_cnt,
+  COALESCE ((
+    SELECT sum(c2.crates_cnt)::int
+    FROM categories as c2
+    WHERE c2.slug = c.slug
+    OR c2.slug LIKE c.slug || '::%'
+  ), 0) as subtree_crates_cnt,
+  c.created_at
+FROM categories as c
+WHERE strpos(lower(c.category), lower($1)) > 0
+ORDER BY
+  lower(c.category) = lower($1) DESC,
+  strpos(lower(c.category), lower($1)) = 1 DESC,
+  c.category ASC
+LIMIT $2 OFFSET $3
@@ -0,0 +1,17 @@
+SELECT
+  c.id,
+  c.category,
+  c.slug,
+  c.description,
+  c.crates_cnt,
+  COALESCE ((
+    SELECT sum(c2.crates_cnt)::int
+    FROM categories as c2
+    WHERE c2.slug = c.slug
+    OR c2.slug LIKE c.slug || '::%'
+  ), 0) as subtree_crates_cnt,
+  c.created_at
+FROM categories as c
+INNER JOIN crates_categories as cc ON cc.category_id = c.id
+WHERE cc.crate_id = $1
+ORDER BY c.category ASC
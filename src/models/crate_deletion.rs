@@ -0,0 +1,53 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+
+use crate::schema::crate_deletions;
+use crate::sql::canon_crate_name;
+
+/// A crate that was deleted by one of its owners. The record is kept after the crate is removed
+/// permanently.
+#[derive(Clone, Debug, PartialEq, Eq, Queryable, Identifiable)]
+pub struct CrateDeletion {
+    pub id: i32,
+    pub crate_name: String,
+    /// The user that deleted the crate, unless the account was removed since.
+    pub deleted_by: Option<i32>,
+    pub deleted_at: NaiveDateTime,
+    /// `owner` if a user owner deleted the crate, `team` if a team member deleted a new crate.
+    pub path: String,
+    /// When `deleted_by` was cleared because the retention period of the record has passed.
+    pub anonymized_at: Option<NaiveDateTime>,
+}
+
+impl CrateDeletion {
+    /// Returns when a crate named `name` was deleted most recently, matching names like
+    /// `Crate::by_name` does.
+    pub fn last_deleted_at(
+        conn: &mut PgConnection,
+        name: &str,
+    ) -> QueryResult<Option<NaiveDateTime>> {
+        crate_deletions::table
+            .filter(canon_crate_name(crate_deletions::crate_name).eq(canon_crate_name(name)))
+            .select(crate_deletions::deleted_at)
+            .order(crate_deletions::deleted_at.desc())
+            .first(conn)
+            .optional()
+    }
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = crate_deletions)]
+pub struct NewCrateDeletion<'a> {
+    pub crate_name: &'a str,
+    pub deleted_by: i32,
+    pub path: &'a str,
+}
+
+impl NewCrateDeletion<'_> {
+    pub fn insert(&self, conn: &mut PgConnection) -> QueryResult<()> {
+        diesel::insert_into(crate_deletions::table)
+            .values(self)
+            .execute(conn)?;
+        Ok(())
+    }
+}
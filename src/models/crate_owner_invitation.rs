@@ -2,9 +2,10 @@ use chrono::{NaiveDateTime, Utc};
 use diesel::prelude::*;
 
 use crate::config;
-use crate::models::{CrateOwner, OwnerKind};
-use crate::schema::{crate_owner_invitations, crate_owners, crates};
+use crate::models::{Crate, CrateOwner, CrateWebhook, OwnerKind, WebhookEvent};
+use crate::schema::{crate_owner_invitations, crate_owners, crates, users};
 use crate::util::errors::{AppResult, OwnershipInvitationExpired};
+use crate::worker;
 
 #[derive(Debug)]
 pub enum NewCrateOwnerInvitationOutcome {
@@ -22,6 +23,9 @@ pub struct CrateOwnerInvitation {
     pub created_at: NaiveDateTime,
     pub token: String,
     pub token_created_at: Option<NaiveDateTime>,
+    /// Whether accepting the invitation transfers the crate, which removes the inviting user from
+    /// its owners.
+    pub is_transfer: bool,
 }
 
 impl CrateOwnerInvitation {
@@ -29,6 +33,7 @@ impl CrateOwnerInvitation {
         invited_user_id: i32,
         invited_by_user_id: i32,
         crate_id: i32,
+        is_transfer: bool,
         conn: &mut PgConnection,
         config: &config::Server,
     ) -> AppResult<NewCrateOwnerInvitationOutcome> {
@@ -38,6 +43,7 @@ impl CrateOwnerInvitation {
             invited_user_id: i32,
             invited_by_user_id: i32,
             crate_id: i32,
+            is_transfer: bool,
         }
 
         // Before actually creating the invite, check if an expired invitation already exists
@@ -65,6 +71,7 @@ impl CrateOwnerInvitation {
                 invited_user_id,
                 invited_by_user_id,
                 crate_id,
+                is_transfer,
             })
             // The ON CONFLICT DO NOTHING clause results in not creating the invite if another one
             // already exists. This does not cause problems with expired invitation as those are
@@ -118,6 +125,32 @@ impl CrateOwnerInvitation {
 
             diesel::delete(&self).execute(conn)?;
 
+            let krate: Crate = Crate::all().find(self.crate_id).first(conn)?;
+            let login: String = users::table
+                .find(self.invited_user_id)
+                .select(users::gh_login)
+                .first(conn)?;
+            let data = json!({ "owner": login });
+            CrateWebhook::enqueue_dispatch(conn, &krate, WebhookEvent::OwnerAdded, data)?;
+
+            if self.is_transfer {
+                let previous_owner = crate_owners::table.find((
+                    self.crate_id,
+                    self.invited_by_user_id,
+                    OwnerKind::User as i32,
+                ));
+                diesel::update(previous_owner)
+                    .set(crate_owners::deleted.eq(true))
+                    .execute(conn)?;
+
+                worker::send_ownership_transfer_emails(
+                    krate.name,
+                    self.invited_by_user_id,
+                    self.invited_user_id,
+                )
+                .enqueue(conn)?;
+            }
+
             Ok(())
         })
     }
@@ -0,0 +1,123 @@
+use chrono::NaiveDateTime;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::sql_types::Integer;
+
+use crate::models::Crate;
+use crate::schema::crate_webhooks;
+use crate::swirl::errors::EnqueueError;
+use crate::worker;
+
+/// An event of a crate that owners can subscribe to with a webhook.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEvent {
+    /// A new version was published.
+    Published,
+    /// A version was yanked.
+    Yanked,
+    /// The crate was deleted by one of its owners.
+    Deleted,
+    /// A user accepted an invitation to become an owner, a team was added as an owner, or the
+    /// crate was transferred to a new owner.
+    OwnerAdded,
+    /// An admin restored the crate after it was deleted by one of its owners.
+    Restored,
+}
+
+impl WebhookEvent {
+    pub const ALL: [WebhookEvent; 5] = [
+        WebhookEvent::Published,
+        WebhookEvent::Yanked,
+        WebhookEvent::Deleted,
+        WebhookEvent::OwnerAdded,
+        WebhookEvent::Restored,
+    ];
+
+    /// The name of the event, as used in the API and in the `X-CratesIo-Event` header.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            WebhookEvent::Published => "published",
+            WebhookEvent::Yanked => "yanked",
+            WebhookEvent::Deleted => "deleted",
+            WebhookEvent::OwnerAdded => "owner_added",
+            WebhookEvent::Restored => "restored",
+        }
+    }
+
+    /// The bit representing this event in `crate_webhooks.events`. The values are persisted and
+    /// must not be changed.
+    fn bit(self) -> i32 {
+        match self {
+            WebhookEvent::Published => 1 << 0,
+            WebhookEvent::Yanked => 1 << 1,
+            WebhookEvent::Deleted => 1 << 2,
+            WebhookEvent::OwnerAdded => 1 << 3,
+            WebhookEvent::Restored => 1 << 4,
+        }
+    }
+
+    /// Combines the events into the bitmask stored in `crate_webhooks.events`.
+    pub fn mask(events: &[WebhookEvent]) -> i32 {
+        events.iter().fold(0, |mask, event| mask | event.bit())
+    }
+}
+
+diesel::infix_operator!(BitAnd, " & ", Integer, backend: Pg);
+
+/// A webhook that is notified about events of a crate, managed by the crate's owners.
+#[derive(Clone, Debug, PartialEq, Eq, Identifiable, Queryable, Associations)]
+#[diesel(belongs_to(Crate))]
+pub struct CrateWebhook {
+    pub id: i32,
+    pub crate_id: i32,
+    pub url: String,
+    /// The secret used to sign the payloads. It is never included in API responses.
+    pub secret: String,
+    events: i32,
+    pub created_at: NaiveDateTime,
+}
+
+impl CrateWebhook {
+    /// Returns the events the webhook is subscribed to.
+    pub fn events(&self) -> Vec<WebhookEvent> {
+        WebhookEvent::ALL
+            .into_iter()
+            .filter(|event| self.events & event.bit() != 0)
+            .collect()
+    }
+
+    /// Enqueues a job to notify each webhook of `krate` that is subscribed to `event`.
+    ///
+    /// The payload of the webhooks is `{"event": ..., "crate": ..., "data": data}`.
+    pub fn enqueue_dispatch(
+        conn: &mut PgConnection,
+        krate: &Crate,
+        event: WebhookEvent,
+        data: serde_json::Value,
+    ) -> Result<(), EnqueueError> {
+        let webhook_ids: Vec<i32> = CrateWebhook::belonging_to(krate)
+            .filter(BitAnd::new(crate_webhooks::events, event.bit().into_sql::<Integer>()).ne(0))
+            .select(crate_webhooks::id)
+            .load(conn)?;
+
+        let payload = json!({
+            "event": event.as_str(),
+            "crate": krate.name,
+            "data": data,
+        });
+        for webhook_id in webhook_ids {
+            worker::dispatch_crate_webhook(webhook_id, event, payload.clone()).enqueue(conn)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Insertable, AsChangeset, Debug)]
+#[diesel(table_name = crate_webhooks)]
+pub struct NewCrateWebhook<'a> {
+    pub crate_id: i32,
+    pub url: &'a str,
+    pub secret: &'a str,
+    pub events: i32,
+}
@@ -34,6 +34,18 @@ pub struct ReverseDependency {
     pub name: String,
 }
 
+/// An edge of the graph returned by `Version::dependency_graph()`.
+#[derive(Debug)]
+pub struct DependencyGraphEdge {
+    pub from_version_id: i32,
+    /// The depth of the dependent version, with the root version at depth 0.
+    pub from_depth: i32,
+    pub to_version_id: i32,
+    pub req: String,
+    pub optional: bool,
+    pub kind: DependencyKind,
+}
+
 #[derive(Copy, Clone, Serialize, Deserialize, Debug, FromSqlRow)]
 #[serde(rename_all = "lowercase")]
 #[repr(u32)]
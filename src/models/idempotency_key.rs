@@ -0,0 +1,89 @@
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+use diesel::sql_types::{Integer, Text};
+use std::time::Duration;
+
+use crate::schema::idempotency_keys;
+
+/// The response to a request with an `Idempotency-Key` header, which is replayed if the client
+/// retries the request with the same key.
+#[derive(Clone, Debug, PartialEq, Eq, Queryable)]
+pub struct IdempotencyKey {
+    pub user_id: i32,
+    pub key: String,
+    /// The method and path of the request, so that a key can't be reused for another request.
+    pub request: String,
+    pub response_status: i32,
+    pub response_body: serde_json::Value,
+    pub created_at: NaiveDateTime,
+}
+
+impl IdempotencyKey {
+    /// Finds the response recorded for `key`, unless it is older than `expiration`.
+    pub fn find(
+        conn: &mut PgConnection,
+        user_id: i32,
+        key: &str,
+        expiration: Duration,
+    ) -> QueryResult<Option<IdempotencyKey>> {
+        idempotency_keys::table
+            .find((user_id, key))
+            .filter(idempotency_keys::created_at.gt(expiration_cutoff(expiration)))
+            .first(conn)
+            .optional()
+    }
+
+    /// Makes concurrent requests with the same key wait until the transaction of the current
+    /// request ends, so that they find its recorded response instead of processing the request
+    /// again.
+    ///
+    /// `conn` must be inside of a transaction, since the lock is released when it ends.
+    pub fn lock(conn: &mut PgConnection, user_id: i32, key: &str) -> QueryResult<()> {
+        diesel::sql_query("SELECT pg_advisory_xact_lock($1, hashtext($2))")
+            .bind::<Integer, _>(user_id)
+            .bind::<Text, _>(key)
+            .execute(conn)?;
+        Ok(())
+    }
+
+    /// Records the response to the request with `key`, replacing an expired response with the
+    /// same key. Expired responses of all users are removed along the way.
+    pub fn record(
+        conn: &mut PgConnection,
+        new_key: NewIdempotencyKey<'_>,
+        expiration: Duration,
+    ) -> QueryResult<()> {
+        diesel::delete(
+            idempotency_keys::table
+                .filter(idempotency_keys::created_at.le(expiration_cutoff(expiration))),
+        )
+        .execute(conn)?;
+
+        diesel::insert_into(idempotency_keys::table)
+            .values(&new_key)
+            .execute(conn)?;
+        Ok(())
+    }
+}
+
+/// Keys created at or before the returned time are expired.
+///
+/// `Server::idempotency_key_expiration` is validated when the configuration is loaded, so the
+/// fallback to the earliest representable time is only reached by configurations that were
+/// built by hand.
+fn expiration_cutoff(expiration: Duration) -> NaiveDateTime {
+    chrono::Duration::from_std(expiration)
+        .ok()
+        .and_then(|expiration| Utc::now().naive_utc().checked_sub_signed(expiration))
+        .unwrap_or(NaiveDateTime::MIN)
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = idempotency_keys)]
+pub struct NewIdempotencyKey<'a> {
+    pub user_id: i32,
+    pub key: &'a str,
+    pub request: &'a str,
+    pub response_status: i32,
+    pub response_body: &'a serde_json::Value,
+}
@@ -3,19 +3,24 @@ use diesel::associations::Identifiable;
 use diesel::pg::Pg;
 use diesel::prelude::*;
 use diesel::sql_types::{Bool, Text};
+use std::collections::{BTreeMap, HashMap};
 use url::Url;
 
 use crate::app::App;
 use crate::controllers::helpers::pagination::*;
 use crate::models::version::TopVersions;
 use crate::models::{
-    CrateOwner, CrateOwnerInvitation, NewCrateOwnerInvitationOutcome, Owner, OwnerKind,
-    ReverseDependency, User, Version,
+    CrateDeletion, CrateOwner, CrateOwnerInvitation, CrateWebhook, Dependency,
+    NewCrateOwnerInvitationOutcome, Owner, OwnerKind, ReverseDependency, Team, User, Version,
+    WebhookEvent,
+};
+use crate::util::errors::{
+    cargo_err, cargo_errs, AppResult, BoxedAppError, CrateFrozen, CrateNotFound,
 };
-use crate::util::errors::{cargo_err, AppResult};
 
+use crate::middleware::rate_limit::RequestRateLimiter;
 use crate::models::helpers::with_count::*;
-use crate::publish_rate_limit::PublishRateLimit;
+use crate::rate_limiter::LimitedAction;
 use crate::schema::*;
 use crate::sql::canon_crate_name;
 
@@ -71,10 +76,77 @@ pub const ALL_COLUMNS: AllColumns = (
     crates::max_upload_size,
 );
 
+/// The default maximum length of crate names, see `config::Server::max_crate_name_length`.
 pub const MAX_NAME_LENGTH: usize = 64;
 
+/// Validates the name of a crate that is about to be created.
+///
+/// This checks that the name only uses allowed characters, that it has at most `max_length`
+/// characters and that it doesn't match a reserved name after normalizing case, hyphens and
+/// underscores. All problems that are found are reported together, as one error detail each.
+///
+/// The length limit only applies to new crates, so that lowering it doesn't prevent existing
+/// crates from publishing further versions.
+pub fn validate_crate_name(
+    conn: &mut PgConnection,
+    name: &str,
+    max_length: usize,
+) -> AppResult<()> {
+    use diesel::dsl::exists;
+    use diesel::select;
+
+    let mut errors = Vec::new();
+
+    if !Crate::valid_ident(name) {
+        errors.push(format!(
+            "the crate name `{name}` is invalid, crate names must start with a letter and \
+             contain only letters, numbers, hyphens, or underscores"
+        ));
+    }
+
+    if name.chars().count() > max_length {
+        errors.push(format!(
+            "the crate name `{name}` is too long, crate names can have at most {max_length} \
+             characters"
+        ));
+    }
+
+    let reserved_name: bool = select(exists(
+        reserved_crate_names::table
+            .filter(canon_crate_name(reserved_crate_names::name).eq(canon_crate_name(name))),
+    ))
+    .get_result(conn)?;
+    if reserved_name {
+        errors.push("cannot upload a crate with a reserved name".into());
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(cargo_errs(errors))
+    }
+}
+
+/// Validates the length of the name a dependency is renamed to in `Cargo.toml`.
+///
+/// The characters are already checked when the publish metadata is deserialized, see
+/// `EncodableDependencyName`.
+pub fn validate_dependency_name(name: &str, max_length: usize) -> AppResult<()> {
+    if name.chars().count() > max_length {
+        return Err(cargo_err(&format_args!(
+            "the dependency name `{name}` is too long, dependency names can have at most \
+             {max_length} characters"
+        )));
+    }
+
+    Ok(())
+}
+
 type CanonCrateName<T> = canon_crate_name::HelperType<T>;
-type All = diesel::dsl::Select<crates::table, AllColumns>;
+type All = diesel::dsl::Filter<
+    diesel::dsl::Select<crates::table, AllColumns>,
+    diesel::dsl::IsNull<crates::deleted_at>,
+>;
 type WithName<'a> = diesel::dsl::Eq<CanonCrateName<crates::name>, CanonCrateName<&'a str>>;
 type ByName<'a> = diesel::dsl::Filter<All, WithName<'a>>;
 type ByExactName<'a> = diesel::dsl::Filter<All, diesel::dsl::Eq<crates::name, &'a str>>;
@@ -98,29 +170,36 @@ impl<'a> NewCrate<'a> {
         self,
         conn: &mut PgConnection,
         uploader: i32,
-        rate_limit: Option<&PublishRateLimit>,
+        rate_limit: Option<&RequestRateLimiter>,
     ) -> AppResult<Crate> {
         use diesel::update;
 
         self.validate()?;
-        self.ensure_name_not_reserved(conn)?;
 
         conn.transaction(|conn| {
             // To avoid race conditions, we try to insert
             // first so we know whether to add an owner
             if let Some(krate) = self.save_new_crate(conn, uploader)? {
                 if let Some(rate_limit) = rate_limit {
-                    rate_limit.check_rate_limit(uploader, conn)?;
+                    rate_limit.check_rate_limit(uploader, LimitedAction::PublishNew, conn)?;
                 }
                 return Ok(krate);
             }
 
             update(crates::table)
                 .filter(canon_crate_name(crates::name).eq(canon_crate_name(self.name)))
+                .filter(crates::deleted_at.is_null())
                 .set(&self)
                 .returning(ALL_COLUMNS)
                 .get_result(conn)
-                .map_err(Into::into)
+                .optional()?
+                .ok_or_else(|| {
+                    cargo_err(&format_args!(
+                        "crate `{}` was deleted and can't be published again until it is \
+                         removed permanently",
+                        self.name
+                    ))
+                })
         })
     }
 
@@ -151,22 +230,6 @@ impl<'a> NewCrate<'a> {
         Ok(())
     }
 
-    fn ensure_name_not_reserved(&self, conn: &mut PgConnection) -> AppResult<()> {
-        use crate::schema::reserved_crate_names::dsl::*;
-        use diesel::dsl::exists;
-        use diesel::select;
-
-        let reserved_name: bool = select(exists(
-            reserved_crate_names.filter(canon_crate_name(name).eq(canon_crate_name(self.name))),
-        ))
-        .get_result(conn)?;
-        if reserved_name {
-            Err(cargo_err("cannot upload a crate with a reserved name"))
-        } else {
-            Ok(())
-        }
-    }
-
     fn save_new_crate(&self, conn: &mut PgConnection, user_id: i32) -> QueryResult<Option<Crate>> {
         use crate::schema::crates::dsl::*;
 
@@ -224,16 +287,60 @@ impl Crate {
         canon_crate_name(crates::name).eq(canon_crate_name(name))
     }
 
+    /// Selects the crate whose name matches `name` after normalization, i.e. ignoring case and
+    /// treating `-` and `_` as equivalent.
+    ///
+    /// This is the same normalization used to detect conflicting names when publishing, so it
+    /// should be used whenever a crate is looked up by a name provided by a user.
     pub fn by_name(name: &str) -> ByName<'_> {
         Crate::all().filter(Self::with_name(name))
     }
 
+    /// Selects the crate whose name is exactly `name`.
     pub fn by_exact_name(name: &str) -> ByExactName<'_> {
         Crate::all().filter(crates::name.eq(name))
     }
 
+    /// Selects all crates, except for those that were deleted and are waiting to be removed
+    /// permanently.
     pub fn all() -> All {
-        crates::table.select(ALL_COLUMNS)
+        crates::table
+            .select(ALL_COLUMNS)
+            .filter(crates::deleted_at.is_null())
+    }
+
+    /// Returns the `404 Not Found` error for a `name` that `by_name` didn't find a crate for.
+    ///
+    /// The error tells clients when a crate with that name was deleted by its owners, so that
+    /// they can tell deleted crates apart from names that were never published.
+    pub fn not_found(conn: &mut PgConnection, name: &str) -> BoxedAppError {
+        match CrateDeletion::last_deleted_at(conn, name) {
+            Ok(deleted_at) => Box::new(CrateNotFound {
+                crate_name: name.to_string(),
+                deleted_at,
+            }),
+            Err(error) => error.into(),
+        }
+    }
+
+    /// Returns whether an admin froze the crate.
+    pub fn is_frozen(&self, conn: &mut PgConnection) -> QueryResult<bool> {
+        crates::table
+            .find(self.id)
+            .select(crates::crate_frozen)
+            .first(conn)
+    }
+
+    /// Returns an error if an admin froze the crate.
+    ///
+    /// Frozen crates can still be read, but writes like publishing, yanking or deleting must
+    /// call this first.
+    pub fn ensure_not_frozen(&self, conn: &mut PgConnection) -> AppResult<()> {
+        if self.is_frozen(conn)? {
+            let crate_name = self.name.clone();
+            return Err(Box::new(CrateFrozen { crate_name }));
+        }
+        Ok(())
     }
 
     pub fn find_version(&self, conn: &mut PgConnection, version: &str) -> AppResult<Version> {
@@ -248,12 +355,8 @@ impl Crate {
             })
     }
 
-    pub fn valid_name(name: &str) -> bool {
-        let under_max_length = name.chars().take(MAX_NAME_LENGTH + 1).count() <= MAX_NAME_LENGTH;
-        Crate::valid_ident(name) && under_max_length
-    }
-
-    fn valid_ident(name: &str) -> bool {
+    /// Validates the characters of a crate name, without checking its length.
+    pub fn valid_ident(name: &str) -> bool {
         Self::valid_feature_prefix(name)
             && name
                 .chars()
@@ -262,12 +365,8 @@ impl Crate {
                 .unwrap_or(false)
     }
 
-    pub fn valid_dependency_name(name: &str) -> bool {
-        let under_max_length = name.chars().take(MAX_NAME_LENGTH + 1).count() <= MAX_NAME_LENGTH;
-        Crate::valid_dependency_ident(name) && under_max_length
-    }
-
-    fn valid_dependency_ident(name: &str) -> bool {
+    /// Validates the characters of a dependency name, without checking its length.
+    pub fn valid_dependency_ident(name: &str) -> bool {
         Self::valid_feature_prefix(name)
             && name
                 .chars()
@@ -333,6 +432,49 @@ impl Crate {
         Ok(users.chain(teams).collect())
     }
 
+    /// Loads the owners of multiple crates at once, so that callers don't need a query per crate.
+    ///
+    /// The map contains an entry for every crate in `crate_ids`, with the owners in the same
+    /// order as `owners()` returns them: users first, then teams.
+    pub fn owners_for_many(
+        crate_ids: &[i32],
+        conn: &mut PgConnection,
+    ) -> QueryResult<HashMap<i32, Vec<Owner>>> {
+        let rows: Vec<(i32, Option<User>, Option<Team>)> = crate_owners::table
+            .left_join(
+                users::table.on(crate_owners::owner_id
+                    .eq(users::id)
+                    .and(crate_owners::owner_kind.eq(OwnerKind::User as i32))),
+            )
+            .left_join(
+                teams::table.on(crate_owners::owner_id
+                    .eq(teams::id)
+                    .and(crate_owners::owner_kind.eq(OwnerKind::Team as i32))),
+            )
+            .filter(crate_owners::deleted.eq(false))
+            .filter(crate_owners::crate_id.eq_any(crate_ids))
+            .order((crate_owners::crate_id, crate_owners::owner_kind))
+            .select((
+                crate_owners::crate_id,
+                users::all_columns.nullable(),
+                teams::all_columns.nullable(),
+            ))
+            .load(conn)?;
+
+        let mut owners: HashMap<i32, Vec<Owner>> =
+            crate_ids.iter().map(|&id| (id, Vec::new())).collect();
+        for (crate_id, user, team) in rows {
+            let owner = match (user, team) {
+                (Some(user), _) => Owner::User(user),
+                (None, Some(team)) => Owner::Team(team),
+                // The owner kind doesn't match the table the owner ID refers to
+                (None, None) => continue,
+            };
+            owners.entry(crate_id).or_default().push(owner);
+        }
+        Ok(owners)
+    }
+
     pub fn owner_add(
         &self,
         app: &App,
@@ -348,7 +490,14 @@ impl Crate {
             // Users are invited and must accept before being added
             Owner::User(user) => {
                 let config = &app.config;
-                match CrateOwnerInvitation::create(user.id, req_user.id, self.id, conn, config)? {
+                match CrateOwnerInvitation::create(
+                    user.id,
+                    req_user.id,
+                    self.id,
+                    false,
+                    conn,
+                    config,
+                )? {
                     NewCrateOwnerInvitationOutcome::InviteCreated { plaintext_token } => {
                         if let Ok(Some(email)) = user.verified_email(conn) {
                             // Swallow any error. Whether or not the email is sent, the invitation
@@ -388,6 +537,9 @@ impl Crate {
                     .set(crate_owners::deleted.eq(false))
                     .execute(conn)?;
 
+                let data = json!({ "owner": owner.login() });
+                CrateWebhook::enqueue_dispatch(conn, self, WebhookEvent::OwnerAdded, data)?;
+
                 Ok(format!(
                     "team {} has been added as an owner of crate {}",
                     owner.login(),
@@ -414,6 +566,8 @@ impl Crate {
     }
 
     /// Returns (dependency, dependent crate name, dependent crate downloads)
+    ///
+    /// Each dependent crate is listed once, ordered by its downloads and then by its name.
     pub(crate) fn reverse_dependencies(
         &self,
         conn: &mut PgConnection,
@@ -432,10 +586,117 @@ impl Crate {
 
         Ok(rows.records_and_total())
     }
+
+    /// Returns the number of distinct crates, and of distinct versions, that depend on this
+    /// crate. Yanked versions and deleted crates are not counted.
+    ///
+    /// Unlike `reverse_dependencies()`, all versions of the dependent crates are considered, not
+    /// just their newest ones.
+    pub(crate) fn reverse_dependencies_count(
+        &self,
+        conn: &mut PgConnection,
+    ) -> QueryResult<(i64, i64)> {
+        use diesel::dsl::count_distinct;
+
+        dependencies::table
+            .inner_join(versions::table.inner_join(crates::table))
+            .filter(dependencies::crate_id.eq(self.id))
+            .filter(versions::yanked.eq(false))
+            .filter(crates::deleted_at.is_null())
+            .select((
+                count_distinct(versions::crate_id),
+                count_distinct(versions::id),
+            ))
+            .get_result(conn)
+    }
+
+    /// Builds the index file entries of all versions of this crate from the database, in the
+    /// order they were published.
+    pub fn index_metadata(
+        &self,
+        conn: &mut PgConnection,
+    ) -> QueryResult<Vec<cargo_registry_index::Crate>> {
+        let versions: Vec<Version> = self.all_versions().order(versions::id).load(conn)?;
+
+        let deps: Vec<(Dependency, String)> = Dependency::belonging_to(&versions)
+            .inner_join(crates::table)
+            .select((dependencies::all_columns, crates::name))
+            .load(conn)?;
+        let mut deps_by_version: HashMap<i32, Vec<cargo_registry_index::Dependency>> =
+            HashMap::new();
+        for (dep, crate_name) in deps {
+            // The index lists renamed dependencies under the name used in `Cargo.toml`
+            let (name, package) = match dep.explicit_name {
+                Some(explicit_name) => (explicit_name, Some(crate_name)),
+                None => (crate_name, None),
+            };
+
+            deps_by_version.entry(dep.version_id).or_default().push(
+                cargo_registry_index::Dependency {
+                    name,
+                    req: dep.req,
+                    features: dep.features,
+                    optional: dep.optional,
+                    default_features: dep.default_features,
+                    target: dep.target,
+                    kind: Some(dep.kind.into()),
+                    package,
+                },
+            );
+        }
+
+        let index_versions = versions
+            .into_iter()
+            .map(|version| {
+                let mut deps = deps_by_version.remove(&version.id).unwrap_or_default();
+                deps.sort();
+
+                let features = serde_json::from_value(version.features).unwrap_or_default();
+                let (features, features2, v) = split_index_features(features);
+
+                cargo_registry_index::Crate {
+                    name: self.name.clone(),
+                    vers: version.num,
+                    deps,
+                    cksum: version.checksum,
+                    features,
+                    features2,
+                    yanked: Some(version.yanked),
+                    links: version.links,
+                    v,
+                }
+            })
+            .collect();
+
+        Ok(index_versions)
+    }
+}
+
+type IndexFeatures = BTreeMap<String, Vec<String>>;
+
+/// Splits the features of a version into those that all cargo versions understand, and those
+/// using the newer `dep:` and `pkg?/feat` syntax, which are only listed in the `features2` field
+/// of version 2 index entries.
+pub(crate) fn split_index_features(
+    features: IndexFeatures,
+) -> (IndexFeatures, Option<IndexFeatures>, Option<u32>) {
+    let (features, features2): (BTreeMap<_, _>, BTreeMap<_, _>) =
+        features.into_iter().partition(|(_k, vals)| {
+            !vals
+                .iter()
+                .any(|v| v.starts_with("dep:") || v.contains("?/"))
+        });
+
+    if features2.is_empty() {
+        (features, None, None)
+    } else {
+        (features, Some(features2), Some(2))
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::validate_dependency_name;
     use crate::models::{Crate, NewCrate};
 
     #[test]
@@ -453,31 +714,39 @@ mod tests {
     }
 
     #[test]
-    fn valid_name() {
-        assert!(Crate::valid_name("foo"));
-        assert!(!Crate::valid_name("京"));
-        assert!(!Crate::valid_name(""));
-        assert!(!Crate::valid_name("💝"));
-        assert!(Crate::valid_name("foo_underscore"));
-        assert!(Crate::valid_name("foo-dash"));
-        assert!(!Crate::valid_name("foo+plus"));
+    fn valid_ident() {
+        assert!(Crate::valid_ident("foo"));
+        assert!(!Crate::valid_ident("京"));
+        assert!(!Crate::valid_ident(""));
+        assert!(!Crate::valid_ident("💝"));
+        assert!(Crate::valid_ident("foo_underscore"));
+        assert!(Crate::valid_ident("foo-dash"));
+        assert!(!Crate::valid_ident("foo+plus"));
         // Starting with an underscore is an invalid crate name.
-        assert!(!Crate::valid_name("_foo"));
-        assert!(!Crate::valid_name("-foo"));
+        assert!(!Crate::valid_ident("_foo"));
+        assert!(!Crate::valid_ident("-foo"));
     }
 
     #[test]
-    fn valid_dependency_name() {
-        assert!(Crate::valid_dependency_name("foo"));
-        assert!(!Crate::valid_dependency_name("京"));
-        assert!(!Crate::valid_dependency_name(""));
-        assert!(!Crate::valid_dependency_name("💝"));
-        assert!(Crate::valid_dependency_name("foo_underscore"));
-        assert!(Crate::valid_dependency_name("foo-dash"));
-        assert!(!Crate::valid_dependency_name("foo+plus"));
+    fn valid_dependency_ident() {
+        assert!(Crate::valid_dependency_ident("foo"));
+        assert!(!Crate::valid_dependency_ident("京"));
+        assert!(!Crate::valid_dependency_ident(""));
+        assert!(!Crate::valid_dependency_ident("💝"));
+        assert!(Crate::valid_dependency_ident("foo_underscore"));
+        assert!(Crate::valid_dependency_ident("foo-dash"));
+        assert!(!Crate::valid_dependency_ident("foo+plus"));
         // Starting with an underscore is a valid dependency name.
-        assert!(Crate::valid_dependency_name("_foo"));
-        assert!(!Crate::valid_dependency_name("-foo"));
+        assert!(Crate::valid_dependency_ident("_foo"));
+        assert!(!Crate::valid_dependency_ident("-foo"));
+    }
+
+    #[test]
+    fn dependency_name_length() {
+        assert_ok!(validate_dependency_name("foo-bar", 7));
+        assert_err!(validate_dependency_name("foo-bar", 6));
+        // Characters are counted, not bytes
+        assert_ok!(validate_dependency_name("fööbär", 6));
     }
 
     #[test]
@@ -31,7 +31,11 @@ SELECT *, COUNT(*) OVER () as total FROM (
       ON crates.id = versions.crate_id
     WHERE dependencies.crate_id = $1
       AND rn = 1
-    ORDER BY crate_downloads DESC
+      AND crates.deleted_at IS NULL
+    ORDER BY crate_downloads DESC, crate_name ASC
 ) t
+-- Crates with the same number of downloads are ordered by name, so that pages
+-- don't overlap
+ORDER BY crate_downloads DESC, crate_name ASC
 OFFSET $2
 LIMIT $3
@@ -1,8 +1,26 @@
 /// Access rights to the crate (publishing and ownership management)
 /// NOTE: The order of these variants matters!
-#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Rights {
     None,
     Publish,
     Full,
 }
+
+impl Rights {
+    /// Whether new versions of the crate can be published.
+    pub fn can_publish(self) -> bool {
+        self >= Rights::Publish
+    }
+
+    /// Whether versions of the crate can be yanked and unyanked.
+    pub fn can_yank(self) -> bool {
+        self >= Rights::Publish
+    }
+
+    /// Whether owners can be added, removed, or the crate can be transferred.
+    pub fn can_manage_owners(self) -> bool {
+        self == Rights::Full
+    }
+}
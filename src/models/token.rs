@@ -1,6 +1,6 @@
 mod scopes;
 
-use chrono::NaiveDateTime;
+use chrono::{NaiveDateTime, Utc};
 use diesel::prelude::*;
 
 pub use self::scopes::{CrateScope, EndpointScope};
@@ -32,12 +32,14 @@ pub struct ApiToken {
     /// A list of endpoint scopes or `None` for the `legacy` endpoint scope (see RFC #2947)
     #[serde(skip)]
     pub endpoint_scopes: Option<Vec<EndpointScope>>,
+    #[serde(with = "rfc3339::option")]
+    pub expired_at: Option<NaiveDateTime>,
 }
 
 impl ApiToken {
     /// Generates a new named API token for a user
     pub fn insert(conn: &mut PgConnection, user_id: i32, name: &str) -> AppResult<CreatedApiToken> {
-        Self::insert_with_scopes(conn, user_id, name, None, None)
+        Self::insert_with_scopes(conn, user_id, name, None, None, None)
     }
 
     pub fn insert_with_scopes(
@@ -46,6 +48,7 @@ impl ApiToken {
         name: &str,
         crate_scopes: Option<Vec<CrateScope>>,
         endpoint_scopes: Option<Vec<EndpointScope>>,
+        expired_at: Option<NaiveDateTime>,
     ) -> AppResult<CreatedApiToken> {
         let token = SecureToken::generate(SecureTokenKind::Api);
 
@@ -56,6 +59,7 @@ impl ApiToken {
                 api_tokens::token.eq(&*token),
                 api_tokens::crate_scopes.eq(crate_scopes),
                 api_tokens::endpoint_scopes.eq(endpoint_scopes),
+                api_tokens::expired_at.eq(expired_at),
             ))
             .get_result(conn)?;
 
@@ -86,6 +90,12 @@ impl ApiToken {
         .or_else(|_| tokens.first(conn))
         .map_err(Into::into)
     }
+
+    /// Returns whether the token has expired and can't be used anymore.
+    pub fn is_expired(&self) -> bool {
+        self.expired_at
+            .map_or(false, |expired_at| expired_at <= Utc::now().naive_utc())
+    }
 }
 
 pub struct CreatedApiToken {
@@ -129,6 +139,7 @@ mod tests {
             .unwrap(),
             crate_scopes: None,
             endpoint_scopes: None,
+            expired_at: None,
         };
         let json = serde_json::to_string(&tok).unwrap();
         assert_some!(json
@@ -156,6 +167,7 @@ mod tests {
                     .and_hms_opt(14, 23, 12),
             )
             .unwrap(),
+            expired_at: None,
         };
         let json = serde_json::to_string(&tok).unwrap();
         assert_some!(json
@@ -106,7 +106,7 @@ impl CrateScope {
         }
 
         let name_without_wildcard = pattern.strip_suffix('*').unwrap_or(pattern);
-        Crate::valid_name(name_without_wildcard)
+        Crate::valid_ident(name_without_wildcard)
     }
 
     pub fn matches(&self, crate_name: &str) -> bool {
@@ -3,7 +3,8 @@ SELECT
   c.category,
   c.slug,
   c.description,
-  sum(c2.crates_cnt)::int as crates_cnt,
+  c.crates_cnt,
+  sum(c2.crates_cnt)::int as subtree_crates_cnt,
   c.created_at
 FROM categories as c
 INNER JOIN categories c2 ON split_part(c2.slug, '::', 1) = c.slug
@@ -0,0 +1,15 @@
+SELECT * FROM (
+  SELECT
+    c.id,
+    c.category,
+    c.slug,
+    c.description,
+    c.crates_cnt,
+    sum(c2.crates_cnt)::int as subtree_crates_cnt,
+    c.created_at
+  FROM categories as c
+  INNER JOIN categories c2 ON split_part(c2.slug, '::', 1) = c.slug
+  WHERE split_part(c.slug, '::', 1) = c.slug
+  GROUP BY c.id
+) AS toplevel
+{} LIMIT $1
@@ -20,6 +20,7 @@ pub struct User {
     pub gh_id: i32,
     pub account_lock_reason: Option<String>,
     pub account_lock_until: Option<NaiveDateTime>,
+    pub is_admin: bool,
 }
 
 /// Represents a new user record insertable to the `users` table
@@ -160,6 +161,23 @@ impl User {
         Ok(best)
     }
 
+    /// Locks the account, so that the user can't authenticate anymore until `until` has passed,
+    /// or until the lock is removed if there is no end date.
+    pub fn lock(
+        &self,
+        conn: &mut PgConnection,
+        reason: &str,
+        until: Option<NaiveDateTime>,
+    ) -> QueryResult<()> {
+        diesel::update(self)
+            .set((
+                users::account_lock_reason.eq(reason),
+                users::account_lock_until.eq(until),
+            ))
+            .execute(conn)?;
+        Ok(())
+    }
+
     /// Queries the database for the verified emails
     /// belonging to a given user
     pub fn verified_email(&self, conn: &mut PgConnection) -> QueryResult<Option<String>> {
@@ -1,11 +1,11 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 use chrono::NaiveDateTime;
 use diesel::prelude::*;
 
 use crate::util::errors::{cargo_err, AppResult};
 
-use crate::models::{Crate, Dependency, User};
+use crate::models::{Crate, Dependency, DependencyGraphEdge, DependencyKind, User};
 use crate::schema::*;
 
 // Queryable has a custom implementation below
@@ -25,6 +25,7 @@ pub struct Version {
     pub published_by: Option<i32>,
     pub checksum: String,
     pub links: Option<String>,
+    pub rust_version: Option<String>,
 }
 
 #[derive(Insertable, Debug)]
@@ -38,6 +39,7 @@ pub struct NewVersion {
     published_by: i32,
     checksum: String,
     links: Option<String>,
+    rust_version: Option<String>,
 }
 
 /// The highest version (semver order) and the most recently updated version.
@@ -102,6 +104,99 @@ impl Version {
             .load(conn)
     }
 
+    /// Returns the edges of the transitive dependency graph of this version, up to `max_depth`
+    /// levels deep.
+    ///
+    /// Dev-dependencies and crates that were deleted are skipped. Every dependency is resolved to
+    /// the highest non-yanked version of its crate that matches its version requirement, and
+    /// dependencies without such a version are left out. Unlike Cargo, this doesn't unify the
+    /// versions of a crate that is depended on several times, nor does it take features into
+    /// account. The edges of versions at `max_depth` are included, so callers can tell whether the
+    /// graph was truncated.
+    ///
+    /// The edges are ordered by the depth of their dependent version, with the root version at
+    /// depth 0, so the first edge leading to a version is from the shallowest path.
+    pub fn dependency_graph(
+        &self,
+        conn: &mut PgConnection,
+        max_depth: i32,
+    ) -> QueryResult<Vec<DependencyGraphEdge>> {
+        let mut edges = Vec::new();
+        let mut visited = HashSet::from([self.id]);
+        let mut frontier = vec![self.id];
+        // The non-yanked versions of each crate, highest first
+        let mut candidates: HashMap<i32, Vec<(semver::Version, i32)>> = HashMap::new();
+
+        for depth in 0..=max_depth {
+            if frontier.is_empty() {
+                break;
+            }
+
+            let dependencies: Vec<(i32, i32, String, bool, DependencyKind)> = dependencies::table
+                .inner_join(crates::table)
+                .filter(dependencies::version_id.eq_any(&frontier))
+                .filter(dependencies::kind.ne(DependencyKind::Dev as i32))
+                .filter(crates::deleted_at.is_null())
+                .select((
+                    dependencies::version_id,
+                    dependencies::crate_id,
+                    dependencies::req,
+                    dependencies::optional,
+                    dependencies::kind,
+                ))
+                .order((dependencies::version_id, dependencies::id))
+                .load(conn)?;
+
+            let mut crate_ids = dependencies
+                .iter()
+                .map(|(_, crate_id, ..)| *crate_id)
+                .filter(|crate_id| !candidates.contains_key(crate_id))
+                .collect::<Vec<_>>();
+            crate_ids.sort_unstable();
+            crate_ids.dedup();
+            let versions: Vec<(i32, i32, String)> = versions::table
+                .filter(versions::crate_id.eq_any(&crate_ids))
+                .filter(versions::yanked.eq(false))
+                .select((versions::crate_id, versions::id, versions::num))
+                .load(conn)?;
+            for crate_id in crate_ids {
+                candidates.entry(crate_id).or_default();
+            }
+            for (crate_id, id, num) in versions {
+                if let Ok(num) = semver::Version::parse(&num) {
+                    candidates.entry(crate_id).or_default().push((num, id));
+                }
+            }
+            for versions in candidates.values_mut() {
+                versions.sort_unstable_by(|a, b| b.cmp(a));
+            }
+
+            let mut next_frontier = Vec::new();
+            for (from_version_id, crate_id, req, optional, kind) in dependencies {
+                let Ok(version_req) = semver::VersionReq::parse(&req) else { continue };
+                let resolved = candidates[&crate_id]
+                    .iter()
+                    .find(|(num, _)| version_req.matches(num));
+                let Some(&(_, to_version_id)) = resolved else { continue };
+
+                if depth < max_depth && visited.insert(to_version_id) {
+                    next_frontier.push(to_version_id);
+                }
+                edges.push(DependencyGraphEdge {
+                    from_version_id,
+                    from_depth: depth,
+                    to_version_id,
+                    req,
+                    optional,
+                    kind,
+                });
+            }
+            frontier = next_frontier;
+        }
+
+        Ok(edges)
+    }
+
     pub fn record_readme_rendering(
         version_id_: i32,
         conn: &mut PgConnection,
@@ -139,6 +234,7 @@ impl NewVersion {
         published_by: i32,
         checksum: String,
         links: Option<String>,
+        rust_version: Option<String>,
     ) -> AppResult<Self> {
         let features = serde_json::to_value(features)?;
 
@@ -151,6 +247,7 @@ impl NewVersion {
             published_by,
             checksum,
             links,
+            rust_version,
         };
 
         new_version.validate_license(license_file)?;
@@ -0,0 +1,987 @@
+use chrono::{NaiveDateTime, Utc};
+use diesel::data_types::PgInterval;
+use diesel::deserialize::{self, FromSql};
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::serialize::{self, Output, ToSql};
+use diesel::sql_types::{Integer, Interval};
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::schema::{emails, publish_limit_buckets, publish_rate_overrides};
+use crate::sql::{date_part, floor, greatest, interval_part, least};
+use crate::util::errors::{AppResult, BoxedAppError, TooManyRequests};
+
+/// An action that users can only perform a limited number of times in a given period.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, FromSqlRow, AsExpression)]
+#[repr(i32)]
+#[diesel(sql_type = Integer)]
+pub enum LimitedAction {
+    PublishNew = 0,
+    UpdateCategories = 1,
+    /// Anonymous requests to the routes in `Server::anonymous_rate_limited_routes`, which are
+    /// limited per IP address instead of per user.
+    AnonymousRead = 2,
+    /// Downloads of the metadata of all crates of a user from `GET /api/v1/me/crates/export`.
+    ExportCrates = 3,
+}
+
+impl LimitedAction {
+    pub const ALL: &'static [LimitedAction] = &[
+        LimitedAction::PublishNew,
+        LimitedAction::UpdateCategories,
+        LimitedAction::AnonymousRead,
+        LimitedAction::ExportCrates,
+    ];
+
+    pub fn default_rate_seconds(&self) -> u64 {
+        match self {
+            LimitedAction::PublishNew => 10 * 60,
+            LimitedAction::UpdateCategories => 60,
+            LimitedAction::AnonymousRead => 1,
+            LimitedAction::ExportCrates => 60 * 60,
+        }
+    }
+
+    pub fn default_burst(&self) -> i32 {
+        match self {
+            LimitedAction::PublishNew => 5,
+            LimitedAction::UpdateCategories => 30,
+            LimitedAction::AnonymousRead => 60,
+            LimitedAction::ExportCrates => 5,
+        }
+    }
+
+    /// The default rate for users with a verified email address.
+    pub fn default_verified_rate_seconds(&self) -> u64 {
+        match self {
+            LimitedAction::PublishNew => 5 * 60,
+            LimitedAction::UpdateCategories => 30,
+            // Anonymous clients never have a verified email address
+            LimitedAction::AnonymousRead => self.default_rate_seconds(),
+            LimitedAction::ExportCrates => 60 * 60,
+        }
+    }
+
+    /// The default burst for users with a verified email address.
+    pub fn default_verified_burst(&self) -> i32 {
+        match self {
+            LimitedAction::PublishNew => 10,
+            LimitedAction::UpdateCategories => 60,
+            LimitedAction::AnonymousRead => self.default_burst(),
+            LimitedAction::ExportCrates => 10,
+        }
+    }
+
+    /// The name of the action in the `RATE_LIMITER_{KEY}_RATE_SECONDS` and
+    /// `RATE_LIMITER_{KEY}_BURST` environment variables.
+    pub fn env_var_key(&self) -> &'static str {
+        match self {
+            LimitedAction::PublishNew => "PUBLISH_NEW",
+            LimitedAction::UpdateCategories => "UPDATE_CATEGORIES",
+            LimitedAction::AnonymousRead => "ANONYMOUS_READ",
+            LimitedAction::ExportCrates => "EXPORT_CRATES",
+        }
+    }
+
+    /// Returns the `429 Too Many Requests` error for users that performed this action too often
+    /// and can try again at `retry_after`.
+    pub fn exceeded(&self, retry_after: NaiveDateTime) -> BoxedAppError {
+        Box::new(TooManyRequests::new(*self, retry_after))
+    }
+
+    pub fn error_message(&self) -> &'static str {
+        match self {
+            LimitedAction::PublishNew => {
+                "You have published too many crates in a short period of time."
+            }
+            LimitedAction::UpdateCategories => {
+                "You have changed the categories of your crates too many times in a short period of time."
+            }
+            LimitedAction::AnonymousRead => {
+                "You have made too many anonymous requests in a short period of time. \
+                 Authenticated requests are not subject to this limit."
+            }
+            LimitedAction::ExportCrates => {
+                "You have exported your crates too many times in a short period of time."
+            }
+        }
+    }
+}
+
+impl FromSql<Integer, Pg> for LimitedAction {
+    fn from_sql(bytes: diesel::pg::PgValue<'_>) -> deserialize::Result<Self> {
+        match <i32 as FromSql<Integer, Pg>>::from_sql(bytes)? {
+            0 => Ok(LimitedAction::PublishNew),
+            1 => Ok(LimitedAction::UpdateCategories),
+            2 => Ok(LimitedAction::AnonymousRead),
+            3 => Ok(LimitedAction::ExportCrates),
+            n => Err(format!("unknown limited action: {n}").into()),
+        }
+    }
+}
+
+impl ToSql<Integer, Pg> for LimitedAction {
+    fn to_sql(&self, out: &mut Output<'_, '_, Pg>) -> serialize::Result {
+        ToSql::<Integer, Pg>::to_sql(&(*self as i32), &mut out.reborrow())
+    }
+}
+
+/// The group of users that a limit applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RateLimitTier {
+    /// Users without a verified email address.
+    Unverified,
+    /// Users with a verified email address, who are much less likely to be abusing the service.
+    Verified,
+}
+
+impl RateLimitTier {
+    pub const ALL: &'static [RateLimitTier] = &[RateLimitTier::Unverified, RateLimitTier::Verified];
+
+    /// The infix of the tier in the rate limiter environment variables.
+    fn env_var_infix(&self) -> &'static str {
+        match self {
+            RateLimitTier::Unverified => "",
+            RateLimitTier::Verified => "VERIFIED_",
+        }
+    }
+}
+
+/// The state of a user's bucket after a token was taken from it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimitStatus {
+    /// The maximum number of tokens in the bucket.
+    pub limit: i32,
+    /// The number of tokens left after the current action.
+    pub remaining: i32,
+    /// When the next token is added to the bucket.
+    pub reset: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimiterConfig {
+    pub rate: Duration,
+    pub burst: i32,
+}
+
+impl RateLimiterConfig {
+    fn default_for(action: LimitedAction, tier: RateLimitTier) -> Self {
+        match tier {
+            RateLimitTier::Unverified => Self {
+                rate: Duration::from_secs(action.default_rate_seconds()),
+                burst: action.default_burst(),
+            },
+            RateLimitTier::Verified => Self {
+                rate: Duration::from_secs(action.default_verified_rate_seconds()),
+                burst: action.default_verified_burst(),
+            },
+        }
+    }
+}
+
+/// The limits of each action and tier.
+pub type RateLimiterConfigs = HashMap<(LimitedAction, RateLimitTier), RateLimiterConfig>;
+
+/// Reads the limits of all actions that are overridden via the `RATE_LIMITER_{KEY}_RATE_SECONDS`
+/// and `RATE_LIMITER_{KEY}_BURST` environment variables, or the
+/// `RATE_LIMITER_{KEY}_VERIFIED_RATE_SECONDS` and `RATE_LIMITER_{KEY}_VERIFIED_BURST` variables
+/// for users with a verified email address.
+///
+/// `WEB_NEW_PKG_RATE_LIMIT_RATE_MINUTES` and `WEB_NEW_PKG_RATE_LIMIT_BURST`, which configured the
+/// publish limit before there were other actions, are still read as aliases of
+/// `RATE_LIMITER_PUBLISH_NEW_RATE_SECONDS` and `RATE_LIMITER_PUBLISH_NEW_BURST`.
+///
+/// Tiers without any of the variables are not included, see `RateLimiter::config_for_action()`
+/// for the limits that are used for them instead.
+pub(crate) fn config_from_environment() -> RateLimiterConfigs {
+    let mut config = HashMap::new();
+    for &action in LimitedAction::ALL {
+        for &tier in RateLimitTier::ALL {
+            let key = action.env_var_key();
+            let infix = tier.env_var_infix();
+            let rate =
+                crate::env_optional::<u64>(&format!("RATE_LIMITER_{key}_{infix}RATE_SECONDS"));
+            let burst = crate::env_optional(&format!("RATE_LIMITER_{key}_{infix}BURST"));
+            let (rate, burst) = match (action, tier) {
+                (LimitedAction::PublishNew, RateLimitTier::Unverified) => (
+                    rate.or_else(|| {
+                        crate::env_optional::<u64>("WEB_NEW_PKG_RATE_LIMIT_RATE_MINUTES")
+                            .map(|minutes| minutes * 60)
+                    }),
+                    burst.or_else(|| crate::env_optional("WEB_NEW_PKG_RATE_LIMIT_BURST")),
+                ),
+                _ => (rate, burst),
+            };
+            if rate.is_none() && burst.is_none() {
+                continue;
+            }
+
+            let default = RateLimiterConfig::default_for(action, tier);
+            config.insert(
+                (action, tier),
+                RateLimiterConfig {
+                    rate: rate.map(Duration::from_secs).unwrap_or(default.rate),
+                    burst: burst.unwrap_or(default.burst),
+                },
+            );
+        }
+    }
+    config
+}
+
+#[derive(Queryable, Insertable, Debug, PartialEq, Clone, Copy)]
+#[diesel(table_name = publish_limit_buckets)]
+#[allow(dead_code)] // Most fields only read in tests
+struct Bucket {
+    user_id: i32,
+    tokens: i32,
+    last_refill: NaiveDateTime,
+    action: LimitedAction,
+}
+
+/// The bucket of an anonymous client, which is only kept in memory.
+#[derive(Debug, Clone, Copy)]
+struct AnonymousBucket {
+    tokens: i32,
+    last_refill: NaiveDateTime,
+}
+
+/// Full buckets are dropped once this many IP addresses are tracked, to bound the memory usage.
+/// If that isn't enough, the buckets that were refilled the longest time ago are dropped.
+const MAX_ANONYMOUS_BUCKETS: usize = 100_000;
+
+/// How many buckets are dropped at once when there are too many, so that the eviction doesn't
+/// run on every request from a new IP address.
+const ANONYMOUS_BUCKETS_EVICTED_AT_ONCE: usize = MAX_ANONYMOUS_BUCKETS / 10;
+
+#[derive(Debug, Clone, Default)]
+pub struct RateLimiter {
+    /// Limits overriding the defaults of `LimitedAction`.
+    config: RateLimiterConfigs,
+    /// Buckets of `LimitedAction::AnonymousRead`, by the IP address of the client.
+    anonymous_buckets: Arc<Mutex<HashMap<IpAddr, AnonymousBucket>>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimiterConfigs) -> Self {
+        Self {
+            config,
+            anonymous_buckets: Default::default(),
+        }
+    }
+
+    /// Returns the limits of an action for the given tier.
+    ///
+    /// If only the unverified tier is configured, verified users get the same limits as
+    /// unverified users, and if neither is configured the defaults of the tier in
+    /// `LimitedAction` are used.
+    fn config_for_action(&self, action: LimitedAction, tier: RateLimitTier) -> RateLimiterConfig {
+        self.config
+            .get(&(action, tier))
+            .or_else(|| self.config.get(&(action, RateLimitTier::Unverified)))
+            .copied()
+            .unwrap_or_else(|| RateLimiterConfig::default_for(action, tier))
+    }
+
+    /// Users without any email address are treated like users with an unverified one.
+    fn tier(&self, user_id: i32, conn: &mut PgConnection) -> QueryResult<RateLimitTier> {
+        let verified = emails::table
+            .filter(emails::user_id.eq(user_id))
+            .select(emails::verified)
+            .first(conn)
+            .optional()?;
+        Ok(match verified {
+            Some(true) => RateLimitTier::Verified,
+            _ => RateLimitTier::Unverified,
+        })
+    }
+
+    /// Returns the limits of an action for a user, taking their tier and overrides into account.
+    fn limits_for_user(
+        &self,
+        user_id: i32,
+        action: LimitedAction,
+        now: NaiveDateTime,
+        conn: &mut PgConnection,
+    ) -> QueryResult<RateLimiterConfig> {
+        let config = self.config_for_action(action, self.tier(user_id, conn)?);
+        let burst = publish_rate_overrides::table
+            .find((user_id, action))
+            .filter(
+                publish_rate_overrides::expires_at
+                    .is_null()
+                    .or(publish_rate_overrides::expires_at.gt(now)),
+            )
+            .select(publish_rate_overrides::burst)
+            .first(conn)
+            .optional()?;
+        Ok(RateLimiterConfig {
+            burst: burst.unwrap_or(config.burst),
+            ..config
+        })
+    }
+
+    pub fn check_rate_limit(
+        &self,
+        user_id: i32,
+        action: LimitedAction,
+        conn: &mut PgConnection,
+    ) -> AppResult<RateLimitStatus> {
+        let now = Utc::now().naive_utc();
+        let bucket = self.take_token(user_id, action, now, conn)?;
+        let limits = self.limits_for_user(user_id, action, now, conn)?;
+        let reset = bucket.last_refill + chrono::Duration::from_std(limits.rate).unwrap();
+        if bucket.tokens >= 1 {
+            Ok(RateLimitStatus {
+                limit: limits.burst,
+                remaining: bucket.tokens - 1,
+                reset,
+            })
+        } else {
+            Err(action.exceeded(reset))
+        }
+    }
+
+    /// Refill a user's bucket as needed, take a token from it,
+    /// and returns the result.
+    ///
+    /// The number of tokens remaining will always be between 0 and the burst of the action in
+    /// the user's tier.
+    /// If the number is 0, the request should be rejected, as the user doesn't
+    /// have a token to take. Technically a "full" bucket would have
+    /// `burst + 1` tokens in it, but that value would never be returned
+    /// since we only refill buckets when trying to take a token from it.
+    fn take_token(
+        &self,
+        user_id: i32,
+        action: LimitedAction,
+        now: NaiveDateTime,
+        conn: &mut PgConnection,
+    ) -> QueryResult<Bucket> {
+        let RateLimiterConfig { rate, burst } = self.limits_for_user(user_id, action, now, conn)?;
+        let refill_rate = refill_rate(rate);
+
+        // Interval division is poorly defined in general (what is 1 month / 30 days?)
+        // However, for the intervals we're dealing with, it is always well
+        // defined, so we convert to an f64 of seconds to represent this.
+        let tokens_to_add = floor(
+            (date_part("epoch", now) - date_part("epoch", publish_limit_buckets::last_refill))
+                / interval_part("epoch", refill_rate),
+        );
+
+        diesel::insert_into(publish_limit_buckets::table)
+            .values((
+                publish_limit_buckets::user_id.eq(user_id),
+                publish_limit_buckets::action.eq(action),
+                publish_limit_buckets::tokens.eq(burst),
+                publish_limit_buckets::last_refill.eq(now),
+            ))
+            .on_conflict((
+                publish_limit_buckets::user_id,
+                publish_limit_buckets::action,
+            ))
+            .do_update()
+            .set((
+                publish_limit_buckets::tokens.eq(least(
+                    burst,
+                    greatest(0, publish_limit_buckets::tokens - 1) + tokens_to_add,
+                )),
+                publish_limit_buckets::last_refill.eq(publish_limit_buckets::last_refill
+                    + refill_rate.into_sql::<Interval>() * tokens_to_add),
+            ))
+            .get_result(conn)
+    }
+}
+
+impl RateLimiter {
+    /// Takes a token from the bucket of an anonymous client.
+    ///
+    /// Unlike the buckets of users, these are not stored in the database, so each instance of
+    /// the application limits anonymous clients on its own.
+    pub fn check_anonymous_rate_limit(&self, ip: IpAddr) -> AppResult<RateLimitStatus> {
+        self.take_anonymous_token(ip, Utc::now().naive_utc())
+    }
+
+    fn take_anonymous_token(&self, ip: IpAddr, now: NaiveDateTime) -> AppResult<RateLimitStatus> {
+        let action = LimitedAction::AnonymousRead;
+        let RateLimiterConfig { rate, burst } =
+            self.config_for_action(action, RateLimitTier::Unverified);
+        let rate = chrono::Duration::from_std(rate).unwrap();
+
+        let mut buckets = self.anonymous_buckets.lock();
+        if buckets.len() >= MAX_ANONYMOUS_BUCKETS && !buckets.contains_key(&ip) {
+            buckets.retain(|_, bucket| now - bucket.last_refill < rate * burst);
+            if buckets.len() >= MAX_ANONYMOUS_BUCKETS {
+                evict_oldest_buckets(&mut buckets, ANONYMOUS_BUCKETS_EVICTED_AT_ONCE);
+            }
+        }
+
+        let bucket = buckets.entry(ip).or_insert(AnonymousBucket {
+            tokens: burst,
+            last_refill: now,
+        });
+
+        let elapsed = (now - bucket.last_refill).num_milliseconds();
+        let tokens_to_add = elapsed / rate.num_milliseconds().max(1);
+        if bucket.tokens as i64 + tokens_to_add >= burst as i64 {
+            bucket.tokens = burst;
+            bucket.last_refill = now;
+        } else if tokens_to_add > 0 {
+            bucket.tokens += tokens_to_add as i32;
+            bucket.last_refill += rate * tokens_to_add as i32;
+        }
+
+        let reset = bucket.last_refill + rate;
+        if bucket.tokens >= 1 {
+            bucket.tokens -= 1;
+            Ok(RateLimitStatus {
+                limit: burst,
+                remaining: bucket.tokens,
+                reset,
+            })
+        } else {
+            Err(action.exceeded(reset))
+        }
+    }
+}
+
+/// Drops the `count` buckets that were refilled the longest time ago.
+fn evict_oldest_buckets(buckets: &mut HashMap<IpAddr, AnonymousBucket>, count: usize) {
+    let mut by_age: Vec<_> = buckets
+        .iter()
+        .map(|(ip, bucket)| (bucket.last_refill, *ip))
+        .collect();
+    if count < by_age.len() {
+        by_age.select_nth_unstable(count);
+    }
+    for (_, ip) in by_age.into_iter().take(count) {
+        buckets.remove(&ip);
+    }
+}
+
+fn refill_rate(rate: Duration) -> PgInterval {
+    use diesel::dsl::*;
+    (rate.as_millis() as i64).milliseconds()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::email::Emails;
+    use crate::test_util::*;
+    use http::StatusCode;
+
+    const ACTION: LimitedAction = LimitedAction::PublishNew;
+
+    #[test]
+    fn take_token_with_no_bucket_creates_new_one() -> QueryResult<()> {
+        let conn = &mut pg_connection();
+        let now = now();
+
+        let rate = simple_limiter(Duration::from_secs(1), 10);
+        let bucket = rate.take_token(new_user(conn, "user1")?, ACTION, now, conn)?;
+        let expected = Bucket {
+            user_id: bucket.user_id,
+            tokens: 10,
+            last_refill: now,
+            action: ACTION,
+        };
+        assert_eq!(expected, bucket);
+
+        let rate = simple_limiter(Duration::from_millis(50), 20);
+        let bucket = rate.take_token(new_user(conn, "user2")?, ACTION, now, conn)?;
+        let expected = Bucket {
+            user_id: bucket.user_id,
+            tokens: 20,
+            last_refill: now,
+            action: ACTION,
+        };
+        assert_eq!(expected, bucket);
+        Ok(())
+    }
+
+    #[test]
+    fn take_token_with_existing_bucket_modifies_existing_bucket() -> QueryResult<()> {
+        let conn = &mut pg_connection();
+        let now = now();
+
+        let rate = simple_limiter(Duration::from_secs(1), 10);
+        let user_id = new_user_bucket(conn, 5, now)?.user_id;
+        let bucket = rate.take_token(user_id, ACTION, now, conn)?;
+        let expected = Bucket {
+            user_id,
+            tokens: 4,
+            last_refill: now,
+            action: ACTION,
+        };
+        assert_eq!(expected, bucket);
+        Ok(())
+    }
+
+    #[test]
+    fn take_token_after_delay_refills() -> QueryResult<()> {
+        let conn = &mut pg_connection();
+        let now = now();
+
+        let rate = simple_limiter(Duration::from_secs(1), 10);
+        let user_id = new_user_bucket(conn, 5, now)?.user_id;
+        let refill_time = now + chrono::Duration::seconds(2);
+        let bucket = rate.take_token(user_id, ACTION, refill_time, conn)?;
+        let expected = Bucket {
+            user_id,
+            tokens: 6,
+            last_refill: refill_time,
+            action: ACTION,
+        };
+        assert_eq!(expected, bucket);
+        Ok(())
+    }
+
+    #[test]
+    fn refill_subsecond_rate() -> QueryResult<()> {
+        let conn = &mut pg_connection();
+        // Subsecond rates have floating point rounding issues, so use a known
+        // timestamp that rounds fine
+        let now =
+            NaiveDateTime::parse_from_str("2019-03-19T21:11:24.620401", "%Y-%m-%dT%H:%M:%S%.f")
+                .unwrap();
+
+        let rate = simple_limiter(Duration::from_millis(100), 10);
+        let user_id = new_user_bucket(conn, 5, now)?.user_id;
+        let refill_time = now + chrono::Duration::milliseconds(300);
+        let bucket = rate.take_token(user_id, ACTION, refill_time, conn)?;
+        let expected = Bucket {
+            user_id,
+            tokens: 7,
+            last_refill: refill_time,
+            action: ACTION,
+        };
+        assert_eq!(expected, bucket);
+        Ok(())
+    }
+
+    #[test]
+    fn last_refill_always_advanced_by_multiple_of_rate() -> QueryResult<()> {
+        let conn = &mut pg_connection();
+        let now = now();
+
+        let rate = simple_limiter(Duration::from_millis(100), 10);
+        let user_id = new_user_bucket(conn, 5, now)?.user_id;
+        let bucket = rate.take_token(
+            user_id,
+            ACTION,
+            now + chrono::Duration::milliseconds(250),
+            conn,
+        )?;
+        let expected_refill_time = now + chrono::Duration::milliseconds(200);
+        let expected = Bucket {
+            user_id,
+            tokens: 6,
+            last_refill: expected_refill_time,
+            action: ACTION,
+        };
+        assert_eq!(expected, bucket);
+        Ok(())
+    }
+
+    #[test]
+    fn zero_tokens_returned_when_user_has_no_tokens_left() -> QueryResult<()> {
+        let conn = &mut pg_connection();
+        let now = now();
+
+        let rate = simple_limiter(Duration::from_secs(1), 10);
+        let user_id = new_user_bucket(conn, 1, now)?.user_id;
+        let bucket = rate.take_token(user_id, ACTION, now, conn)?;
+        let expected = Bucket {
+            user_id,
+            tokens: 0,
+            last_refill: now,
+            action: ACTION,
+        };
+        assert_eq!(expected, bucket);
+
+        let bucket = rate.take_token(user_id, ACTION, now, conn)?;
+        assert_eq!(expected, bucket);
+        Ok(())
+    }
+
+    #[test]
+    fn a_user_with_no_tokens_gets_a_token_after_exactly_rate() -> QueryResult<()> {
+        let conn = &mut pg_connection();
+        let now = now();
+
+        let rate = simple_limiter(Duration::from_secs(1), 10);
+        let user_id = new_user_bucket(conn, 0, now)?.user_id;
+        let refill_time = now + chrono::Duration::seconds(1);
+        let bucket = rate.take_token(user_id, ACTION, refill_time, conn)?;
+        let expected = Bucket {
+            user_id,
+            tokens: 1,
+            last_refill: refill_time,
+            action: ACTION,
+        };
+        assert_eq!(expected, bucket);
+
+        Ok(())
+    }
+
+    #[test]
+    fn tokens_never_refill_past_burst() -> QueryResult<()> {
+        let conn = &mut pg_connection();
+        let now = now();
+
+        let rate = simple_limiter(Duration::from_secs(1), 10);
+        let user_id = new_user_bucket(conn, 8, now)?.user_id;
+        let refill_time = now + chrono::Duration::seconds(4);
+        let bucket = rate.take_token(user_id, ACTION, refill_time, conn)?;
+        let expected = Bucket {
+            user_id,
+            tokens: 10,
+            last_refill: refill_time,
+            action: ACTION,
+        };
+        assert_eq!(expected, bucket);
+
+        Ok(())
+    }
+
+    #[test]
+    fn override_is_used_instead_of_global_burst_if_present() -> QueryResult<()> {
+        let conn = &mut pg_connection();
+        let now = now();
+
+        let rate = simple_limiter(Duration::from_secs(1), 10);
+        let user_id = new_user(conn, "user1")?;
+        let other_user_id = new_user(conn, "user2")?;
+
+        diesel::insert_into(publish_rate_overrides::table)
+            .values((
+                publish_rate_overrides::user_id.eq(user_id),
+                publish_rate_overrides::action.eq(ACTION),
+                publish_rate_overrides::burst.eq(20),
+            ))
+            .execute(conn)?;
+
+        let bucket = rate.take_token(user_id, ACTION, now, conn)?;
+        let other_bucket = rate.take_token(other_user_id, ACTION, now, conn)?;
+
+        assert_eq!(20, bucket.tokens);
+        assert_eq!(10, other_bucket.tokens);
+        Ok(())
+    }
+
+    #[test]
+    fn overrides_can_expire() -> QueryResult<()> {
+        let conn = &mut pg_connection();
+        let now = now();
+
+        let rate = simple_limiter(Duration::from_secs(1), 10);
+        let user_id = new_user(conn, "user1")?;
+        let other_user_id = new_user(conn, "user2")?;
+
+        diesel::insert_into(publish_rate_overrides::table)
+            .values((
+                publish_rate_overrides::user_id.eq(user_id),
+                publish_rate_overrides::action.eq(ACTION),
+                publish_rate_overrides::burst.eq(20),
+                publish_rate_overrides::expires_at.eq(now + chrono::Duration::days(30)),
+            ))
+            .execute(conn)?;
+
+        let bucket = rate.take_token(user_id, ACTION, now, conn)?;
+        let other_bucket = rate.take_token(other_user_id, ACTION, now, conn)?;
+
+        assert_eq!(20, bucket.tokens);
+        assert_eq!(10, other_bucket.tokens);
+
+        // Manually expire the rate limit
+        diesel::update(publish_rate_overrides::table)
+            .set(publish_rate_overrides::expires_at.eq(now - chrono::Duration::days(30)))
+            .filter(publish_rate_overrides::user_id.eq(user_id))
+            .execute(conn)?;
+
+        let bucket = rate.take_token(user_id, ACTION, now, conn)?;
+        let other_bucket = rate.take_token(other_user_id, ACTION, now, conn)?;
+
+        // The number of tokens of user_id is 10 and not 9 because when the new burst limit is
+        // lower than the amount of available tokens, the number of available tokens is reset to
+        // the new burst limit.
+        assert_eq!(10, bucket.tokens);
+        assert_eq!(9, other_bucket.tokens);
+
+        Ok(())
+    }
+
+    #[test]
+    fn actions_without_config_use_the_default_limits() {
+        let limiter = RateLimiter::default();
+        let config = limiter.config_for_action(ACTION, RateLimitTier::Unverified);
+        assert_eq!(
+            config.rate,
+            Duration::from_secs(ACTION.default_rate_seconds())
+        );
+        assert_eq!(config.burst, ACTION.default_burst());
+
+        let config = limiter.config_for_action(ACTION, RateLimitTier::Verified);
+        assert_eq!(
+            config.rate,
+            Duration::from_secs(ACTION.default_verified_rate_seconds())
+        );
+        assert_eq!(config.burst, ACTION.default_verified_burst());
+    }
+
+    #[test]
+    fn legacy_publish_variables_are_still_read() {
+        std::env::set_var("WEB_NEW_PKG_RATE_LIMIT_RATE_MINUTES", "2");
+        std::env::set_var("WEB_NEW_PKG_RATE_LIMIT_BURST", "3");
+        let config = config_from_environment();
+        std::env::remove_var("WEB_NEW_PKG_RATE_LIMIT_RATE_MINUTES");
+        std::env::remove_var("WEB_NEW_PKG_RATE_LIMIT_BURST");
+
+        let config = config[&(LimitedAction::PublishNew, RateLimitTier::Unverified)];
+        assert_eq!(config.rate, Duration::from_secs(2 * 60));
+        assert_eq!(config.burst, 3);
+    }
+
+    #[test]
+    fn configured_limits_are_enforced() -> QueryResult<()> {
+        let conn = &mut pg_connection();
+        let user_id = new_user(conn, "user1")?;
+
+        let limiter = simple_limiter(Duration::from_secs(60 * 60), 2);
+        assert!(limiter.check_rate_limit(user_id, ACTION, conn).is_ok());
+        assert!(limiter.check_rate_limit(user_id, ACTION, conn).is_ok());
+        assert!(limiter.check_rate_limit(user_id, ACTION, conn).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn exceeded_limits_have_the_same_response_as_the_limiter() -> QueryResult<()> {
+        let conn = &mut pg_connection();
+        let now = now();
+        let user_id = new_user_bucket(conn, 0, now)?.user_id;
+
+        let rate = Duration::from_secs(60 * 60);
+        let limiter = simple_limiter(rate, 10);
+        let limiter_response = limiter
+            .check_rate_limit(user_id, ACTION, conn)
+            .unwrap_err()
+            .response();
+
+        let retry_after = now + chrono::Duration::from_std(rate).unwrap();
+        let response = ACTION.exceeded(retry_after).response();
+
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.status(), limiter_response.status());
+        assert_eq!(response.headers(), limiter_response.headers());
+
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let limiter_body = hyper::body::to_bytes(limiter_response.into_body())
+            .await
+            .unwrap();
+        assert_eq!(body, limiter_body);
+        Ok(())
+    }
+
+    #[test]
+    fn verified_users_get_the_verified_limits() -> QueryResult<()> {
+        let conn = &mut pg_connection();
+        let user_id = new_user(conn, "user1")?;
+        verify_email(conn, user_id)?;
+
+        let limiter = tiered_limiter();
+        for _ in 0..4 {
+            assert!(limiter.check_rate_limit(user_id, ACTION, conn).is_ok());
+        }
+        assert!(limiter.check_rate_limit(user_id, ACTION, conn).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn unverified_users_get_the_stricter_limits() -> QueryResult<()> {
+        let conn = &mut pg_connection();
+        let user_id = new_user(conn, "user1")?;
+
+        let limiter = tiered_limiter();
+        let status = limiter.check_rate_limit(user_id, ACTION, conn).unwrap();
+        assert_eq!(status.limit, 1);
+        assert!(limiter.check_rate_limit(user_id, ACTION, conn).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn verified_users_get_a_larger_allowance_by_default() -> QueryResult<()> {
+        let conn = &mut pg_connection();
+        let unverified_id = new_user(conn, "user1")?;
+        let verified_id = new_user(conn, "user2")?;
+        verify_email(conn, verified_id)?;
+
+        let limiter = RateLimiter::default();
+        let unverified = limiter
+            .check_rate_limit(unverified_id, ACTION, conn)
+            .unwrap();
+        let verified = limiter.check_rate_limit(verified_id, ACTION, conn).unwrap();
+        assert_eq!(unverified.limit, ACTION.default_burst());
+        assert_eq!(verified.limit, ACTION.default_verified_burst());
+        assert!(verified.limit > unverified.limit);
+        Ok(())
+    }
+
+    #[test]
+    fn verified_tier_falls_back_to_the_unverified_limits() {
+        let limiter = simple_limiter(Duration::from_secs(1), 10);
+        let config = limiter.config_for_action(ACTION, RateLimitTier::Verified);
+        assert_eq!(config.burst, 10);
+    }
+
+    #[test]
+    fn anonymous_clients_are_limited_per_ip_address() {
+        let now = now();
+        let limiter = anonymous_limiter(Duration::from_secs(1), 2);
+        let ip = IpAddr::from([127, 0, 0, 1]);
+        let other_ip = IpAddr::from([127, 0, 0, 2]);
+
+        assert_eq!(
+            assert_ok!(limiter.take_anonymous_token(ip, now)).remaining,
+            1
+        );
+        assert_eq!(
+            assert_ok!(limiter.take_anonymous_token(ip, now)).remaining,
+            0
+        );
+        let error = assert_err!(limiter.take_anonymous_token(ip, now));
+        assert_eq!(error.response().status(), StatusCode::TOO_MANY_REQUESTS);
+
+        assert_eq!(
+            assert_ok!(limiter.take_anonymous_token(other_ip, now)).remaining,
+            1
+        );
+    }
+
+    #[test]
+    fn anonymous_buckets_are_refilled_over_time() {
+        let now = now();
+        let limiter = anonymous_limiter(Duration::from_secs(1), 2);
+        let ip = IpAddr::from([127, 0, 0, 1]);
+
+        assert_ok!(limiter.take_anonymous_token(ip, now));
+        assert_ok!(limiter.take_anonymous_token(ip, now));
+        assert_err!(limiter.take_anonymous_token(ip, now));
+
+        let later = now + chrono::Duration::milliseconds(1500);
+        let status = assert_ok!(limiter.take_anonymous_token(ip, later));
+        assert_eq!(status.remaining, 0);
+        assert_eq!(status.reset, now + chrono::Duration::seconds(2));
+        assert_err!(limiter.take_anonymous_token(ip, later));
+
+        let much_later = now + chrono::Duration::hours(1);
+        let status = assert_ok!(limiter.take_anonymous_token(ip, much_later));
+        assert_eq!(status.remaining, 1);
+    }
+
+    #[test]
+    fn oldest_anonymous_buckets_are_evicted_when_there_are_too_many() {
+        let now = now();
+        let limiter = anonymous_limiter(Duration::from_secs(60 * 60), 1);
+        let oldest_ip = IpAddr::from([10, 0, 0, 1]);
+
+        assert_ok!(limiter.take_anonymous_token(oldest_ip, now - chrono::Duration::seconds(1)));
+        assert_err!(limiter.take_anonymous_token(oldest_ip, now));
+
+        // None of these buckets are full, so they can't simply be dropped
+        for i in 1..MAX_ANONYMOUS_BUCKETS as u32 {
+            assert_ok!(limiter.take_anonymous_token(IpAddr::from(i.to_be_bytes()), now));
+        }
+        assert_eq!(
+            limiter.anonymous_buckets.lock().len(),
+            MAX_ANONYMOUS_BUCKETS
+        );
+
+        let new_ip = IpAddr::from([10, 0, 0, 2]);
+        assert_ok!(limiter.take_anonymous_token(new_ip, now));
+        assert!(limiter.anonymous_buckets.lock().len() <= MAX_ANONYMOUS_BUCKETS);
+
+        // The oldest bucket was evicted, so the client starts over with a full bucket
+        assert_ok!(limiter.take_anonymous_token(oldest_ip, now));
+        assert_err!(limiter.take_anonymous_token(new_ip, now));
+    }
+
+    fn anonymous_limiter(rate: Duration, burst: i32) -> RateLimiter {
+        RateLimiter::new(HashMap::from([(
+            (LimitedAction::AnonymousRead, RateLimitTier::Unverified),
+            RateLimiterConfig { rate, burst },
+        )]))
+    }
+
+    fn simple_limiter(rate: Duration, burst: i32) -> RateLimiter {
+        RateLimiter::new(HashMap::from([(
+            (ACTION, RateLimitTier::Unverified),
+            RateLimiterConfig { rate, burst },
+        )]))
+    }
+
+    fn tiered_limiter() -> RateLimiter {
+        let rate = Duration::from_secs(60 * 60);
+        RateLimiter::new(HashMap::from([
+            (
+                (ACTION, RateLimitTier::Unverified),
+                RateLimiterConfig { rate, burst: 1 },
+            ),
+            (
+                (ACTION, RateLimitTier::Verified),
+                RateLimiterConfig { rate, burst: 4 },
+            ),
+        ]))
+    }
+
+    fn verify_email(conn: &mut PgConnection, user_id: i32) -> QueryResult<()> {
+        diesel::insert_into(emails::table)
+            .values((
+                emails::user_id.eq(user_id),
+                emails::email.eq("user@example.com"),
+                emails::verified.eq(true),
+            ))
+            .execute(conn)?;
+        Ok(())
+    }
+
+    fn new_user(conn: &mut PgConnection, gh_login: &str) -> QueryResult<i32> {
+        use crate::models::NewUser;
+
+        let user = NewUser {
+            gh_login,
+            ..NewUser::default()
+        }
+        .create_or_update(None, &Emails::new_in_memory(), conn)?;
+        Ok(user.id)
+    }
+
+    fn new_user_bucket(
+        conn: &mut PgConnection,
+        tokens: i32,
+        now: NaiveDateTime,
+    ) -> QueryResult<Bucket> {
+        diesel::insert_into(publish_limit_buckets::table)
+            .values(Bucket {
+                user_id: new_user(conn, "new_user")?,
+                tokens,
+                last_refill: now,
+                action: ACTION,
+            })
+            .get_result(conn)
+    }
+
+    /// Strips ns precision from `Utc::now`. PostgreSQL only has microsecond
+    /// precision, but some platforms (notably Linux) provide nanosecond
+    /// precision, meaning that round tripping through the database would
+    /// change the value.
+    fn now() -> NaiveDateTime {
+        let now = Utc::now().naive_utc();
+        let nanos = now.timestamp_subsec_nanos();
+        now - chrono::Duration::nanoseconds(nanos.into())
+    }
+}
@@ -1,10 +1,13 @@
 use axum::extract::DefaultBodyLimit;
+use axum::middleware::from_fn_with_state;
 use axum::response::IntoResponse;
-use axum::routing::{delete, get, post, put};
+use axum::routing::{delete, get, patch, post, put};
 use axum::Router;
+use chrono::NaiveDate;
 
 use crate::app::AppState;
 use crate::controllers::*;
+use crate::middleware::deprecation::{add_deprecation_headers, Deprecation};
 use crate::util::errors::not_found;
 use crate::Env;
 
@@ -44,11 +47,22 @@ pub fn build_axum_router(state: AppState) -> Router {
             get(version::deprecated::show_by_id),
         )
         // Routes used by the frontend
-        .route("/api/v1/crates/:crate_id", get(krate::metadata::show))
+        .route(
+            "/api/v1/crates/:crate_id",
+            get(krate::metadata::show).delete(krate::delete::delete),
+        )
+        .route(
+            "/api/v1/crates/:crate_id/delete/confirm",
+            post(krate::delete::confirm),
+        )
         .route(
             "/api/v1/crates/:crate_id/:version",
             get(version::metadata::show),
         )
+        .route(
+            "/api/v1/crates/:crate_id/latest",
+            get(version::metadata::latest),
+        )
         .route(
             "/api/v1/crates/:crate_id/:version/readme",
             get(krate::metadata::readme),
@@ -57,6 +71,10 @@ pub fn build_axum_router(state: AppState) -> Router {
             "/api/v1/crates/:crate_id/:version/dependencies",
             get(version::metadata::dependencies),
         )
+        .route(
+            "/api/v1/crates/:crate_id/:version/dependency_graph",
+            get(version::metadata::dependency_graph),
+        )
         .route(
             "/api/v1/crates/:crate_id/:version/downloads",
             get(version::downloads::downloads),
@@ -69,10 +87,18 @@ pub fn build_axum_router(state: AppState) -> Router {
             "/api/v1/crates/:crate_id/downloads",
             get(krate::downloads::downloads),
         )
+        .route(
+            "/api/v1/crates/:crate_id/downloads_by_version",
+            get(krate::downloads::downloads_by_version),
+        )
         .route(
             "/api/v1/crates/:crate_id/versions",
             get(krate::metadata::versions),
         )
+        .route(
+            "/api/v1/crates/:crate_id/versions/export",
+            get(krate::export::export),
+        )
         .route(
             "/api/v1/crates/:crate_id/follow",
             put(krate::follow::follow).delete(krate::follow::unfollow),
@@ -89,13 +115,40 @@ pub fn build_axum_router(state: AppState) -> Router {
             "/api/v1/crates/:crate_id/owner_user",
             get(krate::owners::owner_user),
         )
+        .route(
+            "/api/v1/crates/:crate_id/me/permissions",
+            get(krate::owners::permissions),
+        )
+        .route(
+            "/api/v1/crates/:crate_id/transfer",
+            post(krate::owners::transfer_ownership),
+        )
+        .route(
+            "/api/v1/crates/:crate_id/webhooks",
+            get(krate::webhooks::list).post(krate::webhooks::create),
+        )
+        .route(
+            "/api/v1/crates/:crate_id/webhooks/:webhook_id",
+            put(krate::webhooks::update).delete(krate::webhooks::delete),
+        )
+        .route(
+            "/api/v1/crates/:crate_id/categories",
+            get(krate::metadata::categories),
+        )
         .route(
             "/api/v1/crates/:crate_id/reverse_dependencies",
             get(krate::metadata::reverse_dependencies),
         )
+        .route(
+            "/api/v1/crates/:crate_id/reverse_dependencies/count",
+            get(krate::metadata::reverse_dependencies_count),
+        )
         .route("/api/v1/keywords", get(keyword::index))
         .route("/api/v1/keywords/:keyword_id", get(keyword::show))
         .route("/api/v1/categories", get(category::index))
+        .route("/api/v1/categories/search", get(category::search))
+        .route("/api/v1/categories/assignable", get(category::assignable))
+        .route("/api/v1/categories/export", get(category::export::export))
         .route("/api/v1/categories/:category_id", get(category::show))
         .route("/api/v1/category_slugs", get(category::slugs))
         .route(
@@ -103,9 +156,15 @@ pub fn build_axum_router(state: AppState) -> Router {
             get(user::other::show).put(user::me::update_user),
         )
         .route("/api/v1/users/:user_id/stats", get(user::other::stats))
+        .route("/api/v1/users/:user_id/crates", get(user::other::crates))
+        .route(
+            "/api/v1/users/:user_id/lock",
+            patch(admin::update_user_lock),
+        )
         .route("/api/v1/teams/:team_id", get(team::show_team))
         .route("/api/v1/me", get(user::me::me))
         .route("/api/v1/me/updates", get(user::me::updates))
+        .route("/api/v1/me/crates/export", get(user::export::export))
         .route("/api/v1/me/tokens", get(token::list).put(token::new))
         .route("/api/v1/me/tokens/:id", delete(token::revoke))
         .route("/api/v1/tokens/current", delete(token::revoke_current))
@@ -138,6 +197,46 @@ pub fn build_axum_router(state: AppState) -> Router {
             "/api/v1/site_metadata",
             get(site_metadata::show_deployed_sha),
         )
+        // Admin operations
+        .route("/api/v1/admin/read_only", put(admin::update_read_only_mode))
+        .route(
+            "/api/v1/admin/users/lock_bulk",
+            post(admin::lock_users_bulk),
+        )
+        .route("/api/v1/admin/users/merge", post(admin::merge_users))
+        .route("/api/v1/admin/locks/expiring", get(admin::expiring_locks))
+        .route(
+            "/api/v1/admin/admins/:login",
+            post(admin::add_admin).delete(admin::remove_admin),
+        )
+        .route("/api/v1/admin/deletions", get(admin::deletions))
+        .route(
+            "/api/v1/admin/crates/:crate_id/restore",
+            put(admin::restore_crate),
+        )
+        .route(
+            "/api/v1/admin/crates/:crate_id/frozen",
+            put(admin::update_crate_frozen),
+        )
+        .route(
+            "/api/v1/admin/crates/:crate_id/reset_downloads",
+            post(admin::reset_crate_downloads),
+        )
+        .route(
+            "/api/v1/admin/crates/:crate_id/resync_index",
+            post(admin::resync_crate_index),
+        )
+        .route(
+            "/api/v1/admin/crates/:crate_id/:version/yank",
+            put(admin::force_yank),
+        )
+        .route(
+            "/api/v1/crates/:crate_id/storage_manifest",
+            get(admin::storage_manifest),
+        )
+        // Health checks for the load balancer
+        .route("/api/v1/health", get(health::health))
+        .route("/api/v1/ready", get(health::ready))
         // Session management
         .route("/api/private/session/begin", get(user::session::begin))
         .route(
@@ -152,6 +251,9 @@ pub fn build_axum_router(state: AppState) -> Router {
             "/api/private/crate_owner_invitations",
             get(crate_owner_invitation::private_list),
         )
+        // Looking up many crates at once in the frontend. This can't be below
+        // `/api/v1/crates`, since `batch` is a valid crate name.
+        .route("/api/private/crates/batch", post(krate::metadata::batch))
         // Alerts from GitHub scanning for exposed API tokens
         .route(
             "/api/github/secret-scanning/verify",
@@ -168,6 +270,17 @@ pub fn build_axum_router(state: AppState) -> Router {
         );
     }
 
+    // No route is deprecated yet, so the deprecation headers are covered by a copy of
+    // `GET /api/v1/me` that only exists in tests
+    if state.config.env() == Env::Test {
+        let deprecation =
+            Deprecation::new(NaiveDate::from_ymd_opt(2027, 6, 30).unwrap(), "/api/v1/me");
+        router = router.route(
+            "/api/private/test/deprecated_me",
+            get(user::me::me).layer(from_fn_with_state(deprecation, add_deprecation_headers)),
+        );
+    }
+
     router
         .fallback(|| async { not_found().into_response() })
         .with_state(state)
@@ -176,10 +289,12 @@ pub fn build_axum_router(state: AppState) -> Router {
 #[cfg(test)]
 mod tests {
     use crate::middleware::log_request::CauseField;
+    use crate::test_util::pg_connection;
     use crate::util::errors::{
         bad_request, cargo_err, forbidden, internal, not_found, AppError, BoxedAppError,
     };
     use axum::response::IntoResponse;
+    use diesel::prelude::*;
     use diesel::result::Error as DieselError;
     use http::StatusCode;
     use serde::de::Error;
@@ -231,4 +346,22 @@ mod tests {
             StatusCode::INTERNAL_SERVER_ERROR
         );
     }
+
+    #[tokio::test]
+    async fn database_errors_are_logged_but_not_exposed() {
+        let mut conn = pg_connection();
+        let err = diesel::sql_query("SELECT * FROM no_such_table")
+            .execute(&mut conn)
+            .unwrap_err();
+
+        let response = BoxedAppError::from(err).into_response();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(
+            response.extensions().get::<CauseField>().unwrap().0,
+            "relation \"no_such_table\" does not exist"
+        );
+
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(body, "Internal Server Error");
+    }
 }
@@ -76,6 +76,12 @@ diesel::table! {
         ///
         /// (Automatically generated by Diesel.)
         endpoint_scopes -> Nullable<Array<Text>>,
+        /// The `expired_at` column of the `api_tokens` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        expired_at -> Nullable<Timestamp>,
     }
 }
 
@@ -120,6 +126,18 @@ diesel::table! {
         ///
         /// (Automatically generated by Diesel.)
         created_at -> Timestamp,
+        /// The `next_attempt_at` column of the `background_jobs` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        next_attempt_at -> Nullable<Timestamp>,
+        /// The `dedup_key` column of the `background_jobs` table.
+        ///
+        /// Its SQL type is `Nullable<Text>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        dedup_key -> Nullable<Text>,
     }
 }
 
@@ -196,6 +214,76 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    /// Representation of the `category_descriptions` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    category_descriptions (category_id, locale) {
+        /// The `category_id` column of the `category_descriptions` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        category_id -> Int4,
+        /// The `locale` column of the `category_descriptions` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        locale -> Varchar,
+        /// The `description` column of the `category_descriptions` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        description -> Varchar,
+    }
+}
+
+diesel::table! {
+    /// Representation of the `crate_deletions` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    crate_deletions (id) {
+        /// The `id` column of the `crate_deletions` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `crate_name` column of the `crate_deletions` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        crate_name -> Varchar,
+        /// The `deleted_by` column of the `crate_deletions` table.
+        ///
+        /// Its SQL type is `Nullable<Int4>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        deleted_by -> Nullable<Int4>,
+        /// The `deleted_at` column of the `crate_deletions` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        deleted_at -> Timestamp,
+        /// The `path` column of the `crate_deletions` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        path -> Varchar,
+        /// The `anonymized_at` column of the `crate_deletions` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        anonymized_at -> Nullable<Timestamp>,
+    }
+}
+
 diesel::table! {
     /// Representation of the `crate_owner_invitations` table.
     ///
@@ -237,6 +325,12 @@ diesel::table! {
         ///
         /// (Automatically generated by Diesel.)
         token_generated_at -> Nullable<Timestamp>,
+        /// The `is_transfer` column of the `crate_owner_invitations` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        is_transfer -> Bool,
     }
 }
 
@@ -296,6 +390,50 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    /// Representation of the `crate_webhooks` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    crate_webhooks (id) {
+        /// The `id` column of the `crate_webhooks` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `crate_id` column of the `crate_webhooks` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        crate_id -> Int4,
+        /// The `url` column of the `crate_webhooks` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        url -> Varchar,
+        /// The `secret` column of the `crate_webhooks` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        secret -> Varchar,
+        /// The `events` column of the `crate_webhooks` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        events -> Int4,
+        /// The `created_at` column of the `crate_webhooks` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     use diesel::sql_types::*;
     use super::sql_types::Tsvector;
@@ -376,6 +514,18 @@ diesel::table! {
         ///
         /// (Automatically generated by Diesel.)
         max_upload_size -> Nullable<Int4>,
+        /// The `deleted_at` column of the `crates` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        deleted_at -> Nullable<Timestamp>,
+        /// The `crate_frozen` column of the `crates` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        crate_frozen -> Bool,
     }
 }
 
@@ -551,6 +701,50 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    /// Representation of the `idempotency_keys` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    idempotency_keys (user_id, key) {
+        /// The `user_id` column of the `idempotency_keys` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Int4,
+        /// The `key` column of the `idempotency_keys` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        key -> Varchar,
+        /// The `request` column of the `idempotency_keys` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        request -> Varchar,
+        /// The `response_status` column of the `idempotency_keys` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        response_status -> Int4,
+        /// The `response_body` column of the `idempotency_keys` table.
+        ///
+        /// Its SQL type is `Jsonb`.
+        ///
+        /// (Automatically generated by Diesel.)
+        response_body -> Jsonb,
+        /// The `created_at` column of the `idempotency_keys` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     /// Representation of the `keywords` table.
     ///
@@ -594,6 +788,12 @@ diesel::table! {
         ///
         /// (Automatically generated by Diesel.)
         total_downloads -> Int8,
+        /// The `read_only` column of the `metadata` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        read_only -> Bool,
     }
 }
 
@@ -601,7 +801,7 @@ diesel::table! {
     /// Representation of the `publish_limit_buckets` table.
     ///
     /// (Automatically generated by Diesel.)
-    publish_limit_buckets (user_id) {
+    publish_limit_buckets (user_id, action) {
         /// The `user_id` column of the `publish_limit_buckets` table.
         ///
         /// Its SQL type is `Int4`.
@@ -620,6 +820,12 @@ diesel::table! {
         ///
         /// (Automatically generated by Diesel.)
         last_refill -> Timestamp,
+        /// The `action` column of the `publish_limit_buckets` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        action -> Int4,
     }
 }
 
@@ -627,7 +833,7 @@ diesel::table! {
     /// Representation of the `publish_rate_overrides` table.
     ///
     /// (Automatically generated by Diesel.)
-    publish_rate_overrides (user_id) {
+    publish_rate_overrides (user_id, action) {
         /// The `user_id` column of the `publish_rate_overrides` table.
         ///
         /// Its SQL type is `Int4`.
@@ -646,6 +852,12 @@ diesel::table! {
         ///
         /// (Automatically generated by Diesel.)
         expires_at -> Nullable<Timestamp>,
+        /// The `action` column of the `publish_rate_overrides` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        action -> Int4,
     }
 }
 
@@ -701,6 +913,26 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    /// Representation of the `scheduled_jobs` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    scheduled_jobs (name) {
+        /// The `name` column of the `scheduled_jobs` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        name -> Varchar,
+        /// The `last_run_at` column of the `scheduled_jobs` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        last_run_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     /// Representation of the `teams` table.
     ///
@@ -798,6 +1030,12 @@ diesel::table! {
         ///
         /// (Automatically generated by Diesel.)
         account_lock_until -> Nullable<Timestamp>,
+        /// The `is_admin` column of the `users` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        is_admin -> Bool,
     }
 }
 
@@ -880,6 +1118,12 @@ diesel::table! {
         ///
         /// (Automatically generated by Diesel.)
         time -> Timestamp,
+        /// The `reason` column of the `version_owner_actions` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        reason -> Nullable<Varchar>,
     }
 }
 
@@ -966,6 +1210,12 @@ diesel::table! {
         ///
         /// (Automatically generated by Diesel.)
         links -> Nullable<Varchar>,
+        /// The `rust_version` column of the `versions` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        rust_version -> Nullable<Varchar>,
     }
 }
 
@@ -991,10 +1241,13 @@ diesel::table! {
 
 diesel::joinable!(api_tokens -> users (user_id));
 diesel::joinable!(badges -> crates (crate_id));
+diesel::joinable!(category_descriptions -> categories (category_id));
+diesel::joinable!(crate_deletions -> users (deleted_by));
 diesel::joinable!(crate_owner_invitations -> crates (crate_id));
 diesel::joinable!(crate_owners -> crates (crate_id));
 diesel::joinable!(crate_owners -> teams (owner_id));
 diesel::joinable!(crate_owners -> users (owner_id));
+diesel::joinable!(crate_webhooks -> crates (crate_id));
 diesel::joinable!(crates_categories -> categories (category_id));
 diesel::joinable!(crates_categories -> crates (crate_id));
 diesel::joinable!(crates_keywords -> crates (crate_id));
@@ -1004,6 +1257,7 @@ diesel::joinable!(dependencies -> versions (version_id));
 diesel::joinable!(emails -> users (user_id));
 diesel::joinable!(follows -> crates (crate_id));
 diesel::joinable!(follows -> users (user_id));
+diesel::joinable!(idempotency_keys -> users (user_id));
 diesel::joinable!(publish_limit_buckets -> users (user_id));
 diesel::joinable!(publish_rate_overrides -> users (user_id));
 diesel::joinable!(readme_renderings -> versions (version_id));
@@ -1021,14 +1275,18 @@ diesel::allow_tables_to_appear_in_same_query!(
     background_jobs,
     badges,
     categories,
+    category_descriptions,
+    crate_deletions,
     crate_owner_invitations,
     crate_owners,
+    crate_webhooks,
     crates,
     crates_categories,
     crates_keywords,
     dependencies,
     emails,
     follows,
+    idempotency_keys,
     keywords,
     metadata,
     publish_limit_buckets,
@@ -1036,6 +1294,7 @@ diesel::allow_tables_to_appear_in_same_query!(
     readme_renderings,
     recent_crate_downloads,
     reserved_crate_names,
+    scheduled_jobs,
     teams,
     users,
     version_downloads,
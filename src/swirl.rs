@@ -1,7 +1,11 @@
+mod retry;
 mod runner;
+mod scheduler;
 mod storage;
 
 pub mod errors;
 
-pub use self::runner::Runner;
+pub use self::retry::{Backoff, RetryPolicy};
+pub use self::runner::{ConcurrencyLimits, Runner};
+pub use self::scheduler::{ScheduledJob, Scheduler};
 pub(crate) use errors::PerformError;
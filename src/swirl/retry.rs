@@ -0,0 +1,127 @@
+use rand::Rng;
+use std::time::Duration;
+
+/// The maximum exponent used by `Backoff::Exponential`, to avoid overflowing the delay.
+const MAX_EXPONENT: u32 = 16;
+
+/// Describes how often, and how quickly, a failed job is retried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// The number of times a job is retried after its initial attempt failed, or `None` to retry
+    /// it indefinitely.
+    pub max_retries: Option<u32>,
+    /// How long to wait before each retry.
+    pub backoff: Backoff,
+}
+
+impl Default for RetryPolicy {
+    /// Retries indefinitely, starting after two minutes and doubling the delay after every
+    /// failure.
+    fn default() -> Self {
+        Self {
+            max_retries: None,
+            backoff: Backoff::Exponential {
+                base: Duration::from_secs(60),
+                jitter: false,
+            },
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Returns how long to wait before running a job again after it failed for the
+    /// `retries`-th time, or `None` if the job should not be retried again.
+    pub fn next_delay(&self, retries: u32) -> Option<Duration> {
+        match self.max_retries {
+            Some(max_retries) if retries > max_retries => None,
+            _ => Some(self.backoff.delay(retries)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backoff {
+    /// Wait the same amount of time before every retry.
+    Fixed(Duration),
+    /// Wait `base * 2^retries` before each retry.
+    ///
+    /// If `jitter` is enabled, the delay is randomly reduced by up to half, so that many jobs
+    /// failing at once (e.g. during an outage) aren't all retried at the same time.
+    Exponential { base: Duration, jitter: bool },
+}
+
+impl Backoff {
+    /// Returns how long to wait after a job failed for the `retries`-th time.
+    pub fn delay(&self, retries: u32) -> Duration {
+        match *self {
+            Backoff::Fixed(delay) => delay,
+            Backoff::Exponential { base, jitter } => {
+                let delay = base.saturating_mul(2u32.pow(retries.min(MAX_EXPONENT)));
+                if jitter {
+                    delay.mul_f64(rand::thread_rng().gen_range(0.5..=1.0))
+                } else {
+                    delay
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINUTE: Duration = Duration::from_secs(60);
+
+    #[test]
+    fn fixed_backoff() {
+        let backoff = Backoff::Fixed(MINUTE);
+        assert_eq!(backoff.delay(1), MINUTE);
+        assert_eq!(backoff.delay(10), MINUTE);
+    }
+
+    #[test]
+    fn exponential_backoff() {
+        let backoff = Backoff::Exponential {
+            base: MINUTE,
+            jitter: false,
+        };
+        assert_eq!(backoff.delay(1), 2 * MINUTE);
+        assert_eq!(backoff.delay(2), 4 * MINUTE);
+        assert_eq!(backoff.delay(5), 32 * MINUTE);
+        assert_eq!(backoff.delay(1000), backoff.delay(MAX_EXPONENT));
+    }
+
+    #[test]
+    fn exponential_backoff_with_jitter() {
+        let backoff = Backoff::Exponential {
+            base: MINUTE,
+            jitter: true,
+        };
+        for _ in 0..100 {
+            let delay = backoff.delay(3);
+            assert!(delay >= 4 * MINUTE && delay <= 8 * MINUTE, "{delay:?}");
+        }
+    }
+
+    #[test]
+    fn default_policy_retries_forever() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.next_delay(1), Some(2 * MINUTE));
+        assert_eq!(
+            policy.next_delay(100),
+            Some(MINUTE * 2u32.pow(MAX_EXPONENT))
+        );
+    }
+
+    #[test]
+    fn limited_policy_gives_up() {
+        let policy = RetryPolicy {
+            max_retries: Some(2),
+            backoff: Backoff::Fixed(MINUTE),
+        };
+        assert_eq!(policy.next_delay(1), Some(MINUTE));
+        assert_eq!(policy.next_delay(2), Some(MINUTE));
+        assert_eq!(policy.next_delay(3), None);
+    }
+}
@@ -1,3 +1,4 @@
+use chrono::{DateTime, Utc};
 use diesel::connection::{AnsiTransactionManager, TransactionManager};
 use diesel::prelude::*;
 use diesel::r2d2;
@@ -7,23 +8,35 @@ use std::error::Error;
 use std::panic::{catch_unwind, AssertUnwindSafe, PanicInfo, UnwindSafe};
 use std::sync::mpsc::{sync_channel, SyncSender};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use threadpool::ThreadPool;
 
 use super::errors::*;
+use super::scheduler::Scheduler;
 use super::storage;
 use crate::background_jobs::{Environment, Job, PerformState};
 use crate::db::{DieselPool, DieselPooledConn};
+use crate::metrics::WorkerMetrics;
 use event::Event;
+use limits::JobTypePermits;
 
 mod event;
+mod limits;
+
+pub use limits::ConcurrencyLimits;
+
+/// How long a job may run before a warning is logged, unless configured otherwise.
+const DEFAULT_SLOW_JOB_THRESHOLD: Duration = Duration::from_secs(60);
 
 /// The core runner responsible for locking and running jobs
 pub struct Runner {
     connection_pool: DieselPool,
     thread_pool: ThreadPool,
+    job_type_permits: Arc<JobTypePermits>,
     environment: Arc<Option<Environment>>,
     job_start_timeout: Duration,
+    metrics: Arc<WorkerMetrics>,
+    slow_job_threshold: Duration,
 }
 
 impl Runner {
@@ -31,29 +44,43 @@ impl Runner {
         environment: Environment,
         url: String,
         job_start_timeout: u64,
+        slow_job_threshold: u64,
+        concurrency_limits: &ConcurrencyLimits,
     ) -> Self {
+        // Each running job needs a connection, and the runner itself needs a few more
+        let max_connections = concurrency_limits.max_jobs as u32 + 5;
         let connection_pool = r2d2::Pool::builder()
-            .max_size(10)
+            .max_size(max_connections)
             .min_idle(Some(0))
             .build_unchecked(ConnectionManager::new(url));
         Self {
             connection_pool: DieselPool::new_background_worker(connection_pool),
-            thread_pool: ThreadPool::new(5),
+            thread_pool: ThreadPool::new(concurrency_limits.max_jobs),
+            job_type_permits: Arc::new(JobTypePermits::new(concurrency_limits)),
             environment: Arc::new(Some(environment)),
             job_start_timeout: Duration::from_secs(job_start_timeout),
+            metrics: Arc::new(new_metrics()),
+            slow_job_threshold: Duration::from_secs(slow_job_threshold),
         }
     }
 
     #[cfg(test)]
-    fn internal_test_runner(environment: Option<Environment>, url: String) -> Self {
+    fn internal_test_runner(
+        environment: Option<Environment>,
+        url: String,
+        concurrency_limits: &ConcurrencyLimits,
+    ) -> Self {
         let connection_pool = r2d2::Pool::builder()
-            .max_size(4)
+            .max_size(concurrency_limits.max_jobs as u32 + 2)
             .build_unchecked(ConnectionManager::new(url));
         Self {
             connection_pool: DieselPool::new_background_worker(connection_pool),
-            thread_pool: ThreadPool::new(2),
+            thread_pool: ThreadPool::new(concurrency_limits.max_jobs),
+            job_type_permits: Arc::new(JobTypePermits::new(concurrency_limits)),
             environment: Arc::new(environment),
             job_start_timeout: Duration::from_secs(10),
+            metrics: Arc::new(new_metrics()),
+            slow_job_threshold: DEFAULT_SLOW_JOB_THRESHOLD,
         }
     }
 
@@ -61,8 +88,11 @@ impl Runner {
         Self {
             connection_pool,
             thread_pool: ThreadPool::new(1),
+            job_type_permits: Arc::new(JobTypePermits::new(&ConcurrencyLimits::default())),
             environment: Arc::new(Some(environment)),
             job_start_timeout: Duration::from_secs(5),
+            metrics: Arc::new(new_metrics()),
+            slow_job_threshold: DEFAULT_SLOW_JOB_THRESHOLD,
         }
     }
 
@@ -126,6 +156,9 @@ impl Runner {
 
         // The connection may not be `Send` so we need to clone the pool instead
         let pool = self.connection_pool.clone();
+        let job_type_permits = self.job_type_permits.clone();
+        let metrics = self.metrics.clone();
+        let slow_job_threshold = self.slow_job_threshold;
         self.thread_pool.execute(move || {
             let conn = &mut *match pool.get() {
                 Ok(conn) => conn,
@@ -137,7 +170,10 @@ impl Runner {
             };
 
             let job_run_result = conn.transaction::<_, diesel::result::Error, _>(|conn| {
-                let job = match storage::find_next_unlocked_job(conn).optional() {
+                let fetch_lock = job_type_permits.lock();
+                let saturated_job_types = job_type_permits.saturated_job_types();
+                let next_job = storage::find_next_unlocked_job(conn, &saturated_job_types);
+                let job = match next_job.optional() {
                     Ok(Some(j)) => {
                         let _ = sender.send(Event::Working);
                         j
@@ -151,13 +187,21 @@ impl Runner {
                         return Err(RollbackTransaction);
                     }
                 };
+                // Kept until the job is done, so that jobs of the same type beyond the limit
+                // stay in the queue until then
+                let _permit = job_type_permits.acquire(&job.job_type);
+                drop(fetch_lock);
                 let job_id = job.id;
+                let job_type = job.job_type.clone();
+                let retries = job.retries;
+                let retry_policy = Job::retry_policy(&job.job_type);
 
                 let initial_depth = get_transaction_depth(conn)?;
                 if initial_depth != 1 {
                     warn!("Initial transaction depth is not 1. This is very unexpected");
                 }
 
+                let start = Instant::now();
                 let result = conn
                     .transaction(|conn| {
                         let pool = pool.to_real_pool();
@@ -171,6 +215,26 @@ impl Runner {
                     })
                     // TODO: Replace with flatten() once that stabilizes
                     .and_then(std::convert::identity);
+                let duration = start.elapsed();
+
+                metrics
+                    .job_duration
+                    .with_label_values(&[&job_type])
+                    .observe(duration.as_secs_f64());
+                let counter = match result {
+                    Ok(_) => &metrics.jobs_succeeded_total,
+                    Err(_) => &metrics.jobs_failed_total,
+                };
+                counter.with_label_values(&[&job_type]).inc();
+
+                if duration > slow_job_threshold {
+                    warn!(
+                        job_id,
+                        job_type,
+                        ?duration,
+                        "Background job took a long time to run"
+                    );
+                }
 
                 // If the job panics it could leave the connection inside an inner transaction(s).
                 // Attempt to roll those back so we can mark the job as failed, but if the rollback
@@ -189,7 +253,7 @@ impl Runner {
                     Ok(_) => storage::delete_successful_job(conn, job_id)?,
                     Err(e) => {
                         eprintln!("Job {job_id} failed to run: {e}");
-                        storage::update_failed_job(conn, job_id);
+                        storage::update_failed_job(conn, job_id, retries, &retry_policy);
                     }
                 }
                 Ok(())
@@ -204,6 +268,21 @@ impl Runner {
         })
     }
 
+    /// Enqueues all jobs of the `scheduler` that are due at `now`.
+    pub fn enqueue_scheduled_jobs(
+        &self,
+        scheduler: &Scheduler,
+        now: DateTime<Utc>,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        scheduler.enqueue_due_jobs(&mut *self.connection()?, now)?;
+        Ok(())
+    }
+
+    /// Returns the metrics recorded by the jobs of this runner.
+    pub fn metrics(&self) -> &WorkerMetrics {
+        &self.metrics
+    }
+
     fn connection(&self) -> Result<DieselPooledConn<'_>, Box<dyn Error + Send + Sync>> {
         self.connection_pool.get().map_err(Into::into)
     }
@@ -239,6 +318,10 @@ impl Runner {
     }
 }
 
+fn new_metrics() -> WorkerMetrics {
+    WorkerMetrics::new().expect("could not initialize worker metrics")
+}
+
 fn get_transaction_depth(conn: &mut PgConnection) -> QueryResult<u32> {
     let transaction_manager = AnsiTransactionManager::transaction_manager_status_mut(conn);
     Ok(transaction_manager
@@ -272,7 +355,9 @@ mod tests {
 
     use super::*;
     use crate::schema::background_jobs::dsl::*;
+    use std::collections::HashMap;
     use std::panic::AssertUnwindSafe;
+    use std::sync::atomic::{AtomicUsize, Ordering};
     use std::sync::mpsc::{sync_channel, SyncSender};
     use std::sync::{Arc, Barrier, Mutex, MutexGuard};
 
@@ -387,6 +472,107 @@ mod tests {
         assert_eq!(1, tries);
     }
 
+    #[test]
+    fn job_metrics_are_recorded() {
+        let _guard = TestGuard::lock();
+        let runner = runner();
+
+        create_dummy_job(&runner);
+        runner.get_single_job(dummy_sender(), |_, _| Ok(()));
+        runner.wait_for_jobs().unwrap();
+
+        create_dummy_job(&runner);
+        runner.get_single_job(dummy_sender(), |_, _| Err("nope".into()));
+        runner.wait_for_jobs().unwrap();
+
+        let metrics = runner.metrics();
+        let duration = metrics.job_duration.with_label_values(&["Foo"]);
+        assert_eq!(duration.get_sample_count(), 2);
+        let succeeded = metrics.jobs_succeeded_total.with_label_values(&["Foo"]);
+        assert_eq!(succeeded.get(), 1);
+        let failed = metrics.jobs_failed_total.with_label_values(&["Foo"]);
+        assert_eq!(failed.get(), 1);
+    }
+
+    #[test]
+    fn jobs_are_not_retried_after_exhausting_their_retry_policy() {
+        let _guard = TestGuard::lock();
+        let runner = runner();
+        let job_id = diesel::insert_into(background_jobs)
+            .values((
+                job_type.eq("delete_version_from_storage"),
+                data.eq(serde_json::json!(null)),
+            ))
+            .returning(id)
+            .get_result::<i64>(&mut *runner.connection().unwrap())
+            .unwrap();
+
+        for _ in 0..10 {
+            // Pretend the backoff delay has already passed
+            diesel::sql_query(
+                "UPDATE background_jobs SET next_attempt_at = NOW() - INTERVAL '1 minute' \
+                 WHERE next_attempt_at IS NOT NULL",
+            )
+            .execute(&mut *runner.connection().unwrap())
+            .unwrap();
+
+            runner.get_single_job(dummy_sender(), |_, _| Err("nope".into()));
+            runner.wait_for_jobs().unwrap();
+        }
+
+        let (tries, next_attempt) = background_jobs
+            .find(job_id)
+            .select((retries, next_attempt_at))
+            .first::<(i32, Option<chrono::NaiveDateTime>)>(&mut *runner.connection().unwrap())
+            .unwrap();
+        assert_eq!(6, tries);
+        assert_eq!(None, next_attempt);
+    }
+
+    #[test]
+    fn jobs_beyond_the_job_type_limit_wait_in_the_queue() {
+        let _guard = TestGuard::lock();
+        let runner = runner_with_limits(&ConcurrencyLimits {
+            max_jobs: 4,
+            per_job_type: HashMap::from([("Foo".to_string(), 2)]),
+        });
+
+        for _ in 0..6 {
+            create_dummy_job(&runner);
+        }
+
+        let running = Arc::new(AtomicUsize::new(0));
+        let max_running = Arc::new(AtomicUsize::new(0));
+        let completed = Arc::new(AtomicUsize::new(0));
+
+        // Each round only runs the jobs that fit into the limit, the others are left for the
+        // next round
+        for _ in 0..6 {
+            for _ in 0..4 {
+                let running = running.clone();
+                let max_running = max_running.clone();
+                let completed = completed.clone();
+                runner.get_single_job(dummy_sender(), move |_, _| {
+                    let now_running = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_running.fetch_max(now_running, Ordering::SeqCst);
+                    std::thread::sleep(Duration::from_millis(50));
+                    running.fetch_sub(1, Ordering::SeqCst);
+                    completed.fetch_add(1, Ordering::SeqCst);
+                    Ok(())
+                });
+            }
+            runner.wait_for_jobs().unwrap();
+        }
+
+        assert_eq!(completed.load(Ordering::SeqCst), 6);
+        assert!(max_running.load(Ordering::SeqCst) <= 2);
+
+        let remaining_jobs = background_jobs
+            .count()
+            .get_result(&mut *runner.connection().unwrap());
+        assert_eq!(Ok(0), remaining_jobs);
+    }
+
     // Since these tests deal with behavior concerning multiple connections
     // running concurrently, they have to run outside of a transaction.
     // Therefore we can't run more than one at a time.
@@ -412,16 +598,23 @@ mod tests {
     }
 
     fn runner() -> Runner {
+        runner_with_limits(&ConcurrencyLimits {
+            max_jobs: 2,
+            per_job_type: HashMap::new(),
+        })
+    }
+
+    fn runner_with_limits(concurrency_limits: &ConcurrencyLimits) -> Runner {
         let database_url =
             dotenv::var("TEST_DATABASE_URL").expect("TEST_DATABASE_URL must be set to run tests");
 
-        super::Runner::internal_test_runner(None, database_url)
+        super::Runner::internal_test_runner(None, database_url, concurrency_limits)
     }
 
     fn create_dummy_job(runner: &Runner) -> storage::BackgroundJob {
         diesel::insert_into(background_jobs)
             .values((job_type.eq("Foo"), data.eq(serde_json::json!(null))))
-            .returning((id, job_type, data))
+            .returning((id, job_type, data, retries))
             .get_result(&mut *runner.connection().unwrap())
             .unwrap()
     }
@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// The number of jobs that run at the same time, unless configured otherwise.
+const DEFAULT_MAX_JOBS: usize = 5;
+
+/// Limits how many jobs the runner executes at the same time.
+///
+/// Jobs beyond the limits stay in the queue until a running job finishes, they are never
+/// dropped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConcurrencyLimits {
+    /// The maximum number of jobs that run at the same time, across all job types.
+    pub max_jobs: usize,
+    /// The maximum number of jobs of a single type that run at the same time. Job types that
+    /// aren't listed here are only limited by `max_jobs`.
+    pub per_job_type: HashMap<String, usize>,
+}
+
+impl Default for ConcurrencyLimits {
+    fn default() -> Self {
+        Self {
+            max_jobs: DEFAULT_MAX_JOBS,
+            per_job_type: HashMap::new(),
+        }
+    }
+}
+
+impl ConcurrencyLimits {
+    /// Reads the limits from the environment.
+    ///
+    /// - `BACKGROUND_JOB_CONCURRENCY`: The maximum number of jobs that run at the same time,
+    ///   defaults to 5.
+    /// - `BACKGROUND_JOB_CONCURRENCY_PER_TYPE`: A comma separated list of `job_type=limit`
+    ///   pairs, e.g. `render_and_upload_readme=2,delete_version_from_storage=1`.
+    pub fn from_environment() -> Self {
+        let max_jobs = dotenv::var("BACKGROUND_JOB_CONCURRENCY")
+            .map(|max_jobs| parse_limit(&max_jobs))
+            .unwrap_or(DEFAULT_MAX_JOBS);
+
+        let per_job_type = dotenv::var("BACKGROUND_JOB_CONCURRENCY_PER_TYPE")
+            .map(|limits| parse_per_job_type(&limits))
+            .unwrap_or_default();
+
+        Self {
+            max_jobs,
+            per_job_type,
+        }
+    }
+}
+
+fn parse_per_job_type(limits: &str) -> HashMap<String, usize> {
+    limits
+        .split(',')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (job_type, limit) = pair
+                .split_once('=')
+                .expect("Invalid value for `BACKGROUND_JOB_CONCURRENCY_PER_TYPE`");
+            (job_type.trim().to_string(), parse_limit(limit))
+        })
+        .collect()
+}
+
+/// A limit of 0 would keep the jobs in the queue forever, so it is rejected as well.
+fn parse_limit(limit: &str) -> usize {
+    match limit.trim().parse() {
+        Ok(limit) if limit > 0 => limit,
+        _ => panic!("Invalid background job concurrency limit: {limit:?}"),
+    }
+}
+
+/// Enforces the per job type limits of `ConcurrencyLimits` with one semaphore per job type.
+///
+/// All permits are acquired while holding the fetch lock, so that the job types that are
+/// excluded from the query can't run out of permits before the fetched job acquires one.
+pub(super) struct JobTypePermits {
+    fetch_lock: Mutex<()>,
+    semaphores: HashMap<String, Arc<Semaphore>>,
+}
+
+impl JobTypePermits {
+    pub(super) fn new(limits: &ConcurrencyLimits) -> Self {
+        let semaphores = limits
+            .per_job_type
+            .iter()
+            .map(|(job_type, limit)| (job_type.clone(), Arc::new(Semaphore::new(*limit))))
+            .collect();
+
+        Self {
+            fetch_lock: Mutex::new(()),
+            semaphores,
+        }
+    }
+
+    /// Has to be held from listing the saturated job types until the fetched job acquired its
+    /// permit.
+    pub(super) fn lock(&self) -> MutexGuard<'_, ()> {
+        // A panic while holding the lock can't leave the semaphores in an inconsistent state
+        self.fetch_lock
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Returns the job types that can't run right now, because too many jobs of these types are
+    /// running already.
+    pub(super) fn saturated_job_types(&self) -> Vec<&str> {
+        self.semaphores
+            .iter()
+            .filter(|(_, semaphore)| semaphore.available_permits() == 0)
+            .map(|(job_type, _)| job_type.as_str())
+            .collect()
+    }
+
+    /// Acquires a permit for a job of the given type, which has to be kept until the job is
+    /// done. Returns `None` for job types without a limit.
+    pub(super) fn acquire(&self, job_type: &str) -> Option<OwnedSemaphorePermit> {
+        let semaphore = self.semaphores.get(job_type)?;
+        let permit = semaphore
+            .clone()
+            .try_acquire_owned()
+            .expect("saturated job types are not fetched");
+        Some(permit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn per_job_type_limits_are_parsed() {
+        let limits = parse_per_job_type(" render_and_upload_readme=2, dump_db = 1 ,");
+        let expected = HashMap::from([
+            ("render_and_upload_readme".to_string(), 2),
+            ("dump_db".to_string(), 1),
+        ]);
+        assert_eq!(limits, expected);
+        assert_eq!(parse_per_job_type(""), HashMap::new());
+    }
+
+    #[test]
+    #[should_panic]
+    fn zero_limits_are_rejected() {
+        parse_per_job_type("dump_db=0");
+    }
+
+    #[test]
+    fn saturated_job_types_are_reported() {
+        let limits = ConcurrencyLimits {
+            max_jobs: 5,
+            per_job_type: HashMap::from([("dump_db".to_string(), 1)]),
+        };
+        let permits = JobTypePermits::new(&limits);
+        assert!(permits.saturated_job_types().is_empty());
+        assert!(permits.acquire("update_downloads").is_none());
+
+        let permit = permits.acquire("dump_db");
+        assert!(permit.is_some());
+        assert_eq!(permits.saturated_job_types(), vec!["dump_db"]);
+
+        drop(permit);
+        assert!(permits.saturated_job_types().is_empty());
+    }
+}
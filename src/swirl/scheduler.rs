@@ -0,0 +1,133 @@
+use chrono::{DateTime, Utc};
+use cron::Schedule;
+use diesel::prelude::*;
+use std::str::FromStr;
+
+use super::errors::EnqueueError;
+use crate::background_jobs::Job;
+
+/// A job that is enqueued on a recurring schedule.
+pub struct ScheduledJob {
+    /// A unique name, used to persist when the job was last enqueued.
+    name: &'static str,
+    schedule: Schedule,
+    job: fn() -> Job,
+}
+
+impl ScheduledJob {
+    /// Creates a new scheduled job.
+    ///
+    /// `schedule` is a cron expression including seconds (e.g. `0 0 3 * * *` for every day at
+    /// 03:00 UTC).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `schedule` is not a valid cron expression.
+    pub fn new(name: &'static str, schedule: &str, job: fn() -> Job) -> Self {
+        let schedule = Schedule::from_str(schedule)
+            .unwrap_or_else(|err| panic!("Invalid schedule for `{name}`: {err}"));
+
+        Self {
+            name,
+            schedule,
+            job,
+        }
+    }
+
+    /// Returns whether the schedule had a tick after `last_run` and no later than `now`.
+    fn is_due(&self, last_run: DateTime<Utc>, now: DateTime<Utc>) -> bool {
+        self.schedule
+            .after(&last_run)
+            .next()
+            .map_or(false, |next_run| next_run <= now)
+    }
+
+    fn enqueue_if_due(
+        &self,
+        conn: &mut PgConnection,
+        now: DateTime<Utc>,
+    ) -> Result<(), EnqueueError> {
+        use crate::schema::scheduled_jobs::dsl::*;
+
+        diesel::insert_into(scheduled_jobs)
+            .values((name.eq(self.name), last_run_at.eq(now.naive_utc())))
+            .on_conflict_do_nothing()
+            .execute(conn)?;
+
+        // Lock the row, so that multiple workers don't enqueue the same tick
+        let last_run = scheduled_jobs
+            .find(self.name)
+            .select(last_run_at)
+            .for_update()
+            .first(conn)?;
+        let last_run = DateTime::<Utc>::from_utc(last_run, Utc);
+
+        if self.is_due(last_run, now) {
+            info!(job = self.name, "Enqueueing scheduled job");
+            (self.job)().enqueue(conn)?;
+
+            diesel::update(scheduled_jobs.find(self.name))
+                .set(last_run_at.eq(now.naive_utc()))
+                .execute(conn)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Enqueues recurring jobs when they are due.
+///
+/// The time each job was last enqueued is persisted in the `scheduled_jobs` table, so restarting
+/// the background worker doesn't enqueue a job twice. Ticks that were missed while no worker was
+/// running result in the job being enqueued once as soon as the scheduler runs again.
+pub struct Scheduler {
+    jobs: Vec<ScheduledJob>,
+}
+
+impl Scheduler {
+    pub fn new(jobs: Vec<ScheduledJob>) -> Self {
+        Self { jobs }
+    }
+
+    /// Enqueues all jobs that had a scheduled tick between their last run and `now`.
+    ///
+    /// Jobs that the scheduler has never seen before are not enqueued immediately, but only
+    /// once their first tick after `now` has passed.
+    pub fn enqueue_due_jobs(
+        &self,
+        conn: &mut PgConnection,
+        now: DateTime<Utc>,
+    ) -> Result<(), EnqueueError> {
+        for scheduled_job in &self.jobs {
+            conn.transaction(|conn| scheduled_job.enqueue_if_due(conn, now))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn time(hour: u32, minute: u32, second: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2023, 3, 24, hour, minute, second)
+            .unwrap()
+    }
+
+    #[test]
+    fn hourly_job_is_due_on_the_hour() {
+        let job = ScheduledJob::new("test", "0 0 * * * *", || Job::UpdateDownloads);
+
+        assert!(!job.is_due(time(10, 30, 0), time(10, 59, 59)));
+        assert!(job.is_due(time(10, 30, 0), time(11, 0, 0)));
+        assert!(job.is_due(time(10, 30, 0), time(13, 15, 0)));
+        assert!(!job.is_due(time(11, 0, 0), time(11, 0, 30)));
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid schedule for `test`")]
+    fn invalid_schedule_panics() {
+        ScheduledJob::new("test", "every hour", || Job::UpdateDownloads);
+    }
+}
@@ -1,9 +1,11 @@
+use chrono::NaiveDateTime;
+use diesel::data_types::PgInterval;
 use diesel::dsl::now;
-use diesel::pg::Pg;
 use diesel::prelude::*;
-use diesel::sql_types::{Bool, Integer, Interval};
+use diesel::sql_types::Interval;
 use diesel::{delete, update};
 
+use super::retry::RetryPolicy;
 use crate::schema::{self, background_jobs};
 
 #[derive(Queryable, Identifiable, Debug, Clone)]
@@ -11,25 +13,24 @@ pub(super) struct BackgroundJob {
     pub(super) id: i64,
     pub(super) job_type: String,
     pub(super) data: serde_json::Value,
-}
-
-fn retriable() -> Box<dyn BoxableExpression<background_jobs::table, Pg, SqlType = Bool>> {
-    use diesel::dsl::*;
-    use schema::background_jobs::dsl::*;
-
-    sql_function!(fn power(x: Integer, y: Integer) -> Integer);
-
-    Box::new(last_retry.lt(now - 1.minute().into_sql::<Interval>() * power(2, retries)))
+    pub(super) retries: i32,
 }
 
 /// Finds the next job that is unlocked, and ready to be retried. If a row is
 /// found, it will be locked.
-pub(super) fn find_next_unlocked_job(conn: &mut PgConnection) -> QueryResult<BackgroundJob> {
+///
+/// Jobs of the `excluded_job_types` are skipped, e.g. because too many of them
+/// are running already.
+pub(super) fn find_next_unlocked_job(
+    conn: &mut PgConnection,
+    excluded_job_types: &[&str],
+) -> QueryResult<BackgroundJob> {
     use schema::background_jobs::dsl::*;
 
     background_jobs
-        .select((id, job_type, data))
-        .filter(retriable())
+        .select((id, job_type, data, retries))
+        .filter(next_attempt_at.le(now))
+        .filter(job_type.ne_all(excluded_job_types))
         .order(id)
         .for_update()
         .skip_locked()
@@ -54,14 +55,36 @@ pub(super) fn delete_successful_job(conn: &mut PgConnection, job_id: i64) -> Que
     Ok(())
 }
 
-/// Marks that we just tried and failed to run a job.
+/// Marks that we just tried and failed to run a job, and schedules its next
+/// attempt according to the `RetryPolicy` of the job.
 ///
 /// Ignores any database errors that may have occurred. If the DB has gone away,
 /// we assume that just trying again with a new connection will succeed.
-pub(super) fn update_failed_job(conn: &mut PgConnection, job_id: i64) {
+pub(super) fn update_failed_job(
+    conn: &mut PgConnection,
+    job_id: i64,
+    previous_retries: i32,
+    policy: &RetryPolicy,
+) {
     use schema::background_jobs::dsl::*;
 
-    let _ = update(background_jobs.find(job_id))
-        .set((retries.eq(retries + 1), last_retry.eq(now)))
-        .execute(conn);
+    let job = update(background_jobs.find(job_id));
+    let _ = match policy.next_delay((previous_retries + 1) as u32) {
+        Some(delay) => {
+            let delay = PgInterval::from_microseconds(delay.as_micros() as i64);
+            job.set((
+                retries.eq(retries + 1),
+                last_retry.eq(now),
+                next_attempt_at.eq((now + delay.into_sql::<Interval>()).nullable()),
+            ))
+            .execute(conn)
+        }
+        None => job
+            .set((
+                retries.eq(retries + 1),
+                last_retry.eq(now),
+                next_attempt_at.eq(None::<NaiveDateTime>),
+            ))
+            .execute(conn),
+    };
 }
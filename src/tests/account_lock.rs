@@ -1,15 +1,15 @@
-use crate::{util::RequestHelper, TestApp};
+use crate::util::{MockCookieUser, MockRequestExt, RequestHelper, Response};
+use crate::TestApp;
+use cargo_registry::schema::users;
 use chrono::{Duration, NaiveDateTime, Utc};
-use http::StatusCode;
+use diesel::prelude::*;
+use http::{Method, StatusCode};
 
 const URL: &str = "/api/v1/me";
 const LOCK_REASON: &str = "test lock reason";
 
 fn lock_account(app: &TestApp, user_id: i32, until: Option<NaiveDateTime>) {
     app.db(|conn| {
-        use cargo_registry::schema::users;
-        use diesel::prelude::*;
-
         diesel::update(users::table)
             .set((
                 users::account_lock_reason.eq(LOCK_REASON),
@@ -63,3 +63,291 @@ fn expired_account_lock() {
 
     user.get::<serde_json::Value>(URL).good();
 }
+
+#[test]
+fn bulk_lock() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let admin = app.db_new_user("admin");
+    admin.make_admin();
+    let other_admin = app.db_new_user("other-admin");
+    other_admin.make_admin();
+    let spammer = app.db_new_user("spammer");
+
+    let body = json!({
+        "logins": ["FOO", "spammer", "nobody", "other-admin", "admin"],
+        "reason": LOCK_REASON,
+        "until": null,
+        "permanent": true,
+    });
+    let response = lock_bulk(&admin, &body);
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.into_json(),
+        json!({
+            "results": [
+                { "login": "FOO", "result": "locked" },
+                { "login": "spammer", "result": "locked" },
+                { "login": "nobody", "result": "not_found" },
+                { "login": "other-admin", "result": "skipped_because_admin" },
+                { "login": "admin", "result": "skipped_because_admin" },
+            ]
+        })
+    );
+
+    let error_message = format!("This account is indefinitely locked. Reason: {LOCK_REASON}");
+    for locked in [&user, &spammer] {
+        let response = locked.get::<()>(URL);
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert_eq!(
+            response.into_json(),
+            json!({ "errors": [{ "detail": error_message }] })
+        );
+    }
+
+    admin.get::<serde_json::Value>(URL).good();
+    other_admin.get::<serde_json::Value>(URL).good();
+    anon.get::<()>(URL).assert_forbidden();
+}
+
+#[test]
+fn bulk_lock_with_expiry() {
+    let until = Utc::now().naive_utc() + Duration::days(1);
+
+    let (app, _anon, user) = TestApp::init().with_user();
+    let admin = app.db_new_user("admin");
+    admin.make_admin();
+
+    let body = json!({ "logins": ["foo"], "reason": LOCK_REASON, "until": until });
+    assert_eq!(lock_bulk(&admin, &body).status(), StatusCode::OK);
+
+    let until = until.format("%Y-%m-%d at %H:%M:%S UTC");
+    let error_message = format!("This account is locked until {until}. Reason: {LOCK_REASON}");
+    assert_eq!(
+        user.get::<()>(URL).into_json(),
+        json!({ "errors": [{ "detail": error_message }] })
+    );
+}
+
+#[test]
+fn bulk_lock_requires_an_admin_and_a_reason() {
+    let (app, _anon, user) = TestApp::init().with_user();
+    let admin = app.db_new_user("admin");
+    admin.make_admin();
+    let spammer = app.db_new_user("spammer");
+
+    let body = json!({ "logins": ["spammer"], "reason": LOCK_REASON });
+    assert_eq!(lock_bulk(&user, &body).status(), StatusCode::FORBIDDEN);
+
+    let body = json!({ "logins": ["spammer"], "reason": " " });
+    assert_eq!(lock_bulk(&admin, &body).status(), StatusCode::BAD_REQUEST);
+
+    spammer.get::<serde_json::Value>(URL).good();
+}
+
+#[test]
+fn lock_duration_is_limited() {
+    let now = Utc::now().naive_utc();
+    let error = "account locks can last at most 90 days, set `permanent` to lock the account for \
+                 longer or indefinitely";
+
+    let (app, _anon, user) = TestApp::init().with_user();
+    let admin = app.db_new_user("admin");
+    admin.make_admin();
+
+    let within_limit = now + Duration::days(89);
+    let over_limit = now + Duration::days(365 * 20);
+
+    let body = json!({ "logins": ["foo"], "reason": LOCK_REASON, "until": within_limit });
+    assert_eq!(lock_bulk(&admin, &body).status(), StatusCode::OK);
+
+    for until in [Some(over_limit), None] {
+        let body = json!({ "logins": ["foo"], "reason": LOCK_REASON, "until": until });
+        let response = lock_bulk(&admin, &body);
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(
+            response.into_json(),
+            json!({ "errors": [{ "detail": error }] })
+        );
+
+        let response = update_lock(&admin, "foo", &json!({ "until": until }));
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    // The rejected requests didn't change the lock
+    let within_limit = within_limit.format("%Y-%m-%d at %H:%M:%S UTC");
+    let error_message =
+        format!("This account is locked until {within_limit}. Reason: {LOCK_REASON}");
+    assert_eq!(
+        user.get::<()>(URL).into_json(),
+        json!({ "errors": [{ "detail": error_message }] })
+    );
+
+    let body = json!({ "until": over_limit, "permanent": true });
+    assert_eq!(update_lock(&admin, "foo", &body).status(), StatusCode::OK);
+
+    let body =
+        json!({ "logins": ["foo"], "reason": LOCK_REASON, "until": over_limit, "permanent": true });
+    assert_eq!(lock_bulk(&admin, &body).status(), StatusCode::OK);
+
+    let over_limit = over_limit.format("%Y-%m-%d at %H:%M:%S UTC");
+    let error_message = format!("This account is locked until {over_limit}. Reason: {LOCK_REASON}");
+    assert_eq!(
+        user.get::<()>(URL).into_json(),
+        json!({ "errors": [{ "detail": error_message }] })
+    );
+}
+
+fn lock_bulk(user: &MockCookieUser, body: &serde_json::Value) -> Response<()> {
+    let mut request = user.post_request("/api/v1/admin/users/lock_bulk");
+    request.with_body(body.to_string().as_bytes());
+    user.run(request)
+}
+
+#[test]
+fn expiring_locks() {
+    let now = Utc::now().naive_utc();
+
+    let (app, _anon, user) = TestApp::init().with_user();
+    let admin = app.db_new_user("admin");
+    admin.make_admin();
+
+    let soon = app.db_new_user("soon");
+    let later = app.db_new_user("later");
+    let next_week = app.db_new_user("next-week");
+    let expired = app.db_new_user("expired");
+    let indefinitely = app.db_new_user("indefinitely");
+    lock_account(&app, later.as_model().id, Some(now + Duration::hours(20)));
+    lock_account(&app, soon.as_model().id, Some(now + Duration::hours(2)));
+    lock_account(&app, next_week.as_model().id, Some(now + Duration::days(7)));
+    lock_account(&app, expired.as_model().id, Some(now - Duration::hours(1)));
+    lock_account(&app, indefinitely.as_model().id, None);
+
+    let logins = |query: &str| {
+        let url = format!("/api/v1/admin/locks/expiring{query}");
+        let json: serde_json::Value = admin.get(&url).good();
+        json["users"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|user| user["login"].as_str().unwrap().to_string())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(logins(""), ["soon", "later"]);
+    assert_eq!(logins("?within_hours=1"), Vec::<String>::new());
+    assert_eq!(logins("?within_hours=3"), ["soon"]);
+    assert_eq!(logins("?within_hours=200"), ["soon", "later", "next-week"]);
+
+    let json: serde_json::Value = admin.get("/api/v1/admin/locks/expiring").good();
+    assert_eq!(json["users"][0]["lock_reason"], LOCK_REASON);
+    assert!(json["users"][0]["lock_until"].is_string());
+
+    for query in ["?within_hours=0", "?within_hours=-1", "?within_hours=soon"] {
+        let url = format!("/api/v1/admin/locks/expiring{query}");
+        assert_eq!(admin.get::<()>(&url).status(), StatusCode::BAD_REQUEST);
+    }
+
+    let response = user.get::<()>("/api/v1/admin/locks/expiring");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[test]
+fn extend_lock() {
+    let now = Utc::now().naive_utc();
+    let until = now + Duration::days(7);
+
+    let (app, _anon, user) = TestApp::init().with_user();
+    let admin = app.db_new_user("admin");
+    admin.make_admin();
+    lock_account(&app, user.as_model().id, Some(now + Duration::hours(1)));
+
+    let response = update_lock(&admin, "foo", &json!({ "until": until }));
+    assert_eq!(response.status(), StatusCode::OK);
+    let json = response.into_json();
+    assert_eq!(json["user"]["login"], "foo");
+    assert_eq!(json["user"]["lock_reason"], LOCK_REASON);
+
+    let until = until.format("%Y-%m-%d at %H:%M:%S UTC");
+    let error_message = format!("This account is locked until {until}. Reason: {LOCK_REASON}");
+    assert_eq!(
+        user.get::<()>(URL).into_json(),
+        json!({ "errors": [{ "detail": error_message }] })
+    );
+
+    // Without an end date, the account is locked indefinitely
+    let response = update_lock(&admin, "foo", &json!({ "until": null, "permanent": true }));
+    assert_eq!(response.status(), StatusCode::OK);
+    let error_message = format!("This account is indefinitely locked. Reason: {LOCK_REASON}");
+    assert_eq!(
+        user.get::<()>(URL).into_json(),
+        json!({ "errors": [{ "detail": error_message }] })
+    );
+}
+
+#[test]
+fn append_to_lock_reason() {
+    let (app, _anon, user) = TestApp::init().with_user();
+    let admin = app.db_new_user("admin");
+    admin.make_admin();
+    lock_account(&app, user.as_model().id, None);
+
+    let body = json!({ "until": null, "append_reason": "spam continued", "permanent": true });
+    let response = update_lock(&admin, "FOO", &body);
+    assert_eq!(response.status(), StatusCode::OK);
+    let reason = format!("{LOCK_REASON}; spam continued");
+    assert_eq!(response.into_json()["user"]["lock_reason"], reason);
+
+    let error_message = format!("This account is indefinitely locked. Reason: {reason}");
+    assert_eq!(
+        user.get::<()>(URL).into_json(),
+        json!({ "errors": [{ "detail": error_message }] })
+    );
+}
+
+#[test]
+fn update_lock_without_until_keeps_the_end_date() {
+    let until = Utc::now().naive_utc() + Duration::days(7);
+
+    let (app, _anon, user) = TestApp::init().with_user();
+    let admin = app.db_new_user("admin");
+    admin.make_admin();
+    lock_account(&app, user.as_model().id, Some(until));
+
+    let response = update_lock(&admin, "foo", &json!({ "append_reason": "spam continued" }));
+    assert_eq!(response.status(), StatusCode::OK);
+    let reason = format!("{LOCK_REASON}; spam continued");
+    assert_eq!(response.into_json()["user"]["lock_reason"], reason);
+
+    let until = until.format("%Y-%m-%d at %H:%M:%S UTC");
+    let error_message = format!("This account is locked until {until}. Reason: {reason}");
+    assert_eq!(
+        user.get::<()>(URL).into_json(),
+        json!({ "errors": [{ "detail": error_message }] })
+    );
+}
+
+#[test]
+fn update_lock_requires_an_admin_and_a_locked_account() {
+    let (app, _anon, user) = TestApp::init().with_user();
+    let admin = app.db_new_user("admin");
+    admin.make_admin();
+    let spammer = app.db_new_user("spammer");
+    lock_account(&app, spammer.as_model().id, None);
+
+    let body = json!({ "until": null, "append_reason": "more", "permanent": true });
+    let response = update_lock(&user, "spammer", &body);
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = update_lock(&admin, "foo", &body);
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    user.get::<serde_json::Value>(URL).good();
+
+    update_lock(&admin, "nobody", &body).assert_not_found();
+}
+
+fn update_lock(user: &MockCookieUser, login: &str, body: &serde_json::Value) -> Response<()> {
+    let url = format!("/api/v1/users/{login}/lock");
+    let mut request = user.request_builder(Method::PATCH, &url);
+    request.with_body(body.to_string().as_bytes());
+    user.run(request)
+}
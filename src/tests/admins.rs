@@ -0,0 +1,77 @@
+use crate::util::{MockCookieUser, RequestHelper, Response, TestApp};
+use cargo_registry::schema::users;
+use diesel::prelude::*;
+use http::{Method, StatusCode};
+
+#[test]
+fn admins_can_add_and_remove_admins() {
+    let (app, _, user) = TestApp::init().with_user();
+    let admin = app.db_new_user("admin");
+    admin.make_admin();
+
+    let response = update_admin(&admin, Method::POST, "FOO");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.into_json(),
+        json!({ "login": "foo", "is_admin": true })
+    );
+    assert!(is_admin(&app, &user));
+
+    // The new admin can use admin endpoints, including this one
+    let response = update_admin(&user, Method::DELETE, "admin");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.into_json(),
+        json!({ "login": "admin", "is_admin": false })
+    );
+    assert!(!is_admin(&app, &admin));
+
+    let response = update_admin(&admin, Method::POST, "admin");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert!(!is_admin(&app, &admin));
+
+    update_admin(&user, Method::POST, "nobody").assert_not_found();
+}
+
+#[test]
+fn last_admin_cannot_be_removed() {
+    let (app, _, user) = TestApp::init().with_user();
+    user.make_admin();
+    let other_user = app.db_new_user("other_user");
+
+    let response = update_admin(&user, Method::DELETE, "foo");
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "the last admin can't be removed" }] })
+    );
+    assert!(is_admin(&app, &user));
+
+    // Removing users that aren't admins doesn't change anything
+    let response = update_admin(&user, Method::DELETE, "other_user");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(is_admin(&app, &user));
+
+    // Once there is another admin, admins can remove themselves
+    let response = update_admin(&user, Method::POST, "other_user");
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = update_admin(&user, Method::DELETE, "foo");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(!is_admin(&app, &user));
+    assert!(is_admin(&app, &other_user));
+}
+
+fn is_admin(app: &TestApp, user: &MockCookieUser) -> bool {
+    app.db(|conn| {
+        users::table
+            .find(user.as_model().id)
+            .select(users::is_admin)
+            .first(conn)
+            .unwrap()
+    })
+}
+
+fn update_admin(user: &MockCookieUser, method: Method, login: &str) -> Response<()> {
+    let url = format!("/api/v1/admin/admins/{login}");
+    user.run(user.request_builder(method, &url))
+}
@@ -26,6 +26,7 @@ use std::{
 use diesel::prelude::*;
 
 mod account_lock;
+mod admins;
 mod authentication;
 mod blocked_routes;
 mod builders;
@@ -47,8 +48,10 @@ mod team;
 mod token;
 mod unhealthy_database;
 mod user;
+mod user_merge;
 mod util;
 mod version;
+mod worker;
 
 #[derive(Deserialize)]
 pub struct CrateList {
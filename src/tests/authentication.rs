@@ -1,3 +1,4 @@
+use crate::builders::CrateBuilder;
 use crate::util::{MockRequestExt, RequestHelper, Response};
 use crate::TestApp;
 
@@ -43,3 +44,60 @@ fn cookie_auth_cannot_find_user() {
     let error = anon.run::<()>(request);
     assert_eq!(error.status(), StatusCode::INTERNAL_SERVER_ERROR);
 }
+
+static WEBSITE_ONLY: &str = "this action can only be performed on the crates.io website";
+static TOKEN_ONLY: &str = "this action can only be performed with an API token";
+
+#[test]
+fn only_cookie_endpoints_reject_tokens() {
+    let (_, _, user, token) = TestApp::init().with_token();
+
+    let response = user.get::<()>(URL);
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = token.get::<()>(URL);
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": WEBSITE_ONLY }] })
+    );
+}
+
+#[test]
+fn only_token_endpoints_reject_cookies() {
+    let (_, anon, user, token) = TestApp::init().with_token();
+
+    let response = user.delete::<()>("/api/v1/tokens/current");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": TOKEN_ONLY }] })
+    );
+
+    let response = anon.delete::<()>("/api/v1/tokens/current");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(response.into_json().to_string().as_bytes(), MUST_LOGIN);
+
+    let response = token.delete::<()>("/api/v1/tokens/current");
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+}
+
+#[test]
+fn cookie_or_token_endpoints_accept_both() {
+    let (app, anon, user, token) = TestApp::init().with_token();
+
+    app.db(|conn| {
+        CrateBuilder::new("foo_auth", user.as_model().id).expect_build(conn);
+    });
+
+    let url = "/api/v1/crates/foo_auth/follow";
+    let response = user.put::<()>(url, b"");
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = token.delete::<()>(url);
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = anon.put::<()>(url, b"");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(response.into_json().to_string().as_bytes(), MUST_LOGIN);
+}
@@ -1,5 +1,5 @@
 use cargo_registry::{
-    models::{Category, Crate, Keyword, NewCrate},
+    models::{category::MAX_CATEGORIES, Category, Crate, Keyword, NewCrate},
     schema::{crates, version_downloads},
     util::errors::AppResult,
 };
@@ -152,7 +152,7 @@ impl<'a> CrateBuilder<'a> {
         }
 
         if !self.categories.is_empty() {
-            Category::update_crate(connection, &krate, &self.categories)?;
+            Category::update_crate(connection, &krate, &self.categories, MAX_CATEGORIES)?;
         }
 
         if !self.keywords.is_empty() {
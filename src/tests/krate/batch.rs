@@ -0,0 +1,96 @@
+use crate::builders::{CrateBuilder, VersionBuilder};
+use crate::util::{MockAnonymousUser, MockRequestExt, RequestHelper, Response, TestApp};
+use cargo_registry::models::Crate;
+use cargo_registry::schema::crates;
+use diesel::prelude::*;
+use http::StatusCode;
+use serde_json::Value;
+
+#[test]
+fn batch_returns_existing_crates_and_lists_missing_ones() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let other_user = app.db_new_user("other_user");
+
+    app.db(|conn| {
+        CrateBuilder::new("foo_batch", user.as_model().id)
+            .version(VersionBuilder::new("1.0.0"))
+            .version(VersionBuilder::new("1.1.0"))
+            .recent_downloads(10)
+            .expect_build(conn);
+        CrateBuilder::new("bar-batch", other_user.as_model().id)
+            .version(VersionBuilder::new("0.1.0"))
+            .expect_build(conn);
+        CrateBuilder::new("foo_deleted", user.as_model().id).expect_build(conn);
+        diesel::update(crates::table.filter(Crate::with_name("foo_deleted")))
+            .set(crates::deleted_at.eq(diesel::dsl::now.nullable()))
+            .execute(conn)
+            .unwrap();
+    });
+
+    let names = [
+        "foo_batch",
+        "bar_batch",
+        "missing",
+        "foo_deleted",
+        "foo_batch",
+    ];
+    let response = batch(&anon, &json!({ "names": names }));
+    assert_eq!(response.status(), StatusCode::OK);
+    let json = response.into_json();
+
+    let crates = json["crates"].as_object().unwrap();
+    assert_eq!(crates.len(), 2);
+
+    let foo_batch = &crates["foo_batch"];
+    assert_eq!(foo_batch["crate"]["name"], "foo_batch");
+    assert_eq!(foo_batch["crate"]["max_version"], "1.1.0");
+    assert_eq!(foo_batch["crate"]["recent_downloads"], 10);
+    let owners = foo_batch["owners"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|owner| owner["login"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(owners, ["foo"]);
+
+    // Names are matched like in the other endpoints, but the requested names are used as keys
+    let bar_batch = &crates["bar_batch"];
+    assert_eq!(bar_batch["crate"]["name"], "bar-batch");
+    assert_eq!(bar_batch["owners"][0]["login"], "other_user");
+
+    assert_eq!(json["missing"], json!(["foo_deleted", "missing"]));
+}
+
+#[test]
+fn batch_size_is_limited() {
+    let (_, anon) = TestApp::init()
+        .with_config(|config| config.max_batch_crates = 2)
+        .empty();
+
+    let response = batch(&anon, &json!({ "names": ["a", "b", "a"] }));
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.into_json()["missing"], json!(["a", "b"]));
+
+    let response = batch(&anon, &json!({ "names": ["a", "b", "c"] }));
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "at most 2 crates can be requested at once" }] })
+    );
+}
+
+#[test]
+fn crate_named_batch_can_be_shown() {
+    let (app, anon, user) = TestApp::init().with_user();
+    app.db(|conn| CrateBuilder::new("batch", user.as_model().id).expect_build(conn));
+
+    let response = anon.get::<Value>("/api/v1/crates/batch");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.into_json()["crate"]["name"], "batch");
+}
+
+fn batch(anon: &MockAnonymousUser, body: &Value) -> Response<()> {
+    let mut request = anon.post_request("/api/private/crates/batch");
+    request.with_body(body.to_string().as_bytes());
+    anon.run(request)
+}
@@ -0,0 +1,1159 @@
+use crate::builders::{CrateBuilder, PublishBuilder, VersionBuilder};
+use crate::new_category;
+use crate::util::{
+    encode_session_header_at, MockCookieUser, MockRequestExt, RequestHelper, Response, TestApp,
+    TestDatabase,
+};
+use cargo_registry::models::{Crate, NewCrateWebhook, WebhookEvent};
+use cargo_registry::schema::{crate_deletions, crate_webhooks, crates, versions};
+use cargo_registry::worker;
+use chrono::{Duration, NaiveDateTime, SubsecRound, Utc};
+use diesel::connection::SimpleConnection;
+use diesel::prelude::*;
+use http::{header, Method, StatusCode};
+use serde_json::Value;
+use tower_service::Service;
+
+#[test]
+fn deleted_crate_can_be_restored() {
+    let (app, anon, user, token) = TestApp::full().with_token();
+    let admin = app.db_new_user("admin");
+    admin.make_admin();
+
+    let crate_to_publish = PublishBuilder::new("foo_restored").version("1.0.0");
+    token.publish_crate(crate_to_publish).good();
+    user.put::<Value>("/api/v1/crates/foo_restored/follow", b"")
+        .good();
+
+    // Crates can only be deleted from the website
+    let response = token.delete::<()>("/api/v1/crates/foo_restored");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = user.delete_crate("foo_restored");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.into_json(), json!({ "ok": true }));
+    app.run_pending_background_jobs();
+
+    let response = anon.get::<()>("/api/v1/crates/foo_restored");
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(anon.search("q=foo_restored").crates.len(), 0);
+
+    // The crate stays in the git index until it is removed permanently
+    assert_eq!(app.crates_from_index_head("foo_restored").len(), 1);
+
+    // Neither the crate nor its versions are listed anywhere else
+    let me = user.get::<Value>("/api/v1/me").good();
+    assert_eq!(me["owned_crates"], json!([]));
+    let updates = user.get::<Value>("/api/v1/me/updates").good();
+    assert_eq!(updates["versions"], json!([]));
+    let version_id: i32 = app.db(|conn| versions::table.select(versions::id).first(conn).unwrap());
+    let versions = anon
+        .get_with_query::<Value>("/api/v1/versions", &format!("ids[]={version_id}"))
+        .good();
+    assert_eq!(versions["versions"], json!([]));
+    let response = anon.get::<()>(&format!("/api/v1/versions/{version_id}"));
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    // Only admins can restore crates
+    let response = user.put::<()>("/api/v1/admin/crates/foo_restored/restore", b"");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    // Everyone who was told about the deletion is told about the restoration as well
+    app.db(|conn| {
+        new_category("Category 1", "cat1", "Category 1 crates")
+            .create_or_update(conn)
+            .unwrap();
+        let crate_id: i32 = crates::table
+            .filter(crates::name.eq("foo_restored"))
+            .select(crates::id)
+            .first(conn)
+            .unwrap();
+        diesel::insert_into(crate_webhooks::table)
+            .values(NewCrateWebhook {
+                crate_id,
+                url: "http://93.184.216.34/crates-io",
+                secret: "secret",
+                events: WebhookEvent::mask(&[WebhookEvent::Restored]),
+            })
+            .execute(conn)
+            .unwrap();
+        diesel::sql_query(
+            "INSERT INTO crates_categories (crate_id, category_id) \
+             SELECT $1, id FROM categories WHERE slug = 'cat1'",
+        )
+        .bind::<diesel::sql_types::Integer, _>(crate_id)
+        .execute(conn)
+        .unwrap();
+    });
+
+    let response = admin.put::<()>("/api/v1/admin/crates/foo_restored/restore", b"");
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // The feeds and webhooks themselves are covered by their own tests
+    let feeds = remove_pending_jobs(&app, "sync_crates_feeds");
+    assert_eq!(feeds.len(), 1);
+    let category_feeds = remove_pending_jobs(&app, "sync_category_feed");
+    assert_eq!(category_feeds, vec![json!({ "slug": "cat1" })]);
+    let webhooks = remove_pending_jobs(&app, "dispatch_crate_webhook");
+    assert_eq!(webhooks.len(), 1);
+    assert_eq!(webhooks[0]["payload"]["event"], "restored");
+    app.run_pending_background_jobs();
+
+    let response = anon.get::<()>("/api/v1/crates/foo_restored");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(anon.search("q=foo_restored").crates.len(), 1);
+
+    // Crates that aren't deleted can't be restored
+    let response = admin.put::<()>("/api/v1/admin/crates/foo_restored/restore", b"");
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn deleted_crate_is_removed_after_grace_period() {
+    let (app, anon, user, token) = TestApp::full().with_token();
+    let admin = app.db_new_user("admin");
+    admin.make_admin();
+
+    let crate_to_publish = PublishBuilder::new("foo_purged").version("1.0.0");
+    token.publish_crate(crate_to_publish).good();
+
+    let response = user.delete_crate("foo_purged");
+    assert_eq!(response.status(), StatusCode::OK);
+    app.run_pending_background_jobs();
+
+    // Crates within their grace period are kept
+    enqueue_purge(&app);
+    app.run_pending_background_jobs();
+    assert_eq!(count_crates(&app, "foo_purged"), 1);
+
+    // The name of the crate can't be used while it can still be restored
+    let crate_to_publish = PublishBuilder::new("foo_purged").version("1.1.0");
+    let response = token.publish_crate(crate_to_publish);
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.into_json()["errors"][0]["detail"],
+        "crate `foo_purged` was deleted and can't be published again until it is removed \
+         permanently"
+    );
+
+    app.db(|conn| {
+        diesel::update(crates::table.filter(crates::name.eq("foo_purged")))
+            .set(crates::deleted_at.eq((Utc::now() - Duration::hours(25)).naive_utc()))
+            .execute(conn)
+            .unwrap();
+    });
+    enqueue_purge(&app);
+    app.run_pending_background_jobs();
+
+    assert_eq!(count_crates(&app, "foo_purged"), 0);
+    assert!(app
+        .upstream_index()
+        .crates_from_index_head("foo_purged")
+        .is_err());
+
+    let response = anon.get::<()>("/api/v1/crates/foo_purged");
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let response = admin.put::<()>("/api/v1/admin/crates/foo_purged/restore", b"");
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn category_feeds_are_synced_on_deletion_and_purge() {
+    let (app, _, user) = TestApp::full().with_user();
+
+    app.db(|conn| {
+        new_category("Category 1", "cat1", "Category 1 crates")
+            .create_or_update(conn)
+            .unwrap();
+        new_category("Category 1::Sub 1", "cat1::sub1", "Sub 1 crates")
+            .create_or_update(conn)
+            .unwrap();
+        CrateBuilder::new("foo_categorized", user.as_model().id)
+            .version("1.0.0")
+            .category("cat1::sub1")
+            .expect_build(conn);
+    });
+
+    let response = user.delete_crate("foo_categorized");
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // The feeds of the parent categories list the crate as well
+    let category_feeds = remove_pending_jobs(&app, "sync_category_feed");
+    let expected = vec![json!({ "slug": "cat1" }), json!({ "slug": "cat1::sub1" })];
+    assert_eq!(category_feeds, expected);
+    remove_pending_jobs(&app, "update_crate_index");
+
+    app.db(|conn| {
+        diesel::update(crates::table.filter(crates::name.eq("foo_categorized")))
+            .set(crates::deleted_at.eq((Utc::now() - Duration::hours(25)).naive_utc()))
+            .execute(conn)
+            .unwrap();
+    });
+    // The feeds are regenerated without the crate, see the recording of this test
+    enqueue_purge(&app);
+    app.run_pending_background_jobs();
+    assert_eq!(count_crates(&app, "foo_categorized"), 0);
+}
+
+#[test]
+fn index_is_consistent_when_purge_jobs_run_out_of_order() {
+    let (app, _, user, token) = TestApp::full().with_token();
+
+    let crate_to_publish = PublishBuilder::new("foo_unordered").version("1.0.0");
+    token.publish_crate(crate_to_publish).good();
+
+    let response = user.delete_crate("foo_unordered");
+    assert_eq!(response.status(), StatusCode::OK);
+    app.run_pending_background_jobs();
+
+    // Like `purge_deleted_crates`, but with a sync of the HTTP-based index that runs before the
+    // crate is removed from storage and from the git index
+    app.db(|conn| {
+        diesel::delete(crates::table.filter(crates::name.eq("foo_unordered")))
+            .execute(conn)
+            .unwrap();
+
+        worker::update_crate_index("foo_unordered".into())
+            .enqueue(conn)
+            .unwrap();
+        worker::delete_version_from_storage("foo_unordered".into(), "1.0.0".into())
+            .enqueue(conn)
+            .unwrap();
+        worker::remove_crate_from_index("foo_unordered".into())
+            .enqueue(conn)
+            .unwrap();
+    });
+
+    // The HTTP recording asserts that the index file is removed by the first sync instead of
+    // being uploaded again
+    app.run_pending_background_jobs();
+
+    assert!(app
+        .upstream_index()
+        .crates_from_index_head("foo_unordered")
+        .is_err());
+}
+
+#[test]
+fn storage_manifest_lists_the_files_removed_after_deletion() {
+    let (app, anon, user, token) = TestApp::full().with_token();
+    let admin = app.db_new_user("admin");
+    admin.make_admin();
+
+    for version in ["1.0.0", "1.1.0"] {
+        let crate_to_publish = PublishBuilder::new("foo_manifest")
+            .version(version)
+            .readme("hello world");
+        token.publish_crate(crate_to_publish).good();
+    }
+    app.run_pending_background_jobs();
+
+    let response = user.get::<()>("/api/v1/crates/foo_manifest/storage_manifest");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let expected = json!({
+        "files": [
+            { "bucket": "default", "path": "crates/foo_manifest/foo_manifest-1.0.0.crate" },
+            { "bucket": "default", "path": "readmes/foo_manifest/foo_manifest-1.0.0.html" },
+            { "bucket": "default", "path": "crates/foo_manifest/foo_manifest-1.1.0.crate" },
+            { "bucket": "default", "path": "readmes/foo_manifest/foo_manifest-1.1.0.html" },
+            { "bucket": "index", "path": "fo/o_/foo_manifest" },
+        ]
+    });
+    let response = admin.get::<()>("/api/v1/crates/Foo-Manifest/storage_manifest");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.into_json(), expected);
+
+    // The paths are the same ones that the files are downloaded from
+    anon.get::<()>("/api/v1/crates/foo_manifest/1.0.0/download")
+        .assert_redirect_ends_with("/crates/foo_manifest/foo_manifest-1.0.0.crate");
+
+    // Deleted crates are listed until they are removed permanently, which also removes the
+    // listed files from storage
+    let response = user.delete_crate("foo_manifest");
+    assert_eq!(response.status(), StatusCode::OK);
+    app.run_pending_background_jobs();
+
+    let response = admin.get::<()>("/api/v1/crates/foo_manifest/storage_manifest");
+    assert_eq!(response.into_json(), expected);
+
+    app.db(|conn| {
+        diesel::update(crates::table.filter(crates::name.eq("foo_manifest")))
+            .set(crates::deleted_at.eq((Utc::now() - Duration::hours(25)).naive_utc()))
+            .execute(conn)
+            .unwrap();
+    });
+    enqueue_purge(&app);
+    app.run_pending_background_jobs();
+
+    let response = admin.get::<()>("/api/v1/crates/foo_manifest/storage_manifest");
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn only_owners_can_delete_crates() {
+    let (app, anon, _, token) = TestApp::full().with_token();
+    let other_user = app.db_new_user("other_user");
+
+    let crate_to_publish = PublishBuilder::new("foo_not_deleted").version("1.0.0");
+    token.publish_crate(crate_to_publish).good();
+
+    let response = other_user.delete_crate("foo_not_deleted");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "only owners have permission to delete crates" }] })
+    );
+
+    let response = anon.get::<()>("/api/v1/crates/foo_not_deleted");
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[test]
+fn team_members_can_delete_new_crates() {
+    let (app, anon) = TestApp::init().empty();
+    create_team_owned_crate(&app, "foo_team_new");
+
+    let team_member = app.db_new_user("user-one-team");
+    let response = team_member.delete_crate("foo_team_new");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.into_json(), json!({ "ok": true }));
+    // This test has no index to update
+    remove_pending_jobs(&app, "update_crate_index");
+
+    let response = anon.get::<()>("/api/v1/crates/foo_team_new");
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn team_members_cannot_delete_old_crates() {
+    let (app, anon) = TestApp::init().empty();
+    create_team_owned_crate(&app, "foo_team_old");
+
+    app.db(|conn| {
+        let created_at = (Utc::now() - Duration::days(2)).naive_utc();
+        diesel::update(crates::table.filter(crates::name.eq("foo_team_old")))
+            .set(crates::created_at.eq(created_at))
+            .execute(conn)
+            .unwrap();
+    });
+
+    let team_member = app.db_new_user("user-one-team");
+    let response = team_member.delete_crate("foo_team_old");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "team members can only delete crates within 24 hours of their creation, ask a user owner to delete this crate" }] })
+    );
+
+    let response = anon.get::<()>("/api/v1/crates/foo_team_old");
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[test]
+fn team_deletion_window_is_separate_from_the_grace_period() {
+    let (app, anon) = TestApp::init()
+        .with_config(|config| {
+            config.crate_deletion_team_window = std::time::Duration::from_secs(60 * 60)
+        })
+        .empty();
+    create_team_owned_crate(&app, "foo_team_window");
+
+    app.db(|conn| {
+        let created_at = (Utc::now() - Duration::hours(2)).naive_utc();
+        diesel::update(crates::table.filter(crates::name.eq("foo_team_window")))
+            .set(crates::created_at.eq(created_at))
+            .execute(conn)
+            .unwrap();
+    });
+
+    let team_member = app.db_new_user("user-one-team");
+    let response = team_member.delete_crate("foo_team_window");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "team members can only delete crates within 1 hours of their creation, ask a user owner to delete this crate" }] })
+    );
+
+    let response = anon.get::<()>("/api/v1/crates/foo_team_window");
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[test]
+fn team_members_can_delete_old_unused_crates_if_enabled() {
+    let (app, anon) = TestApp::init()
+        .with_config(|config| config.crate_deletion_allow_unused = true)
+        .empty();
+    create_team_owned_crate(&app, "foo_team_unused");
+    make_old(&app, "foo_team_unused");
+
+    let team_member = app.db_new_user("user-one-team");
+    let response = team_member.delete_crate("foo_team_unused");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.into_json(), json!({ "ok": true }));
+    // This test has no index to update
+    remove_pending_jobs(&app, "update_crate_index");
+
+    let response = anon.get::<()>("/api/v1/crates/foo_team_unused");
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn team_members_cannot_delete_old_downloaded_crates_if_enabled() {
+    let (app, anon) = TestApp::init()
+        .with_config(|config| config.crate_deletion_allow_unused = true)
+        .empty();
+    create_team_owned_crate(&app, "foo_team_used");
+    make_old(&app, "foo_team_used");
+
+    app.db(|conn| {
+        diesel::update(crates::table.filter(crates::name.eq("foo_team_used")))
+            .set(crates::downloads.eq(1))
+            .execute(conn)
+            .unwrap();
+    });
+
+    let team_member = app.db_new_user("user-one-team");
+    let response = team_member.delete_crate("foo_team_used");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "team members can only delete crates within 24 hours of their creation, or crates that were never downloaded and have no reverse dependencies, ask a user owner to delete this crate" }] })
+    );
+
+    let response = anon.get::<()>("/api/v1/crates/foo_team_used");
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[test]
+fn team_members_cannot_delete_old_crates_with_reverse_dependencies_if_enabled() {
+    let (app, anon) = TestApp::init()
+        .with_config(|config| config.crate_deletion_allow_unused = true)
+        .empty();
+    create_team_owned_crate(&app, "foo_team_dependency");
+    make_old(&app, "foo_team_dependency");
+
+    let other_user = app.db_new_user("other_user");
+    app.db(|conn| {
+        let krate: Crate = Crate::by_name("foo_team_dependency").first(conn).unwrap();
+        CrateBuilder::new("foo_dependent", other_user.as_model().id)
+            .version(VersionBuilder::new("1.0.0").dependency(&krate, None))
+            .expect_build(conn);
+    });
+
+    let team_member = app.db_new_user("user-one-team");
+    let response = team_member.delete_crate("foo_team_dependency");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "team members can only delete crates within 24 hours of their creation, or crates that were never downloaded and have no reverse dependencies, ask a user owner to delete this crate" }] })
+    );
+
+    let response = anon.get::<()>("/api/v1/crates/foo_team_dependency");
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[test]
+fn deletion_requires_a_confirmation_token() {
+    let (app, anon, user) = TestApp::init().with_user();
+
+    app.db(|conn| {
+        CrateBuilder::new("foo_confirmed", user.as_model().id).expect_build(conn);
+    });
+
+    let response = user.delete::<()>("/api/v1/crates/foo_confirmed");
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": unconfirmed_error("foo_confirmed") }] })
+    );
+
+    let response = delete_with_token(&user, "foo_confirmed", "99999999999.deadbeef");
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = anon.get::<()>("/api/v1/crates/foo_confirmed");
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let url = "/api/v1/crates/foo_confirmed/delete/confirm";
+    let response = user.run::<()>(user.post_request(url));
+    assert_eq!(response.status(), StatusCode::OK);
+    let json = response.into_json();
+    let token = json["token"].as_str().unwrap();
+    assert!(json["expires_at"].is_string());
+
+    let response = delete_with_token(&user, "foo_confirmed", token);
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.into_json(), json!({ "ok": true }));
+    // This test has no index to update
+    remove_pending_jobs(&app, "update_crate_index");
+
+    let response = anon.get::<()>("/api/v1/crates/foo_confirmed");
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn deletion_requires_a_recent_login() {
+    let (app, anon, user) = TestApp::init().with_user();
+    app.db(|conn| {
+        CrateBuilder::new("foo_stale_session", user.as_model().id).expect_build(conn);
+    });
+
+    let session_key = app.as_inner().session_key();
+    let logged_in_at = (Utc::now() - Duration::hours(2)).naive_utc();
+    let stale_cookie = encode_session_header_at(session_key, user.as_model().id, logged_in_at);
+    let expected = json!({ "errors": [{ "detail": "this action requires a recent login, please log out and log in again" }] });
+
+    let mut request = user.post_request("/api/v1/crates/foo_stale_session/delete/confirm");
+    request.header(header::COOKIE, &stale_cookie);
+    let response = user.run::<()>(request);
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(response.into_json(), expected);
+
+    // Tokens that were confirmed with a recent login can't be used with an older session either
+    let confirmation = user.delete_confirmation("foo_stale_session");
+    let mut request = user.request_builder(Method::DELETE, "/api/v1/crates/foo_stale_session");
+    request.header(header::COOKIE, &stale_cookie);
+    request.header("X-Crates-Io-Delete-Confirmation", &confirmation);
+    let response = user.run::<()>(request);
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(response.into_json(), expected);
+
+    let response = anon.get::<()>("/api/v1/crates/foo_stale_session");
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = user.delete_crate("foo_stale_session");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.into_json(), json!({ "ok": true }));
+    // This test has no index to update
+    remove_pending_jobs(&app, "update_crate_index");
+}
+
+#[test]
+fn confirmation_tokens_are_only_valid_for_one_crate_and_user() {
+    let (app, _, user) = TestApp::init().with_user();
+    let other_user = app.db_new_user("other_user");
+
+    app.db(|conn| {
+        CrateBuilder::new("foo_confirmed", user.as_model().id).expect_build(conn);
+        CrateBuilder::new("foo_confirmed_other", user.as_model().id).expect_build(conn);
+    });
+
+    let token = user.delete_confirmation("foo_confirmed_other");
+    let response = delete_with_token(&user, "foo_confirmed", &token);
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": unconfirmed_error("foo_confirmed") }] })
+    );
+
+    let token = other_user.delete_confirmation("foo_confirmed");
+    let response = delete_with_token(&user, "foo_confirmed", &token);
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    assert_eq!(deleted_crates(&app), Vec::<String>::new());
+}
+
+#[test]
+fn confirmation_tokens_expire() {
+    let (app, _, user) = TestApp::init()
+        .with_config(|config| {
+            config.crate_deletion_confirmation_expiration = std::time::Duration::ZERO
+        })
+        .with_user();
+
+    app.db(|conn| {
+        CrateBuilder::new("foo_confirmed", user.as_model().id).expect_build(conn);
+    });
+
+    let token = user.delete_confirmation("foo_confirmed");
+    let response = delete_with_token(&user, "foo_confirmed", &token);
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": unconfirmed_error("foo_confirmed") }] })
+    );
+    assert_eq!(deleted_crates(&app), Vec::<String>::new());
+}
+
+#[test]
+fn only_existing_crates_can_be_confirmed() {
+    let (_, anon, user) = TestApp::init().with_user();
+
+    let url = "/api/v1/crates/foo_missing/delete/confirm";
+    let response = user.run::<()>(user.post_request(url));
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let response = anon.run::<()>(anon.post_request(url));
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[test]
+fn deletion_uses_normalized_crate_names() {
+    let (app, _, user) = TestApp::init().with_user();
+    let admin = app.db_new_user("admin");
+    admin.make_admin();
+
+    app.db(|conn| {
+        CrateBuilder::new("foo_normalized", user.as_model().id).expect_build(conn);
+        CrateBuilder::new("foo_normalized_other", user.as_model().id).expect_build(conn);
+    });
+
+    // Names that only differ in case or in `-` vs. `_` refer to the same crate, like they do
+    // when publishing
+    let response = user.delete_crate("Foo-Normalized");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(deleted_crates(&app), ["foo_normalized"]);
+    // This test has no index to update
+    remove_pending_jobs(&app, "update_crate_index");
+
+    let response = user.delete_crate("foo-normalized");
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let response = admin.put::<()>("/api/v1/admin/crates/FOO-NORMALIZED/restore", b"");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(deleted_crates(&app), Vec::<String>::new());
+    remove_pending_jobs(&app, "update_crate_index");
+    remove_pending_jobs(&app, "sync_crates_feeds");
+
+    // Other crates are never matched
+    let response = user.delete_crate("foo-normalize");
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(deleted_crates(&app), Vec::<String>::new());
+}
+
+#[test]
+fn retried_deletion_returns_the_recorded_response() {
+    let (app, _, user) = TestApp::full().with_user();
+
+    app.db(|conn| {
+        CrateBuilder::new("foo_idempotent", user.as_model().id).expect_build(conn);
+        CrateBuilder::new("foo_idempotent_other", user.as_model().id).expect_build(conn);
+    });
+
+    let response = delete_with_key(&user, "foo_idempotent", "retry-key");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.into_json(), json!({ "ok": true }));
+    app.run_pending_background_jobs();
+
+    // The crate is gone, but the retry gets the original response
+    let response = delete_with_key(&user, "foo_idempotent", "retry-key");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.into_json(), json!({ "ok": true }));
+    assert_eq!(deleted_crates(&app), ["foo_idempotent"]);
+
+    let response = user.delete_crate("foo_idempotent");
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    // The key can't be used to delete another crate
+    let response = delete_with_key(&user, "foo_idempotent_other", "retry-key");
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "the `Idempotency-Key` header was already used for a different request" }] })
+    );
+
+    // Keys are separate for each user
+    let other_user = app.db_new_user("other_user");
+    let response = delete_with_key(&other_user, "foo_idempotent", "retry-key");
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn failed_deletion_is_not_recorded() {
+    let (app, _, user) = TestApp::full().with_user();
+    let other_user = app.db_new_user("other_user");
+
+    app.db(|conn| {
+        CrateBuilder::new("foo_idempotent_failed", user.as_model().id).expect_build(conn);
+    });
+
+    let response = delete_with_key(&other_user, "foo_idempotent_failed", "failing-key");
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "only owners have permission to delete crates" }] })
+    );
+    assert_eq!(deleted_crates(&app), Vec::<String>::new());
+
+    let response = delete_with_key(&user, "foo_idempotent_failed", "failing-key");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(deleted_crates(&app), ["foo_idempotent_failed"]);
+    app.run_pending_background_jobs();
+}
+
+#[test]
+fn idempotency_keys_expire() {
+    let (app, _, user) = TestApp::full()
+        .with_config(|config| config.idempotency_key_expiration = std::time::Duration::ZERO)
+        .with_user();
+
+    app.db(|conn| {
+        CrateBuilder::new("foo_idempotent_expired", user.as_model().id).expect_build(conn);
+    });
+
+    let response = delete_with_key(&user, "foo_idempotent_expired", "expiring-key");
+    assert_eq!(response.status(), StatusCode::OK);
+    app.run_pending_background_jobs();
+
+    let response = delete_with_key(&user, "foo_idempotent_expired", "expiring-key");
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn concurrent_deletions_are_serialized() {
+    // The test database pool only has a single connection, which can't be shared by concurrent
+    // requests, and the test needs to hold a connection of its own while they are running
+    let (app, _, user) = TestApp::full()
+        .with_database(TestDatabase::SlowRealPool { replica: false })
+        .with_config(|config| config.db.primary.pool_size = 5)
+        .with_user();
+
+    app.db(|conn| {
+        CrateBuilder::new("foo_concurrent", user.as_model().id).expect_build(conn);
+    });
+
+    let token = user.delete_confirmation("foo_concurrent");
+
+    let rt = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+
+    // Both requests are started while the crate row is locked by the test, so that they are
+    // guaranteed to overlap instead of one finishing before the other one starts
+    let [first, second] = app.db(|conn| {
+        conn.transaction(|conn| {
+            crates::table
+                .filter(crates::name.eq("foo_concurrent"))
+                .select(crates::id)
+                .for_update()
+                .execute(conn)?;
+
+            let responses = [(); 2].map(|_| {
+                let mut request =
+                    user.request_builder(Method::DELETE, "/api/v1/crates/foo_concurrent");
+                request.header("X-Crates-Io-Delete-Confirmation", &token);
+                let mut router = app.router().clone();
+                rt.spawn(async move { router.call(request.map(hyper::Body::from)).await })
+            });
+            std::thread::sleep(std::time::Duration::from_millis(500));
+
+            Ok::<_, diesel::result::Error>(responses)
+        })
+        .unwrap()
+    });
+    let first = rt.block_on(first).unwrap().unwrap();
+    let second = rt.block_on(second).unwrap().unwrap();
+
+    let mut statuses = [first.status(), second.status()];
+    statuses.sort();
+    assert_eq!(statuses, [StatusCode::OK, StatusCode::NOT_FOUND]);
+    assert_eq!(deleted_crates(&app), ["foo_concurrent"]);
+    app.run_pending_background_jobs();
+}
+
+#[test]
+fn failed_enqueue_names_the_job() {
+    let (app, anon, user) = TestApp::init().with_user();
+    app.db(|conn| {
+        CrateBuilder::new("foo_enqueue", user.as_model().id).expect_build(conn);
+
+        // Makes enqueueing the index update fail, which is the first job of a deletion
+        conn.batch_execute(
+            "CREATE FUNCTION fail_enqueue() RETURNS trigger AS $$ \
+             BEGIN RAISE EXCEPTION 'enqueue failed'; END $$ LANGUAGE plpgsql; \
+             CREATE TRIGGER fail_enqueue BEFORE INSERT ON background_jobs FOR EACH ROW \
+             WHEN (NEW.job_type = 'update_crate_index') EXECUTE FUNCTION fail_enqueue();",
+        )
+        .unwrap();
+    });
+
+    let response = user.delete_crate("foo_enqueue");
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "the deletion of `foo_enqueue` failed because the `update_crate_index` background job could not be enqueued, the crate was not deleted" }] })
+    );
+
+    // The deletion was rolled back
+    let response = anon.get::<()>("/api/v1/crates/foo_enqueue");
+    assert_eq!(response.status(), StatusCode::OK);
+    let deletions: i64 = app.db(|conn| crate_deletions::table.count().get_result(conn).unwrap());
+    assert_eq!(deletions, 0);
+}
+
+#[test]
+fn deletion_outcomes_are_counted() {
+    let (app, anon, user) = TestApp::init()
+        .with_config(|config| config.metrics_authorization_token = Some("secret".into()))
+        .with_user();
+    let other_user = app.db_new_user("other_user");
+
+    app.db(|conn| {
+        CrateBuilder::new("foo_frozen", user.as_model().id).expect_build(conn);
+        diesel::update(crates::table.filter(crates::name.eq("foo_frozen")))
+            .set(crates::crate_frozen.eq(true))
+            .execute(conn)
+            .unwrap();
+    });
+
+    for _ in 0..2 {
+        let response = other_user.delete_crate("foo_frozen");
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+    let response = user.delete_crate("foo_frozen");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let mut request = anon.get_request("/api/private/metrics/instance");
+    request.header("Authorization", "Bearer secret");
+    let metrics = anon.run::<()>(request).into_text();
+    assert!(metrics
+        .contains("cratesio_instance_crate_deletions_total{outcome=\"blocked_not_owner\"} 2"));
+    assert!(
+        metrics.contains("cratesio_instance_crate_deletions_total{outcome=\"blocked_frozen\"} 1")
+    );
+    assert!(!metrics.contains("cratesio_instance_crate_deletions_total{outcome=\"success\"}"));
+}
+
+#[test]
+fn admins_can_list_deleted_crates() {
+    let (app, _, user) = TestApp::init().with_user();
+    let admin = app.db_new_user("admin");
+    admin.make_admin();
+    create_team_owned_crate(&app, "foo_deleted_team");
+
+    app.db(|conn| {
+        CrateBuilder::new("foo_deleted_first", user.as_model().id).expect_build(conn);
+        CrateBuilder::new("foo_deleted_second", user.as_model().id).expect_build(conn);
+    });
+
+    let response = user.delete_crate("foo_deleted_first");
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = user.delete_crate("foo_deleted_second");
+    assert_eq!(response.status(), StatusCode::OK);
+    let team_member = app.db_new_user("user-one-team");
+    let response = team_member.delete_crate("foo_deleted_team");
+    assert_eq!(response.status(), StatusCode::OK);
+    // This test has no index to update
+    remove_pending_jobs(&app, "update_crate_index");
+
+    // Failed deletions aren't recorded
+    let response = user.delete_crate("foo_deleted_first");
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let response = user.get::<()>("/api/v1/admin/deletions");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let json = admin.get::<Value>("/api/v1/admin/deletions").good();
+    assert_eq!(json["meta"]["total"], 3);
+    assert_eq!(
+        summarize_deletions(&json),
+        json!([
+            ["foo_deleted_team", "user-one-team", "team"],
+            ["foo_deleted_second", "foo", "owner"],
+            ["foo_deleted_first", "foo", "owner"],
+        ])
+    );
+
+    let json = admin
+        .get_with_query::<Value>("/api/v1/admin/deletions", "per_page=1&page=2")
+        .good();
+    assert_eq!(json["meta"]["total"], 3);
+    assert_eq!(json["deletions"][0]["crate_name"], "foo_deleted_second");
+
+    // The record is kept when the crate is removed permanently
+    app.db(|conn| {
+        let deleted_at = (Utc::now() - Duration::days(2)).naive_utc();
+        diesel::update(crate_deletions::table)
+            .filter(crate_deletions::crate_name.eq("foo_deleted_first"))
+            .set(crate_deletions::deleted_at.eq(deleted_at))
+            .execute(conn)
+            .unwrap();
+        diesel::delete(crates::table.filter(crates::name.eq("foo_deleted_first")))
+            .execute(conn)
+            .unwrap();
+    });
+
+    let json = admin.get::<Value>("/api/v1/admin/deletions").good();
+    assert_eq!(json["deletions"][2]["crate_name"], "foo_deleted_first");
+
+    let since = (Utc::now() - Duration::days(1)).naive_utc();
+    let query = format!("since={}", since.format("%Y-%m-%dT%H:%M:%S"));
+    let json = admin
+        .get_with_query::<Value>("/api/v1/admin/deletions", &query)
+        .good();
+    assert_eq!(json["meta"]["total"], 2);
+    assert_eq!(json["deletions"][0]["crate_name"], "foo_deleted_team");
+    assert_eq!(json["deletions"][1]["crate_name"], "foo_deleted_second");
+
+    let response = admin.get_with_query::<()>("/api/v1/admin/deletions", "since=yesterday");
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[test]
+fn deletion_records_are_anonymized_after_retention_period() {
+    let (app, _, user) = TestApp::full().with_user();
+    let user_id = user.as_model().id;
+
+    // Postgres only stores microseconds
+    let now = Utc::now().naive_utc().trunc_subsecs(6);
+    let long_ago = now - Duration::days(400);
+    let recently = now - Duration::days(30);
+    app.db(|conn| {
+        for (crate_name, deleted_at) in [("foo_long_ago", long_ago), ("foo_recently", recently)] {
+            diesel::insert_into(crate_deletions::table)
+                .values((
+                    crate_deletions::crate_name.eq(crate_name),
+                    crate_deletions::deleted_by.eq(user_id),
+                    crate_deletions::deleted_at.eq(deleted_at),
+                    crate_deletions::path.eq("owner"),
+                ))
+                .execute(conn)
+                .unwrap();
+        }
+
+        worker::anonymize_crate_deletions().enqueue(conn).unwrap();
+    });
+    app.run_pending_background_jobs();
+
+    let deletions: Vec<(String, Option<i32>, NaiveDateTime, bool)> = app.db(|conn| {
+        crate_deletions::table
+            .select((
+                crate_deletions::crate_name,
+                crate_deletions::deleted_by,
+                crate_deletions::deleted_at,
+                crate_deletions::anonymized_at.is_not_null(),
+            ))
+            .order(crate_deletions::crate_name)
+            .load(conn)
+            .unwrap()
+    });
+    assert_eq!(
+        deletions,
+        vec![
+            ("foo_long_ago".to_string(), None, long_ago, true),
+            ("foo_recently".to_string(), Some(user_id), recently, false),
+        ]
+    );
+}
+
+#[test]
+fn owners_can_see_that_they_can_delete_a_crate() {
+    let (app, _, user) = TestApp::init().with_user();
+    app.db(|conn| {
+        CrateBuilder::new("foo_deletable", user.as_model().id).expect_build(conn);
+    });
+
+    let json = user
+        .get_with_query::<Value>("/api/v1/crates/foo_deletable", "include=delete_status")
+        .good();
+    assert_eq!(json["crate"]["name"], "foo_deletable");
+    assert_eq!(
+        json["delete_status"],
+        json!({ "eligible": true, "blockers": [] })
+    );
+
+    // The status is only included on request
+    let json = user.get::<Value>("/api/v1/crates/foo_deletable").good();
+    assert!(json.get("delete_status").is_none());
+}
+
+#[test]
+fn owners_can_see_why_they_cannot_delete_a_crate() {
+    let (app, _, user) = TestApp::init().with_user();
+    app.db(|conn| {
+        CrateBuilder::new("foo_frozen", user.as_model().id).expect_build(conn);
+        diesel::update(crates::table.filter(crates::name.eq("foo_frozen")))
+            .set(crates::crate_frozen.eq(true))
+            .execute(conn)
+            .unwrap();
+    });
+
+    let json = user
+        .get_with_query::<Value>("/api/v1/crates/foo_frozen", "include=delete_status")
+        .good();
+    assert_eq!(
+        json["delete_status"],
+        json!({ "eligible": false, "blockers": ["frozen"] })
+    );
+
+    create_team_owned_crate(&app, "foo_team_old");
+    app.db(|conn| {
+        let created_at = (Utc::now() - Duration::days(2)).naive_utc();
+        diesel::update(crates::table.filter(crates::name.eq("foo_team_old")))
+            .set(crates::created_at.eq(created_at))
+            .execute(conn)
+            .unwrap();
+    });
+
+    let team_member = app.db_new_user("user-one-team");
+    let json = team_member
+        .get_with_query::<Value>("/api/v1/crates/foo_team_old", "include=delete_status")
+        .good();
+    assert_eq!(
+        json["delete_status"],
+        json!({ "eligible": false, "blockers": ["team_grace_period_passed"] })
+    );
+}
+
+#[test]
+fn non_owners_cannot_delete_a_crate() {
+    let (app, anon, user) = TestApp::init().with_user();
+    app.db(|conn| {
+        CrateBuilder::new("foo_not_owned", user.as_model().id).expect_build(conn);
+    });
+    let expected = json!({ "eligible": false, "blockers": ["not_owner"] });
+
+    let other_user = app.db_new_user("other_user");
+    let json = other_user
+        .get_with_query::<Value>("/api/v1/crates/foo_not_owned", "include=delete_status")
+        .good();
+    assert_eq!(json["delete_status"], expected);
+
+    let json = anon
+        .get_with_query::<Value>("/api/v1/crates/foo_not_owned", "include=delete_status")
+        .good();
+    assert_eq!(json["delete_status"], expected);
+
+    // API tokens aren't accepted for deletions, even from the owner
+    let token = user.db_new_token("arbitrary token name");
+    let json = token
+        .get_with_query::<Value>("/api/v1/crates/foo_not_owned", "include=delete_status")
+        .good();
+    assert_eq!(json["delete_status"], expected);
+}
+
+fn delete_with_key(user: &MockCookieUser, crate_name: &str, key: &str) -> Response<()> {
+    let url = format!("/api/v1/crates/{crate_name}");
+    let mut request = user.request_builder(Method::DELETE, &url);
+    request.header("Idempotency-Key", key);
+    let token = user.delete_confirmation(crate_name);
+    request.header("X-Crates-Io-Delete-Confirmation", &token);
+    user.run(request)
+}
+
+fn delete_with_token(user: &MockCookieUser, crate_name: &str, token: &str) -> Response<()> {
+    let url = format!("/api/v1/crates/{crate_name}");
+    let mut request = user.request_builder(Method::DELETE, &url);
+    request.header("X-Crates-Io-Delete-Confirmation", token);
+    user.run(request)
+}
+
+fn unconfirmed_error(crate_name: &str) -> String {
+    format!(
+        "The deletion of the `{crate_name}` crate has to be confirmed first. The token in the \
+         `X-Crates-Io-Delete-Confirmation` header is missing, invalid or expired."
+    )
+}
+
+/// Creates a crate that is owned by a user, and by the `github:test-org:all` team.
+fn create_team_owned_crate(app: &TestApp, crate_name: &str) {
+    let owner = app.db_new_user("user-all-teams");
+    app.db(|conn| {
+        CrateBuilder::new(crate_name, owner.as_model().id).expect_build(conn);
+    });
+
+    let token = owner.db_new_token("arbitrary token name");
+    token
+        .add_named_owner(crate_name, "github:test-org:all")
+        .good();
+}
+
+/// Moves the creation of the crate to before the window in which team members can delete it.
+fn make_old(app: &TestApp, crate_name: &str) {
+    app.db(|conn| {
+        let created_at = (Utc::now() - Duration::days(2)).naive_utc();
+        diesel::update(crates::table.filter(crates::name.eq(crate_name)))
+            .set(crates::created_at.eq(created_at))
+            .execute(conn)
+            .unwrap();
+    });
+}
+
+/// Returns the crate name, the login of the user that deleted it and the path of each deletion.
+fn summarize_deletions(json: &Value) -> Value {
+    json["deletions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|deletion| {
+            json!([
+                deletion["crate_name"],
+                deletion["deleted_by"],
+                deletion["path"]
+            ])
+        })
+        .collect()
+}
+
+/// Removes the pending jobs of a type from the queue, and returns their data.
+fn remove_pending_jobs(app: &TestApp, job_type: &str) -> Vec<Value> {
+    use cargo_registry::schema::background_jobs;
+
+    app.db(|conn| {
+        diesel::delete(background_jobs::table.filter(background_jobs::job_type.eq(job_type)))
+            .returning(background_jobs::data)
+            .get_results(conn)
+            .unwrap()
+    })
+}
+
+fn enqueue_purge(app: &TestApp) {
+    app.db(|conn| worker::purge_deleted_crates().enqueue(conn).unwrap());
+}
+
+fn deleted_crates(app: &TestApp) -> Vec<String> {
+    app.db(|conn| {
+        crates::table
+            .filter(crates::deleted_at.is_not_null())
+            .select(crates::name)
+            .load(conn)
+            .unwrap()
+    })
+}
+
+fn count_crates(app: &TestApp, name: &str) -> i64 {
+    app.db(|conn| {
+        crates::table
+            .filter(crates::name.eq(name))
+            .count()
+            .get_result(conn)
+            .unwrap()
+    })
+}
+
+#[test]
+fn deleted_crates_report_when_they_were_deleted() {
+    let (app, anon, user) = TestApp::init().with_user();
+
+    app.db(|conn| {
+        CrateBuilder::new("foo_gone", user.as_model().id).expect_build(conn);
+    });
+
+    let response = user.delete_crate("foo_gone");
+    assert_eq!(response.status(), StatusCode::OK);
+    // This test has no index to update
+    remove_pending_jobs(&app, "update_crate_index");
+
+    let deleted_at: NaiveDateTime = app.db(|conn| {
+        crate_deletions::table
+            .select(crate_deletions::deleted_at)
+            .first(conn)
+            .unwrap()
+    });
+    let expected = json!({
+        "errors": [{
+            "detail": format!(
+                "crate `Foo-Gone` was deleted on {} and is no longer available",
+                deleted_at.format("%Y-%m-%d at %H:%M:%S UTC"),
+            ),
+            "code": "crate_deleted",
+            "deleted_at": deleted_at,
+        }]
+    });
+
+    // Names are matched like the crate itself would be
+    let response = anon.get::<()>("/api/v1/crates/Foo-Gone");
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(response.into_json(), expected);
+}
@@ -0,0 +1,70 @@
+use crate::builders::PublishBuilder;
+use crate::util::{RequestHelper, Response, TestApp};
+use cargo_registry::models::{VersionAction, VersionOwnerAction};
+use http::StatusCode;
+
+#[test]
+#[allow(unknown_lints, clippy::bool_assert_comparison)] // for claim::assert_some_eq! with bool
+fn admins_can_yank_versions_they_dont_own() {
+    let (app, anon, user, token) = TestApp::full().with_token();
+    let admin = app.db_new_user("admin");
+    admin.make_admin();
+
+    let crate_to_publish = PublishBuilder::new("foo_force_yank").version("1.0.0");
+    token.publish_crate(crate_to_publish).good();
+    app.run_pending_background_jobs();
+
+    let body = json!({ "reason": "malware" }).to_string();
+    let response = force_yank(&anon, "foo_force_yank", "1.0.0", &body);
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = force_yank(&user, "foo_force_yank", "1.0.0", &body);
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = force_yank(&admin, "foo_force_yank", "1.0.0", &body);
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.into_json(), json!({ "ok": true }));
+
+    let json = anon.show_version("foo_force_yank", "1.0.0");
+    assert!(json.version.yanked);
+
+    let crates = app.crates_from_index_head("foo_force_yank");
+    assert_eq!(crates.len(), 1);
+    assert_some_eq!(crates[0].yanked, true);
+
+    let actions = app.db(|conn| VersionOwnerAction::all(conn).unwrap());
+    let yank = actions.last().unwrap();
+    assert_eq!(yank.action, VersionAction::Yank);
+    assert_eq!(yank.user_id, admin.as_model().id);
+    assert_eq!(yank.reason.as_deref(), Some("malware"));
+}
+
+#[test]
+fn reason_is_optional() {
+    let (app, anon, _, token) = TestApp::full().with_token();
+    let admin = app.db_new_user("admin");
+    admin.make_admin();
+
+    let crate_to_publish = PublishBuilder::new("foo_force_yank").version("1.0.0");
+    token.publish_crate(crate_to_publish).good();
+
+    let response = force_yank(&admin, "foo_force_yank", "1.0.0", "");
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let json = anon.show_version("foo_force_yank", "1.0.0");
+    assert!(json.version.yanked);
+
+    let actions = app.db(|conn| VersionOwnerAction::all(conn).unwrap());
+    assert_eq!(actions.last().unwrap().reason, None);
+}
+
+fn force_yank(
+    user: &impl RequestHelper,
+    crate_name: &str,
+    version: &str,
+    body: &str,
+) -> Response<()> {
+    let url = format!("/api/v1/admin/crates/{crate_name}/{version}/yank");
+    let response = user.put(&url, body.as_bytes());
+    user.app().run_pending_background_jobs();
+    response
+}
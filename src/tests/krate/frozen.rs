@@ -0,0 +1,70 @@
+use crate::builders::PublishBuilder;
+use crate::util::{MockCookieUser, RequestHelper, TestApp};
+use http::StatusCode;
+
+const FROZEN_ERROR: &str = "The crate `foo_frozen` has been frozen by the crates.io team and \
+                            can't be modified. Please contact help@crates.io for more information.";
+
+#[test]
+fn frozen_crate_rejects_writes_but_stays_visible() {
+    let (app, anon, user, token) = TestApp::full().with_token();
+    let admin = app.db_new_user("admin");
+    admin.make_admin();
+
+    let crate_to_publish = PublishBuilder::new("foo_frozen").version("1.0.0");
+    token.publish_crate(crate_to_publish).good();
+
+    assert_eq!(set_frozen(&admin, true), json!({ "frozen": true }));
+
+    let crate_to_publish = PublishBuilder::new("foo_frozen").version("1.1.0");
+    let response = token.publish_crate(crate_to_publish);
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": FROZEN_ERROR }] })
+    );
+
+    let response = token.delete::<()>("/api/v1/crates/foo_frozen/1.0.0/yank");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = user.delete_crate("foo_frozen");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": FROZEN_ERROR }] })
+    );
+
+    let json = anon.show_crate("foo_frozen");
+    assert_eq!(json.versions.unwrap().len(), 1);
+    assert_eq!(anon.search("q=foo_frozen").crates.len(), 1);
+
+    assert_eq!(set_frozen(&admin, false), json!({ "frozen": false }));
+
+    let crate_to_publish = PublishBuilder::new("foo_frozen").version("1.1.0");
+    token.publish_crate(crate_to_publish).good();
+}
+
+#[test]
+fn only_admins_can_freeze_crates() {
+    let (_, _, user, token) = TestApp::full().with_token();
+
+    let crate_to_publish = PublishBuilder::new("foo_frozen").version("1.0.0");
+    token.publish_crate(crate_to_publish).good();
+
+    let body = json!({ "frozen": true }).to_string();
+    let response = user.put::<()>("/api/v1/admin/crates/foo_frozen/frozen", body.as_bytes());
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = token.put::<()>("/api/v1/admin/crates/foo_frozen/frozen", body.as_bytes());
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let crate_to_publish = PublishBuilder::new("foo_frozen").version("1.1.0");
+    token.publish_crate(crate_to_publish).good();
+}
+
+fn set_frozen(admin: &MockCookieUser, frozen: bool) -> serde_json::Value {
+    let body = json!({ "frozen": frozen }).to_string();
+    let response = admin.put::<()>("/api/v1/admin/crates/foo_frozen/frozen", body.as_bytes());
+    assert_eq!(response.status(), StatusCode::OK);
+    response.into_json()
+}
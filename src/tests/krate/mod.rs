@@ -1,4 +1,10 @@
+mod batch;
+mod deletion;
 mod following;
+mod force_yank;
+mod frozen;
 mod publish;
+mod reset_downloads;
+mod resync_index;
 mod versions;
 mod yanking;
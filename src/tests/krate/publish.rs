@@ -1,12 +1,15 @@
 use crate::builders::{CrateBuilder, DependencyBuilder, PublishBuilder};
 use crate::new_category;
-use crate::util::{RequestHelper, TestApp};
+use crate::util::{RequestHelper, Response, TestApp};
 use cargo_registry::controllers::krate::publish::{
     missing_metadata_error_message, MISSING_RIGHTS_ERROR_MESSAGE, WILDCARD_ERROR_MESSAGE,
 };
 use cargo_registry::models::krate::MAX_NAME_LENGTH;
-use cargo_registry::schema::{api_tokens, emails, versions_published_by};
+use cargo_registry::models::token::CrateScope;
+use cargo_registry::rate_limiter::LimitedAction;
+use cargo_registry::schema::{api_tokens, emails, versions, versions_published_by};
 use cargo_registry::views::GoodCrate;
+use chrono::{NaiveDate, Utc};
 use diesel::{delete, update, ExpressionMethods, QueryDsl, RunQueryDsl};
 use flate2::write::GzEncoder;
 use flate2::Compression;
@@ -100,16 +103,55 @@ fn invalid_names() {
     let error_message = "expected a valid crate name";
     bad_name("", error_message);
     bad_name("foo bar", error_message);
-    bad_name(&"a".repeat(MAX_NAME_LENGTH + 1), error_message);
     bad_name("snow☃", error_message);
     bad_name("áccênts", error_message);
 
+    let error_message = "crate names can have at most 64 characters";
+    bad_name(&"a".repeat(MAX_NAME_LENGTH + 1), error_message);
+
     let error_message = "cannot upload a crate with a reserved name";
     bad_name("std", error_message);
     bad_name("STD", error_message);
     bad_name("compiler-rt", error_message);
     bad_name("compiler_rt", error_message);
     bad_name("coMpiLer_Rt", error_message);
+    bad_name("Compiler-RT", error_message);
+}
+
+#[test]
+fn max_name_length_is_configurable() {
+    let (_, _, _, token) = TestApp::full()
+        .with_config(|config| config.max_crate_name_length = 10)
+        .with_token();
+
+    let crate_to_publish = PublishBuilder::new("foo_eleven").version("1.0.0");
+    token.publish_crate(crate_to_publish).good();
+
+    let crate_to_publish = PublishBuilder::new("foo_twelves").version("1.0.0");
+    let response = token.publish_crate(crate_to_publish);
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "the crate name `foo_twelves` is too long, crate names can have at most 10 characters" }] })
+    );
+}
+
+#[test]
+fn all_name_errors_are_reported() {
+    let (_, _, _, token) = TestApp::full()
+        .with_config(|config| config.max_crate_name_length = 8)
+        .with_token();
+
+    let crate_to_publish = PublishBuilder::new("Compiler_RT").version("1.0.0");
+    let response = token.publish_crate(crate_to_publish);
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [
+            { "detail": "the crate name `Compiler_RT` is too long, crate names can have at most 8 characters" },
+            { "detail": "cannot upload a crate with a reserved name" },
+        ] })
+    );
 }
 
 #[test]
@@ -134,6 +176,32 @@ fn new_krate_with_token() {
     assert_eq!(json.krate.max_version, "1.0.0");
 }
 
+#[test]
+fn new_krate_with_crate_scoped_token() {
+    let crate_scopes = Some(vec![CrateScope::try_from("foo_scoped").unwrap()]);
+    let (_, _, _, token) = TestApp::full().with_scoped_token(crate_scopes, None);
+
+    let crate_to_publish = PublishBuilder::new("foo_scoped").version("1.0.0");
+    let json: GoodCrate = token.publish_crate(crate_to_publish).good();
+
+    assert_eq!(json.krate.name, "foo_scoped");
+    assert_eq!(json.krate.max_version, "1.0.0");
+}
+
+#[test]
+fn new_krate_with_wrong_crate_scope() {
+    let crate_scopes = Some(vec![CrateScope::try_from("bar").unwrap()]);
+    let (_, _, _, token) = TestApp::full().with_scoped_token(crate_scopes, None);
+
+    let crate_to_publish = PublishBuilder::new("foo_scoped").version("1.0.0");
+    let response = token.publish_crate(crate_to_publish);
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "must be logged in to perform that action" }] })
+    );
+}
+
 #[test]
 fn new_krate_weird_version() {
     let (_, _, _, token) = TestApp::full().with_token();
@@ -195,6 +263,30 @@ fn new_with_underscore_renamed_dependency() {
     assert_eq!(crates[0].deps[0].package.as_ref().unwrap(), "package-name");
 }
 
+#[test]
+fn renamed_dependency_length_is_configurable() {
+    let (app, _, user, token) = TestApp::full()
+        .with_config(|config| config.max_crate_name_length = 10)
+        .with_token();
+
+    app.db(|conn| {
+        // Insert a crate directly into the database so that new-krate can depend on it
+        CrateBuilder::new("package-name", user.as_model().id).expect_build(conn);
+    });
+
+    let dependency = DependencyBuilder::new("package-name").rename("my-long-name");
+
+    let crate_to_publish = PublishBuilder::new("new-krate")
+        .version("1.0.0")
+        .dependency(dependency);
+    let response = token.publish_crate(crate_to_publish);
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "the dependency name `my-long-name` is too long, dependency names can have at most 10 characters" }] })
+    );
+}
+
 #[test]
 fn new_krate_with_dependency() {
     use crate::routes::crates::versions::dependencies::Deps;
@@ -762,13 +854,136 @@ fn good_categories() {
     });
 
     let crate_to_publish = PublishBuilder::new("foo_good_cat").category("cat1");
-    let json = token.publish_crate(crate_to_publish).good();
+    let response = token.put::<GoodCrate>("/api/v1/crates/new", &crate_to_publish.body());
+    let json = response.good();
+
+    // Use a fixed publish time, since it is included in the recorded category feed
+    let published_at = NaiveDate::from_ymd_opt(2023, 3, 28)
+        .unwrap()
+        .and_hms_opt(12, 0, 0)
+        .unwrap();
+    app.db(|conn| {
+        update(versions::table)
+            .set(versions::created_at.eq(published_at))
+            .execute(conn)
+            .unwrap();
+    });
+    app.run_pending_background_jobs();
 
     assert_eq!(json.krate.name, "foo_good_cat");
     assert_eq!(json.krate.max_version, "1.0.0");
     assert_eq!(json.warnings.invalid_categories.len(), 0);
 }
 
+#[test]
+fn changing_categories_is_rate_limited() {
+    let (app, _, _, token) = TestApp::full()
+        .with_rate_limit(LimitedAction::UpdateCategories, Duration::from_secs(60), 2)
+        .with_token();
+
+    app.db(|conn| {
+        new_category("Category 1", "cat1", "Category 1 crates")
+            .create_or_update(conn)
+            .unwrap();
+        new_category("Category 2", "cat2", "Category 2 crates")
+            .create_or_update(conn)
+            .unwrap();
+    });
+
+    // Use fixed, distinct publish times, since they determine the contents of the recorded
+    // category feeds
+    let publish = |version: &str, category, day| {
+        let crate_to_publish = PublishBuilder::new("foo_cat_churn")
+            .version(version)
+            .category(category);
+        let response = token.put::<GoodCrate>("/api/v1/crates/new", &crate_to_publish.body());
+        let published_at = NaiveDate::from_ymd_opt(2023, 3, day)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap();
+        app.db(|conn| {
+            update(versions::table.filter(versions::num.eq(version)))
+                .set(versions::created_at.eq(published_at))
+                .execute(conn)
+                .unwrap();
+        });
+        app.run_pending_background_jobs();
+        response
+    };
+
+    publish("1.0.0", "cat1", 1).good();
+    // Publishing with the same categories doesn't count towards the limit
+    publish("1.0.1", "cat1", 2).good();
+    publish("1.0.2", "cat2", 3).good();
+
+    let response = publish("1.0.3", "cat1", 4);
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[test]
+fn too_many_categories() {
+    let (app, anon, _, token) = TestApp::full()
+        .with_config(|config| config.max_categories_per_crate = 1)
+        .with_token();
+
+    app.db(|conn| {
+        new_category("Category 1", "cat1", "Category 1 crates")
+            .create_or_update(conn)
+            .unwrap();
+        new_category("Category 2", "cat2", "Category 2 crates")
+            .create_or_update(conn)
+            .unwrap();
+    });
+
+    let crate_to_publish = PublishBuilder::new("foo_many_cats")
+        .category("cat1")
+        .category("cat2");
+    let response = token.publish_crate(crate_to_publish);
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "crates can be in at most 1 categories" }] })
+    );
+
+    let response = anon.get::<()>("/api/v1/crates/foo_many_cats");
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn category_limit_can_be_raised() {
+    let (app, _, _, token) = TestApp::full()
+        .with_config(|config| config.max_categories_per_crate = 6)
+        .with_token();
+
+    let slugs = ["cat1", "cat2", "cat3", "cat4", "cat5", "cat6"];
+    app.db(|conn| {
+        for slug in slugs {
+            new_category(slug, slug, "Category crates")
+                .create_or_update(conn)
+                .unwrap();
+        }
+    });
+
+    let crate_to_publish = slugs
+        .iter()
+        .fold(PublishBuilder::new("foo_six_cats"), |builder, slug| {
+            builder.category(slug)
+        });
+    let response = token.put::<GoodCrate>("/api/v1/crates/new", &crate_to_publish.body());
+    assert!(response.good().warnings.invalid_categories.is_empty());
+
+    // The category feeds are covered by `good_categories`
+    app.db(|conn| {
+        use cargo_registry::schema::background_jobs::dsl::*;
+
+        let deleted = diesel::delete(background_jobs.filter(job_type.eq("sync_category_feed")))
+            .execute(conn)
+            .unwrap();
+        assert_eq!(deleted, slugs.len());
+    });
+    app.run_pending_background_jobs();
+}
+
 #[test]
 fn ignored_categories() {
     let (_, _, _, token) = TestApp::full().with_token();
@@ -925,7 +1140,7 @@ fn tarball_bigger_than_max_upload_size() {
 #[test]
 fn publish_new_crate_rate_limited() {
     let (_, anon, _, token) = TestApp::full()
-        .with_publish_rate_limit(Duration::from_millis(500), 1)
+        .with_rate_limit(LimitedAction::PublishNew, Duration::from_millis(500), 1)
         .with_token();
 
     // Upload a new crate
@@ -950,10 +1165,55 @@ fn publish_new_crate_rate_limited() {
     assert_eq!(json.krate.max_version, "1.0.0");
 }
 
+#[test]
+fn publish_new_crate_includes_rate_limit_headers() {
+    let (_, _, _, token) = TestApp::full()
+        .with_rate_limit(LimitedAction::PublishNew, Duration::from_secs(60), 5)
+        .with_token();
+
+    let header = |response: &Response<GoodCrate>, name| {
+        response.headers()[name].to_str().unwrap().to_string()
+    };
+
+    let response = token.publish_crate(PublishBuilder::new("rate_limited1"));
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(header(&response, "x-ratelimit-limit"), "5");
+    assert_eq!(header(&response, "x-ratelimit-remaining"), "4");
+    let reset: i64 = header(&response, "x-ratelimit-reset").parse().unwrap();
+    assert!(reset > Utc::now().timestamp());
+
+    let response = token.publish_crate(PublishBuilder::new("rate_limited2"));
+    assert_eq!(header(&response, "x-ratelimit-remaining"), "3");
+
+    // Publishing a new version of an existing crate is not rate limited
+    let response = token.publish_crate(PublishBuilder::new("rate_limited1").version("1.0.1"));
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(!response.headers().contains_key("x-ratelimit-remaining"));
+}
+
+#[test]
+fn publish_new_crate_uses_verified_rate_limit() {
+    let (_, _, _, token) = TestApp::full()
+        .with_rate_limit(LimitedAction::PublishNew, Duration::from_secs(60), 1)
+        .with_verified_rate_limit(LimitedAction::PublishNew, Duration::from_secs(60), 2)
+        .with_token();
+
+    // The user of the token has a verified email address, so they get the higher allowance
+    token
+        .publish_crate(PublishBuilder::new("rate_limited1"))
+        .good();
+    token
+        .publish_crate(PublishBuilder::new("rate_limited2"))
+        .good();
+
+    let response = token.publish_crate(PublishBuilder::new("rate_limited3"));
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+}
+
 #[test]
 fn publish_rate_limit_doesnt_affect_existing_crates() {
     let (_, _, _, token) = TestApp::full()
-        .with_publish_rate_limit(Duration::from_millis(500), 1)
+        .with_rate_limit(LimitedAction::PublishNew, Duration::from_millis(500), 1)
         .with_token();
 
     // Upload a new crate
@@ -995,3 +1255,23 @@ fn features_version_2() {
     )]);
     assert_eq!(crates[0].features2, Some(features2));
 }
+
+#[test]
+fn new_krate_with_rust_version() {
+    let (_, anon, _, token) = TestApp::full().with_token();
+
+    let manifest =
+        b"[package]\nname = \"foo_msrv\"\nversion = \"1.0.0\"\nrust-version = \"1.59\"\n";
+    let files = [("foo_msrv-1.0.0/Cargo.toml", manifest as &[_])];
+    let crate_to_publish = PublishBuilder::new("foo_msrv").files(&files);
+    token.publish_crate(crate_to_publish).good();
+
+    let json = anon.show_version("foo_msrv", "1.0.0");
+    assert_eq!(json.version.rust_version.as_deref(), Some("1.59"));
+
+    let crate_to_publish = PublishBuilder::new("foo_msrv").version("1.0.1");
+    token.publish_crate(crate_to_publish).good();
+
+    let json = anon.show_version("foo_msrv", "1.0.1");
+    assert_none!(json.version.rust_version);
+}
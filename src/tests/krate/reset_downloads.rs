@@ -0,0 +1,104 @@
+use crate::builders::{CrateBuilder, VersionBuilder};
+use crate::util::{RequestHelper, Response, TestApp};
+use cargo_registry::schema::{metadata, version_downloads, versions};
+use diesel::dsl::count_star;
+use diesel::prelude::*;
+use http::StatusCode;
+
+#[test]
+fn admins_can_reset_crate_downloads() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let admin = app.db_new_user("admin");
+    admin.make_admin();
+
+    app.db(|conn| {
+        CrateBuilder::new("foo_reset", user.as_model().id)
+            .version(VersionBuilder::new("1.0.0"))
+            .downloads(100)
+            .recent_downloads(20)
+            .expect_build(conn);
+        CrateBuilder::new("foo_reset_other", user.as_model().id)
+            .downloads(10)
+            .recent_downloads(5)
+            .expect_build(conn);
+
+        diesel::update(versions::table)
+            .set(versions::downloads.eq(50))
+            .execute(conn)
+            .unwrap();
+        diesel::update(metadata::table)
+            .set(metadata::total_downloads.eq(110))
+            .execute(conn)
+            .unwrap();
+    });
+
+    let response = reset_downloads(&admin, "Foo-Reset");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.into_json(), json!({ "downloads": 0 }));
+
+    // The recent downloads are updated by the `update_downloads` background job
+    app.db(|conn| {
+        sql_function!(fn refresh_recent_crate_downloads());
+        diesel::select(refresh_recent_crate_downloads())
+            .execute(conn)
+            .unwrap();
+    });
+
+    let json = anon.show_crate("foo_reset");
+    assert_eq!(json.krate.downloads, 0);
+    assert!(json.versions.unwrap().iter().all(|v| v.downloads == 0));
+
+    // Other crates keep their downloads
+    let json = anon.show_crate("foo_reset_other");
+    assert_eq!(json.krate.downloads, 10);
+    assert_eq!(json.versions.unwrap()[0].downloads, 50);
+
+    app.db(|conn| {
+        let rows: i64 = version_downloads::table
+            .select(count_star())
+            .get_result(conn)
+            .unwrap();
+        assert_eq!(rows, 1);
+
+        let total: i64 = metadata::table
+            .select(metadata::total_downloads)
+            .get_result(conn)
+            .unwrap();
+        assert_eq!(total, 10);
+    });
+}
+
+#[test]
+fn only_admins_can_reset_crate_downloads() {
+    let (app, anon, user) = TestApp::init().with_user();
+
+    app.db(|conn| {
+        CrateBuilder::new("foo_reset", user.as_model().id)
+            .downloads(100)
+            .expect_build(conn);
+    });
+
+    let response = reset_downloads(&user, "foo_reset");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = reset_downloads(&anon, "foo_reset");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let json = anon.show_crate("foo_reset");
+    assert_eq!(json.krate.downloads, 100);
+}
+
+#[test]
+fn resetting_downloads_of_unknown_crate_fails() {
+    let (app, _) = TestApp::init().empty();
+    let admin = app.db_new_user("admin");
+    admin.make_admin();
+
+    let response = reset_downloads(&admin, "foo_unknown");
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+fn reset_downloads(user: &impl RequestHelper, crate_name: &str) -> Response<()> {
+    let url = format!("/api/v1/admin/crates/{crate_name}/reset_downloads");
+    user.run(user.post_request(&url))
+}
@@ -0,0 +1,60 @@
+use crate::builders::PublishBuilder;
+use crate::util::{RequestHelper, Response, TestApp};
+use cargo_registry::schema::{background_jobs, versions};
+use diesel::prelude::*;
+use http::StatusCode;
+
+#[test]
+fn admins_can_resync_the_index_of_a_crate() {
+    let (app, anon, user, token) = TestApp::full().with_token();
+    let admin = app.db_new_user("admin");
+    admin.make_admin();
+
+    let crate_to_publish = PublishBuilder::new("foo_resync").version("1.0.0");
+    token.publish_crate(crate_to_publish).good();
+    app.run_pending_background_jobs();
+
+    // Simulate the index drifting from the database
+    app.db(|conn| {
+        diesel::update(versions::table)
+            .set(versions::yanked.eq(true))
+            .execute(conn)
+            .unwrap();
+    });
+
+    let response = resync_index(&anon, "foo_resync");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = resync_index(&user, "foo_resync");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = resync_index(&admin, "foo_resync");
+    assert_eq!(response.status(), StatusCode::OK);
+    let json = response.into_json();
+
+    let jobs: Vec<(i64, String)> = app.db(|conn| {
+        background_jobs::table
+            .select((background_jobs::id, background_jobs::job_type))
+            .order(background_jobs::id)
+            .load(conn)
+            .unwrap()
+    });
+    let job_types = jobs
+        .iter()
+        .map(|(_, job_type)| job_type)
+        .collect::<Vec<_>>();
+    assert_eq!(job_types, ["sync_to_git_index", "update_crate_index"]);
+    let job_ids = jobs.iter().map(|(id, _)| *id).collect::<Vec<_>>();
+    assert_eq!(json, json!({ "job_ids": job_ids }));
+
+    app.run_pending_background_jobs();
+
+    let crates = app.crates_from_index_head("foo_resync");
+    assert_eq!(crates.len(), 1);
+    assert_eq!(crates[0].vers, "1.0.0");
+    assert_eq!(crates[0].yanked, Some(true));
+}
+
+fn resync_index(user: &impl RequestHelper, crate_name: &str) -> Response<()> {
+    let url = format!("/api/v1/admin/crates/{crate_name}/resync_index");
+    user.run(user.post_request(&url))
+}
@@ -1,8 +1,9 @@
-use crate::builders::CrateBuilder;
+use crate::builders::{CrateBuilder, VersionBuilder};
 use crate::util::{RequestHelper, TestApp};
 use cargo_registry::schema::versions;
 use cargo_registry::views::EncodableVersion;
 use diesel::{prelude::*, update};
+use http::StatusCode;
 
 #[derive(Deserialize)]
 struct VersionsList {
@@ -41,3 +42,42 @@ fn versions() {
         user.gh_login
     );
 }
+
+fn version_nums(anon: &impl RequestHelper, query: &str) -> Vec<String> {
+    let url = format!("/api/v1/crates/foo_yanked/versions{query}");
+    let json: VersionsList = anon.get(&url).good();
+    json.versions.into_iter().map(|v| v.num).collect()
+}
+
+#[test]
+fn versions_can_be_filtered_by_yanked_status() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+    app.db(|conn| {
+        CrateBuilder::new("foo_yanked", user.id)
+            .version("0.9.0")
+            .version(VersionBuilder::new("0.10.0").yanked(true))
+            .version("1.0.0-beta.1")
+            .version(VersionBuilder::new("1.0.0").yanked(true))
+            .expect_build(conn);
+    });
+
+    let all = ["1.0.0", "1.0.0-beta.1", "0.10.0", "0.9.0"];
+    assert_eq!(version_nums(&anon, ""), all);
+    assert_eq!(version_nums(&anon, "?include_yanked=true"), all);
+    assert_eq!(
+        version_nums(&anon, "?include_yanked=false"),
+        ["1.0.0-beta.1", "0.9.0"]
+    );
+    assert_eq!(
+        version_nums(&anon, "?include_yanked=only"),
+        ["1.0.0", "0.10.0"]
+    );
+
+    let response = anon.get::<()>("/api/v1/crates/foo_yanked/versions?include_yanked=maybe");
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "invalid value for ?include_yanked= (expected 'true', 'false', or 'only')" }] })
+    );
+}
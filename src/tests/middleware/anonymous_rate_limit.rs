@@ -0,0 +1,104 @@
+use crate::builders::CrateBuilder;
+use crate::util::{MockAnonymousUser, RequestHelper, TestApp};
+use cargo_registry::rate_limiter::LimitedAction;
+use http::{header, StatusCode};
+use std::time::Duration;
+
+const REVERSE_DEPENDENCIES: &str = "/api/v1/crates/foo/reverse_dependencies";
+
+fn limited_app() -> (TestApp, MockAnonymousUser) {
+    TestApp::init()
+        .with_config(|config| {
+            config
+                .anonymous_rate_limited_routes
+                .insert("/api/v1/crates/:crate_id/reverse_dependencies".into());
+        })
+        .with_rate_limit(LimitedAction::AnonymousRead, Duration::from_secs(60), 2)
+        .empty()
+}
+
+#[test]
+fn anonymous_requests_are_rate_limited() {
+    let (app, anon) = limited_app();
+    let user = app.db_new_user("foo");
+    app.db(|conn| {
+        CrateBuilder::new("foo", user.as_model().id).expect_build(conn);
+    });
+
+    for _ in 0..2 {
+        assert_eq!(
+            anon.get::<()>(REVERSE_DEPENDENCIES).status(),
+            StatusCode::OK
+        );
+    }
+
+    let response = anon.get::<()>(REVERSE_DEPENDENCIES);
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(response.headers().contains_key(header::RETRY_AFTER));
+
+    // Routes that are not configured are not limited
+    assert_eq!(
+        anon.get::<()>("/api/v1/crates/foo").status(),
+        StatusCode::OK
+    );
+}
+
+#[test]
+fn authenticated_requests_are_not_rate_limited() {
+    let (app, anon) = limited_app();
+    let user = app.db_new_user("foo");
+    let token = user.db_new_token("bar");
+    app.db(|conn| {
+        CrateBuilder::new("foo", user.as_model().id).expect_build(conn);
+    });
+
+    for _ in 0..2 {
+        assert_eq!(
+            anon.get::<()>(REVERSE_DEPENDENCIES).status(),
+            StatusCode::OK
+        );
+    }
+    assert_eq!(
+        anon.get::<()>(REVERSE_DEPENDENCIES).status(),
+        StatusCode::TOO_MANY_REQUESTS
+    );
+
+    // Requests from the same IP address are still allowed if they are authenticated
+    for _ in 0..3 {
+        assert_eq!(
+            user.get::<()>(REVERSE_DEPENDENCIES).status(),
+            StatusCode::OK
+        );
+        assert_eq!(
+            token.get::<()>(REVERSE_DEPENDENCIES).status(),
+            StatusCode::OK
+        );
+    }
+}
+
+#[test]
+fn requests_without_a_client_ip_share_a_bucket() {
+    let (app, anon) = limited_app();
+    let user = app.db_new_user("foo");
+    app.db(|conn| {
+        CrateBuilder::new("foo", user.as_model().id).expect_build(conn);
+    });
+
+    let request_without_ip = || {
+        let mut request = anon.get_request(REVERSE_DEPENDENCIES);
+        request.headers_mut().remove("x-real-ip");
+        anon.run::<()>(request)
+    };
+
+    for _ in 0..2 {
+        assert_eq!(request_without_ip().status(), StatusCode::OK);
+    }
+    let response = request_without_ip();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    // Clients with a known IP address have their own bucket
+    assert_eq!(
+        anon.get::<()>(REVERSE_DEPENDENCIES).status(),
+        StatusCode::OK
+    );
+}
@@ -0,0 +1,32 @@
+use crate::util::{RequestHelper, TestApp};
+use http::{header, StatusCode};
+
+#[test]
+fn deprecated_routes_include_deprecation_headers() {
+    let (_, _, user) = TestApp::init().with_user();
+
+    let response = user.get::<()>("/api/private/test/deprecated_me");
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let headers = response.headers();
+    assert_eq!(headers["deprecation"], "true");
+    assert_eq!(headers["sunset"], "Wed, 30 Jun 2027 00:00:00 GMT");
+    assert_eq!(
+        headers[header::LINK],
+        "</api/v1/me>; rel=\"successor-version\""
+    );
+
+    // The response itself is unchanged
+    let expected = user.get::<()>("/api/v1/me").into_json();
+    assert_eq!(response.into_json(), expected);
+}
+
+#[test]
+fn other_routes_do_not_include_deprecation_headers() {
+    let (_, _, user) = TestApp::init().with_user();
+
+    let response = user.get::<()>("/api/v1/me");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(!response.headers().contains_key("deprecation"));
+    assert!(!response.headers().contains_key("sunset"));
+}
@@ -1 +1,4 @@
+mod anonymous_rate_limit;
+mod deprecation;
 mod head;
+mod prefer;
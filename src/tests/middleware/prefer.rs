@@ -0,0 +1,87 @@
+use crate::builders::{CrateBuilder, VersionBuilder};
+use crate::util::{MockRequestExt, RequestHelper, TestApp};
+use http::{Method, StatusCode};
+
+#[test]
+fn return_minimal_omits_the_body() {
+    let (app, _, user) = TestApp::init().with_user();
+    app.db(|conn| {
+        CrateBuilder::new("foo_prefer", user.as_model().id).expect_build(conn);
+    });
+
+    let mut req = user.request_builder(Method::PUT, "/api/v1/crates/foo_prefer/follow");
+    req.header("Prefer", "return=minimal");
+    let res = user.run::<()>(req);
+    assert_eq!(res.status(), StatusCode::NO_CONTENT);
+    assert_eq!(res.headers()["preference-applied"], "return=minimal");
+    assert!(res.headers().get("content-type").is_none());
+    assert_eq!(res.into_text(), "");
+}
+
+#[test]
+fn return_representation_keeps_the_body() {
+    let (app, _, user) = TestApp::init().with_user();
+    app.db(|conn| {
+        CrateBuilder::new("foo_prefer", user.as_model().id).expect_build(conn);
+    });
+
+    let mut req = user.request_builder(Method::PUT, "/api/v1/crates/foo_prefer/follow");
+    req.header("Prefer", "respond-async, return=representation");
+    let res = user.run::<()>(req);
+    assert_eq!(res.status(), StatusCode::OK);
+    assert!(res.headers().get("preference-applied").is_none());
+    assert_eq!(res.into_json(), json!({ "ok": true }));
+
+    let req = user.request_builder(Method::PUT, "/api/v1/crates/foo_prefer/follow");
+    let res = user.run::<()>(req);
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.into_json(), json!({ "ok": true }));
+}
+
+#[test]
+fn return_minimal_is_ignored_for_reads() {
+    let (_, anon) = TestApp::init().empty();
+
+    let mut req = anon.request_builder(Method::GET, "/api/v1/summary");
+    req.header("Prefer", "return=minimal");
+    let res = anon.run::<()>(req);
+    assert_eq!(res.status(), StatusCode::OK);
+    assert!(res.headers().get("preference-applied").is_none());
+    assert!(res.into_json()["num_crates"].is_number());
+}
+
+#[test]
+fn return_minimal_keeps_error_responses() {
+    let (_, anon) = TestApp::init().empty();
+
+    let mut req = anon.request_builder(Method::PUT, "/api/v1/does-not-exist");
+    req.header("Prefer", "return=minimal; foo=bar");
+    let res = anon.run::<()>(req);
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    assert!(res.headers().get("preference-applied").is_none());
+    assert_eq!(
+        res.into_json(),
+        json!({ "errors": [{ "detail": "Not Found" }] })
+    );
+}
+
+#[test]
+fn return_minimal_keeps_cargo_error_responses() {
+    let (app, _, _, token) = TestApp::init().with_token();
+    let other_user = app.db_new_user("other_user");
+    app.db(|conn| {
+        CrateBuilder::new("foo_not_owned", other_user.as_model().id)
+            .version(VersionBuilder::new("1.0.0"))
+            .expect_build(conn);
+    });
+
+    let mut req = token.request_builder(Method::DELETE, "/api/v1/crates/foo_not_owned/1.0.0/yank");
+    req.header("Prefer", "return=minimal");
+    let res = token.run::<()>(req);
+    assert_eq!(res.status(), StatusCode::OK);
+    assert!(res.headers().get("preference-applied").is_none());
+    assert_eq!(
+        res.into_json(),
+        json!({ "errors": [{ "detail": "must already be an owner to yank or unyank" }] })
+    );
+}
@@ -2,7 +2,9 @@ use crate::{
     add_team_to_crate,
     builders::{CrateBuilder, PublishBuilder},
     new_team,
-    util::{MockAnonymousUser, MockCookieUser, MockTokenUser, RequestHelper, Response},
+    util::{
+        MockAnonymousUser, MockCookieUser, MockRequestExt, MockTokenUser, RequestHelper, Response,
+    },
     TestApp,
 };
 use cargo_registry::{
@@ -111,6 +113,18 @@ impl MockCookieUser {
     }
 }
 
+impl MockTokenUser {
+    /// As the owner of the token, transfer the named crate to the user with the given login.
+    fn try_transfer_crate<T>(&self, krate_name: &str, login: &str) -> Response<T> {
+        let url = format!("/api/v1/crates/{krate_name}/transfer");
+        let body = json!({ "owner": login });
+
+        let mut request = self.post_request(&url);
+        request.with_body(body.to_string().as_bytes());
+        self.run(request)
+    }
+}
+
 impl MockAnonymousUser {
     fn accept_ownership_invitation_by_token(&self, token: &str) {
         #[derive(Deserialize)]
@@ -530,6 +544,43 @@ fn check_ownership_two_crates() {
     assert_eq!(json.crates[0].name, krate_owned_by_team.name);
 }
 
+#[test]
+fn owners_for_many_groups_owners_by_crate() {
+    use cargo_registry::models::Owner;
+
+    let (app, _, user) = TestApp::init().with_user();
+    let user = user.as_model();
+    let user2 = app.db_new_user("user_bar");
+    let user2 = user2.as_model();
+
+    app.db(|conn| {
+        let team = new_team("team_foo").create_or_update(conn).unwrap();
+        let krate_user_only = CrateBuilder::new("user_only", user.id).expect_build(conn);
+        let krate_mixed = CrateBuilder::new("mixed", user2.id).expect_build(conn);
+        add_team_to_crate(&team, &krate_mixed, user2, conn).unwrap();
+        let krate_unrequested = CrateBuilder::new("unrequested", user.id).expect_build(conn);
+
+        let owners = Crate::owners_for_many(&[krate_user_only.id, krate_mixed.id], conn).unwrap();
+
+        let owner_ids = |crate_id| {
+            owners[&crate_id]
+                .iter()
+                .map(|owner| match owner {
+                    Owner::User(user) => ("user", user.id),
+                    Owner::Team(team) => ("team", team.id),
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(owners.len(), 2);
+        assert!(!owners.contains_key(&krate_unrequested.id));
+        assert_eq!(owner_ids(krate_user_only.id), [("user", user.id)]);
+        assert_eq!(
+            owner_ids(krate_mixed.id),
+            [("user", user2.id), ("team", team.id)]
+        );
+    });
+}
+
 /// Given a crate owned by both a team and a user, check that the
 /// JSON returned by the /owner_team route and /owner_user route
 /// contains the correct kind of owner
@@ -810,6 +861,38 @@ fn test_decline_expired_invitation() {
     assert_eq!(json.users.len(), 1);
 }
 
+#[test]
+fn expired_invitations_are_purged() {
+    use cargo_registry::schema::crate_owner_invitations;
+    use cargo_registry::worker;
+
+    let (app, anon, owner, owner_token) = TestApp::full().with_token();
+    let owner = owner.as_model();
+    let invited_user = app.db_new_user("demo_user");
+    let expired_crate = app.db(|conn| CrateBuilder::new("expired", owner.id).expect_build(conn));
+    let pending_crate = app.db(|conn| CrateBuilder::new("pending", owner.id).expect_build(conn));
+
+    owner_token.add_user_owner("expired", "demo_user");
+    owner_token.add_user_owner("pending", "demo_user");
+    expire_invitation(&app, expired_crate.id);
+
+    app.db(|conn| worker::purge_expired_invitations().enqueue(conn).unwrap());
+    app.run_pending_background_jobs();
+
+    let crate_ids: Vec<i32> = app.db(|conn| {
+        crate_owner_invitations::table
+            .select(crate_owner_invitations::crate_id)
+            .load(conn)
+            .unwrap()
+    });
+    assert_eq!(crate_ids, vec![pending_crate.id]);
+
+    // The invitation that didn't expire yet can still be accepted
+    invited_user.accept_ownership_invitation(&pending_crate.name, pending_crate.id);
+    let json = anon.show_crate_owners("pending");
+    assert_eq!(json.users.len(), 2);
+}
+
 #[test]
 fn test_accept_expired_invitation_by_mail() {
     let (app, anon, owner, owner_token) = TestApp::init().with_token();
@@ -1235,3 +1318,119 @@ fn invitation_list_other_crates() {
         owner.get_with_query::<()>("/api/private/crate_owner_invitations", "crate_name=crate_2");
     assert_eq!(resp.status(), StatusCode::FORBIDDEN);
 }
+
+#[test]
+fn transfer_crate() {
+    let (app, anon, owner, owner_token) = TestApp::full().with_token();
+    let new_owner = app.db_new_user("new_owner");
+    let krate =
+        app.db(|conn| CrateBuilder::new("transferred", owner.as_model().id).expect_build(conn));
+
+    // A pending invitation is superseded by the transfer
+    owner_token.add_user_owner("transferred", "new_owner");
+
+    let response = owner_token.try_transfer_crate::<()>("transferred", "new_owner");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.into_json(),
+        json!({ "ok": true, "msg": "user new_owner has been invited to take over crate transferred" })
+    );
+
+    // Nothing changes until the new owner accepts the transfer
+    let json = anon.show_crate_owners("transferred");
+    assert_eq!(json.users.len(), 1);
+    assert_eq!(json.users[0].login, "foo");
+    assert_eq!(
+        new_owner.list_invitations().crate_owner_invitations.len(),
+        1
+    );
+
+    new_owner.accept_ownership_invitation("transferred", krate.id);
+
+    let json = anon.show_crate_owners("transferred");
+    let logins = json
+        .users
+        .iter()
+        .map(|owner| owner.login.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(logins, ["new_owner"]);
+
+    // The previous owner can't manage the crate anymore
+    let response = owner_token.try_transfer_crate::<()>("transferred", "new_owner");
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "only owners have permission to transfer crates" }] })
+    );
+
+    app.run_pending_background_jobs();
+    let emails = app.as_inner().emails.mails_in_memory().unwrap();
+    let invite_emails = emails
+        .iter()
+        .filter(|email| email.subject == "Crate ownership transfer")
+        .collect::<Vec<_>>();
+    assert_eq!(invite_emails.len(), 1);
+    let transfer_emails = emails
+        .iter()
+        .filter(|email| email.subject == "Crate ownership transferred")
+        .collect::<Vec<_>>();
+    assert_eq!(transfer_emails.len(), 2);
+    for email in transfer_emails {
+        assert!(email.body.starts_with(&format!(
+            "foo has transferred their ownership of the crate {}\nto new_owner.",
+            krate.name
+        )));
+    }
+}
+
+#[test]
+fn declined_transfer_keeps_the_owner() {
+    let (app, anon, owner, owner_token) = TestApp::init().with_token();
+    let new_owner = app.db_new_user("new_owner");
+    let krate =
+        app.db(|conn| CrateBuilder::new("not_transferred", owner.as_model().id).expect_build(conn));
+
+    let response = owner_token.try_transfer_crate::<()>("not_transferred", "new_owner");
+    assert_eq!(response.status(), StatusCode::OK);
+    new_owner.decline_ownership_invitation("not_transferred", krate.id);
+
+    let json = anon.show_crate_owners("not_transferred");
+    assert_eq!(json.users.len(), 1);
+    assert_eq!(json.users[0].login, "foo");
+}
+
+#[test]
+fn transfer_crate_to_unknown_user() {
+    let (app, anon, owner, owner_token) = TestApp::init().with_token();
+    app.db(|conn| CrateBuilder::new("not_transferred", owner.as_model().id).expect_build(conn));
+
+    let response = owner_token.try_transfer_crate::<()>("not_transferred", "unknown");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "could not find user with login `unknown`" }] })
+    );
+
+    let json = anon.show_crate_owners("not_transferred");
+    assert_eq!(json.users.len(), 1);
+    assert_eq!(json.users[0].login, "foo");
+}
+
+#[test]
+fn transfer_crate_as_non_owner() {
+    let (app, anon, _, token) = TestApp::init().with_token();
+    let other_user = app.db_new_user("other_user");
+    app.db(|conn| {
+        CrateBuilder::new("not_transferred", other_user.as_model().id).expect_build(conn)
+    });
+
+    let response = token.try_transfer_crate::<()>("not_transferred", "foo");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "only owners have permission to transfer crates" }] })
+    );
+
+    let json = anon.show_crate_owners("not_transferred");
+    assert_eq!(json.users.len(), 1);
+    assert_eq!(json.users[0].login, "other_user");
+}
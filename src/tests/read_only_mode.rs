@@ -1,4 +1,5 @@
-use crate::builders::CrateBuilder;
+use crate::builders::{CrateBuilder, PublishBuilder};
+use crate::util::MockCookieUser;
 use crate::{RequestHelper, TestApp};
 
 use diesel::prelude::*;
@@ -58,6 +59,95 @@ fn can_download_crate_in_read_only_mode() {
     })
 }
 
+#[test]
+fn admin_can_toggle_read_only_mode() {
+    let (app, anon, user, token) = TestApp::full().with_token();
+    user.make_admin();
+
+    let crate_to_publish = PublishBuilder::new("foo_admin_read_only").version("1.0.0");
+    token.publish_crate(crate_to_publish).good();
+
+    let json = set_admin_read_only_mode(&user, true);
+    assert_eq!(json, json!({ "read_only": true }));
+    assert!(app.db(stored_read_only_mode));
+
+    let response = token.delete::<()>("/api/v1/crates/foo_admin_read_only/1.0.0/yank");
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+    let crate_to_publish = PublishBuilder::new("foo_admin_read_only").version("1.1.0");
+    let response = token.put::<()>("/api/v1/crates/new", &crate_to_publish.body());
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+    let response = anon.get::<()>("/api/v1/crates/foo_admin_read_only");
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = anon.get::<()>("/api/v1/site_metadata");
+    assert_eq!(response.into_json()["read_only"], json!(true));
+
+    let json = set_admin_read_only_mode(&user, false);
+    assert_eq!(json, json!({ "read_only": false }));
+    assert!(!app.db(stored_read_only_mode));
+
+    let response = token.delete::<()>("/api/v1/crates/foo_admin_read_only/1.0.0/yank");
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[test]
+fn admin_read_only_mode_is_shared_by_all_instances() {
+    use cargo_registry::schema::metadata;
+
+    let (app, anon, user, token) = TestApp::init().with_token();
+    app.db(|conn| {
+        CrateBuilder::new("foo_shared_read_only", user.as_model().id)
+            .version("1.0.0")
+            .expect_build(conn);
+
+        // Simulates another instance of the application enabling read-only mode
+        diesel::update(metadata::table)
+            .set(metadata::read_only.eq(true))
+            .execute(conn)
+            .unwrap();
+    });
+
+    let response = token.delete::<()>("/api/v1/crates/foo_shared_read_only/1.0.0/yank");
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+    let response = anon.get::<()>("/api/v1/site_metadata");
+    assert_eq!(response.into_json()["read_only"], json!(true));
+}
+
+#[test]
+fn only_admins_can_toggle_read_only_mode() {
+    let (_, _, user, token) = TestApp::init().with_token();
+
+    let body = json!({ "read_only": true }).to_string();
+    let response = user.put::<()>("/api/v1/admin/read_only", body.as_bytes());
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    // API tokens can't be used for admin actions
+    let response = token.put::<()>("/api/v1/admin/read_only", body.as_bytes());
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = token.delete::<()>("/api/v1/crates/foo/1.0.0/yank");
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+fn set_admin_read_only_mode(admin: &MockCookieUser, read_only: bool) -> serde_json::Value {
+    let body = json!({ "read_only": read_only }).to_string();
+    let response = admin.put::<()>("/api/v1/admin/read_only", body.as_bytes());
+    assert_eq!(response.status(), StatusCode::OK);
+    response.into_json()
+}
+
+fn stored_read_only_mode(conn: &mut PgConnection) -> bool {
+    use cargo_registry::schema::metadata;
+
+    metadata::table
+        .select(metadata::read_only)
+        .first(conn)
+        .unwrap()
+}
+
 fn set_read_only(conn: &mut PgConnection) -> QueryResult<()> {
     diesel::sql_query("SET TRANSACTION READ ONLY").execute(conn)?;
     diesel::sql_query("SAVEPOINT test_post_readonly").execute(conn)?;
@@ -0,0 +1,54 @@
+use crate::new_category;
+use crate::util::{RequestHelper, TestApp};
+use cargo_registry::schema::categories;
+use diesel::prelude::*;
+use serde_json::Value;
+
+#[test]
+fn assignable_categories_are_nested() {
+    let (app, anon) = TestApp::init().empty();
+
+    app.db(|conn| {
+        for (slug, crates_cnt) in [
+            ("foo", 1),
+            ("foo::bar", 2),
+            ("foo::bar::baz", 3),
+            ("foo-qux", 4),
+            ("qux", 0),
+        ] {
+            new_category(slug, slug, "").create_or_update(conn).unwrap();
+            diesel::update(categories::table.filter(categories::slug.eq(slug)))
+                .set(categories::crates_cnt.eq(crates_cnt))
+                .execute(conn)
+                .unwrap();
+        }
+    });
+
+    let json: Value = anon.get("/api/v1/categories/assignable").good();
+    let categories = json["categories"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|category| {
+            (
+                category["slug"].as_str().unwrap(),
+                category["depth"].as_u64().unwrap(),
+                category["crates_cnt"].as_i64().unwrap(),
+                category["subtree_crates_cnt"].as_i64().unwrap(),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    // Subcategories come right after their parent, even though `foo-qux` sorts before `foo::bar`
+    assert_eq!(
+        categories,
+        vec![
+            ("foo", 0, 1, 6),
+            ("foo::bar", 1, 2, 5),
+            ("foo::bar::baz", 2, 3, 3),
+            ("foo-qux", 0, 4, 4),
+            ("qux", 0, 0, 0),
+        ]
+    );
+    assert_eq!(json["categories"][2]["category"], "baz");
+}
@@ -0,0 +1,105 @@
+use crate::new_category;
+use crate::util::{RequestHelper, TestApp};
+use http::{header, StatusCode};
+use serde_json::Value;
+
+#[test]
+fn export_streams_all_categories() {
+    let (app, anon) = TestApp::init().empty();
+
+    let ids = app.db(|conn| {
+        [
+            ("foo", "Foo crates"),
+            ("foo::bar", "Bar crates"),
+            ("foo::bar::baz", "Baz crates"),
+            ("qux", "Qux crates"),
+        ]
+        .into_iter()
+        .map(|(name, description)| {
+            new_category(name, name, description)
+                .create_or_update(conn)
+                .unwrap()
+                .id
+        })
+        .collect::<Vec<_>>()
+    });
+
+    let response = anon.get::<()>("/api/v1/categories/export");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()[header::CONTENT_TYPE],
+        "application/x-ndjson"
+    );
+
+    // The categories are exported in the order in which they were created
+    assert_eq!(
+        parse_lines(&response.into_text()),
+        vec![
+            exported(ids[0], "foo", "Foo crates"),
+            exported(ids[1], "foo::bar", "Bar crates"),
+            exported(ids[2], "foo::bar::baz", "Baz crates"),
+            exported(ids[3], "qux", "Qux crates"),
+        ]
+    );
+
+    let response = anon.get_with_query::<()>("/api/v1/categories/export", "format=tree");
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let with_subcategories = |mut category: Value, subcategories: Vec<Value>| {
+        category["subcategories"] = subcategories.into();
+        category
+    };
+    assert_eq!(
+        parse_lines(&response.into_text()),
+        vec![
+            with_subcategories(
+                exported(ids[0], "foo", "Foo crates"),
+                vec![with_subcategories(
+                    exported(ids[1], "foo::bar", "Bar crates"),
+                    vec![with_subcategories(
+                        exported(ids[2], "foo::bar::baz", "Baz crates"),
+                        vec![]
+                    )]
+                )]
+            ),
+            with_subcategories(exported(ids[3], "qux", "Qux crates"), vec![]),
+        ]
+    );
+}
+
+#[test]
+fn export_of_no_categories() {
+    let (_, anon) = TestApp::init().empty();
+
+    let response = anon.get::<()>("/api/v1/categories/export");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.into_text(), "");
+}
+
+#[test]
+fn export_rejects_unknown_formats() {
+    let (_, anon) = TestApp::init().empty();
+
+    let response = anon.get_with_query::<()>("/api/v1/categories/export", "format=xml");
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "`format` must be `flat` or `tree`" }] })
+    );
+}
+
+fn exported(id: i32, name: &str, description: &str) -> Value {
+    json!({
+        "id": id,
+        "category": name,
+        "slug": name,
+        "description": description,
+        "crates_cnt": 0,
+    })
+}
+
+fn parse_lines(text: &str) -> Vec<Value> {
+    text.lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
@@ -1,6 +1,7 @@
 use crate::builders::CrateBuilder;
 use crate::new_category;
-use crate::util::{MockAnonymousUser, RequestHelper, TestApp};
+use crate::util::{MockAnonymousUser, RequestHelper, TestApp, TestDatabase};
+use cargo_registry::models::category::MAX_CATEGORIES;
 use cargo_registry::models::Category;
 use insta::assert_yaml_snapshot;
 use serde_json::Value;
@@ -50,39 +51,41 @@ fn update_crate() {
     });
 
     // Updating with no categories has no effect
-    app.db(|conn| Category::update_crate(conn, &krate, &[]).unwrap());
+    app.db(|conn| Category::update_crate(conn, &krate, &[], MAX_CATEGORIES).unwrap());
     assert_eq!(count(&anon, "cat1"), 0);
     assert_eq!(count(&anon, "category-2"), 0);
 
     // Happy path adding one category
-    app.db(|conn| Category::update_crate(conn, &krate, &["cat1"]).unwrap());
+    app.db(|conn| Category::update_crate(conn, &krate, &["cat1"], MAX_CATEGORIES).unwrap());
     assert_eq!(count(&anon, "cat1"), 1);
     assert_eq!(count(&anon, "category-2"), 0);
 
     // Replacing one category with another
-    app.db(|conn| Category::update_crate(conn, &krate, &["category-2"]).unwrap());
+    app.db(|conn| Category::update_crate(conn, &krate, &["category-2"], MAX_CATEGORIES).unwrap());
     assert_eq!(count(&anon, "cat1"), 0);
     assert_eq!(count(&anon, "category-2"), 1);
 
     // Removing one category
-    app.db(|conn| Category::update_crate(conn, &krate, &[]).unwrap());
+    app.db(|conn| Category::update_crate(conn, &krate, &[], MAX_CATEGORIES).unwrap());
     assert_eq!(count(&anon, "cat1"), 0);
     assert_eq!(count(&anon, "category-2"), 0);
 
     // Adding 2 categories
-    app.db(|conn| Category::update_crate(conn, &krate, &["cat1", "category-2"]).unwrap());
+    app.db(|conn| {
+        Category::update_crate(conn, &krate, &["cat1", "category-2"], MAX_CATEGORIES).unwrap()
+    });
     assert_eq!(count(&anon, "cat1"), 1);
     assert_eq!(count(&anon, "category-2"), 1);
 
     // Removing all categories
-    app.db(|conn| Category::update_crate(conn, &krate, &[]).unwrap());
+    app.db(|conn| Category::update_crate(conn, &krate, &[], MAX_CATEGORIES).unwrap());
     assert_eq!(count(&anon, "cat1"), 0);
     assert_eq!(count(&anon, "category-2"), 0);
 
     // Attempting to add one valid category and one invalid category
     app.db(|conn| {
         let invalid_categories =
-            Category::update_crate(conn, &krate, &["cat1", "catnope"]).unwrap();
+            Category::update_crate(conn, &krate, &["cat1", "catnope"], MAX_CATEGORIES).unwrap();
         assert_eq!(invalid_categories, vec!["catnope"]);
     });
     assert_eq!(count(&anon, "cat1"), 1);
@@ -95,17 +98,265 @@ fn update_crate() {
     assert_eq!(json.meta.total, 2);
 
     // Attempting to add a category by display text; must use slug
-    app.db(|conn| Category::update_crate(conn, &krate, &["Category 2"]).unwrap());
+    app.db(|conn| Category::update_crate(conn, &krate, &["Category 2"], MAX_CATEGORIES).unwrap());
     assert_eq!(count(&anon, "cat1"), 0);
     assert_eq!(count(&anon, "category-2"), 0);
 
     // Add a category and its subcategory
     app.db(|conn| {
         assert_ok!(new_category("cat1::bar", "cat1::bar", "bar crates").create_or_update(conn));
-        Category::update_crate(conn, &krate, &["cat1", "cat1::bar"]).unwrap();
+        Category::update_crate(conn, &krate, &["cat1", "cat1::bar"], MAX_CATEGORIES).unwrap();
     });
 
     assert_eq!(count(&anon, "cat1"), 1);
     assert_eq!(count(&anon, "cat1::bar"), 1);
     assert_eq!(count(&anon, "category-2"), 0);
 }
+
+#[test]
+fn add_and_remove_categories() {
+    fn count(anon: &MockAnonymousUser, category: &str) -> usize {
+        let json = anon.show_category(category);
+        json.category.crates_cnt as usize
+    }
+
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+
+    let krate = app.db(|conn| {
+        for slug in ["cat1", "cat2", "cat3"] {
+            assert_ok!(new_category(slug, slug, "Category crates").create_or_update(conn));
+        }
+
+        CrateBuilder::new("foo_crate", user.id).expect_build(conn)
+    });
+    app.db(|conn| Category::update_crate(conn, &krate, &["cat1"], MAX_CATEGORIES).unwrap());
+
+    // Adding keeps the existing categories, and adding them again has no effect
+    app.db(|conn| {
+        let invalid_categories =
+            Category::add_categories(conn, &krate, &["cat1", "cat2", "catnope"], MAX_CATEGORIES)
+                .unwrap();
+        assert_eq!(invalid_categories, vec!["catnope"]);
+    });
+    assert_eq!(count(&anon, "cat1"), 1);
+    assert_eq!(count(&anon, "cat2"), 1);
+    assert_eq!(count(&anon, "cat3"), 0);
+
+    app.db(|conn| Category::add_categories(conn, &krate, &["cat3"], MAX_CATEGORIES).unwrap());
+    assert_eq!(count(&anon, "cat1"), 1);
+    assert_eq!(count(&anon, "cat2"), 1);
+    assert_eq!(count(&anon, "cat3"), 1);
+
+    // Removing only removes the given categories
+    app.db(|conn| {
+        let invalid_categories =
+            Category::remove_categories(conn, &krate, &["cat1", "cat3", "catnope"]).unwrap();
+        assert_eq!(invalid_categories, vec!["catnope"]);
+    });
+    assert_eq!(count(&anon, "cat1"), 0);
+    assert_eq!(count(&anon, "cat2"), 1);
+    assert_eq!(count(&anon, "cat3"), 0);
+
+    // Removing categories that the crate isn't in has no effect
+    app.db(|conn| Category::remove_categories(conn, &krate, &["cat1"]).unwrap());
+    assert_eq!(count(&anon, "cat1"), 0);
+    assert_eq!(count(&anon, "cat2"), 1);
+}
+
+#[test]
+fn category_limit_counts_the_resulting_categories() {
+    fn count(anon: &MockAnonymousUser, category: &str) -> usize {
+        let json = anon.show_category(category);
+        json.category.crates_cnt as usize
+    }
+
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+
+    let krate = app.db(|conn| {
+        for slug in ["cat1", "cat2", "cat3"] {
+            assert_ok!(new_category(slug, slug, "Category crates").create_or_update(conn));
+        }
+
+        CrateBuilder::new("foo_crate", user.id).expect_build(conn)
+    });
+
+    // Crates can be in exactly as many categories as the limit, and invalid slugs don't count
+    app.db(|conn| Category::update_crate(conn, &krate, &["cat1", "cat2", "catnope"], 2).unwrap());
+    assert_eq!(count(&anon, "cat1"), 1);
+    assert_eq!(count(&anon, "cat2"), 1);
+
+    // Going over the limit fails without changing the categories
+    app.db(|conn| {
+        let error = Category::update_crate(conn, &krate, &["cat1", "cat2", "cat3"], 2).unwrap_err();
+        assert_eq!(error.to_string(), "crates can be in at most 2 categories");
+
+        let error = Category::add_categories(conn, &krate, &["cat3"], 2).unwrap_err();
+        assert_eq!(error.to_string(), "crates can be in at most 2 categories");
+    });
+    assert_eq!(count(&anon, "cat1"), 1);
+    assert_eq!(count(&anon, "cat2"), 1);
+    assert_eq!(count(&anon, "cat3"), 0);
+
+    // Adding categories that the crate is already in doesn't count twice
+    app.db(|conn| Category::add_categories(conn, &krate, &["cat1"], 2).unwrap());
+    app.db(|conn| Category::add_categories(conn, &krate, &["cat3"], 3).unwrap());
+    assert_eq!(count(&anon, "cat1"), 1);
+    assert_eq!(count(&anon, "cat3"), 1);
+}
+
+#[test]
+fn concurrent_category_additions_stay_within_the_limit() {
+    use cargo_registry::schema::{crates, crates_categories};
+    use diesel::prelude::*;
+
+    // The concurrent additions need connections of their own, which the single connection of
+    // the test database pool can't provide
+    let (app, _, user) = TestApp::init()
+        .with_database(TestDatabase::SlowRealPool { replica: false })
+        .with_config(|config| config.db.primary.pool_size = 5)
+        .with_user();
+    let user = user.as_model();
+
+    let krate = app.db(|conn| {
+        for slug in ["cat1", "cat2"] {
+            assert_ok!(new_category(slug, slug, "Category crates").create_or_update(conn));
+        }
+
+        CrateBuilder::new("foo_crate", user.id).expect_build(conn)
+    });
+
+    // Both additions are started while the crate row is locked by the test, so that they are
+    // guaranteed to overlap instead of one finishing before the other one starts
+    let results = app.db(|conn| {
+        conn.transaction(|conn| {
+            crates::table
+                .find(krate.id)
+                .select(crates::id)
+                .for_update()
+                .execute(conn)?;
+
+            let handles = ["cat1", "cat2"].map(|slug| {
+                let pool = app.as_inner().primary_database.clone();
+                let krate = krate.clone();
+                std::thread::spawn(move || {
+                    let conn = &mut *pool.get().unwrap();
+                    Category::add_categories(conn, &krate, &[slug], 1).is_ok()
+                })
+            });
+            std::thread::sleep(std::time::Duration::from_millis(500));
+
+            Ok::<_, diesel::result::Error>(handles)
+        })
+        .unwrap()
+    });
+    let mut results = results.map(|handle| handle.join().unwrap());
+    results.sort();
+    assert_eq!(results, [false, true]);
+
+    let num_categories: i64 = app.db(|conn| {
+        crates_categories::table
+            .filter(crates_categories::crate_id.eq(krate.id))
+            .count()
+            .get_result(conn)
+            .unwrap()
+    });
+    assert_eq!(num_categories, 1);
+}
+
+#[test]
+fn show_with_localized_descriptions() {
+    use cargo_registry::schema::category_descriptions;
+    use diesel::prelude::*;
+
+    let (app, anon) = TestApp::init().empty();
+
+    app.db(|conn| {
+        new_category("Foo Bar", "foo-bar", "Foo Bar crates")
+            .create_or_update(conn)
+            .unwrap();
+        let subcategory = new_category("Foo Bar::Baz", "foo-bar::baz", "Baz crates")
+            .create_or_update(conn)
+            .unwrap();
+
+        diesel::insert_into(category_descriptions::table)
+            .values((
+                category_descriptions::category_id.eq(subcategory.id),
+                category_descriptions::locale.eq("pt-br"),
+                category_descriptions::description.eq("Crates de Baz"),
+            ))
+            .execute(conn)
+            .unwrap();
+    });
+
+    let json: Value = anon.get("/api/v1/categories/foo-bar?locale=pt-BR").good();
+    assert_eq!(json["category"]["description"], "Foo Bar crates");
+    assert_eq!(
+        json["category"]["subcategories"][0]["description"],
+        "Crates de Baz"
+    );
+
+    let json: Value = anon
+        .get("/api/v1/categories/foo-bar::baz?locale=pt-BR")
+        .good();
+    assert_eq!(json["category"]["description"], "Crates de Baz");
+    assert_eq!(
+        json["category"]["parent_categories"][0]["description"],
+        "Foo Bar crates"
+    );
+}
+
+#[test]
+fn localized_responses_vary_by_language() {
+    let (app, anon, user) = TestApp::init().with_user();
+    app.db(|conn| {
+        new_category("Foo Bar", "foo-bar", "Foo Bar crates")
+            .create_or_update(conn)
+            .unwrap();
+        CrateBuilder::new("foo_crate", user.as_model().id).expect_build(conn);
+    });
+
+    for url in [
+        "/api/v1/categories",
+        "/api/v1/categories/search?q=foo",
+        "/api/v1/categories/assignable",
+        "/api/v1/categories/foo-bar",
+        "/api/v1/category_slugs",
+        "/api/v1/crates/foo_crate/categories",
+    ] {
+        let response = anon.get::<()>(url);
+        assert_eq!(response.status(), http::StatusCode::OK, "{url}");
+        assert_eq!(
+            response.headers()[http::header::VARY],
+            "Accept-Language",
+            "{url}"
+        );
+    }
+}
+
+#[test]
+fn show_includes_parent_categories() {
+    let (app, anon) = TestApp::init().empty();
+
+    app.db(|conn| {
+        new_category("Foo", "foo", "")
+            .create_or_update(conn)
+            .unwrap();
+        new_category("Foo::Bar", "foo::bar", "")
+            .create_or_update(conn)
+            .unwrap();
+        new_category("Foo::Bar::Baz", "foo::bar::baz", "")
+            .create_or_update(conn)
+            .unwrap();
+    });
+
+    let json: Value = anon.get("/api/v1/categories/foo::bar::baz").good();
+    let parents = json["category"]["parent_categories"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|parent| parent["slug"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(parents, ["foo", "foo::bar"]);
+}
@@ -1,7 +1,11 @@
 use crate::new_category;
-use crate::util::{RequestHelper, TestApp};
+use crate::util::{MockRequestExt, RequestHelper, TestApp};
+use cargo_registry::schema::categories;
+use chrono::{Duration, Utc};
+use diesel::prelude::*;
+use http::StatusCode;
 use insta::assert_yaml_snapshot;
-use serde_json::Value;
+use serde_json::{json, Value};
 
 #[test]
 fn index() {
@@ -27,3 +31,169 @@ fn index() {
         ".categories[].created_at" => "[datetime]",
     });
 }
+
+#[test]
+fn index_includes_crates_of_subcategories_separately() {
+    let (app, anon) = TestApp::init().empty();
+
+    app.db(|conn| {
+        new_category("foo", "foo", "Foo crates")
+            .create_or_update(conn)
+            .unwrap();
+        new_category("foo::bar", "foo::bar", "Bar crates")
+            .create_or_update(conn)
+            .unwrap();
+
+        for (slug, crates_cnt) in [("foo", 42), ("foo::bar", 78)] {
+            diesel::update(categories::table.filter(categories::slug.eq(slug)))
+                .set(categories::crates_cnt.eq(crates_cnt))
+                .execute(conn)
+                .unwrap();
+        }
+    });
+
+    let json: Value = anon.get("/api/v1/categories").good();
+    assert_eq!(json["categories"][0]["crates_cnt"], 42);
+    assert_eq!(json["categories"][0]["subtree_crates_cnt"], 120);
+}
+
+#[test]
+fn index_with_localized_descriptions() {
+    use cargo_registry::schema::category_descriptions;
+
+    let (app, anon) = TestApp::init().empty();
+
+    app.db(|conn| {
+        let category = new_category("foo", "foo", "Foo crates")
+            .create_or_update(conn)
+            .unwrap();
+        new_category("qux", "qux", "Qux crates")
+            .create_or_update(conn)
+            .unwrap();
+
+        diesel::insert_into(category_descriptions::table)
+            .values((
+                category_descriptions::category_id.eq(category.id),
+                category_descriptions::locale.eq("de"),
+                category_descriptions::description.eq("Foo-Crates"),
+            ))
+            .execute(conn)
+            .unwrap();
+    });
+
+    let descriptions = |json: Value| {
+        json["categories"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|category| category["description"].as_str().unwrap().to_string())
+            .collect::<Vec<_>>()
+    };
+
+    // Without a requested locale, the English descriptions are used
+    let json: Value = anon.get("/api/v1/categories").good();
+    assert_eq!(descriptions(json), ["Foo crates", "Qux crates"]);
+
+    // Translations are used where they exist, with the English description as a fallback
+    let mut request = anon.get_request("/api/v1/categories");
+    request.header("Accept-Language", "de-CH, fr;q=0.5");
+    let json = anon.run::<Value>(request).good();
+    assert_eq!(descriptions(json), ["Foo-Crates", "Qux crates"]);
+
+    // The query parameter takes precedence over the header
+    let mut request = anon.get_request("/api/v1/categories?locale=fr");
+    request.header("Accept-Language", "de");
+    let json = anon.run::<Value>(request).good();
+    assert_eq!(descriptions(json), ["Foo crates", "Qux crates"]);
+}
+
+/// Creates top-level categories `b` to `e`, which come in that order for every sort.
+fn create_sorted_categories(app: &TestApp) {
+    app.db(|conn| {
+        for (crates_cnt, age_in_days, name) in [(4, 1, "b"), (3, 2, "c"), (2, 3, "d"), (1, 4, "e")]
+        {
+            create_category(conn, name, crates_cnt, age_in_days);
+        }
+    });
+}
+
+fn create_category(conn: &mut PgConnection, name: &str, crates_cnt: i32, age_in_days: i64) {
+    let category = new_category(name, name, "").create_or_update(conn).unwrap();
+    let created_at = (Utc::now() - Duration::days(age_in_days)).naive_utc();
+    diesel::update(&category)
+        .set((
+            categories::crates_cnt.eq(crates_cnt),
+            categories::created_at.eq(created_at),
+        ))
+        .execute(conn)
+        .unwrap();
+}
+
+fn slugs(json: &Value) -> Vec<&str> {
+    json["categories"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|category| category["slug"].as_str().unwrap())
+        .collect()
+}
+
+#[test]
+fn index_by_cursor() {
+    for sort in ["alpha", "crates", "recent"] {
+        let (app, anon) = TestApp::init().empty();
+        create_sorted_categories(&app);
+
+        let url = format!("/api/v1/categories?sort={sort}&per_page=2");
+        let first: Value = anon.get(&url).good();
+        assert_eq!(slugs(&first), ["b", "c"], "sort={sort}");
+        let cursor = first["meta"]["next_cursor"].as_str().unwrap();
+
+        // A new category at the start of the list doesn't push `c` onto the next page
+        app.db(|conn| create_category(conn, "a", 10, 0));
+
+        let second: Value = anon.get(&format!("{url}&seek={cursor}")).good();
+        assert_eq!(slugs(&second), ["d", "e"], "sort={sort}");
+        assert_eq!(second["meta"]["next_cursor"], Value::Null, "sort={sort}");
+        assert_eq!(second["meta"]["total"], 5, "sort={sort}");
+
+        // Offset pagination is still available, and sees the new category
+        let json: Value = anon.get(&format!("{url}&page=2")).good();
+        assert_eq!(slugs(&json), ["c", "d"], "sort={sort}");
+        assert_eq!(json["meta"]["next_cursor"], Value::Null, "sort={sort}");
+    }
+}
+
+#[test]
+fn index_by_cursor_breaks_ties_by_id() {
+    let (app, anon) = TestApp::init().empty();
+    app.db(|conn| {
+        for name in ["b", "c", "d"] {
+            create_category(conn, name, 1, 1);
+        }
+    });
+
+    let url = "/api/v1/categories?sort=crates&per_page=2";
+    let first: Value = anon.get(url).good();
+    assert_eq!(slugs(&first), ["b", "c"]);
+    let cursor = first["meta"]["next_cursor"].as_str().unwrap();
+
+    let second: Value = anon.get(&format!("{url}&seek={cursor}")).good();
+    assert_eq!(slugs(&second), ["d"]);
+}
+
+#[test]
+fn index_rejects_cursor_for_another_sort() {
+    let (app, anon) = TestApp::init().empty();
+    create_sorted_categories(&app);
+
+    let json: Value = anon.get("/api/v1/categories?sort=crates&per_page=2").good();
+    let cursor = json["meta"]["next_cursor"].as_str().unwrap();
+
+    let response = anon.get::<()>(&format!("/api/v1/categories?sort=alpha&seek={cursor}"));
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "the `seek` cursor is for `sort=crates`" }] })
+    );
+}
@@ -1,2 +1,5 @@
+pub mod assignable;
+pub mod export;
 pub mod get;
 pub mod list;
+pub mod search;
@@ -0,0 +1,116 @@
+use crate::builders::CrateBuilder;
+use crate::new_category;
+use crate::util::{RequestHelper, TestApp};
+use cargo_registry::models::category::MAX_CATEGORIES;
+use cargo_registry::models::Category;
+use http::StatusCode;
+use serde_json::Value;
+
+fn slugs(json: &Value) -> Vec<&str> {
+    json["categories"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|category| category["slug"].as_str().unwrap())
+        .collect()
+}
+
+#[test]
+fn search() {
+    let (app, anon) = TestApp::init().empty();
+
+    app.db(|conn| {
+        new_category("Web programming", "web-programming", "Web crates")
+            .create_or_update(conn)
+            .unwrap();
+        new_category(
+            "Web programming::WebSocket",
+            "web-programming::websocket",
+            "",
+        )
+        .create_or_update(conn)
+        .unwrap();
+        new_category("Web", "web", "Just web")
+            .create_or_update(conn)
+            .unwrap();
+        new_category("Websites", "websites", "")
+            .create_or_update(conn)
+            .unwrap();
+        new_category("Network programming", "network-programming", "")
+            .create_or_update(conn)
+            .unwrap();
+    });
+
+    // Exact matches come first, then prefix matches, then any other matches
+    let json: Value = anon.get("/api/v1/categories/search?q=WEB").good();
+    assert_eq!(
+        slugs(&json),
+        [
+            "web",
+            "web-programming",
+            "web-programming::websocket",
+            "websites"
+        ]
+    );
+    assert_eq!(json["meta"]["total"], 4);
+    assert_eq!(json["categories"][0]["category"], "Web");
+    assert_eq!(json["categories"][0]["description"], "Just web");
+
+    let json: Value = anon.get("/api/v1/categories/search?q=programming").good();
+    assert_eq!(
+        slugs(&json),
+        [
+            "network-programming",
+            "web-programming",
+            "web-programming::websocket"
+        ]
+    );
+
+    // Results are paginated
+    let json: Value = anon
+        .get("/api/v1/categories/search?q=web&per_page=2&page=2")
+        .good();
+    assert_eq!(slugs(&json), ["web-programming::websocket", "websites"]);
+    assert_eq!(json["meta"]["total"], 4);
+
+    let json: Value = anon.get("/api/v1/categories/search?q=database").good();
+    assert_eq!(slugs(&json), Vec::<&str>::new());
+    assert_eq!(json["meta"]["total"], 0);
+}
+
+#[test]
+fn search_counts_the_crates_of_subcategories() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+
+    app.db(|conn| {
+        new_category("Foo", "foo", "")
+            .create_or_update(conn)
+            .unwrap();
+        new_category("Foo::Bar", "foo::bar", "")
+            .create_or_update(conn)
+            .unwrap();
+        let krate = CrateBuilder::new("krate", user.id).expect_build(conn);
+        Category::update_crate(conn, &krate, &["foo::bar"], MAX_CATEGORIES).unwrap();
+    });
+
+    let json: Value = anon.get("/api/v1/categories/search?q=foo").good();
+    assert_eq!(slugs(&json), ["foo", "foo::bar"]);
+    assert_eq!(json["categories"][0]["crates_cnt"], 0);
+    assert_eq!(json["categories"][0]["subtree_crates_cnt"], 1);
+    assert_eq!(json["categories"][1]["crates_cnt"], 1);
+    assert_eq!(json["categories"][1]["subtree_crates_cnt"], 1);
+}
+
+#[test]
+fn search_requires_a_query() {
+    let (_, anon) = TestApp::init().empty();
+
+    for url in [
+        "/api/v1/categories/search",
+        "/api/v1/categories/search?q=%20",
+    ] {
+        let response = anon.get::<()>(url);
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
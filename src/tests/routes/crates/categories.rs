@@ -0,0 +1,60 @@
+use crate::builders::CrateBuilder;
+use crate::new_category;
+use crate::util::{RequestHelper, TestApp};
+use cargo_registry::models::category::MAX_CATEGORIES;
+use cargo_registry::models::Category;
+use http::StatusCode;
+use serde_json::Value;
+
+#[test]
+fn categories_include_rollup_counts_and_parents() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+
+    app.db(|conn| {
+        new_category("Foo", "foo", "Foo crates")
+            .create_or_update(conn)
+            .unwrap();
+        new_category("Foo::Bar", "foo::bar", "Bar crates")
+            .create_or_update(conn)
+            .unwrap();
+
+        let nested = CrateBuilder::new("foo_nested", user.id).expect_build(conn);
+        Category::update_crate(conn, &nested, &["foo::bar"], MAX_CATEGORIES).unwrap();
+        let toplevel = CrateBuilder::new("foo_toplevel", user.id).expect_build(conn);
+        Category::update_crate(conn, &toplevel, &["foo"], MAX_CATEGORIES).unwrap();
+    });
+
+    let json: Value = anon.get("/api/v1/crates/foo_nested/categories").good();
+    let categories = json["categories"].as_array().unwrap();
+    assert_eq!(categories.len(), 1);
+    assert_eq!(categories[0]["slug"], "foo::bar");
+    assert_eq!(categories[0]["category"], "Bar");
+    assert_eq!(categories[0]["description"], "Bar crates");
+    assert_eq!(categories[0]["crates_cnt"], 1);
+    assert_eq!(categories[0]["subtree_crates_cnt"], 1);
+
+    let parents = categories[0]["parent_categories"].as_array().unwrap();
+    assert_eq!(parents.len(), 1);
+    assert_eq!(parents[0]["slug"], "foo");
+    // The crates of the subcategory are only included in the subtree count, like for the
+    // top-level categories
+    assert_eq!(parents[0]["crates_cnt"], 1);
+    assert_eq!(parents[0]["subtree_crates_cnt"], 2);
+
+    let json: Value = anon.get("/api/v1/crates/foo_toplevel/categories").good();
+    let categories = json["categories"].as_array().unwrap();
+    assert_eq!(categories.len(), 1);
+    assert_eq!(categories[0]["slug"], "foo");
+    assert_eq!(categories[0]["crates_cnt"], 1);
+    assert_eq!(categories[0]["subtree_crates_cnt"], 2);
+    assert_eq!(categories[0]["parent_categories"], json!([]));
+}
+
+#[test]
+fn categories_of_unknown_crate() {
+    let (_, anon) = TestApp::init().empty();
+
+    let response = anon.get::<()>("/api/v1/crates/unknown/categories");
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
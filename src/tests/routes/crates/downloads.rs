@@ -1,8 +1,9 @@
 use crate::builders::{CrateBuilder, VersionBuilder};
 use crate::util::{MockAnonymousUser, RequestHelper, TestApp};
 use cargo_registry::views::EncodableVersionDownload;
-use chrono::{Duration, Utc};
+use chrono::{Duration, NaiveDate, Utc};
 use http::StatusCode;
+use serde_json::Value;
 
 #[derive(Deserialize)]
 struct Downloads {
@@ -81,3 +82,216 @@ fn download() {
     assert_dl_count(&anon, "FOO_DOWNLOAD/1.0.0", Some(&query), 2);
     assert_dl_count(&anon, "FOO_DOWNLOAD", Some(&query), 2);
 }
+
+#[test]
+fn downloads_by_version() {
+    use cargo_registry::schema::{version_downloads, versions};
+    use diesel::prelude::*;
+
+    #[derive(Deserialize)]
+    struct VersionDownloads {
+        version: String,
+        downloads: i64,
+        yanked: bool,
+    }
+
+    #[derive(Deserialize)]
+    struct Response {
+        versions: Vec<VersionDownloads>,
+    }
+
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+
+    let today = Utc::now().date_naive();
+    let last_week = today - Duration::days(7);
+
+    app.db(|conn| {
+        CrateBuilder::new("foo_by_version", user.id)
+            .version("1.0.0")
+            .version(VersionBuilder::new("1.1.0").yanked(true))
+            .version("2.0.0")
+            .expect_build(conn);
+
+        let mut version_id = |num: &str| -> i32 {
+            versions::table
+                .filter(versions::num.eq(num))
+                .select(versions::id)
+                .first(conn)
+                .unwrap()
+        };
+        let (v1, v2) = (version_id("1.0.0"), version_id("1.1.0"));
+
+        diesel::insert_into(version_downloads::table)
+            .values(&vec![
+                (
+                    version_downloads::version_id.eq(v1),
+                    version_downloads::downloads.eq(3),
+                    version_downloads::date.eq(today),
+                ),
+                (
+                    version_downloads::version_id.eq(v1),
+                    version_downloads::downloads.eq(5),
+                    version_downloads::date.eq(last_week),
+                ),
+                (
+                    version_downloads::version_id.eq(v2),
+                    version_downloads::downloads.eq(2),
+                    version_downloads::date.eq(last_week),
+                ),
+            ])
+            .execute(conn)
+            .unwrap();
+    });
+
+    let url = "/api/v1/crates/foo_by_version/downloads_by_version";
+    let summary = |response: Response| {
+        response
+            .versions
+            .into_iter()
+            .map(|v| (v.version, v.downloads, v.yanked))
+            .collect::<Vec<_>>()
+    };
+
+    let json: Response = anon.get(url).good();
+    assert_eq!(
+        summary(json),
+        vec![
+            ("2.0.0".into(), 0, false),
+            ("1.1.0".into(), 2, true),
+            ("1.0.0".into(), 8, false),
+        ]
+    );
+
+    let query = format!("date_range={}..{}", today.format("%F"), today.format("%F"));
+    let json: Response = anon.get_with_query(url, &query).good();
+    assert_eq!(
+        summary(json),
+        vec![
+            ("2.0.0".into(), 0, false),
+            ("1.1.0".into(), 0, true),
+            ("1.0.0".into(), 3, false),
+        ]
+    );
+
+    let response = anon.get_with_query::<()>(url, "date_range=yesterday");
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "invalid date_range, expected `YYYY-MM-DD..YYYY-MM-DD`" }] })
+    );
+
+    let response = anon.get::<()>("/api/v1/crates/missing/downloads_by_version");
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn downloads_over_a_date_range() {
+    use cargo_registry::schema::{version_downloads, versions};
+    use diesel::prelude::*;
+
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+
+    let versions = ["1.0.0", "1.1.0", "1.2.0", "1.3.0", "1.4.0", "1.5.0"];
+    app.db(|conn| {
+        let mut builder = CrateBuilder::new("foo_range", user.id);
+        for num in versions {
+            builder = builder.version(num);
+        }
+        builder.expect_build(conn);
+
+        let version_id = |conn: &mut PgConnection, num: &str| -> i32 {
+            versions::table
+                .filter(versions::num.eq(num))
+                .select(versions::id)
+                .first(conn)
+                .unwrap()
+        };
+        let (oldest, latest) = (version_id(conn, "1.0.0"), version_id(conn, "1.5.0"));
+
+        let rows = [
+            (oldest, 1, "2023-01-01"),
+            (latest, 2, "2023-01-01"),
+            (oldest, 4, "2023-01-02"),
+            (latest, 8, "2023-01-03"),
+            (latest, 16, "2023-01-04"),
+        ]
+        .map(|(version_id, downloads, date)| {
+            (
+                version_downloads::version_id.eq(version_id),
+                version_downloads::downloads.eq(downloads),
+                version_downloads::date.eq(NaiveDate::parse_from_str(date, "%F").unwrap()),
+            )
+        });
+        diesel::insert_into(version_downloads::table)
+            .values(&rows[..])
+            .execute(conn)
+            .unwrap();
+    });
+
+    let url = "/api/v1/crates/foo_range/downloads";
+    let json: Value = anon
+        .get_with_query(url, "from=2023-01-01&to=2023-01-03")
+        .good();
+    assert_eq!(
+        json["meta"],
+        json!({
+            "extra_downloads": [
+                { "date": "2023-01-01", "downloads": 1 },
+                { "date": "2023-01-02", "downloads": 4 },
+            ],
+            "daily_downloads": [
+                { "date": "2023-01-01", "downloads": 3 },
+                { "date": "2023-01-02", "downloads": 4 },
+                { "date": "2023-01-03", "downloads": 8 },
+            ],
+            "from": "2023-01-01",
+            "to": "2023-01-03",
+        })
+    );
+    let dates = json["version_downloads"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|download| download["date"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(dates, ["2023-01-01", "2023-01-03"]);
+
+    // The last 90 days are returned by default, which doesn't include any of the downloads
+    let json: Value = anon.get(url).good();
+    assert_eq!(json["meta"]["daily_downloads"], json!([]));
+}
+
+#[test]
+fn downloads_date_range_is_validated() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+    app.db(|conn| {
+        CrateBuilder::new("foo_range", user.id).expect_build(conn);
+    });
+
+    let url = "/api/v1/crates/foo_range/downloads";
+    let error = |query: &str| {
+        let response = anon.get_with_query::<()>(url, query);
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        response.into_json()["errors"][0]["detail"].clone()
+    };
+
+    assert_eq!(
+        error("from=2023-01-31&to=2023-01-01"),
+        "`from` must not be after `to`"
+    );
+    assert_eq!(
+        error("from=2021-01-01&to=2023-01-01"),
+        "at most 366 days of downloads can be requested at once"
+    );
+    assert_eq!(
+        error("to=-262144-01-01"),
+        "`to` is too early, use `from` to set the range"
+    );
+    assert_eq!(
+        error("to=tomorrow"),
+        "invalid `to` date, expected `YYYY-MM-DD`"
+    );
+}
@@ -1,6 +1,7 @@
 use crate::builders::{CrateBuilder, VersionBuilder};
 use crate::util::{RequestHelper, TestApp};
 use crate::{new_category, new_user};
+use cargo_registry::models::category::MAX_CATEGORIES;
 use cargo_registry::models::Category;
 use cargo_registry::schema::crates;
 use diesel::{dsl::*, prelude::*, update};
@@ -86,8 +87,8 @@ fn index_queries() {
         new_category("Category 1::Ba'r", "cat1::bar", "Ba'r crates")
             .create_or_update(conn)
             .unwrap();
-        Category::update_crate(conn, &krate, &["cat1"]).unwrap();
-        Category::update_crate(conn, &krate2, &["cat1::bar"]).unwrap();
+        Category::update_crate(conn, &krate, &["cat1"], MAX_CATEGORIES).unwrap();
+        Category::update_crate(conn, &krate2, &["cat1::bar"], MAX_CATEGORIES).unwrap();
     });
 
     let cl = anon.search("category=cat1");
@@ -664,8 +665,8 @@ fn test_default_sort_recent() {
         new_category("Animal", "animal", "animal crates")
             .create_or_update(conn)
             .unwrap();
-        Category::update_crate(conn, &green_crate, &["animal"]).unwrap();
-        Category::update_crate(conn, &potato_crate, &["animal"]).unwrap();
+        Category::update_crate(conn, &green_crate, &["animal"], MAX_CATEGORIES).unwrap();
+        Category::update_crate(conn, &potato_crate, &["animal"], MAX_CATEGORIES).unwrap();
     });
 
     // test that index for categories is sorted by recent_downloads
@@ -1,3 +1,4 @@
+mod categories;
 pub mod downloads;
 mod following;
 mod list;
@@ -6,3 +7,4 @@ pub mod owners;
 mod read;
 mod reverse_dependencies;
 pub mod versions;
+mod webhooks;
@@ -1 +1,2 @@
 mod add;
+mod permissions;
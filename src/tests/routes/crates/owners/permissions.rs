@@ -0,0 +1,76 @@
+use crate::builders::CrateBuilder;
+use crate::util::{RequestHelper, TestApp};
+use cargo_registry::schema::crates;
+use chrono::{Duration, Utc};
+use diesel::prelude::*;
+use http::StatusCode;
+
+const URL: &str = "/api/v1/crates/foo_permissions/me/permissions";
+
+#[test]
+fn permissions_of_owners_team_members_and_other_users() {
+    let (app, anon) = TestApp::init().empty();
+    let owner = app.db_new_user("user-all-teams");
+    let owner_token = owner.db_new_token("arbitrary token name");
+    app.db(|conn| {
+        CrateBuilder::new("foo_permissions", owner.as_model().id).expect_build(conn);
+    });
+    owner_token
+        .add_named_owner("foo_permissions", "github:test-org:all")
+        .good();
+
+    let json = owner.get::<()>(URL).into_json();
+    assert_eq!(
+        json,
+        json!({ "permissions": {
+            "rights": "full",
+            "can_publish": true,
+            "can_yank": true,
+            "can_delete": true,
+            "can_manage_owners": true,
+        } })
+    );
+
+    // Team members can only delete crates that were just published
+    let team_member = app.db_new_user("user-one-team");
+    let json = team_member.get::<()>(URL).into_json();
+    assert_eq!(
+        json,
+        json!({ "permissions": {
+            "rights": "publish",
+            "can_publish": true,
+            "can_yank": true,
+            "can_delete": true,
+            "can_manage_owners": false,
+        } })
+    );
+
+    app.db(|conn| {
+        diesel::update(crates::table.filter(crates::name.eq("foo_permissions")))
+            .set(crates::created_at.eq(Utc::now().naive_utc() - Duration::days(7)))
+            .execute(conn)
+            .unwrap();
+    });
+    let json = team_member.get::<()>(URL).into_json();
+    assert_eq!(json["permissions"]["can_delete"], false);
+
+    let other_user = app.db_new_user("user-org-owner");
+    let json = other_user.get::<()>(URL).into_json();
+    assert_eq!(
+        json,
+        json!({ "permissions": {
+            "rights": "none",
+            "can_publish": false,
+            "can_yank": false,
+            "can_delete": false,
+            "can_manage_owners": false,
+        } })
+    );
+
+    let response = anon.get::<()>(URL);
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    other_user
+        .get::<()>("/api/v1/crates/unknown/me/permissions")
+        .assert_not_found();
+}
@@ -1,6 +1,24 @@
 use crate::builders::{CrateBuilder, PublishBuilder, VersionBuilder};
-use crate::util::{RequestHelper, TestApp};
+use crate::util::{MockRequestExt, RequestHelper, TestApp};
 use diesel::prelude::*;
+use http::{header, StatusCode};
+
+#[test]
+fn show_unknown_crate() {
+    let (_, anon) = TestApp::init().empty();
+
+    let response = anon.get::<()>("/api/v1/crates/foo_unknown");
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(
+        response.into_json(),
+        json!({
+            "errors": [{
+                "detail": "crate `foo_unknown` could not be found",
+                "code": "crate_not_found",
+            }]
+        })
+    );
+}
 
 #[test]
 fn show() {
@@ -116,6 +134,39 @@ fn show_minimal() {
     assert!(json.keywords.is_none());
 }
 
+#[test]
+fn show_is_conditional_on_etag() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+
+    let krate = app.db(|conn| {
+        CrateBuilder::new("foo_show_etag", user.id)
+            .version(VersionBuilder::new("1.0.0"))
+            .expect_build(conn)
+    });
+
+    let response = anon.get::<()>("/api/v1/crates/foo_show_etag");
+    assert_eq!(response.status(), StatusCode::OK);
+    let etag = response.headers()[header::ETAG].clone();
+    assert!(etag.to_str().unwrap().starts_with("W/\""));
+
+    let mut request = anon.get_request("/api/v1/crates/foo_show_etag");
+    request.header(header::IF_NONE_MATCH, etag.to_str().unwrap());
+    let response = anon.run::<()>(request);
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(response.headers()[header::ETAG], etag);
+    assert_eq!(response.into_text(), "");
+
+    // Publishing a new version changes the tag
+    app.db(|conn| VersionBuilder::new("1.1.0").expect_build(krate.id, user.id, conn));
+
+    let mut request = anon.get_request("/api/v1/crates/foo_show_etag");
+    request.header(header::IF_NONE_MATCH, etag.to_str().unwrap());
+    let response = anon.run::<()>(request);
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_ne!(response.headers()[header::ETAG], etag);
+}
+
 #[test]
 fn version_size() {
     let (_, _, user) = TestApp::full().with_user();
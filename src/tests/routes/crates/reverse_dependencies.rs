@@ -1,7 +1,9 @@
 use crate::builders::{CrateBuilder, VersionBuilder};
 use crate::util::{RequestHelper, TestApp};
 use crate::CrateMeta;
+use cargo_registry::schema::crates;
 use cargo_registry::views::{EncodableDependency, EncodableVersion};
+use diesel::prelude::*;
 
 #[derive(Deserialize)]
 struct RevDeps {
@@ -10,11 +12,27 @@ struct RevDeps {
     meta: CrateMeta,
 }
 
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+struct RevDepsCount {
+    crates: i64,
+    versions: i64,
+}
+
 impl crate::util::MockAnonymousUser {
     fn reverse_dependencies(&self, krate_name: &str) -> RevDeps {
         let url = format!("/api/v1/crates/{krate_name}/reverse_dependencies");
         self.get(&url).good()
     }
+
+    fn reverse_dependencies_with_query(&self, krate_name: &str, query: &str) -> RevDeps {
+        let url = format!("/api/v1/crates/{krate_name}/reverse_dependencies");
+        self.get_with_query(&url, query).good()
+    }
+
+    fn reverse_dependencies_count(&self, krate_name: &str) -> RevDepsCount {
+        let url = format!("/api/v1/crates/{krate_name}/reverse_dependencies/count");
+        self.get(&url).good()
+    }
 }
 
 #[test]
@@ -215,3 +233,157 @@ fn reverse_dependencies_query_supports_u64_version_number_parts() {
     assert_eq!(deps.versions[0].krate, "c2");
     assert_eq!(deps.versions[0].num, large_but_valid_version_number);
 }
+
+#[test]
+fn reverse_dependencies_are_ordered_by_downloads_then_name() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+
+    app.db(|conn| {
+        let c1 = CrateBuilder::new("c1", user.id).expect_build(conn);
+        CrateBuilder::new("c2", user.id)
+            .downloads(10)
+            .version(VersionBuilder::new("1.0.0").dependency(&c1, None))
+            .expect_build(conn);
+        // Multiple versions depending on `c1` still result in a single entry
+        CrateBuilder::new("c3", user.id)
+            .downloads(30)
+            .version(VersionBuilder::new("1.0.0").dependency(&c1, None))
+            .version(
+                VersionBuilder::new("1.1.0")
+                    .dependency(&c1, None)
+                    .dependency(&c1, Some("foo")),
+            )
+            .expect_build(conn);
+        CrateBuilder::new("c5", user.id)
+            .downloads(20)
+            .version(VersionBuilder::new("1.0.0").dependency(&c1, None))
+            .expect_build(conn);
+        CrateBuilder::new("c4", user.id)
+            .downloads(20)
+            .version(VersionBuilder::new("1.0.0").dependency(&c1, None))
+            .expect_build(conn);
+    });
+
+    let deps = anon.reverse_dependencies("c1");
+    assert_eq!(deps.meta.total, 4);
+    assert_eq!(dependent_crate_names(&deps), ["c3", "c4", "c5", "c2"]);
+}
+
+#[test]
+fn reverse_dependencies_can_be_paginated() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+
+    app.db(|conn| {
+        let c1 = CrateBuilder::new("c1", user.id).expect_build(conn);
+        for name in ["c2", "c3", "c4"] {
+            CrateBuilder::new(name, user.id)
+                .version(VersionBuilder::new("1.0.0").dependency(&c1, None))
+                .expect_build(conn);
+        }
+    });
+
+    let first_page = anon.reverse_dependencies_with_query("c1", "per_page=2&page=1");
+    assert_eq!(first_page.meta.total, 3);
+    assert_eq!(first_page.dependencies.len(), 2);
+
+    let second_page = anon.reverse_dependencies_with_query("c1", "per_page=2&page=2");
+    assert_eq!(second_page.meta.total, 3);
+    assert_eq!(second_page.dependencies.len(), 1);
+
+    // All crates have the same number of downloads, so they are ordered by name
+    assert_eq!(dependent_crate_names(&first_page), ["c2", "c3"]);
+    assert_eq!(dependent_crate_names(&second_page), ["c4"]);
+}
+
+/// Returns the names of the dependent crates, in the order of `dependencies`.
+fn dependent_crate_names(deps: &RevDeps) -> Vec<&str> {
+    deps.dependencies
+        .iter()
+        .map(|dep| {
+            let version = deps.versions.iter().find(|v| v.id == dep.version_id);
+            version.unwrap().krate.as_str()
+        })
+        .collect()
+}
+
+#[test]
+fn reverse_dependencies_count() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+
+    app.db(|conn| {
+        let c1 = CrateBuilder::new("c1", user.id).expect_build(conn);
+        let c2 = CrateBuilder::new("c2", user.id)
+            .version(VersionBuilder::new("1.0.0").dependency(&c1, None))
+            .expect_build(conn);
+        CrateBuilder::new("c3", user.id)
+            .version(VersionBuilder::new("1.0.0").dependency(&c1, None))
+            .version(
+                VersionBuilder::new("1.1.0")
+                    .dependency(&c1, None)
+                    .dependency(&c1, Some("foo"))
+                    .dependency(&c2, None),
+            )
+            .version(
+                VersionBuilder::new("1.2.0")
+                    .dependency(&c1, None)
+                    .yanked(true),
+            )
+            .expect_build(conn);
+    });
+
+    // Several crates, with multiple versions and duplicate dependencies. The yanked version
+    // isn't counted.
+    let count = anon.reverse_dependencies_count("c1");
+    assert_eq!(
+        count,
+        RevDepsCount {
+            crates: 2,
+            versions: 3
+        }
+    );
+
+    let count = anon.reverse_dependencies_count("c2");
+    assert_eq!(
+        count,
+        RevDepsCount {
+            crates: 1,
+            versions: 1
+        }
+    );
+
+    let count = anon.reverse_dependencies_count("c3");
+    assert_eq!(
+        count,
+        RevDepsCount {
+            crates: 0,
+            versions: 0
+        }
+    );
+
+    // Deleted crates aren't counted either
+    app.db(|conn| {
+        diesel::update(crates::table.filter(crates::name.eq("c3")))
+            .set(crates::deleted_at.eq(diesel::dsl::now.nullable()))
+            .execute(conn)
+            .unwrap();
+    });
+
+    let count = anon.reverse_dependencies_count("c1");
+    assert_eq!(
+        count,
+        RevDepsCount {
+            crates: 1,
+            versions: 1
+        }
+    );
+}
+
+#[test]
+fn reverse_dependencies_count_of_unknown_crate() {
+    let (_, anon) = TestApp::init().empty();
+    anon.get::<()>("/api/v1/crates/unknown/reverse_dependencies/count")
+        .assert_not_found();
+}
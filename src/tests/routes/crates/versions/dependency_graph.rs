@@ -0,0 +1,258 @@
+use crate::builders::{CrateBuilder, VersionBuilder};
+use crate::util::{MockAnonymousUser, RequestHelper, TestApp};
+use cargo_registry::models::Crate;
+use cargo_registry::schema::{crates, dependencies, versions};
+use diesel::prelude::*;
+use http::StatusCode;
+
+#[derive(Deserialize)]
+struct DependencyGraph {
+    nodes: Vec<Node>,
+    edges: Vec<Edge>,
+    meta: Meta,
+}
+
+#[derive(Deserialize)]
+struct Node {
+    version_id: i32,
+    #[serde(rename = "crate")]
+    krate: String,
+    num: String,
+    depth: i32,
+}
+
+#[derive(Deserialize)]
+struct Edge {
+    from: i32,
+    to: i32,
+    req: String,
+    kind: String,
+}
+
+#[derive(Deserialize)]
+struct Meta {
+    max_depth: i32,
+    truncated: bool,
+}
+
+impl DependencyGraph {
+    fn nodes(&self) -> Vec<(&str, &str, i32)> {
+        self.nodes
+            .iter()
+            .map(|node| (node.krate.as_str(), node.num.as_str(), node.depth))
+            .collect()
+    }
+
+    /// Returns the edges as pairs of crate names, sorted by name.
+    fn edges(&self) -> Vec<(&str, &str)> {
+        let name = |id| {
+            let node = self.nodes.iter().find(|node| node.version_id == id);
+            node.unwrap().krate.as_str()
+        };
+        let mut edges = self
+            .edges
+            .iter()
+            .map(|edge| (name(edge.from), name(edge.to)))
+            .collect::<Vec<_>>();
+        edges.sort_unstable();
+        edges
+    }
+}
+
+fn dependency_graph(anon: &MockAnonymousUser, query: &str) -> DependencyGraph {
+    anon.get_with_query("/api/v1/crates/top/1.0.0/dependency_graph", query)
+        .good()
+}
+
+/// Creates `top`, which depends on `left` and `right`, which both depend on `bottom`.
+///
+/// Returns the ID of the user owning the crates.
+fn diamond(app: &TestApp) -> i32 {
+    let user = app.db_new_user("user");
+    let user = user.as_model();
+
+    app.db(|conn| {
+        let bottom = CrateBuilder::new("bottom", user.id)
+            .version("1.0.0")
+            .version("2.0.0")
+            .expect_build(conn);
+        let left = CrateBuilder::new("left", user.id)
+            .version(VersionBuilder::new("1.0.0").dependency(&bottom, None))
+            .expect_build(conn);
+        let right = CrateBuilder::new("right", user.id)
+            .version(VersionBuilder::new("1.0.0").dependency(&bottom, None))
+            .expect_build(conn);
+        CrateBuilder::new("top", user.id)
+            .version(
+                VersionBuilder::new("1.0.0")
+                    .dependency(&left, None)
+                    .dependency(&right, None),
+            )
+            .expect_build(conn);
+    });
+
+    user.id
+}
+
+#[test]
+fn diamond_dependency_graph() {
+    let (app, anon) = TestApp::init().empty();
+    diamond(&app);
+
+    let graph = dependency_graph(&anon, "");
+    assert_eq!(
+        graph.nodes(),
+        [
+            ("top", "1.0.0", 0),
+            ("left", "1.0.0", 1),
+            ("right", "1.0.0", 1),
+            ("bottom", "2.0.0", 2),
+        ]
+    );
+    assert_eq!(
+        graph.edges(),
+        [
+            ("left", "bottom"),
+            ("right", "bottom"),
+            ("top", "left"),
+            ("top", "right"),
+        ]
+    );
+    assert!(graph.edges.iter().all(|edge| edge.kind == "normal"));
+    assert!(graph.edges.iter().all(|edge| edge.req == ">= 0"));
+    assert_eq!(graph.meta.max_depth, 10);
+    assert!(!graph.meta.truncated);
+}
+
+#[test]
+fn dependency_graph_is_truncated_at_max_depth() {
+    let (app, anon) = TestApp::init().empty();
+    diamond(&app);
+
+    let graph = dependency_graph(&anon, "max_depth=1");
+    assert_eq!(
+        graph.nodes(),
+        [
+            ("top", "1.0.0", 0),
+            ("left", "1.0.0", 1),
+            ("right", "1.0.0", 1)
+        ]
+    );
+    assert_eq!(graph.edges(), [("top", "left"), ("top", "right")]);
+    assert_eq!(graph.meta.max_depth, 1);
+    assert!(graph.meta.truncated);
+
+    // The whole graph fits into two levels
+    let graph = dependency_graph(&anon, "max_depth=2");
+    assert_eq!(graph.nodes.len(), 4);
+    assert!(!graph.meta.truncated);
+}
+
+#[test]
+fn dependency_graph_with_cycle() {
+    let (app, anon) = TestApp::init().empty();
+    let user_id = diamond(&app);
+
+    // A new version of `bottom` depends on `top` again
+    app.db(|conn| {
+        let top: Crate = Crate::by_name("top").first(conn).unwrap();
+        let bottom: Crate = Crate::by_name("bottom").first(conn).unwrap();
+        VersionBuilder::new("3.0.0")
+            .dependency(&top, None)
+            .expect_build(bottom.id, user_id, conn);
+    });
+
+    let graph = dependency_graph(&anon, "");
+    assert_eq!(
+        graph.nodes(),
+        [
+            ("top", "1.0.0", 0),
+            ("left", "1.0.0", 1),
+            ("right", "1.0.0", 1),
+            ("bottom", "3.0.0", 2),
+        ]
+    );
+    assert_eq!(
+        graph.edges(),
+        [
+            ("bottom", "top"),
+            ("left", "bottom"),
+            ("right", "bottom"),
+            ("top", "left"),
+            ("top", "right"),
+        ]
+    );
+    assert!(!graph.meta.truncated);
+}
+
+#[test]
+fn dependency_graph_respects_version_requirements() {
+    let (app, anon) = TestApp::init().empty();
+    diamond(&app);
+
+    // `left` needs the older version of `bottom`
+    app.db(|conn| {
+        let left: Crate = Crate::by_name("left").first(conn).unwrap();
+        let left_versions = versions::table
+            .filter(versions::crate_id.eq(left.id))
+            .select(versions::id);
+        diesel::update(dependencies::table.filter(dependencies::version_id.eq_any(left_versions)))
+            .set(dependencies::req.eq("^1.0"))
+            .execute(conn)
+            .unwrap();
+    });
+
+    let graph = dependency_graph(&anon, "");
+    assert_eq!(
+        graph.nodes(),
+        [
+            ("top", "1.0.0", 0),
+            ("left", "1.0.0", 1),
+            ("right", "1.0.0", 1),
+            ("bottom", "1.0.0", 2),
+            ("bottom", "2.0.0", 2),
+        ]
+    );
+    assert_eq!(graph.edges.len(), 4);
+}
+
+#[test]
+fn dependency_graph_skips_deleted_crates() {
+    let (app, anon) = TestApp::init().empty();
+    diamond(&app);
+
+    app.db(|conn| {
+        diesel::update(crates::table.filter(crates::name.eq("right")))
+            .set(crates::deleted_at.eq(diesel::dsl::now.nullable()))
+            .execute(conn)
+            .unwrap();
+    });
+
+    let graph = dependency_graph(&anon, "");
+    assert_eq!(
+        graph.nodes(),
+        [
+            ("top", "1.0.0", 0),
+            ("left", "1.0.0", 1),
+            ("bottom", "2.0.0", 2)
+        ]
+    );
+    assert_eq!(graph.edges(), [("left", "bottom"), ("top", "left")]);
+}
+
+#[test]
+fn dependency_graph_with_invalid_max_depth() {
+    let (app, anon) = TestApp::init().empty();
+    diamond(&app);
+
+    for max_depth in ["0", "26", "deep"] {
+        let query = format!("max_depth={max_depth}");
+        let response =
+            anon.get_with_query::<()>("/api/v1/crates/top/1.0.0/dependency_graph", &query);
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            response.into_json(),
+            json!({ "errors": [{ "detail": "max_depth must be between 1 and 25" }] })
+        );
+    }
+}
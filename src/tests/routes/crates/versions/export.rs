@@ -0,0 +1,92 @@
+use crate::builders::{CrateBuilder, VersionBuilder};
+use crate::util::{RequestHelper, TestApp};
+use chrono::NaiveDate;
+use http::{header, StatusCode};
+use serde_json::Value;
+
+#[test]
+fn export_streams_all_versions() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+
+    let created_at = NaiveDate::from_ymd_opt(2023, 4, 1)
+        .unwrap()
+        .and_hms_opt(12, 0, 0)
+        .unwrap();
+    let checksum = |c: char| c.to_string().repeat(64);
+
+    app.db(|conn| {
+        CrateBuilder::new("foo_export", user.id)
+            .version(
+                VersionBuilder::new("1.0.0")
+                    .created_at(created_at)
+                    .checksum(&checksum('a'))
+                    .size(100),
+            )
+            .version(
+                VersionBuilder::new("1.1.0")
+                    .created_at(created_at)
+                    .checksum(&checksum('b'))
+                    .size(200)
+                    .yanked(true),
+            )
+            .version(
+                VersionBuilder::new("0.9.0")
+                    .created_at(created_at)
+                    .checksum(&checksum('c'))
+                    .size(300),
+            )
+            .expect_build(conn);
+
+        CrateBuilder::new("foo_not_exported", user.id).expect_build(conn);
+    });
+
+    let response = anon.get::<()>("/api/v1/crates/foo_export/versions/export");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()[header::CONTENT_TYPE],
+        "application/x-ndjson"
+    );
+
+    let text = response.into_text();
+    let lines = text
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect::<Vec<Value>>();
+
+    // The versions are exported in the order in which they were published
+    assert_eq!(
+        lines,
+        vec![
+            json!({
+                "num": "1.0.0",
+                "created_at": "2023-04-01T12:00:00+00:00",
+                "yanked": false,
+                "checksum": checksum('a'),
+                "crate_size": 100,
+            }),
+            json!({
+                "num": "1.1.0",
+                "created_at": "2023-04-01T12:00:00+00:00",
+                "yanked": true,
+                "checksum": checksum('b'),
+                "crate_size": 200,
+            }),
+            json!({
+                "num": "0.9.0",
+                "created_at": "2023-04-01T12:00:00+00:00",
+                "yanked": false,
+                "checksum": checksum('c'),
+                "crate_size": 300,
+            }),
+        ]
+    );
+}
+
+#[test]
+fn export_of_unknown_crate() {
+    let (_, anon) = TestApp::init().empty();
+
+    anon.get::<()>("/api/v1/crates/unknown/versions/export")
+        .assert_not_found();
+}
@@ -0,0 +1,60 @@
+use crate::builders::{CrateBuilder, VersionBuilder};
+use crate::util::{MockAnonymousUser, RequestHelper, TestApp};
+use serde_json::Value;
+
+fn latest(anon: &MockAnonymousUser, query: &str) -> Value {
+    let json: Value = anon
+        .get(&format!("/api/v1/crates/foo_latest/latest{query}"))
+        .good();
+    json["version"]["num"].clone()
+}
+
+#[test]
+fn latest_skips_yanked_versions_and_prereleases() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+
+    app.db(|conn| {
+        // `created_at` isn't used for the ordering, so `0.10.0` is published before `0.9.0`
+        CrateBuilder::new("foo_latest", user.id)
+            .version("0.10.0")
+            .version("0.9.0")
+            .version(VersionBuilder::new("0.11.0").yanked(true))
+            .version("0.12.0-beta.1")
+            .expect_build(conn);
+    });
+
+    assert_eq!(latest(&anon, ""), "0.10.0");
+    assert_eq!(latest(&anon, "?include_prerelease=yes"), "0.12.0-beta.1");
+    assert_eq!(latest(&anon, "?include_prerelease=no"), "0.10.0");
+
+    let json: Value = anon.get("/api/v1/crates/foo_latest/latest").good();
+    assert_eq!(json["version"]["crate"], "foo_latest");
+    assert_eq!(json["version"]["yanked"], false);
+}
+
+#[test]
+fn latest_without_usable_versions() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+
+    app.db(|conn| {
+        CrateBuilder::new("foo_latest", user.id)
+            .version(VersionBuilder::new("1.0.0").yanked(true))
+            .version("1.1.0-alpha.1")
+            .expect_build(conn);
+        CrateBuilder::new("foo_all_yanked", user.id)
+            .version(VersionBuilder::new("1.0.0").yanked(true))
+            .version(VersionBuilder::new("2.0.0-rc.1").yanked(true))
+            .expect_build(conn);
+    });
+
+    anon.get::<()>("/api/v1/crates/foo_latest/latest")
+        .assert_not_found();
+    assert_eq!(latest(&anon, "?include_prerelease=yes"), "1.1.0-alpha.1");
+
+    anon.get::<()>("/api/v1/crates/foo_all_yanked/latest?include_prerelease=yes")
+        .assert_not_found();
+    anon.get::<()>("/api/v1/crates/missing/latest")
+        .assert_not_found();
+}
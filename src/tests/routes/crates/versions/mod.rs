@@ -1,5 +1,9 @@
 mod authors;
 pub mod dependencies;
+mod dependency_graph;
 pub mod download;
+mod export;
+mod latest;
 mod read;
+mod readme;
 pub mod yank_unyank;
@@ -0,0 +1,60 @@
+use crate::builders::{CrateBuilder, VersionBuilder};
+use crate::util::{MockRequestExt, RequestHelper, TestApp};
+use cargo_registry::models::Version;
+use http::{header, Method, StatusCode};
+
+#[test]
+fn rendered_readme_is_returned() {
+    let (app, anon, user) = TestApp::full().with_user();
+
+    app.db(|conn| {
+        let krate = CrateBuilder::new("foo_readme", user.as_model().id).expect_build(conn);
+        let version = VersionBuilder::new("1.0.0").expect_build(krate.id, user.as_model().id, conn);
+        Version::record_readme_rendering(version.id, conn).unwrap();
+    });
+
+    // The HTTP recording only contains a single download of the README, so the conditional
+    // request below must not download it again
+    let response = anon.get::<()>("/api/v1/crates/foo-readme/1.0.0/readme");
+    assert_eq!(response.status(), StatusCode::OK);
+    let headers = response.headers();
+    assert_eq!(headers[header::CONTENT_TYPE], "text/html; charset=utf-8");
+    assert_eq!(headers[header::CACHE_CONTROL], "no-cache");
+    assert_eq!(headers[header::CONTENT_SECURITY_POLICY], "sandbox");
+    let etag = headers[header::ETAG].clone();
+    assert_eq!(response.into_text(), "<p>Hello, readme!</p>\n");
+
+    let mut request = anon.request_builder(Method::GET, "/api/v1/crates/foo_readme/1.0.0/readme");
+    request.header(header::IF_NONE_MATCH, etag.to_str().unwrap());
+    let response = anon.run::<()>(request);
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+    // The frontend only asks for the location of the README
+    let mut request = anon.request_builder(Method::GET, "/api/v1/crates/foo_readme/1.0.0/readme");
+    request.header(header::ACCEPT, "application/json");
+    let response = anon.run::<()>(request);
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.into_json(),
+        json!({ "url": "https://alexcrichton-test.s3.amazonaws.com/readmes/foo_readme/foo_readme-1.0.0.html" })
+    );
+}
+
+#[test]
+fn readme_is_not_found_without_rendering() {
+    let (app, anon, user) = TestApp::init().with_user();
+
+    app.db(|conn| {
+        CrateBuilder::new("foo_readme", user.as_model().id)
+            .version(VersionBuilder::new("1.0.0"))
+            .expect_build(conn);
+    });
+
+    anon.get::<()>("/api/v1/crates/foo_readme/1.0.0/readme")
+        .assert_not_found();
+    anon.get::<()>("/api/v1/crates/foo_readme/2.0.0/readme")
+        .assert_not_found();
+
+    let response = anon.get::<()>("/api/v1/crates/foo_unknown/1.0.0/readme");
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,166 @@
+use crate::builders::CrateBuilder;
+use crate::util::{MockRequestExt, RequestHelper, Response, TestApp};
+use cargo_registry::models::{NewCrateWebhook, WebhookEvent};
+use cargo_registry::schema::crate_webhooks;
+use diesel::prelude::*;
+use http::StatusCode;
+use serde_json::Value;
+
+fn create_webhook<T: RequestHelper>(client: &T, crate_name: &str, body: Value) -> Response<Value> {
+    let url = format!("/api/v1/crates/{crate_name}/webhooks");
+    let mut request = client.post_request(&url);
+    request.with_body(body.to_string().as_bytes());
+    client.run(request)
+}
+
+fn webhook_body(events: &[&str]) -> Value {
+    json!({
+        "url": "http://hooks.example.com/crates-io",
+        "secret": "secret",
+        "events": events,
+    })
+}
+
+#[test]
+fn manage_webhooks() {
+    let (app, _, user) = TestApp::init().with_user();
+    app.db(|conn| CrateBuilder::new("hooked", user.as_model().id).expect_build(conn));
+
+    let json = create_webhook(&user, "hooked", webhook_body(&["published", "deleted"])).good();
+    let webhook = &json["webhook"];
+    assert_eq!(webhook["url"], "http://hooks.example.com/crates-io");
+    assert_eq!(webhook["events"], json!(["published", "deleted"]));
+    assert!(webhook.get("secret").is_none());
+    let id = webhook["id"].as_i64().unwrap();
+
+    let url = format!("/api/v1/crates/hooked/webhooks/{id}");
+    let body = webhook_body(&["yanked"]).to_string();
+    let json = user.put::<Value>(&url, body.as_bytes()).good();
+    assert_eq!(json["webhook"]["events"], json!(["yanked"]));
+
+    let json = user.get::<Value>("/api/v1/crates/hooked/webhooks").good();
+    let webhooks = json["webhooks"].as_array().unwrap();
+    assert_eq!(webhooks.len(), 1);
+    assert_eq!(webhooks[0]["id"], id);
+    assert!(webhooks[0].get("secret").is_none());
+
+    let response = user.delete::<()>(&url);
+    assert_eq!(response.status(), StatusCode::OK);
+    user.delete::<()>(&url).assert_not_found();
+
+    let json = user.get::<Value>("/api/v1/crates/hooked/webhooks").good();
+    assert_eq!(json["webhooks"], json!([]));
+}
+
+#[test]
+fn invalid_webhooks_are_rejected() {
+    let (app, _, user) = TestApp::init().with_user();
+    app.db(|conn| CrateBuilder::new("hooked", user.as_model().id).expect_build(conn));
+
+    let invalid_bodies = [
+        (
+            json!({ "url": "ftp://hooks.example.com", "secret": "secret", "events": ["deleted"] }),
+            "the webhook url must be an http or https url",
+        ),
+        (
+            json!({ "url": "http://hooks.example.com", "secret": "", "events": ["deleted"] }),
+            "the webhook secret must not be empty",
+        ),
+        (
+            json!({ "url": "http://hooks.example.com", "secret": "secret", "events": [] }),
+            "a webhook must subscribe to at least one event",
+        ),
+    ];
+
+    let private_urls = [
+        "http://127.0.0.1/",
+        "http://169.254.169.254/latest/meta-data",
+        "http://10.0.0.1:8080/",
+        "http://localhost:8080/",
+        "http://metadata.google.internal/",
+        "http://[::1]/",
+    ];
+    let invalid_bodies = invalid_bodies.into_iter().chain(private_urls.map(|url| {
+        (
+            json!({ "url": url, "secret": "secret", "events": ["deleted"] }),
+            "the webhook url must point to a public host",
+        )
+    }));
+
+    for (body, detail) in invalid_bodies {
+        let response = create_webhook(&user, "hooked", body);
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            response.into_json(),
+            json!({ "errors": [{ "detail": detail }] })
+        );
+    }
+}
+
+#[test]
+fn only_owners_can_manage_webhooks() {
+    let (app, _, user) = TestApp::init().with_user();
+    let owner = app.db_new_user("owner");
+    app.db(|conn| CrateBuilder::new("hooked", owner.as_model().id).expect_build(conn));
+
+    let response = create_webhook(&user, "hooked", webhook_body(&["deleted"]));
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "only owners have permission to manage webhooks" }] })
+    );
+
+    let response = user.get::<()>("/api/v1/crates/hooked/webhooks");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "only owners have permission to manage webhooks" }] })
+    );
+}
+
+#[test]
+fn deleting_a_crate_dispatches_signed_webhooks() {
+    let (app, _, user) = TestApp::full().with_user();
+    app.db(|conn| CrateBuilder::new("hooked", user.as_model().id).expect_build(conn));
+
+    // An IP address, so that the webhook can be dispatched without a DNS lookup
+    let mut body = webhook_body(&["deleted"]);
+    body["url"] = json!("http://93.184.216.34/crates-io");
+    create_webhook(&user, "hooked", body).good();
+    // Not subscribed to deletions, so this one must not receive a request
+    let mut body = webhook_body(&["published"]);
+    body["url"] = json!("http://unsubscribed.example.com/crates-io");
+    create_webhook(&user, "hooked", body).good();
+
+    let response = user.delete_crate("hooked");
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // The HTTP recording asserts the payload and its `x-cratesio-event` and
+    // `x-crates-io-signature` headers
+    app.run_pending_background_jobs();
+}
+
+#[test]
+fn webhooks_to_private_hosts_are_not_dispatched() {
+    let (app, _, user) = TestApp::full().with_user();
+    let krate = app.db(|conn| CrateBuilder::new("hooked", user.as_model().id).expect_build(conn));
+
+    // Webhooks registered before the URLs were validated can still point to private hosts
+    app.db(|conn| {
+        diesel::insert_into(crate_webhooks::table)
+            .values(NewCrateWebhook {
+                crate_id: krate.id,
+                url: "http://169.254.169.254/latest/meta-data",
+                secret: "secret",
+                events: WebhookEvent::mask(&[WebhookEvent::Deleted]),
+            })
+            .execute(conn)
+            .unwrap();
+    });
+
+    let response = user.delete_crate("hooked");
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // The HTTP recording only contains the deletion of the crate file
+    app.run_pending_background_jobs();
+}
@@ -0,0 +1,50 @@
+use crate::util::{RequestHelper, TestApp, TestDatabase};
+use http::StatusCode;
+use std::time::Duration;
+
+#[test]
+fn health_is_ok() {
+    let (_, anon) = TestApp::init().empty();
+
+    let response = anon.get::<()>("/api/v1/health");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.into_json(), json!({ "ok": true }));
+}
+
+#[test]
+fn ready_when_all_dependencies_are_available() {
+    let (_, anon) = TestApp::with_proxy().empty();
+
+    let response = anon.get::<()>("/api/v1/ready");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.into_json(), json!({ "ok": true }));
+}
+
+#[test]
+fn not_ready_when_the_database_is_down() {
+    let (app, anon) = TestApp::with_proxy()
+        .with_database(TestDatabase::SlowRealPool { replica: false })
+        .empty();
+
+    app.primary_db_chaosproxy().break_networking();
+
+    let response = anon.get::<()>("/api/v1/ready");
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [
+            { "detail": "database is unavailable" },
+            { "detail": "primary database is unavailable" },
+        ] })
+    );
+
+    // The health check doesn't depend on the database
+    let response = anon.get::<()>("/api/v1/health");
+    assert_eq!(response.status(), StatusCode::OK);
+
+    app.primary_db_chaosproxy().restore_networking();
+    app.as_inner()
+        .primary_database
+        .wait_until_healthy(Duration::from_millis(2000))
+        .expect("the database did not return healthy");
+}
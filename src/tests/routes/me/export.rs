@@ -0,0 +1,82 @@
+use crate::builders::{CrateBuilder, VersionBuilder};
+use crate::util::{RequestHelper, TestApp};
+use cargo_registry::rate_limiter::LimitedAction;
+use flate2::read::GzDecoder;
+use http::{header, StatusCode};
+use serde_json::Value;
+use std::io::Read;
+use std::time::Duration;
+
+#[test]
+fn export_contains_all_owned_crates() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let other_user = app.db_new_user("other_user");
+
+    app.db(|conn| {
+        CrateBuilder::new("foo_export", user.as_model().id)
+            .version(VersionBuilder::new("1.0.0"))
+            .version(VersionBuilder::new("1.1.0").yanked(true))
+            .keyword("backup")
+            .expect_build(conn);
+        CrateBuilder::new("bar_export", user.as_model().id).expect_build(conn);
+        CrateBuilder::new("baz_not_owned", other_user.as_model().id).expect_build(conn);
+    });
+
+    anon.get::<()>("/api/v1/me/crates/export")
+        .assert_forbidden();
+
+    let response = user.get::<()>("/api/v1/me/crates/export");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "application/gzip");
+
+    let bytes = response.into_bytes();
+    let mut archive = tar::Archive::new(GzDecoder::new(&*bytes));
+    let entries = archive
+        .entries()
+        .unwrap()
+        .map(|entry| {
+            let mut entry = entry.unwrap();
+            let path = entry.path().unwrap().display().to_string();
+            let mut json = String::new();
+            entry.read_to_string(&mut json).unwrap();
+            (path, serde_json::from_str::<Value>(&json).unwrap())
+        })
+        .collect::<Vec<_>>();
+
+    let paths = entries.iter().map(|(path, _)| path).collect::<Vec<_>>();
+    assert_eq!(paths, ["bar_export.json", "foo_export.json"]);
+
+    let krate = &entries[1].1;
+    assert_eq!(krate["name"], "foo_export");
+    assert_eq!(krate["keywords"], json!(["backup"]));
+    assert_eq!(krate["categories"], json!([]));
+    assert_eq!(krate["owners"], json!([{ "login": "foo", "kind": "user" }]));
+
+    let versions = krate["versions"].as_array().unwrap();
+    let versions = versions
+        .iter()
+        .map(|version| (version["num"].as_str().unwrap(), version["yanked"].clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(versions, [("1.0.0", json!(false)), ("1.1.0", json!(true))]);
+}
+
+#[test]
+fn export_is_rate_limited() {
+    let (_, _, user) = TestApp::init()
+        .with_rate_limit(LimitedAction::ExportCrates, Duration::from_secs(60 * 60), 1)
+        .with_user();
+
+    let response = user.get::<()>("/api/v1/me/crates/export");
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = user.get::<()>("/api/v1/me/crates/export");
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[test]
+fn api_tokens_cannot_export_crates() {
+    let (_, _, _, token) = TestApp::init().with_token();
+    token
+        .get::<()>("/api/v1/me/crates/export")
+        .assert_forbidden();
+}
@@ -1,4 +1,5 @@
 mod email_notifications;
+mod export;
 pub mod get;
 pub mod tokens;
 mod updates;
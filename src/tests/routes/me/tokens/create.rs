@@ -2,6 +2,7 @@ use crate::util::{RequestHelper, TestApp};
 use cargo_registry::models::token::{CrateScope, EndpointScope};
 use cargo_registry::models::ApiToken;
 use cargo_registry::views::EncodableApiTokenWithToken;
+use chrono::{Duration, Utc};
 use diesel::prelude::*;
 use http::StatusCode;
 
@@ -215,3 +216,64 @@ fn create_token_with_invalid_endpoint_scope() {
         json!({ "errors": [{ "detail": "invalid endpoint scope" }] })
     );
 }
+
+#[test]
+fn create_token_with_expiry() {
+    let (app, _, user) = TestApp::init().with_user();
+
+    let json = json!({
+        "api_token": {
+            "name": "bar",
+            "expires_in_days": 30,
+        }
+    });
+
+    let json: NewResponse = user
+        .put("/api/v1/me/tokens", &serde_json::to_vec(&json).unwrap())
+        .good();
+    assert_some!(json.api_token.expired_at);
+
+    let tokens: Vec<ApiToken> =
+        app.db(|conn| assert_ok!(ApiToken::belonging_to(user.as_model()).load(conn)));
+    assert_eq!(tokens.len(), 1);
+    let expires_in = tokens[0].expired_at.unwrap() - Utc::now().naive_utc();
+    assert!(expires_in > Duration::days(29) && expires_in <= Duration::days(30));
+}
+
+#[test]
+fn create_token_with_zero_expiry() {
+    let (_, _, user) = TestApp::init().with_user();
+
+    let json = json!({
+        "api_token": {
+            "name": "bar",
+            "expires_in_days": 0,
+        }
+    });
+
+    let response = user.put::<()>("/api/v1/me/tokens", &serde_json::to_vec(&json).unwrap());
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "expires_in_days must be at least 1" }] })
+    );
+}
+
+#[test]
+fn create_token_with_too_large_expiry() {
+    let (_, _, user) = TestApp::init().with_user();
+
+    let json = json!({
+        "api_token": {
+            "name": "bar",
+            "expires_in_days": u32::MAX,
+        }
+    });
+
+    let response = user.put::<()>("/api/v1/me/tokens", &serde_json::to_vec(&json).unwrap());
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "expires_in_days is too large" }] })
+    );
+}
@@ -57,10 +57,10 @@ fn revoke_current_token_with_cookie_user() {
 
     // Revoke the token
     let response = user.delete::<()>("/api/v1/tokens/current");
-    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
     assert_eq!(
         response.into_json(),
-        json!({ "errors": [{ "detail": "token not provided" }] })
+        json!({ "errors": [{ "detail": "this action can only be performed with an API token" }] })
     );
 
     // Ensure that the token still exists in the database after the failed request
@@ -0,0 +1,79 @@
+use crate::builders::CrateBuilder;
+use crate::util::{RequestHelper, TestApp};
+use crate::{add_team_to_crate, new_team};
+use cargo_registry::models::CrateOwner;
+use cargo_registry::schema::crate_owners;
+use diesel::prelude::*;
+use serde_json::{json, Value};
+
+#[test]
+fn lists_crates_with_sole_ownership() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+    let other_user = app.db_new_user("other");
+    let other_user = other_user.as_model();
+
+    app.db(|conn| {
+        CrateBuilder::new("sole", user.id).expect_build(conn);
+        let shared = CrateBuilder::new("shared", user.id).expect_build(conn);
+        CrateBuilder::new("not_mine", other_user.id).expect_build(conn);
+
+        diesel::insert_into(crate_owners::table)
+            .values(CrateOwner {
+                crate_id: shared.id,
+                owner_id: other_user.id,
+                created_by: user.id,
+                owner_kind: 0,
+                email_notifications: true,
+            })
+            .execute(conn)
+            .unwrap();
+    });
+
+    let json: Value = anon.get("/api/v1/users/FOO/crates").good();
+    assert_eq!(
+        json,
+        json!({ "crates": [
+            { "name": "shared", "owner_kind": "user", "sole_owner": false },
+            { "name": "sole", "owner_kind": "user", "sole_owner": true },
+        ] })
+    );
+
+    let json: Value = anon.get("/api/v1/users/other/crates").good();
+    assert_eq!(
+        json,
+        json!({ "crates": [
+            { "name": "not_mine", "owner_kind": "user", "sole_owner": true },
+            { "name": "shared", "owner_kind": "user", "sole_owner": false },
+        ] })
+    );
+}
+
+#[test]
+fn lists_crates_of_teams() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+
+    app.db(|conn| {
+        let team = new_team("github:test-org:core")
+            .create_or_update(conn)
+            .unwrap();
+        let krate = CrateBuilder::new("team_crate", user.id).expect_build(conn);
+        add_team_to_crate(&team, &krate, user, conn).unwrap();
+    });
+
+    let json: Value = anon.get("/api/v1/users/github:test-org:core/crates").good();
+    assert_eq!(
+        json,
+        json!({ "crates": [
+            { "name": "team_crate", "owner_kind": "team", "sole_owner": false },
+        ] })
+    );
+}
+
+#[test]
+fn unknown_login() {
+    let (_, anon) = TestApp::init().empty();
+    anon.get::<()>("/api/v1/users/nobody/crates")
+        .assert_not_found();
+}
@@ -1,3 +1,4 @@
+mod crates;
 mod read;
 mod stats;
 pub mod update;
@@ -1,6 +1,9 @@
 use crate::util::MockRequestExt;
 use crate::{RequestHelper, TestApp};
-use cargo_registry::{models::ApiToken, util::errors::TOKEN_FORMAT_ERROR, views::EncodableMe};
+use cargo_registry::schema::api_tokens;
+use cargo_registry::util::errors::{TOKEN_EXPIRED_ERROR, TOKEN_FORMAT_ERROR};
+use cargo_registry::{models::ApiToken, views::EncodableMe};
+use chrono::{Duration, Utc};
 use diesel::prelude::*;
 use http::{header, StatusCode};
 
@@ -39,3 +42,40 @@ fn old_tokens_give_specific_error_message() {
         json!({ "errors": [{ "detail": TOKEN_FORMAT_ERROR }] })
     );
 }
+
+#[test]
+fn tokens_that_have_not_expired_yet_work() {
+    let (app, _, _, token) = TestApp::init().with_token();
+
+    let expired_at = Utc::now().naive_utc() + Duration::days(1);
+    app.db(|conn| {
+        diesel::update(api_tokens::table.find(token.as_model().id))
+            .set(api_tokens::expired_at.eq(expired_at))
+            .execute(conn)
+            .unwrap();
+    });
+
+    // Use the token once
+    token.search("following=1");
+}
+
+#[test]
+fn expired_tokens_give_specific_error_message() {
+    let url = "/api/v1/crates?following=1";
+    let (app, _, _, token) = TestApp::init().with_token();
+
+    let expired_at = Utc::now().naive_utc() - Duration::days(1);
+    app.db(|conn| {
+        diesel::update(api_tokens::table.find(token.as_model().id))
+            .set(api_tokens::expired_at.eq(expired_at))
+            .execute(conn)
+            .unwrap();
+    });
+
+    let response = token.get::<()>(url);
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": TOKEN_EXPIRED_ERROR }] })
+    );
+}
@@ -0,0 +1,175 @@
+use crate::builders::CrateBuilder;
+use crate::util::{MockCookieUser, MockRequestExt, RequestHelper, Response, TestApp};
+use cargo_registry::schema::{api_tokens, crate_owners, crates, emails, users};
+use diesel::prelude::*;
+use http::StatusCode;
+
+const URL: &str = "/api/v1/admin/users/merge";
+
+#[test]
+fn merge_moves_crates_tokens_and_email() {
+    let (app, _, admin) = TestApp::init().with_user();
+    admin.make_admin();
+    let source = app.db_new_user("old-account");
+    let target = app.db_new_user("new-account");
+    let source_id = source.as_model().id;
+    let target_id = target.as_model().id;
+
+    app.db(|conn| {
+        CrateBuilder::new("foo_merged", source_id).expect_build(conn);
+        diesel::delete(emails::table.filter(emails::user_id.eq(target_id)))
+            .execute(conn)
+            .unwrap();
+    });
+    let token = source.db_new_token("old token");
+
+    let body = json!({ "source_login": "Old-Account", "target_login": "new-account" });
+    let response = merge(&admin, &body);
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.into_json(),
+        json!({ "crates": 1, "api_tokens": 1, "email_moved": true })
+    );
+
+    assert_eq!(owned_crates(&app, source_id), Vec::<String>::new());
+    assert_eq!(owned_crates(&app, target_id), vec!["foo_merged"]);
+
+    app.db(|conn| {
+        let token_user: i32 = api_tokens::table
+            .find(token.as_model().id)
+            .select(api_tokens::user_id)
+            .first(conn)
+            .unwrap();
+        assert_eq!(token_user, target_id);
+
+        // Every test user has the same email address, so count them per user instead
+        let source_emails: i64 = emails::table
+            .filter(emails::user_id.eq(source_id))
+            .count()
+            .get_result(conn)
+            .unwrap();
+        assert_eq!(source_emails, 0);
+        let target_emails: i64 = emails::table
+            .filter(emails::user_id.eq(target_id))
+            .count()
+            .get_result(conn)
+            .unwrap();
+        assert_eq!(target_emails, 1);
+
+        let lock_reason: Option<String> = users::table
+            .find(source_id)
+            .select(users::account_lock_reason)
+            .first(conn)
+            .unwrap();
+        assert_eq!(lock_reason.as_deref(), Some("merged into new-account"));
+    });
+
+    let response = source.get::<()>("/api/v1/me");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    target.get::<serde_json::Value>("/api/v1/me").good();
+}
+
+#[test]
+fn merge_deduplicates_crates_owned_by_both_accounts() {
+    let (app, _, admin) = TestApp::init().with_user();
+    admin.make_admin();
+    let source = app.db_new_user("old-account");
+    let target = app.db_new_user("new-account");
+    let source_id = source.as_model().id;
+    let target_id = target.as_model().id;
+
+    app.db(|conn| {
+        let both = CrateBuilder::new("foo_both", source_id).expect_build(conn);
+        add_user_owner(conn, both.id, target_id, false);
+
+        // The target was an owner of this crate before, but was removed since
+        let removed = CrateBuilder::new("foo_removed", source_id).expect_build(conn);
+        add_user_owner(conn, removed.id, target_id, true);
+
+        CrateBuilder::new("foo_target_only", target_id).expect_build(conn);
+    });
+
+    let body = json!({ "source_login": "old-account", "target_login": "new-account" });
+    let response = merge(&admin, &body);
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.into_json(),
+        json!({ "crates": 2, "api_tokens": 0, "email_moved": false })
+    );
+
+    assert_eq!(owned_crates(&app, source_id), Vec::<String>::new());
+    assert_eq!(
+        owned_crates(&app, target_id),
+        vec!["foo_both", "foo_removed", "foo_target_only"]
+    );
+
+    // The target keeps its own email address
+    let email_users: Vec<i32> = app.db(|conn| {
+        emails::table
+            .filter(emails::user_id.eq_any([source_id, target_id]))
+            .order(emails::user_id)
+            .select(emails::user_id)
+            .load(conn)
+            .unwrap()
+    });
+    assert_eq!(email_users, vec![source_id, target_id]);
+}
+
+#[test]
+fn merge_requires_an_admin_and_two_accounts() {
+    let (app, _, admin) = TestApp::init().with_user();
+    let source = app.db_new_user("old-account");
+    app.db_new_user("new-account");
+
+    let body = json!({ "source_login": "old-account", "target_login": "new-account" });
+    assert_eq!(merge(&admin, &body).status(), StatusCode::FORBIDDEN);
+    assert_eq!(merge(&source, &body).status(), StatusCode::FORBIDDEN);
+
+    admin.make_admin();
+
+    let body = json!({ "source_login": "old-account", "target_login": "OLD-account" });
+    let response = merge(&admin, &body);
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(
+        response.into_json(),
+        json!({ "errors": [{ "detail": "an account can't be merged into itself" }] })
+    );
+
+    let body = json!({ "source_login": "old-account", "target_login": "nobody" });
+    merge(&admin, &body).assert_not_found();
+
+    // Nothing was changed by the failed merges
+    source.get::<serde_json::Value>("/api/v1/me").good();
+}
+
+fn merge(user: &MockCookieUser, body: &serde_json::Value) -> Response<()> {
+    let mut request = user.post_request(URL);
+    request.with_body(body.to_string().as_bytes());
+    user.run(request)
+}
+
+fn add_user_owner(conn: &mut PgConnection, crate_id: i32, user_id: i32, deleted: bool) {
+    diesel::insert_into(crate_owners::table)
+        .values((
+            crate_owners::crate_id.eq(crate_id),
+            crate_owners::owner_id.eq(user_id),
+            crate_owners::owner_kind.eq(0),
+            crate_owners::deleted.eq(deleted),
+        ))
+        .execute(conn)
+        .unwrap();
+}
+
+fn owned_crates(app: &TestApp, user_id: i32) -> Vec<String> {
+    app.db(|conn| {
+        crate_owners::table
+            .inner_join(crates::table)
+            .filter(crate_owners::owner_id.eq(user_id))
+            .filter(crate_owners::owner_kind.eq(0))
+            .filter(crate_owners::deleted.eq(false))
+            .order(crates::name)
+            .select(crates::name)
+            .load(conn)
+            .unwrap()
+    })
+}
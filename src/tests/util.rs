@@ -61,11 +61,26 @@ pub use test_app::{TestApp, TestDatabase};
 /// The implementation matches roughly what is happening inside of our
 /// session middleware.
 pub fn encode_session_header(session_key: &cookie::Key, user_id: i32) -> String {
+    let authenticated_at = chrono::Utc::now().naive_utc();
+    encode_session_header_at(session_key, user_id, authenticated_at)
+}
+
+/// Like `encode_session_header`, but for a session where the user logged in at
+/// `authenticated_at`.
+pub fn encode_session_header_at(
+    session_key: &cookie::Key,
+    user_id: i32,
+    authenticated_at: chrono::NaiveDateTime,
+) -> String {
     let cookie_name = "cargo_session";
 
     // build session data map
     let mut map = HashMap::new();
     map.insert("user_id".into(), user_id.to_string());
+    map.insert(
+        "authenticated_at".into(),
+        authenticated_at.timestamp().to_string(),
+    );
 
     // encode the map into a cookie value string
     let encoded = session::encode(&map);
@@ -288,14 +303,54 @@ impl MockCookieUser {
         endpoint_scopes: Option<Vec<EndpointScope>>,
     ) -> MockTokenUser {
         let token = self.app.db(|conn| {
-            ApiToken::insert_with_scopes(conn, self.user.id, name, crate_scopes, endpoint_scopes)
-                .unwrap()
+            ApiToken::insert_with_scopes(
+                conn,
+                self.user.id,
+                name,
+                crate_scopes,
+                endpoint_scopes,
+                None,
+            )
+            .unwrap()
         });
         MockTokenUser {
             app: self.app.clone(),
             token,
         }
     }
+
+    /// Makes the user a crates.io administrator
+    ///
+    /// This method updates the database directly
+    pub fn make_admin(&self) {
+        use cargo_registry::schema::users;
+        use diesel::prelude::*;
+
+        self.app.db(|conn| {
+            diesel::update(users::table.find(self.user.id))
+                .set(users::is_admin.eq(true))
+                .execute(conn)
+                .unwrap();
+        });
+    }
+
+    /// Returns the token that confirms the deletion of a crate, or an empty string if the
+    /// deletion can't be confirmed, e.g. because the crate doesn't exist
+    pub fn delete_confirmation(&self, crate_name: &str) -> String {
+        let url = format!("/api/v1/crates/{crate_name}/delete/confirm");
+        let json = self.run::<()>(self.post_request(&url)).into_json();
+        json["token"].as_str().unwrap_or_default().to_string()
+    }
+
+    /// Confirms the deletion of a crate and deletes it
+    #[track_caller]
+    pub fn delete_crate(&self, crate_name: &str) -> Response<()> {
+        let url = format!("/api/v1/crates/{crate_name}");
+        let mut request = self.request_builder(Method::DELETE, &url);
+        let token = self.delete_confirmation(crate_name);
+        request.header("X-Crates-Io-Delete-Confirmation", &token);
+        self.run(request)
+    }
 }
 
 /// A type that can generate token authenticated requests
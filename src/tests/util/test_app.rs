@@ -1,19 +1,24 @@
 use super::{MockAnonymousUser, MockCookieUser, MockTokenUser};
 use crate::record;
 use crate::util::{chaosproxy::ChaosProxy, fresh_schema::FreshSchema};
-use cargo_registry::config::{self, BalanceCapacityConfig, DbPoolConfig};
+use cargo_registry::config::{self, BalanceCapacityConfig, DbPoolConfig, FeedConfig};
+use cargo_registry::rate_limiter::{LimitedAction, RateLimitTier, RateLimiterConfig};
 use cargo_registry::{background_jobs::Environment, App, Emails};
 use cargo_registry_index::testing::UpstreamIndex;
 use cargo_registry_index::{Credentials, Repository as WorkerRepository, RepositoryConfig};
 use std::{rc::Rc, sync::Arc, time::Duration};
 
 use crate::util::github::{MockGitHubClient, MOCK_GITHUB_DATA};
+use cargo_registry::models::category::MAX_CATEGORIES;
+use cargo_registry::models::krate::MAX_NAME_LENGTH;
 use cargo_registry::models::token::{CrateScope, EndpointScope};
 use cargo_registry::swirl::Runner;
 use diesel::PgConnection;
+use http::HeaderName;
 use oauth2::{ClientId, ClientSecret};
 use reqwest::{blocking::Client, Proxy};
 use std::collections::HashSet;
+use tempfile::TempDir;
 
 struct TestAppInner {
     app: Arc<App>,
@@ -22,6 +27,8 @@ struct TestAppInner {
     router: axum::Router,
     index: Option<UpstreamIndex>,
     runner: Option<Runner>,
+    // The directory of the local storage backend (if used) is removed when this is dropped.
+    _local_storage: Option<TempDir>,
 
     primary_db_chaosproxy: Option<Arc<ChaosProxy>>,
     replica_db_chaosproxy: Option<Arc<ChaosProxy>>,
@@ -75,6 +82,7 @@ impl TestApp {
             index: None,
             build_job_runner: false,
             test_database: TestDatabase::TestPool,
+            local_storage: None,
         }
     }
 
@@ -140,6 +148,28 @@ impl TestApp {
             .unwrap()
     }
 
+    /// Obtain the paths of all files in the default bucket of the storage backend, sorted
+    pub fn stored_files(&self) -> Vec<String> {
+        let uploader = self.0.app.config.uploader();
+        let client = self.0.app.http_client();
+
+        let mut paths = Vec::new();
+        let mut continuation_token = None;
+        loop {
+            let (files, next) = uploader
+                .list_files(client, "", continuation_token.as_deref())
+                .unwrap();
+            paths.extend(files.into_iter().map(|file| file.path));
+            match next {
+                Some(next) => continuation_token = Some(next),
+                None => break,
+            }
+        }
+
+        paths.sort();
+        paths
+    }
+
     #[track_caller]
     pub fn run_pending_background_jobs(&self) {
         let runner = &self.0.runner;
@@ -192,6 +222,7 @@ pub struct TestAppBuilder {
     index: Option<UpstreamIndex>,
     build_job_runner: bool,
     test_database: TestDatabase,
+    local_storage: Option<TempDir>,
 }
 
 impl TestAppBuilder {
@@ -239,7 +270,14 @@ impl TestAppBuilder {
                 app.config.uploader().clone(),
                 app.http_client().clone(),
                 None,
-            );
+                app.config.feeds.clone(),
+                app.emails.clone(),
+                app.config.crate_deletion_grace_period,
+                app.config.crate_deletion_audit_retention,
+                app.config.ownership_invitations_expiration_days,
+                app.config.mirror.clone(),
+            )
+            .send_webhooks_with_http_client();
 
             Some(Runner::test_runner(
                 environment,
@@ -256,6 +294,7 @@ impl TestAppBuilder {
             router,
             index: self.index,
             runner,
+            _local_storage: self.local_storage,
             primary_db_chaosproxy,
             replica_db_chaosproxy,
         };
@@ -305,10 +344,26 @@ impl TestAppBuilder {
         self
     }
 
-    pub fn with_publish_rate_limit(self, rate: Duration, burst: i32) -> Self {
+    pub fn with_rate_limit(self, action: LimitedAction, rate: Duration, burst: i32) -> Self {
         self.with_config(|config| {
-            config.publish_rate_limit.rate = rate;
-            config.publish_rate_limit.burst = burst;
+            config.rate_limiter.insert(
+                (action, RateLimitTier::Unverified),
+                RateLimiterConfig { rate, burst },
+            );
+        })
+    }
+
+    pub fn with_verified_rate_limit(
+        self,
+        action: LimitedAction,
+        rate: Duration,
+        burst: i32,
+    ) -> Self {
+        self.with_config(|config| {
+            config.rate_limiter.insert(
+                (action, RateLimitTier::Verified),
+                RateLimiterConfig { rate, burst },
+            );
         })
     }
 
@@ -322,6 +377,16 @@ impl TestAppBuilder {
         self
     }
 
+    /// Stores the uploaded files in a temporary directory instead of the S3 bucket, so that the
+    /// test doesn't need an HTTP recording for them
+    pub fn with_local_storage(mut self) -> Self {
+        let local_storage = TempDir::new().unwrap();
+        let root = local_storage.path().to_path_buf();
+        self.config.base = config::Base::test_with_local_storage(root);
+        self.local_storage = Some(local_storage);
+        self
+    }
+
     /// Configures the test database
     pub fn with_database(mut self, test_database: TestDatabase) -> Self {
         self.config.use_test_database_pool = false;
@@ -339,7 +404,7 @@ fn simple_config() -> config::Server {
         gh_client_secret: ClientSecret::new(dotenv::var("GH_CLIENT_SECRET").unwrap_or_default()),
         max_upload_size: 3000,
         max_unpack_size: 2000,
-        publish_rate_limit: Default::default(),
+        rate_limiter: Default::default(),
         new_version_rate_limit: Some(10),
         blocked_traffic: Default::default(),
         max_allowed_page_offset: 200,
@@ -359,6 +424,21 @@ fn simple_config() -> config::Server {
         version_id_cache_ttl: Duration::from_secs(5 * 60),
         cdn_user_agent: "Amazon CloudFront".to_string(),
         balance_capacity: BalanceCapacityConfig::for_testing(),
+        feeds: FeedConfig::for_testing(),
+        crate_deletion_grace_period: Duration::from_secs(24 * 60 * 60),
+        crate_deletion_confirmation_expiration: Duration::from_secs(5 * 60),
+        crate_deletion_max_auth_age: Duration::from_secs(60 * 60),
+        crate_deletion_audit_retention: Duration::from_secs(365 * 24 * 60 * 60),
+        crate_deletion_team_window: Duration::from_secs(24 * 60 * 60),
+        crate_deletion_allow_unused: false,
+        max_crate_name_length: MAX_NAME_LENGTH,
+        mirror: None,
+        idempotency_key_expiration: Duration::from_secs(24 * 60 * 60),
+        max_categories_per_crate: MAX_CATEGORIES,
+        max_account_lock_duration: Duration::from_secs(90 * 24 * 60 * 60),
+        max_batch_crates: 100,
+        anonymous_rate_limited_routes: HashSet::new(),
+        client_ip_header: HeaderName::from_static("x-real-ip"),
     }
 }
 
@@ -376,7 +456,7 @@ fn build_app(config: config::Server, proxy: Option<String>) -> (Arc<App>, axum::
 
     // Use the in-memory email backend for all tests, allowing tests to analyze the emails sent by
     // the application. This will also prevent cluttering the filesystem.
-    app.emails = Emails::new_in_memory();
+    app.emails = Arc::new(Emails::new_in_memory());
 
     // Use a custom mock for the GitHub client, allowing to define the GitHub users and
     // organizations without actually having to create GitHub accounts.
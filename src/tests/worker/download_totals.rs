@@ -0,0 +1,94 @@
+use crate::builders::{CrateBuilder, VersionBuilder};
+use crate::util::TestApp;
+use cargo_registry::schema::{crates, metadata, versions};
+use cargo_registry::worker;
+use diesel::prelude::*;
+
+fn set_downloads(app: &TestApp, crate_name: &str, crate_downloads: i32, version_downloads: i32) {
+    app.db(|conn| {
+        let crate_id = diesel::update(crates::table.filter(crates::name.eq(crate_name)))
+            .set(crates::downloads.eq(crate_downloads))
+            .returning(crates::id)
+            .get_result::<i32>(conn)
+            .unwrap();
+        diesel::update(versions::table.filter(versions::crate_id.eq(crate_id)))
+            .set(versions::downloads.eq(version_downloads))
+            .execute(conn)
+            .unwrap();
+    });
+}
+
+fn crate_downloads(app: &TestApp, crate_name: &str) -> i32 {
+    app.db(|conn| {
+        crates::table
+            .filter(crates::name.eq(crate_name))
+            .select(crates::downloads)
+            .first(conn)
+            .unwrap()
+    })
+}
+
+fn setup() -> TestApp {
+    let (app, _, user) = TestApp::full().with_user();
+
+    app.db(|conn| {
+        for name in ["foo_matching", "foo_drifted", "foo_slightly_drifted"] {
+            CrateBuilder::new(name, user.as_model().id)
+                .version(VersionBuilder::new("1.0.0"))
+                .version(VersionBuilder::new("1.1.0"))
+                .expect_build(conn);
+        }
+        diesel::update(metadata::table)
+            .set(metadata::total_downloads.eq(1000))
+            .execute(conn)
+            .unwrap();
+    });
+
+    set_downloads(&app, "foo_matching", 20, 10);
+    set_downloads(&app, "foo_drifted", 100, 10);
+    set_downloads(&app, "foo_slightly_drifted", 25, 10);
+
+    app
+}
+
+#[test]
+fn mismatched_download_totals_are_only_reported_by_default() {
+    let app = setup();
+
+    app.db(|conn| {
+        worker::verify_download_totals(1, 0, false)
+            .enqueue(conn)
+            .unwrap();
+    });
+    app.run_pending_background_jobs();
+
+    assert_eq!(crate_downloads(&app, "foo_matching"), 20);
+    assert_eq!(crate_downloads(&app, "foo_drifted"), 100);
+    assert_eq!(crate_downloads(&app, "foo_slightly_drifted"), 25);
+}
+
+#[test]
+fn mismatched_download_totals_can_be_fixed() {
+    let app = setup();
+
+    // Each job checks a single crate, so this also checks that the batches cover all crates
+    app.db(|conn| {
+        worker::verify_download_totals(1, 10, true)
+            .enqueue(conn)
+            .unwrap();
+    });
+    app.run_pending_background_jobs();
+
+    assert_eq!(crate_downloads(&app, "foo_matching"), 20);
+    assert_eq!(crate_downloads(&app, "foo_drifted"), 20);
+    // The difference is within the threshold
+    assert_eq!(crate_downloads(&app, "foo_slightly_drifted"), 25);
+
+    let total_downloads: i64 = app.db(|conn| {
+        metadata::table
+            .select(metadata::total_downloads)
+            .first(conn)
+            .unwrap()
+    });
+    assert_eq!(total_downloads, 920);
+}
@@ -0,0 +1,225 @@
+use crate::builders::{CrateBuilder, PublishBuilder, VersionBuilder};
+use crate::new_category;
+use crate::util::{RequestHelper, TestApp};
+use cargo_registry::schema::{crates, versions};
+use cargo_registry::views::GoodCrate;
+use cargo_registry::worker;
+use chrono::NaiveDate;
+use diesel::prelude::*;
+
+#[test]
+fn category_feed_includes_subcategories() {
+    let (app, _, user) = TestApp::full().with_user();
+    let user_id = user.as_model().id;
+
+    let published_at = |day| {
+        NaiveDate::from_ymd_opt(2023, 3, day)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap()
+    };
+
+    app.db(|conn| {
+        new_category("Category 1", "cat1", "Category 1 crates")
+            .create_or_update(conn)
+            .unwrap();
+        new_category("Category 1::Subcategory", "cat1::sub", "Subcategory crates")
+            .create_or_update(conn)
+            .unwrap();
+        new_category("Category 2", "cat2", "Category 2 crates")
+            .create_or_update(conn)
+            .unwrap();
+
+        CrateBuilder::new("foo", user_id)
+            .description("The foo crate")
+            .category("cat1")
+            .version(VersionBuilder::new("1.0.0").created_at(published_at(1)))
+            .version(
+                VersionBuilder::new("1.1.0")
+                    .created_at(published_at(2))
+                    .yanked(true),
+            )
+            .expect_build(conn);
+        CrateBuilder::new("bar", user_id)
+            .category("cat1::sub")
+            .version(VersionBuilder::new("0.1.0").created_at(published_at(3)))
+            .expect_build(conn);
+        CrateBuilder::new("baz", user_id)
+            .category("cat2")
+            .version(VersionBuilder::new("2.0.0").created_at(published_at(4)))
+            .expect_build(conn);
+
+        worker::sync_category_feed("cat1".into())
+            .enqueue(conn)
+            .unwrap();
+    });
+
+    // The HTTP recording asserts that the feed contains `foo v1.0.0` and `bar v0.1.0`, but
+    // neither the yanked version nor the crate from the other category.
+    app.run_pending_background_jobs();
+}
+
+#[test]
+fn user_feed_lists_published_crates() {
+    let (app, _, _, token) = TestApp::full()
+        .with_config(|config| config.feeds.user_feed_length = 10)
+        .with_token();
+
+    for name in ["alpha", "beta"] {
+        let crate_to_publish = PublishBuilder::new(name).description(&format!("The {name} crate"));
+        token
+            .put::<GoodCrate>("/api/v1/crates/new", &crate_to_publish.body())
+            .good();
+    }
+
+    // Use fixed publish times, since they are included in the recorded feed
+    app.db(|conn| {
+        for (name, day) in [("alpha", 1), ("beta", 2)] {
+            let published_at = NaiveDate::from_ymd_opt(2023, 3, day)
+                .unwrap()
+                .and_hms_opt(12, 0, 0)
+                .unwrap();
+            let crate_id = crates::table
+                .filter(crates::name.eq(name))
+                .select(crates::id);
+            diesel::update(versions::table.filter(versions::crate_id.eq_any(crate_id)))
+                .set(versions::created_at.eq(published_at))
+                .execute(conn)
+                .unwrap();
+        }
+    });
+
+    // The HTTP recording asserts that `rss/users/foo.xml` lists both `beta v1.0.0` and
+    // `alpha v1.0.0`.
+    app.run_pending_background_jobs();
+}
+
+#[test]
+fn crates_feeds_are_rendered_as_rss_and_atom() {
+    let (app, _, user) = TestApp::full().with_user();
+    let user_id = user.as_model().id;
+
+    let published_at = |day| {
+        NaiveDate::from_ymd_opt(2023, 3, day)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap()
+    };
+
+    app.db(|conn| {
+        let krate_foo = CrateBuilder::new("foo", user_id)
+            .description("The foo crate")
+            .version(VersionBuilder::new("1.0.0").created_at(published_at(1)))
+            .version(VersionBuilder::new("1.1.0").created_at(published_at(3)))
+            .expect_build(conn);
+        let krate_bar = CrateBuilder::new("bar", user_id)
+            .version(VersionBuilder::new("0.1.0").created_at(published_at(2)))
+            .expect_build(conn);
+
+        for (krate, day) in [(krate_foo, 1), (krate_bar, 2)] {
+            diesel::update(crates::table.find(krate.id))
+                .set(crates::created_at.eq(published_at(day)))
+                .execute(conn)
+                .unwrap();
+        }
+
+        worker::sync_crates_feeds().enqueue(conn).unwrap();
+    });
+
+    // The HTTP recording asserts that `rss/crates.xml` and `atom/crates.xml` list `bar` and
+    // `foo`, and that `rss/updates.xml` and `atom/updates.xml` list all three versions.
+    app.run_pending_background_jobs();
+}
+
+#[test]
+fn crates_feeds_are_limited_to_the_configured_length() {
+    let (app, _, _, token) = TestApp::full()
+        .with_config(|config| {
+            config.feeds.crates_feed_length = 2;
+            config.feeds.updates_feed_length = 1;
+        })
+        .with_token();
+
+    for name in ["alpha", "beta", "gamma"] {
+        let crate_to_publish = PublishBuilder::new(name);
+        token.publish_crate(crate_to_publish).good();
+    }
+
+    let published_at = |day| {
+        NaiveDate::from_ymd_opt(2023, 3, day)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap()
+    };
+
+    // Use fixed publish times, since they are included in the recorded feeds
+    app.db(|conn| {
+        for (name, day) in [("alpha", 1), ("beta", 2), ("gamma", 3)] {
+            let crate_id = crates::table
+                .filter(crates::name.eq(name))
+                .select(crates::id);
+            diesel::update(crates::table.filter(crates::name.eq(name)))
+                .set(crates::created_at.eq(published_at(day)))
+                .execute(conn)
+                .unwrap();
+            diesel::update(versions::table.filter(versions::crate_id.eq_any(crate_id)))
+                .set(versions::created_at.eq(published_at(day)))
+                .execute(conn)
+                .unwrap();
+        }
+
+        worker::sync_crates_feeds().enqueue(conn).unwrap();
+    });
+
+    // The HTTP recording asserts that the `crates` feeds only list `gamma` and `beta`, and that
+    // the `updates` feeds only list `gamma v1.0.0`.
+    app.run_pending_background_jobs();
+}
+
+/// Publishes `foo v1.0.0` and the yanked `foo v1.1.0`, and regenerates the global feeds.
+fn sync_crates_feeds_with_yanked_version(app: &TestApp, user_id: i32) {
+    let published_at = |day| {
+        NaiveDate::from_ymd_opt(2023, 3, day)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap()
+    };
+
+    app.db(|conn| {
+        let krate = CrateBuilder::new("foo", user_id)
+            .version(VersionBuilder::new("1.0.0").created_at(published_at(1)))
+            .version(
+                VersionBuilder::new("1.1.0")
+                    .created_at(published_at(2))
+                    .yanked(true),
+            )
+            .expect_build(conn);
+        diesel::update(crates::table.find(krate.id))
+            .set(crates::created_at.eq(published_at(1)))
+            .execute(conn)
+            .unwrap();
+
+        worker::sync_crates_feeds().enqueue(conn).unwrap();
+    });
+
+    app.run_pending_background_jobs();
+}
+
+#[test]
+fn updates_feed_excludes_yanked_versions() {
+    let (app, _, user) = TestApp::full().with_user();
+
+    // The HTTP recording asserts that the `updates` feeds only list `foo v1.0.0`.
+    sync_crates_feeds_with_yanked_version(&app, user.as_model().id);
+}
+
+#[test]
+fn updates_feed_includes_yanked_versions_if_configured() {
+    let (app, _, user) = TestApp::full()
+        .with_config(|config| config.feeds.include_yanked = true)
+        .with_user();
+
+    // The HTTP recording asserts that the `updates` feeds list `foo v1.1.0 (yanked)` and
+    // `foo v1.0.0`.
+    sync_crates_feeds_with_yanked_version(&app, user.as_model().id);
+}
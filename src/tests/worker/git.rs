@@ -0,0 +1,27 @@
+use crate::util::TestApp;
+use cargo_registry::schema::background_jobs;
+use cargo_registry::worker;
+use diesel::prelude::*;
+
+#[test]
+fn index_syncs_for_the_same_crate_are_coalesced() {
+    let (app, _) = TestApp::full().empty();
+
+    app.db(|conn| {
+        worker::update_crate_index("foo".into())
+            .enqueue(conn)
+            .unwrap();
+        worker::update_crate_index("foo".into())
+            .enqueue(conn)
+            .unwrap();
+        worker::update_crate_index("bar".into())
+            .enqueue(conn)
+            .unwrap();
+
+        let job_count: i64 = background_jobs::table.count().get_result(conn).unwrap();
+        assert_eq!(job_count, 2);
+    });
+
+    // The HTTP recording asserts that the index file of `foo` is only synced once.
+    app.run_pending_background_jobs();
+}
@@ -0,0 +1,74 @@
+use crate::builders::PublishBuilder;
+use crate::util::{MockTokenUser, RequestHelper, TestApp};
+use cargo_registry::schema::{crates, versions};
+use cargo_registry::worker;
+use diesel::prelude::*;
+
+fn setup() -> TestApp {
+    let (app, _, _, token) = TestApp::full().with_token();
+
+    for name in ["foo_consistent", "foo_drifted"] {
+        publish(&token, name);
+    }
+    app.run_pending_background_jobs();
+
+    // Simulate the index drifting from the database
+    app.db(|conn| {
+        let crate_id = crates::table
+            .filter(crates::name.eq("foo_drifted"))
+            .select(crates::id)
+            .first::<i32>(conn)
+            .unwrap();
+        diesel::update(versions::table.filter(versions::crate_id.eq(crate_id)))
+            .set(versions::yanked.eq(true))
+            .execute(conn)
+            .unwrap();
+    });
+
+    app
+}
+
+fn publish(token: &MockTokenUser, name: &str) {
+    token
+        .publish_crate(PublishBuilder::new(name).version("1.0.0"))
+        .good();
+}
+
+fn index_yanked(app: &TestApp, crate_name: &str) -> Option<bool> {
+    let crates = app.crates_from_index_head(crate_name);
+    assert_eq!(crates.len(), 1);
+    crates[0].yanked
+}
+
+#[test]
+fn inconsistent_index_files_are_only_reported_by_default() {
+    let app = setup();
+
+    app.db(|conn| {
+        worker::verify_index_consistency(1, false)
+            .enqueue(conn)
+            .unwrap();
+    });
+    app.run_pending_background_jobs();
+
+    // The HTTP recording asserts that no index file is synced
+    assert_eq!(index_yanked(&app, "foo_consistent"), Some(false));
+    assert_eq!(index_yanked(&app, "foo_drifted"), Some(false));
+}
+
+#[test]
+fn inconsistent_index_files_can_be_fixed() {
+    let app = setup();
+
+    // Each job checks a single crate, so this also checks that the batches cover all crates
+    app.db(|conn| {
+        worker::verify_index_consistency(1, true)
+            .enqueue(conn)
+            .unwrap();
+    });
+    app.run_pending_background_jobs();
+
+    // The HTTP recording asserts that only the index file of `foo_drifted` is synced
+    assert_eq!(index_yanked(&app, "foo_consistent"), Some(false));
+    assert_eq!(index_yanked(&app, "foo_drifted"), Some(true));
+}
@@ -0,0 +1,121 @@
+use crate::builders::CrateBuilder;
+use crate::util::TestApp;
+use cargo_registry::config::MirrorConfig;
+use cargo_registry::schema::crates;
+use cargo_registry::worker;
+use chrono::{NaiveDate, NaiveDateTime};
+use diesel::prelude::*;
+use diesel::sql_types::{Jsonb, Text};
+use http::StatusCode;
+
+fn mirror() -> MirrorConfig {
+    MirrorConfig {
+        deletion_webhook_url: "http://mirror.example.com/webhooks/deletion".into(),
+        webhook_secret: "secret".into(),
+    }
+}
+
+fn deleted_at() -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(2023, 4, 1)
+        .unwrap()
+        .and_hms_opt(12, 0, 0)
+        .unwrap()
+}
+
+#[test]
+fn deletion_webhook_is_signed() {
+    let (app, _) = TestApp::full()
+        .with_config(|config| config.mirror = Some(mirror()))
+        .empty();
+
+    app.db(|conn| {
+        worker::notify_mirror_of_deletion("foo".into(), deleted_at(), "malware".into())
+            .enqueue(conn)
+            .unwrap();
+    });
+
+    // The HTTP recording asserts the JSON payload and its `x-crates-io-signature` header
+    app.run_pending_background_jobs();
+}
+
+#[test]
+fn restoration_webhook_is_signed() {
+    let (app, _) = TestApp::full()
+        .with_config(|config| config.mirror = Some(mirror()))
+        .empty();
+
+    app.db(|conn| {
+        worker::notify_mirror_of_restoration("foo".into(), deleted_at())
+            .enqueue(conn)
+            .unwrap();
+    });
+
+    // The HTTP recording asserts the JSON payload and that it is sent to the same URL as
+    // deletions, with `restored` in the `x-cratesio-event` header
+    app.run_pending_background_jobs();
+}
+
+#[test]
+fn deletion_webhook_is_skipped_without_mirror() {
+    let (app, _) = TestApp::full().empty();
+
+    app.db(|conn| {
+        worker::notify_mirror_of_deletion("foo".into(), deleted_at(), "malware".into())
+            .enqueue(conn)
+            .unwrap();
+    });
+
+    // There is no HTTP recording, so any request would fail the test
+    app.run_pending_background_jobs();
+}
+
+#[test]
+fn deleting_a_crate_notifies_the_mirror() {
+    let (app, _, user) = TestApp::full()
+        .with_config(|config| config.mirror = Some(mirror()))
+        .with_user();
+
+    app.db(|conn| {
+        CrateBuilder::new("foo_mirrored", user.as_model().id).expect_build(conn);
+    });
+
+    let response = user.delete_crate("foo_mirrored");
+    assert_eq!(response.status(), StatusCode::OK);
+
+    app.db(|conn| {
+        use cargo_registry::schema::background_jobs::dsl::*;
+
+        let job: serde_json::Value = background_jobs
+            .filter(job_type.eq("notify_mirror_of_deletion"))
+            .select(data)
+            .first(conn)
+            .unwrap();
+        let crate_deleted_at: Option<NaiveDateTime> = crates::table
+            .filter(crates::name.eq("foo_mirrored"))
+            .select(crates::deleted_at)
+            .first(conn)
+            .unwrap();
+        assert_eq!(
+            job,
+            json!({
+                "crate_name": "foo_mirrored",
+                "deleted_at": crate_deleted_at.unwrap(),
+                "reason": "deleted by an owner",
+            })
+        );
+
+        // Pin the deletion time, so that the recorded webhook doesn't change between runs
+        diesel::update(background_jobs.filter(job_type.eq("notify_mirror_of_deletion")))
+            .set(
+                data.eq(
+                    diesel::dsl::sql::<Jsonb>("jsonb_set(data, '{deleted_at}', ")
+                        .bind::<Text, _>(serde_json::to_string(&deleted_at()).unwrap())
+                        .sql("::jsonb)"),
+                ),
+            )
+            .execute(conn)
+            .unwrap();
+    });
+
+    app.run_pending_background_jobs();
+}
@@ -0,0 +1,8 @@
+mod download_totals;
+mod feeds;
+mod git;
+mod index_consistency;
+mod mirror;
+mod scheduler;
+mod storage;
+mod tokens;
@@ -0,0 +1,53 @@
+use crate::util::TestApp;
+use cargo_registry::schema::background_jobs;
+use cargo_registry::swirl::{ScheduledJob, Scheduler};
+use cargo_registry::worker;
+use chrono::{DateTime, TimeZone, Utc};
+use diesel::prelude::*;
+
+fn time(hour: u32, minute: u32, second: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2023, 3, 24, hour, minute, second)
+        .unwrap()
+}
+
+/// Returns the number of enqueued jobs, and removes them from the queue again.
+fn take_enqueued_jobs(conn: &mut PgConnection) -> usize {
+    diesel::delete(background_jobs::table)
+        .execute(conn)
+        .unwrap()
+}
+
+#[test]
+fn scheduled_jobs_are_enqueued_when_due() {
+    let (app, _) = TestApp::init().empty();
+
+    let scheduler = || {
+        Scheduler::new(vec![ScheduledJob::new(
+            "update_downloads",
+            "0 0 * * * *",
+            worker::update_downloads,
+        )])
+    };
+
+    app.db(|conn| {
+        // The first run only records the current time
+        scheduler().enqueue_due_jobs(conn, time(10, 30, 0)).unwrap();
+        assert_eq!(take_enqueued_jobs(conn), 0);
+
+        scheduler()
+            .enqueue_due_jobs(conn, time(10, 59, 59))
+            .unwrap();
+        assert_eq!(take_enqueued_jobs(conn), 0);
+
+        scheduler().enqueue_due_jobs(conn, time(11, 0, 0)).unwrap();
+        assert_eq!(take_enqueued_jobs(conn), 1);
+
+        // A restarted worker must not enqueue the same tick again
+        scheduler().enqueue_due_jobs(conn, time(11, 0, 30)).unwrap();
+        assert_eq!(take_enqueued_jobs(conn), 0);
+
+        // Missed ticks result in a single job
+        scheduler().enqueue_due_jobs(conn, time(14, 15, 0)).unwrap();
+        assert_eq!(take_enqueued_jobs(conn), 1);
+    });
+}
@@ -0,0 +1,116 @@
+use crate::builders::{CrateBuilder, PublishBuilder, VersionBuilder};
+use crate::util::{RequestHelper, TestApp};
+use cargo_registry::worker;
+use chrono::{NaiveDate, Utc};
+
+#[test]
+fn delete_version_from_storage() {
+    let (app, _, _, token) = TestApp::full().with_token();
+
+    let crate_to_publish = PublishBuilder::new("foo_delete").readme("");
+    token.publish_crate(crate_to_publish).good();
+
+    let crate_to_publish = PublishBuilder::new("foo_delete").version("1.1.0");
+    token.publish_crate(crate_to_publish).good();
+
+    // The HTTP recording asserts that only the files of `1.1.0` are deleted. `1.1.0` has no
+    // rendered readme, so that request responds with a 404, which must not fail the job.
+    app.db(|conn| {
+        worker::delete_version_from_storage("foo_delete".into(), "1.1.0".into())
+            .enqueue(conn)
+            .unwrap();
+    });
+    app.run_pending_background_jobs();
+}
+
+#[test]
+fn files_are_stored_and_deleted_with_local_storage() {
+    let (app, _, _, token) = TestApp::full().with_local_storage().with_token();
+
+    let crate_to_publish = PublishBuilder::new("foo_local").readme("# foo_local");
+    token.publish_crate(crate_to_publish).good();
+
+    let crate_to_publish = PublishBuilder::new("foo_local").version("1.1.0");
+    token.publish_crate(crate_to_publish).good();
+    app.run_pending_background_jobs();
+
+    assert_eq!(
+        app.stored_files(),
+        vec![
+            "crates/foo_local/foo_local-1.0.0.crate",
+            "crates/foo_local/foo_local-1.1.0.crate",
+            "readmes/foo_local/foo_local-1.0.0.html",
+        ]
+    );
+
+    app.db(|conn| {
+        worker::delete_version_from_storage("foo_local".into(), "1.0.0".into())
+            .enqueue(conn)
+            .unwrap();
+    });
+    app.run_pending_background_jobs();
+
+    assert_eq!(
+        app.stored_files(),
+        vec!["crates/foo_local/foo_local-1.1.0.crate"]
+    );
+
+    // `1.1.0` has no readme, which must not fail the job
+    app.db(|conn| {
+        worker::delete_version_from_storage("foo_local".into(), "1.1.0".into())
+            .enqueue(conn)
+            .unwrap();
+    });
+    app.run_pending_background_jobs();
+
+    assert!(app.stored_files().is_empty());
+}
+
+/// Returns a `min_age_hours` for which the files in the HTTP recordings that were modified before
+/// March 2023 count as old enough to be removed, while the ones modified after it don't.
+fn min_age_hours() -> i64 {
+    let threshold = NaiveDate::from_ymd_opt(2023, 3, 1)
+        .unwrap()
+        .and_hms_opt(0, 0, 0)
+        .unwrap();
+    (Utc::now().naive_utc() - threshold).num_hours()
+}
+
+#[test]
+fn orphaned_files_are_removed_from_storage() {
+    let (app, _, user) = TestApp::full().with_user();
+
+    app.db(|conn| {
+        CrateBuilder::new("foo_valid", user.as_model().id)
+            .version(VersionBuilder::new("1.0.0"))
+            .expect_build(conn);
+    });
+
+    // The HTTP recording asserts that only the old files without a version in the database are
+    // deleted, and that all pages of the listing are processed.
+    app.db(|conn| {
+        worker::sweep_orphaned_storage(min_age_hours(), false)
+            .enqueue(conn)
+            .unwrap();
+    });
+    app.run_pending_background_jobs();
+}
+
+#[test]
+fn orphaned_files_are_kept_in_a_dry_run() {
+    let (app, _, user) = TestApp::full().with_user();
+
+    app.db(|conn| {
+        CrateBuilder::new("foo_valid", user.as_model().id)
+            .version(VersionBuilder::new("1.0.0"))
+            .expect_build(conn);
+    });
+
+    // The HTTP recording only contains the listing requests, so any deletion would fail the test
+    app.db(|conn| {
+        worker::sweep_orphaned_storage(min_age_hours(), true)
+            .enqueue(conn)
+            .unwrap();
+    });
+    app.run_pending_background_jobs();
+}
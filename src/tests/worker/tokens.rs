@@ -0,0 +1,39 @@
+use crate::util::TestApp;
+use cargo_registry::models::ApiToken;
+use cargo_registry::schema::api_tokens;
+use cargo_registry::worker;
+use chrono::{Duration, Utc};
+use diesel::prelude::*;
+
+#[test]
+fn prune_expired_tokens() {
+    let (app, _, user) = TestApp::full().with_user();
+    let user_id = user.as_model().id;
+
+    let now = Utc::now().naive_utc();
+    app.db(|conn| {
+        for (name, expired_at) in [
+            ("never", None),
+            ("recently", Some(now - Duration::days(1))),
+            ("long ago", Some(now - Duration::days(60))),
+        ] {
+            let token = ApiToken::insert(conn, user_id, name).unwrap();
+            diesel::update(api_tokens::table.find(token.model.id))
+                .set(api_tokens::expired_at.eq(expired_at))
+                .execute(conn)
+                .unwrap();
+        }
+
+        worker::prune_expired_tokens().enqueue(conn).unwrap();
+    });
+    app.run_pending_background_jobs();
+
+    let names: Vec<String> = app.db(|conn| {
+        api_tokens::table
+            .select(api_tokens::name)
+            .order(api_tokens::name)
+            .load(conn)
+            .unwrap()
+    });
+    assert_eq!(names, vec!["never", "recently"]);
+}
@@ -1,18 +1,23 @@
 use anyhow::Result;
-use reqwest::{blocking::Client, header};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use quick_xml::events::Event;
+use reqwest::{blocking::Client, header, StatusCode};
 
 use crate::util::errors::{internal, AppResult};
 
 use reqwest::blocking::Body;
-use std::env;
 use std::fs::{self, File};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use crate::models::Crate;
 
 const CACHE_CONTROL_IMMUTABLE: &str = "public,max-age=31536000,immutable";
 const CACHE_CONTROL_README: &str = "public,max-age=604800";
 const CACHE_CONTROL_INDEX: &str = "public,max-age=600";
+const CACHE_CONTROL_FEED: &str = "public,max-age=600";
+
+/// The subdirectory of `Uploader::Local` that contains the files of the index bucket.
+const LOCAL_INDEX_DIR: &str = "index";
 
 #[derive(Clone, Debug)]
 pub enum Uploader {
@@ -24,16 +29,28 @@ pub enum Uploader {
         cdn: Option<String>,
     },
 
-    /// For development usage only: "uploads" crate files to `dist` and serves them
-    /// from there as well to enable local publishing and download
-    Local,
+    /// Stores the files in a directory on the local filesystem, from which they are served as
+    /// well. Used during development, and by instances that don't have an S3 bucket.
+    ///
+    /// The files of the index bucket are stored in the `index` subdirectory.
+    Local { root: PathBuf },
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum UploadBucket {
     Default,
     Index,
 }
 
+/// A file in the default bucket, as returned by `Uploader::list_files`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredFile {
+    /// The internal path of the file, e.g. `crates/foo/foo-1.0.0.crate`.
+    pub path: String,
+    pub last_modified: NaiveDateTime,
+}
+
 impl Uploader {
     /// Returns the URL of an uploaded crate's version archive.
     ///
@@ -52,7 +69,7 @@ impl Uploader {
                 let path = Uploader::crate_path(crate_name, version);
                 format!("https://{host}/{path}")
             }
-            Uploader::Local => format!("/{}", Uploader::crate_path(crate_name, version)),
+            Uploader::Local { .. } => format!("/{}", Uploader::crate_path(crate_name, version)),
         }
     }
 
@@ -73,8 +90,42 @@ impl Uploader {
                 let path = Uploader::readme_path(crate_name, version);
                 format!("https://{host}/{path}")
             }
-            Uploader::Local => format!("/{}", Uploader::readme_path(crate_name, version)),
+            Uploader::Local { .. } => format!("/{}", Uploader::readme_path(crate_name, version)),
+        }
+    }
+
+    /// Returns the bucket and internal path of each file that is stored for a crate with the
+    /// given versions, i.e. the files that are removed once the crate is deleted permanently.
+    ///
+    /// The function doesn't check for the existence of the files.
+    pub fn crate_files(
+        &self,
+        crate_name: &str,
+        versions: &[String],
+    ) -> Vec<(UploadBucket, String)> {
+        let mut files = Vec::new();
+        for version in versions {
+            files.push((
+                UploadBucket::Default,
+                Uploader::crate_path(crate_name, version),
+            ));
+            files.push((
+                UploadBucket::Default,
+                Uploader::readme_path(crate_name, version),
+            ));
+        }
+
+        let has_index_bucket = match *self {
+            Uploader::S3 {
+                ref index_bucket, ..
+            } => index_bucket.is_some(),
+            Uploader::Local { .. } => true,
+        };
+        if has_index_bucket {
+            files.push((UploadBucket::Index, Uploader::index_path(crate_name)));
         }
+
+        files
     }
 
     /// Returns the internal path of an uploaded crate's version archive.
@@ -87,28 +138,48 @@ impl Uploader {
         format!("readmes/{name}/{name}-{version}.html")
     }
 
+    /// Returns the crate name and version of the internal path of a crate's version archive or
+    /// readme, or `None` for all other paths.
+    pub(crate) fn parse_version_file_path(path: &str) -> Option<(&str, &str)> {
+        let (rest, extension) = match path.split_once('/')? {
+            ("crates", rest) => (rest, ".crate"),
+            ("readmes", rest) => (rest, ".html"),
+            _ => return None,
+        };
+        let (name, file_name) = rest.split_once('/')?;
+        let version = file_name
+            .strip_prefix(name)?
+            .strip_prefix('-')?
+            .strip_suffix(extension)?;
+        Some((name, version))
+    }
+
+    /// Returns the internal path of an RSS feed.
+    fn rss_feed_path(name: &str) -> String {
+        format!("rss/{name}.xml")
+    }
+
+    /// Returns the internal path of an Atom feed.
+    fn atom_feed_path(name: &str) -> String {
+        format!("atom/{name}.xml")
+    }
+
     /// Returns the internal path of an uploaded crate's index file.
     fn index_path(name: &str) -> String {
         cargo_registry_index::Repository::relative_index_file_for_url(name)
     }
 
-    /// Returns the absolute path to the locally uploaded file.
-    fn local_uploads_path(path: &str, upload_bucket: UploadBucket) -> PathBuf {
-        let path = match upload_bucket {
-            UploadBucket::Index => PathBuf::from("index").join(path),
-            UploadBucket::Default => PathBuf::from(path),
-        };
-        env::current_dir().unwrap().join("local_uploads").join(path)
+    /// Returns the path to the locally uploaded file in `root`.
+    fn local_uploads_path(root: &Path, path: &str, upload_bucket: UploadBucket) -> PathBuf {
+        match upload_bucket {
+            UploadBucket::Index => root.join(LOCAL_INDEX_DIR).join(path),
+            UploadBucket::Default => root.join(path),
+        }
     }
 
     /// Uploads a file using the configured uploader (either `S3`, `Local`).
     ///
     /// It returns the path of the uploaded file.
-    ///
-    /// # Panics
-    ///
-    /// This function can panic on an `Self::Local` during development.
-    /// Production and tests use `Self::S3` which should not panic.
     pub fn upload<R: Into<Body>>(
         &self,
         client: &Client,
@@ -135,10 +206,11 @@ impl Uploader {
 
                 Ok(Some(String::from(path)))
             }
-            Uploader::Local => {
-                let filename = Self::local_uploads_path(path, upload_bucket);
-                let dir = filename.parent().unwrap();
-                fs::create_dir_all(dir)?;
+            Uploader::Local { ref root } => {
+                let filename = Self::local_uploads_path(root, path, upload_bucket);
+                if let Some(dir) = filename.parent() {
+                    fs::create_dir_all(dir)?;
+                }
                 let mut file = File::create(&filename)?;
                 let mut body = content.into();
                 let mut buffer = body.buffer()?;
@@ -148,6 +220,25 @@ impl Uploader {
         }
     }
 
+    /// Downloads a file from the default bucket, or returns `None` if it doesn't exist.
+    pub(crate) fn download(&self, client: &Client, path: &str) -> Result<Option<Vec<u8>>> {
+        match *self {
+            Uploader::S3 { ref bucket, .. } => match bucket.get(client, path) {
+                Ok(response) => Ok(Some(response.bytes()?.to_vec())),
+                Err(error) if error.status() == Some(StatusCode::NOT_FOUND) => Ok(None),
+                Err(error) => Err(error.into()),
+            },
+            Uploader::Local { ref root } => {
+                let filename = Self::local_uploads_path(root, path, UploadBucket::Default);
+                match fs::read(filename) {
+                    Ok(content) => Ok(Some(content)),
+                    Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                    Err(error) => Err(error.into()),
+                }
+            }
+        }
+    }
+
     /// Deletes a file using the configured uploader (either `S3`, `Local`).
     pub fn delete(&self, client: &Client, path: &str, upload_bucket: UploadBucket) -> Result<()> {
         match *self {
@@ -162,14 +253,61 @@ impl Uploader {
                 };
 
                 if let Some(bucket) = bucket {
-                    bucket.delete(client, path)?;
+                    match bucket.delete(client, path) {
+                        // The file is already gone, which is what we wanted anyway
+                        Err(error) if error.status() == Some(StatusCode::NOT_FOUND) => {}
+                        result => {
+                            result?;
+                        }
+                    }
                 }
             }
-            Uploader::Local => {
-                let filename = Self::local_uploads_path(path, upload_bucket);
-                // Ignore errors if the local index file doesn't exist; this can happen if you
-                // aren't running the background job worker locally
-                let _ = std::fs::remove_file(filename);
+            Uploader::Local { ref root } => {
+                let filename = Self::local_uploads_path(root, path, upload_bucket);
+                match fs::remove_file(filename) {
+                    // The file is already gone, e.g. because the background worker never ran
+                    // during development
+                    Err(error) if error.kind() == std::io::ErrorKind::NotFound => {}
+                    result => result?,
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Lists the files in the default bucket whose internal paths start with `prefix`, one page
+    /// at a time. The returned continuation token can be passed back to get the next page, and
+    /// is `None` on the last page.
+    pub fn list_files(
+        &self,
+        client: &Client,
+        prefix: &str,
+        continuation_token: Option<&str>,
+    ) -> Result<(Vec<StoredFile>, Option<String>)> {
+        match *self {
+            Uploader::S3 { ref bucket, .. } => {
+                let xml = bucket.list(client, prefix, continuation_token)?.text()?;
+                parse_object_list(&xml)
+            }
+            Uploader::Local { ref root } => {
+                let mut files = Vec::new();
+                list_local_files(root, &root.join(prefix), &mut files)?;
+                // The index bucket is a subdirectory of the default one
+                let index_dir = format!("{LOCAL_INDEX_DIR}/");
+                files.retain(|file| !file.path.starts_with(&index_dir));
+                Ok((files, None))
+            }
+        }
+    }
+
+    /// Checks whether the storage backend can be reached, without uploading anything.
+    pub fn check_reachability(&self, client: &Client) -> Result<()> {
+        match *self {
+            Uploader::S3 { ref bucket, .. } => {
+                bucket.head_bucket(client)?;
+            }
+            Uploader::Local { ref root } => {
+                fs::create_dir_all(root)?;
             }
         }
         Ok(())
@@ -225,6 +363,85 @@ impl Uploader {
         Ok(())
     }
 
+    /// Downloads the rendered readme of a crate's version, or returns `None` if it doesn't exist.
+    pub(crate) fn download_readme(
+        &self,
+        http_client: &Client,
+        crate_name: &str,
+        vers: &str,
+    ) -> AppResult<Option<Vec<u8>>> {
+        let path = Uploader::readme_path(crate_name, vers);
+        self.download(http_client, &path)
+            .map_err(|e| internal(format!("failed to download readme: {e}")))
+    }
+
+    /// Deletes an uploaded crate's version archive, if it exists.
+    pub(crate) fn delete_crate(
+        &self,
+        http_client: &Client,
+        crate_name: &str,
+        vers: &str,
+    ) -> Result<()> {
+        let path = Uploader::crate_path(crate_name, vers);
+        self.delete(http_client, &path, UploadBucket::Default)
+    }
+
+    /// Deletes an uploaded crate's version readme, if it exists.
+    pub(crate) fn delete_readme(
+        &self,
+        http_client: &Client,
+        crate_name: &str,
+        vers: &str,
+    ) -> Result<()> {
+        let path = Uploader::readme_path(crate_name, vers);
+        self.delete(http_client, &path, UploadBucket::Default)
+    }
+
+    /// Uploads an RSS feed, e.g. `rss/crates.xml` for the `crates` feed.
+    pub(crate) fn upload_rss_feed(
+        &self,
+        http_client: &Client,
+        name: &str,
+        feed: String,
+    ) -> Result<()> {
+        let path = Uploader::rss_feed_path(name);
+        self.upload_feed(http_client, &path, feed, "application/rss+xml")
+    }
+
+    /// Uploads an Atom feed, e.g. `atom/crates.xml` for the `crates` feed.
+    pub(crate) fn upload_atom_feed(
+        &self,
+        http_client: &Client,
+        name: &str,
+        feed: String,
+    ) -> Result<()> {
+        let path = Uploader::atom_feed_path(name);
+        self.upload_feed(http_client, &path, feed, "application/atom+xml")
+    }
+
+    fn upload_feed(
+        &self,
+        http_client: &Client,
+        path: &str,
+        feed: String,
+        content_type: &str,
+    ) -> Result<()> {
+        let mut extra_headers = header::HeaderMap::new();
+        extra_headers.insert(
+            header::CACHE_CONTROL,
+            header::HeaderValue::from_static(CACHE_CONTROL_FEED),
+        );
+        self.upload(
+            http_client,
+            path,
+            feed,
+            content_type,
+            extra_headers,
+            UploadBucket::Default,
+        )?;
+        Ok(())
+    }
+
     pub(crate) fn upload_index(
         &self,
         http_client: &Client,
@@ -267,3 +484,138 @@ impl Uploader {
         }
     }
 }
+
+/// Parses the XML document returned by the `ListObjectsV2` API of S3 into the listed files and
+/// the continuation token for the next page.
+fn parse_object_list(xml: &str) -> Result<(Vec<StoredFile>, Option<String>)> {
+    let mut reader = quick_xml::Reader::from_str(xml);
+    reader.trim_text(true);
+
+    let mut files = Vec::new();
+    let mut continuation_token = None;
+    let mut element = Vec::new();
+    let mut path = None;
+    let mut last_modified = None;
+    loop {
+        match reader.read_event()? {
+            Event::Start(start) => element = start.name().as_ref().to_vec(),
+            Event::Text(text) => match element.as_slice() {
+                b"Key" => path = Some(text.unescape()?.into_owned()),
+                b"LastModified" => {
+                    let date = DateTime::parse_from_rfc3339(&text.unescape()?)?;
+                    last_modified = Some(date.with_timezone(&Utc).naive_utc());
+                }
+                b"NextContinuationToken" => {
+                    continuation_token = Some(text.unescape()?.into_owned());
+                }
+                _ => {}
+            },
+            Event::End(end) => {
+                if end.name().as_ref() == b"Contents" {
+                    if let (Some(path), Some(last_modified)) = (path.take(), last_modified.take()) {
+                        files.push(StoredFile {
+                            path,
+                            last_modified,
+                        });
+                    }
+                }
+                element.clear();
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok((files, continuation_token))
+}
+
+/// Adds the files in `dir` and its subdirectories to `files`, with paths relative to `root`.
+fn list_local_files(root: &Path, dir: &Path, files: &mut Vec<StoredFile>) -> Result<()> {
+    let entries = match fs::read_dir(dir) {
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        entries => entries?,
+    };
+
+    for entry in entries {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if metadata.is_dir() {
+            list_local_files(root, &entry.path(), files)?;
+        } else {
+            let path = entry.path();
+            let path = path.strip_prefix(root)?.to_string_lossy().into_owned();
+            let last_modified = DateTime::<Utc>::from(metadata.modified()?).naive_utc();
+            files.push(StoredFile {
+                path,
+                last_modified,
+            });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn version_file_paths_are_parsed() {
+        let parse = Uploader::parse_version_file_path;
+        assert_eq!(
+            parse("crates/foo-bar/foo-bar-1.0.0-beta.1.crate"),
+            Some(("foo-bar", "1.0.0-beta.1"))
+        );
+        assert_eq!(
+            parse("readmes/foo/foo-1.0.0+build.html"),
+            Some(("foo", "1.0.0+build"))
+        );
+        assert_eq!(parse("crates/foo/bar-1.0.0.crate"), None);
+        assert_eq!(parse("crates/foo/foo-1.0.0.html"), None);
+        assert_eq!(parse("rss/crates.xml"), None);
+    }
+
+    #[test]
+    fn object_lists_are_parsed() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <ListBucketResult xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+                <Name>bucket</Name>
+                <Prefix>crates/</Prefix>
+                <IsTruncated>true</IsTruncated>
+                <NextContinuationToken>next&amp;page</NextContinuationToken>
+                <Contents>
+                    <Key>crates/foo/foo-1.0.0.crate</Key>
+                    <LastModified>2023-04-01T12:00:00.000Z</LastModified>
+                    <ETag>&quot;abc&quot;</ETag>
+                    <Size>35</Size>
+                </Contents>
+                <Contents>
+                    <Key>crates/foo/foo-1.1.0.crate</Key>
+                    <LastModified>2023-04-02T12:00:00.000Z</LastModified>
+                    <Size>36</Size>
+                </Contents>
+            </ListBucketResult>"#;
+
+        let date = |day| {
+            NaiveDate::from_ymd_opt(2023, 4, day)
+                .unwrap()
+                .and_hms_opt(12, 0, 0)
+                .unwrap()
+        };
+        let (files, continuation_token) = parse_object_list(xml).unwrap();
+        assert_eq!(
+            files,
+            vec![
+                StoredFile {
+                    path: "crates/foo/foo-1.0.0.crate".into(),
+                    last_modified: date(1),
+                },
+                StoredFile {
+                    path: "crates/foo/foo-1.1.0.crate".into(),
+                    last_modified: date(2),
+                },
+            ]
+        );
+        assert_eq!(continuation_token.as_deref(), Some("next&page"));
+    }
+}
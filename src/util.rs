@@ -7,6 +7,7 @@ pub use self::request_helpers::*;
 mod bytes_request;
 pub mod errors;
 mod io_util;
+pub mod public_url;
 mod request_helpers;
 pub mod rfc3339;
 pub mod token;
@@ -48,9 +49,34 @@ impl CargoVcsInfo {
     }
 }
 
+/// Represents relevant contents of the normalized `Cargo.toml` file included in
+/// the uploaded crate tarball
+#[derive(Debug, Default, Deserialize, Eq, PartialEq)]
+pub struct Manifest {
+    #[serde(default)]
+    pub package: Option<ManifestPackage>,
+}
+
+#[derive(Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub struct ManifestPackage {
+    pub rust_version: Option<String>,
+}
+
+impl Manifest {
+    pub fn from_contents(contents: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(contents)
+    }
+
+    /// Returns the minimum supported Rust version declared by the package, if any.
+    pub fn rust_version(&self) -> Option<&str> {
+        self.package.as_ref()?.rust_version.as_deref()
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::CargoVcsInfo;
+    use super::{CargoVcsInfo, Manifest};
 
     #[test]
     fn test_cargo_vcs_info() {
@@ -74,4 +100,22 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn test_manifest() {
+        assert_eq!(Manifest::from_contents("").unwrap().rust_version(), None);
+        assert_eq!(
+            Manifest::from_contents("[package]\nname = \"foo\"")
+                .unwrap()
+                .rust_version(),
+            None
+        );
+        assert_eq!(
+            Manifest::from_contents("[package]\nname = \"foo\"\nrust-version = \"1.59\"")
+                .unwrap()
+                .rust_version(),
+            Some("1.59")
+        );
+        assert_err!(Manifest::from_contents("[package"));
+    }
 }
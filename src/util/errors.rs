@@ -15,6 +15,7 @@
 
 use axum::response::IntoResponse;
 use std::any::{Any, TypeId};
+use std::collections::HashSet;
 use std::error::Error;
 use std::fmt;
 
@@ -28,15 +29,22 @@ use crate::db::PoolError;
 use crate::middleware::log_request::{CauseField, ErrorField};
 
 mod json;
+pub mod schema;
 
-pub use json::TOKEN_FORMAT_ERROR;
 pub(crate) use json::{
-    InsecurelyGeneratedTokenRevoked, MetricsDisabled, NotFound, OwnershipInvitationExpired,
-    ReadOnlyMode, RouteBlocked, TooManyRequests,
+    CrateFrozen, CrateNotFound, DeletionNotConfirmed, DependenciesUnavailable, ExpiredApiToken,
+    InsecurelyGeneratedTokenRevoked, LockTooLong, MetricsDisabled, NotFound,
+    OwnershipInvitationExpired, ReadOnlyMode, RouteBlocked, TooManyCategories, TooManyRequests,
 };
+pub use json::{TOKEN_EXPIRED_ERROR, TOKEN_FORMAT_ERROR};
 
 pub type BoxedAppError = Box<dyn AppError>;
 
+/// Added to the extensions of the responses of `cargo_err` and `cargo_errs`, so that middleware
+/// can tell these failures apart from successful responses despite their status 200.
+#[derive(Clone, Copy, Debug)]
+pub struct CargoErrorResponse;
+
 /// Returns an error with status 200 and the provided description as JSON
 ///
 /// This is for backwards compatibility with cargo endpoints.  For all other
@@ -46,6 +54,20 @@ pub fn cargo_err<S: ToString + ?Sized>(error: &S) -> BoxedAppError {
     Box::new(json::Ok(error.to_string()))
 }
 
+/// Returns an error with status 200 and one entry in the JSON `errors` list for each of the
+/// provided descriptions
+///
+/// This is meant for validations that can find several problems at once, so that they can
+/// all be fixed before retrying. See `cargo_err` for why the status is 200.
+///
+/// The descriptions are kept in the order in which they were provided, and only the first of
+/// any duplicates is kept. The `Display` output lists the same descriptions, separated by `; `.
+pub fn cargo_errs(mut errors: Vec<String>) -> BoxedAppError {
+    let mut seen = HashSet::new();
+    errors.retain(|error| seen.insert(error.clone()));
+    Box::new(json::OkMultiple(errors))
+}
+
 // The following are intended to be used for errors being sent back to the Ember
 // frontend, not to cargo as cargo does not handle non-200 response codes well
 // (see <https://github.com/rust-lang/cargo/issues/3995>), but Ember requires
@@ -67,6 +89,21 @@ pub fn forbidden() -> BoxedAppError {
     Box::new(json::Forbidden)
 }
 
+/// Returns an error with status 403, asking the user to log in again
+pub fn recent_auth_required() -> BoxedAppError {
+    Box::new(json::RecentAuthRequired)
+}
+
+/// Returns an error with status 403 for API tokens used on endpoints of the website
+pub fn website_only() -> BoxedAppError {
+    Box::new(json::WebsiteOnly)
+}
+
+/// Returns an error with status 403 for website sessions used on endpoints for API tokens
+pub fn token_only() -> BoxedAppError {
+    Box::new(json::TokenOnly)
+}
+
 pub fn not_found() -> BoxedAppError {
     Box::new(json::NotFound)
 }
@@ -187,8 +224,25 @@ impl<E: Error + Send + 'static> AppError for E {
 
         sentry::capture_error(self);
 
-        server_error_response(self.to_string())
+        // The full chain of sources is only logged, the client just sees a generic message
+        let mut response = server_error_response(self.to_string());
+        response
+            .extensions_mut()
+            .insert(CauseField(error_chain(self)));
+        response
+    }
+}
+
+/// Formats an error and all of its sources, in the same format as `ChainedError`.
+fn error_chain(error: &dyn Error) -> String {
+    let mut chain = error.to_string();
+    let mut source = error.source();
+    while let Some(err) = source {
+        chain.push_str(" caused by ");
+        chain.push_str(&err.to_string());
+        source = err.source();
     }
+    chain
 }
 
 impl From<base64::DecodeError> for BoxedAppError {
@@ -238,7 +292,7 @@ impl From<lettre::address::AddressError> for BoxedAppError {
 impl From<PoolError> for BoxedAppError {
     fn from(err: PoolError) -> BoxedAppError {
         match err {
-            PoolError::UnhealthyPool => service_unavailable("Service unavailable"),
+            PoolError::UnhealthyPool => err.chain(service_unavailable("Service unavailable")),
             _ => Box::new(err),
         }
     }
@@ -398,3 +452,19 @@ fn chain_error_user_facing() {
         "outer caused by permission denied" // never logged
     );
 }
+
+#[tokio::test]
+async fn cargo_errs_removes_duplicates() {
+    let errors = ["first", "second", "first", "third", "second"];
+    let err = cargo_errs(errors.iter().map(|error| error.to_string()).collect());
+    assert_eq!(err.to_string(), "first; second; third");
+
+    let response = err.response();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(
+        json,
+        json!({ "errors": [{ "detail": "first" }, { "detail": "second" }, { "detail": "third" }] })
+    );
+}
@@ -2,15 +2,50 @@ use axum::response::{IntoResponse, Response};
 use axum::Json;
 use std::fmt;
 
-use super::{AppError, BoxedAppError, InternalAppErrorStatic};
+use super::{AppError, BoxedAppError, CargoErrorResponse, InternalAppErrorStatic};
 
+use crate::rate_limiter::LimitedAction;
 use chrono::NaiveDateTime;
 use http::{header, StatusCode};
 
 /// Generates a response with the provided status and description as JSON
 fn json_error(detail: &str, status: StatusCode) -> Response {
-    let json = json!({ "errors": [{ "detail": detail }] });
-    (status, Json(json)).into_response()
+    json_errors(&[detail], status)
+}
+
+/// The body of all JSON error responses.
+///
+/// The JSON Schema of this body is published in `error_body.schema.json`, see the `schema`
+/// module.
+#[derive(Serialize)]
+pub(super) struct ErrorBody<'a> {
+    pub(super) errors: Vec<ErrorContent<'a>>,
+}
+
+/// A single error within an `ErrorBody`.
+///
+/// Only some errors include a machine-readable `code`, see `OPTIONAL_FIELDS` in the `schema`
+/// module for the fields that can be missing.
+#[derive(Serialize)]
+pub(super) struct ErrorContent<'a> {
+    pub(super) detail: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) code: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) deleted_at: Option<NaiveDateTime>,
+}
+
+/// Generates a response with the provided status and one error object per description as JSON
+fn json_errors<S: AsRef<str>>(details: &[S], status: StatusCode) -> Response {
+    let errors = details
+        .iter()
+        .map(|detail| ErrorContent {
+            detail: detail.as_ref(),
+            code: None,
+            deleted_at: None,
+        })
+        .collect();
+    (status, Json(ErrorBody { errors })).into_response()
 }
 
 // The following structs are empty and do not provide a custom message to the user
@@ -48,6 +83,51 @@ impl fmt::Display for Forbidden {
     }
 }
 
+#[derive(Debug)]
+pub(super) struct RecentAuthRequired;
+
+impl AppError for RecentAuthRequired {
+    fn response(&self) -> Response {
+        json_error(&self.to_string(), StatusCode::FORBIDDEN)
+    }
+}
+
+impl fmt::Display for RecentAuthRequired {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        "this action requires a recent login, please log out and log in again".fmt(f)
+    }
+}
+
+#[derive(Debug)]
+pub(super) struct WebsiteOnly;
+
+impl AppError for WebsiteOnly {
+    fn response(&self) -> Response {
+        json_error(&self.to_string(), StatusCode::FORBIDDEN)
+    }
+}
+
+impl fmt::Display for WebsiteOnly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        "this action can only be performed on the crates.io website".fmt(f)
+    }
+}
+
+#[derive(Debug)]
+pub(super) struct TokenOnly;
+
+impl AppError for TokenOnly {
+    fn response(&self) -> Response {
+        json_error(&self.to_string(), StatusCode::FORBIDDEN)
+    }
+}
+
+impl fmt::Display for TokenOnly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        "this action can only be performed with an API token".fmt(f)
+    }
+}
+
 impl AppError for ReadOnlyMode {
     fn response(&self) -> Response {
         let detail = "Crates.io is currently in read-only mode for maintenance. \
@@ -67,19 +147,27 @@ impl fmt::Display for ReadOnlyMode {
 #[derive(Debug)]
 pub(super) struct Ok(pub(super) String);
 #[derive(Debug)]
+pub(super) struct OkMultiple(pub(super) Vec<String>);
+#[derive(Debug)]
 pub(super) struct BadRequest(pub(super) String);
 #[derive(Debug)]
 pub(super) struct ServerError(pub(super) String);
 #[derive(Debug)]
 pub(crate) struct ServiceUnavailable(pub(super) String);
+/// Lists the services that the application depends on, but that can't be reached.
+#[derive(Debug)]
+pub(crate) struct DependenciesUnavailable(pub Vec<&'static str>);
 #[derive(Debug)]
 pub(crate) struct TooManyRequests {
+    pub action: LimitedAction,
     pub retry_after: NaiveDateTime,
 }
 
 impl AppError for Ok {
     fn response(&self) -> Response {
-        json_error(&self.0, StatusCode::OK)
+        let mut response = json_error(&self.0, StatusCode::OK);
+        response.extensions_mut().insert(CargoErrorResponse);
+        response
     }
 }
 
@@ -89,6 +177,20 @@ impl fmt::Display for Ok {
     }
 }
 
+impl AppError for OkMultiple {
+    fn response(&self) -> Response {
+        let mut response = json_errors(&self.0, StatusCode::OK);
+        response.extensions_mut().insert(CargoErrorResponse);
+        response
+    }
+}
+
+impl fmt::Display for OkMultiple {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.join("; ").fmt(f)
+    }
+}
+
 impl AppError for BadRequest {
     fn response(&self) -> Response {
         json_error(&self.0, StatusCode::BAD_REQUEST)
@@ -125,15 +227,41 @@ impl fmt::Display for ServiceUnavailable {
     }
 }
 
+impl AppError for DependenciesUnavailable {
+    fn response(&self) -> Response {
+        let details = self
+            .0
+            .iter()
+            .map(|dependency| format!("{dependency} is unavailable"))
+            .collect::<Vec<_>>();
+        json_errors(&details, StatusCode::SERVICE_UNAVAILABLE)
+    }
+}
+
+impl fmt::Display for DependenciesUnavailable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Unavailable dependencies: {}", self.0.join(", "))
+    }
+}
+
+impl TooManyRequests {
+    pub(crate) fn new(action: LimitedAction, retry_after: NaiveDateTime) -> Self {
+        Self {
+            action,
+            retry_after,
+        }
+    }
+}
+
 impl AppError for TooManyRequests {
     fn response(&self) -> Response {
         const HTTP_DATE_FORMAT: &str = "%a, %d %b %Y %H:%M:%S GMT";
         let retry_after = self.retry_after.format(HTTP_DATE_FORMAT);
 
         let detail = format!(
-            "You have published too many crates in a \
-             short period of time. Please try again after {retry_after} or email \
-             help@crates.io to have your limit increased."
+            "{} Please try again after {retry_after} or email \
+             help@crates.io to have your limit increased.",
+            self.action.error_message()
         );
         let mut response = json_error(&detail, StatusCode::TOO_MANY_REQUESTS);
         response.headers_mut().insert(
@@ -192,6 +320,36 @@ impl fmt::Display for InsecurelyGeneratedTokenRevoked {
     }
 }
 
+#[derive(Debug, Clone, Copy)]
+pub struct ExpiredApiToken;
+
+impl ExpiredApiToken {
+    pub fn boxed() -> BoxedAppError {
+        Box::new(Self)
+    }
+}
+
+impl AppError for ExpiredApiToken {
+    fn response(&self) -> Response {
+        json_error(&self.to_string(), StatusCode::UNAUTHORIZED)
+    }
+
+    fn cause(&self) -> Option<&dyn AppError> {
+        Some(&InternalAppErrorStatic {
+            description: "expired token",
+        })
+    }
+}
+
+pub const TOKEN_EXPIRED_ERROR: &str =
+    "The given API token has expired. You can generate a new token at https://crates.io/me.";
+
+impl fmt::Display for ExpiredApiToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(TOKEN_EXPIRED_ERROR)
+    }
+}
+
 #[derive(Debug)]
 pub(super) struct AccountLocked {
     pub(super) reason: String,
@@ -245,6 +403,137 @@ impl fmt::Display for OwnershipInvitationExpired {
     }
 }
 
+/// A write to a crate that was frozen by an admin, see `Crate::ensure_not_frozen`.
+#[derive(Debug)]
+pub(crate) struct CrateFrozen {
+    pub(crate) crate_name: String,
+}
+
+impl AppError for CrateFrozen {
+    fn response(&self) -> Response {
+        json_error(&self.to_string(), StatusCode::FORBIDDEN)
+    }
+}
+
+impl fmt::Display for CrateFrozen {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "The crate `{}` has been frozen by the crates.io team and can't be modified. \
+             Please contact help@crates.io for more information.",
+            self.crate_name
+        )
+    }
+}
+
+/// A crate deletion without a valid token from `krate::delete::confirm`.
+#[derive(Debug)]
+pub(crate) struct DeletionNotConfirmed {
+    pub(crate) crate_name: String,
+}
+
+impl AppError for DeletionNotConfirmed {
+    fn response(&self) -> Response {
+        json_error(&self.to_string(), StatusCode::BAD_REQUEST)
+    }
+}
+
+impl fmt::Display for DeletionNotConfirmed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "The deletion of the `{}` crate has to be confirmed first. The token in the \
+             `X-Crates-Io-Delete-Confirmation` header is missing, invalid or expired.",
+            self.crate_name
+        )
+    }
+}
+
+/// Returned when a crate would end up in more categories than allowed.
+#[derive(Debug)]
+pub(crate) struct TooManyCategories {
+    pub(crate) max: usize,
+}
+
+impl AppError for TooManyCategories {
+    fn response(&self) -> Response {
+        json_error(&self.to_string(), StatusCode::UNPROCESSABLE_ENTITY)
+    }
+}
+
+impl fmt::Display for TooManyCategories {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "crates can be in at most {} categories", self.max)
+    }
+}
+
+/// Returned when an account lock would last longer than allowed without being marked as
+/// permanent.
+#[derive(Debug)]
+pub(crate) struct LockTooLong {
+    pub(crate) max_days: u64,
+}
+
+impl AppError for LockTooLong {
+    fn response(&self) -> Response {
+        json_error(&self.to_string(), StatusCode::UNPROCESSABLE_ENTITY)
+    }
+}
+
+impl fmt::Display for LockTooLong {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "account locks can last at most {} days, set `permanent` to lock the account \
+             for longer or indefinitely",
+            self.max_days
+        )
+    }
+}
+
+/// Returned when no crate with the requested name exists.
+///
+/// The `code` of the error is `crate_deleted` if a crate with that name was deleted by its
+/// owners, and `crate_not_found` otherwise.
+#[derive(Debug)]
+pub(crate) struct CrateNotFound {
+    pub(crate) crate_name: String,
+    /// When the crate was last deleted, according to the `crate_deletions` table.
+    pub(crate) deleted_at: Option<NaiveDateTime>,
+}
+
+impl AppError for CrateNotFound {
+    fn response(&self) -> Response {
+        let code = match self.deleted_at {
+            Some(_) => "crate_deleted",
+            None => "crate_not_found",
+        };
+        let error = ErrorContent {
+            detail: &self.to_string(),
+            code: Some(code),
+            deleted_at: self.deleted_at,
+        };
+        let body = ErrorBody {
+            errors: vec![error],
+        };
+        (StatusCode::NOT_FOUND, Json(body)).into_response()
+    }
+}
+
+impl fmt::Display for CrateNotFound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.deleted_at {
+            Some(deleted_at) => write!(
+                f,
+                "crate `{}` was deleted on {} and is no longer available",
+                self.crate_name,
+                deleted_at.format("%Y-%m-%d at %H:%M:%S UTC")
+            ),
+            None => write!(f, "crate `{}` could not be found", self.crate_name),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct MetricsDisabled;
 
@@ -277,7 +566,6 @@ impl fmt::Display for RouteBlocked {
 
 impl IntoResponse for RouteBlocked {
     fn into_response(self) -> Response {
-        let body = Json(json!({ "errors": [{ "detail": self.to_string() }] }));
-        (StatusCode::SERVICE_UNAVAILABLE, body).into_response()
+        self.response()
     }
 }
@@ -0,0 +1,90 @@
+//! The JSON Schema of the body of error responses, for generating API clients.
+//!
+//! The schema is derived from what the `ErrorBody` struct serializes to, so that it can't get
+//! out of sync with the responses. A copy is committed as `error_body.schema.json`, and a test
+//! makes sure that it is up to date.
+
+use chrono::NaiveDateTime;
+use serde_json::{json, Map, Value};
+
+use super::json::{ErrorBody, ErrorContent};
+
+/// The fields of `ErrorContent` that are only included in some errors.
+const OPTIONAL_FIELDS: [&str; 2] = ["code", "deleted_at"];
+
+/// Returns the JSON Schema of the body of all JSON error responses.
+pub fn error_body_schema() -> Value {
+    let example = ErrorBody {
+        errors: vec![ErrorContent {
+            detail: "",
+            code: Some(""),
+            deleted_at: NaiveDateTime::from_timestamp_opt(0, 0),
+        }],
+    };
+    let example = serde_json::to_value(example).expect("error bodies can be serialized");
+
+    let mut inferred = schema_for(&example);
+    if let Some(Value::Array(required)) = inferred.pointer_mut("/properties/errors/items/required")
+    {
+        required.retain(|field| !OPTIONAL_FIELDS.contains(&field.as_str().unwrap_or_default()));
+    }
+
+    let mut schema = json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "ErrorBody",
+    });
+    if let (Value::Object(schema), Value::Object(inferred)) = (&mut schema, inferred) {
+        schema.extend(inferred);
+    }
+    schema
+}
+
+/// Returns the schema of values that have the same shape as `value`.
+///
+/// All fields of objects are required and no other fields are allowed, and the items of arrays
+/// have the shape of their first item.
+fn schema_for(value: &Value) -> Value {
+    match value {
+        Value::Null => json!({ "type": "null" }),
+        Value::Bool(_) => json!({ "type": "boolean" }),
+        Value::Number(number) if number.is_f64() => json!({ "type": "number" }),
+        Value::Number(_) => json!({ "type": "integer" }),
+        Value::String(_) => json!({ "type": "string" }),
+        Value::Array(items) => {
+            let mut schema = json!({ "type": "array" });
+            if let Some(item) = items.first() {
+                schema["items"] = schema_for(item);
+            }
+            schema
+        }
+        Value::Object(fields) => {
+            let properties = fields
+                .iter()
+                .map(|(name, value)| (name.clone(), schema_for(value)))
+                .collect::<Map<_, _>>();
+            json!({
+                "type": "object",
+                "properties": properties,
+                "required": fields.keys().collect::<Vec<_>>(),
+                "additionalProperties": false,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn committed_schema_is_up_to_date() {
+        let committed: Value =
+            serde_json::from_str(include_str!("error_body.schema.json")).unwrap();
+        let expected = serde_json::to_string_pretty(&error_body_schema()).unwrap();
+        assert_eq!(
+            committed,
+            error_body_schema(),
+            "`src/util/errors/error_body.schema.json` is out of date, it should be:\n{expected}"
+        );
+    }
+}
@@ -0,0 +1,230 @@
+//! Checks that URLs supplied by users point to the public internet, so that requests to them
+//! can't reach the services in the private network of crates.io, like the metadata endpoint of
+//! the cloud provider at `169.254.169.254`.
+
+use reqwest::redirect::Policy;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs};
+use url::{Host, Url};
+
+/// Hostnames with these suffixes only resolve within a private network.
+const PRIVATE_DOMAIN_SUFFIXES: &[&str] =
+    &["localhost", "local", "localdomain", "internal", "home.arpa"];
+
+/// Returns whether the URL is an `http` or `https` URL that doesn't obviously point to a private
+/// host, without resolving its hostname.
+///
+/// This rejects IP addresses that aren't globally reachable, `localhost` and similar names, and
+/// names without a dot, which could be completed to internal hosts by the DNS search domains.
+pub fn is_public_url(url: &Url) -> bool {
+    if !matches!(url.scheme(), "http" | "https") {
+        return false;
+    }
+
+    match url.host() {
+        Some(Host::Ipv4(ip)) => is_public_ipv4(ip),
+        Some(Host::Ipv6(ip)) => is_public_ipv6(ip),
+        Some(Host::Domain(domain)) => is_public_domain(domain),
+        None => false,
+    }
+}
+
+/// Like `is_public_url`, but also resolves the hostname and returns its addresses if all of them
+/// are public, since any name can resolve to a private address.
+///
+/// Requests to the URL must only connect to the returned addresses, e.g. by passing them to
+/// `ClientBuilder::resolve_to_addrs()`. Otherwise the name could resolve to a different address
+/// by the time the request is made.
+pub fn public_addrs(url: &Url) -> std::io::Result<Option<Vec<SocketAddr>>> {
+    if !is_public_url(url) {
+        return Ok(None);
+    }
+
+    let (Some(host), Some(port)) = (url.host_str(), url.port_or_known_default()) else {
+        return Ok(None);
+    };
+
+    // IPv6 addresses are enclosed in brackets in URLs, but not in socket addresses
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+
+    let addrs = (host, port).to_socket_addrs()?.collect::<Vec<_>>();
+    if addrs.is_empty() || !addrs.iter().all(|addr| is_public_ip(addr.ip())) {
+        return Ok(None);
+    }
+    Ok(Some(addrs))
+}
+
+/// Returns a redirect policy for clients that send requests to URLs supplied by users.
+///
+/// Only redirects to public URLs on the host of the original request are followed, since the
+/// addresses of other hosts haven't been checked by `public_addrs()`.
+pub fn redirect_policy() -> Policy {
+    Policy::custom(|attempt| {
+        let original = &attempt.previous()[0];
+        if attempt.previous().len() >= 10 {
+            attempt.error("too many redirects")
+        } else if !may_follow_redirect(original, attempt.url()) {
+            attempt.stop()
+        } else {
+            attempt.follow()
+        }
+    })
+}
+
+fn may_follow_redirect(original: &Url, next: &Url) -> bool {
+    is_public_url(next) && next.host() == original.host()
+}
+
+pub fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_ipv4(ip),
+        IpAddr::V6(ip) => is_public_ipv6(ip),
+    }
+}
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+    let is_reserved = match a {
+        // "This network"
+        0 => true,
+        // Shared address space for carrier-grade NAT
+        100 => (64..128).contains(&b),
+        // IETF protocol assignments
+        192 => b == 0 && c == 0,
+        // Benchmarking
+        198 => b == 18 || b == 19,
+        // Reserved for future use, including the broadcast address
+        240..=255 => true,
+        _ => false,
+    };
+
+    !(is_reserved
+        || ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_documentation()
+        || ip.is_multicast())
+}
+
+fn is_public_ipv6(ip: Ipv6Addr) -> bool {
+    // Addresses that embed an IPv4 address, like `::ffff:127.0.0.1`, reach that address
+    if let Some(ipv4) = ip.to_ipv4_mapped() {
+        return is_public_ipv4(ipv4);
+    }
+
+    let segments = ip.segments();
+    let is_nat64 = segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0];
+    if is_nat64 {
+        let [a, b] = segments[6].to_be_bytes();
+        let [c, d] = segments[7].to_be_bytes();
+        return is_public_ipv4(Ipv4Addr::new(a, b, c, d));
+    }
+
+    let is_unique_local = segments[0] & 0xfe00 == 0xfc00;
+    // Includes the deprecated site-local addresses in `fec0::/10`
+    let is_link_local = segments[0] & 0xff80 == 0xfe80;
+    let is_documentation = segments[0] == 0x2001 && segments[1] == 0xdb8;
+
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        || is_unique_local
+        || is_link_local
+        || is_documentation)
+}
+
+fn is_public_domain(domain: &str) -> bool {
+    let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+    if !domain.contains('.') {
+        return false;
+    }
+
+    !PRIVATE_DOMAIN_SUFFIXES.iter().any(|suffix| {
+        domain == *suffix
+            || domain
+                .strip_suffix(suffix)
+                .map_or(false, |rest| rest.ends_with('.'))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_public(url: &str) -> bool {
+        is_public_url(&Url::parse(url).unwrap())
+    }
+
+    #[test]
+    fn private_hosts_are_rejected() {
+        assert!(!is_public("http://127.0.0.1/"));
+        assert!(!is_public("http://2130706433/"));
+        assert!(!is_public("http://10.1.2.3/"));
+        assert!(!is_public("http://172.16.0.1/"));
+        assert!(!is_public("http://192.168.1.1/"));
+        assert!(!is_public("http://169.254.169.254/latest/meta-data"));
+        assert!(!is_public("http://100.64.0.1/"));
+        assert!(!is_public("http://0.0.0.0/"));
+        assert!(!is_public("http://[::1]/"));
+        assert!(!is_public("http://[::ffff:127.0.0.1]/"));
+        assert!(!is_public("http://[64:ff9b::a9fe:a9fe]/"));
+        assert!(!is_public("http://[fd00::1]/"));
+        assert!(!is_public("http://[fe80::1]/"));
+        assert!(!is_public("http://localhost:8888/"));
+        assert!(!is_public("http://api.localhost/"));
+        assert!(!is_public("http://metadata.google.internal/"));
+        assert!(!is_public("http://printer.local./"));
+        assert!(!is_public("http://intranet/"));
+        assert!(!is_public("ftp://hooks.example.com/"));
+    }
+
+    #[test]
+    fn public_hosts_are_accepted() {
+        assert!(is_public("http://hooks.example.com/crates-io"));
+        assert!(is_public("https://hooks.example.com:8443/"));
+        assert!(is_public("http://93.184.216.34/"));
+        assert!(is_public("http://[2606:2800:220:1::1]/"));
+        assert!(is_public("http://notlocal.com/"));
+    }
+
+    #[test]
+    fn ip_literals_are_not_resolved() {
+        let url = Url::parse("http://169.254.169.254/").unwrap();
+        assert_eq!(public_addrs(&url).unwrap(), None);
+        let url = Url::parse("http://93.184.216.34/").unwrap();
+        let addrs = public_addrs(&url).unwrap().unwrap();
+        assert_eq!(addrs, vec!["93.184.216.34:80".parse().unwrap()]);
+        let url = Url::parse("https://[2606:2800:220:1::1]/").unwrap();
+        let addrs = public_addrs(&url).unwrap().unwrap();
+        assert_eq!(addrs, vec!["[2606:2800:220:1::1]:443".parse().unwrap()]);
+    }
+
+    fn may_follow(original: &str, next: &str) -> bool {
+        may_follow_redirect(&Url::parse(original).unwrap(), &Url::parse(next).unwrap())
+    }
+
+    #[test]
+    fn redirects_on_the_same_host_are_followed() {
+        assert!(may_follow(
+            "http://hooks.example.com/crates-io",
+            "http://hooks.example.com/v2/crates-io"
+        ));
+        assert!(may_follow(
+            "http://hooks.example.com/crates-io",
+            "https://hooks.example.com:8443/crates-io"
+        ));
+        assert!(may_follow(
+            "http://93.184.216.34/crates-io",
+            "http://93.184.216.34/v2"
+        ));
+    }
+
+    #[test]
+    fn redirects_to_other_hosts_are_not_followed() {
+        let original = "http://hooks.example.com/crates-io";
+        assert!(!may_follow(original, "http://other.example.com/crates-io"));
+        assert!(!may_follow(original, "http://93.184.216.34/crates-io"));
+        assert!(!may_follow(original, "http://169.254.169.254/latest/meta-data"));
+        assert!(!may_follow(original, "http://localhost/"));
+        assert!(!may_follow(original, "ftp://hooks.example.com/"));
+    }
+}
@@ -3,9 +3,9 @@ use url::Url;
 
 use crate::github;
 use crate::models::{
-    Category, Crate, CrateOwnerInvitation, CreatedApiToken, Dependency, DependencyKind, Keyword,
-    Owner, ReverseDependency, Team, TopVersions, User, Version, VersionDownload,
-    VersionOwnerAction,
+    AssignableCategory, Category, CategoryWithSubtree, Crate, CrateOwnerInvitation, CrateWebhook,
+    CreatedApiToken, Dependency, DependencyKind, Keyword, Owner, ReverseDependency, Rights, Team,
+    TopVersions, User, Version, VersionDownload, VersionOwnerAction, WebhookEvent,
 };
 use crate::util::rfc3339;
 
@@ -25,6 +25,10 @@ pub struct EncodableCategory {
     #[serde(with = "rfc3339")]
     pub created_at: NaiveDateTime,
     pub crates_cnt: i32,
+    /// The crates in the category and all of its subcategories. Not included for categories that
+    /// are loaded without their subcategories, like the `categories` of `GET /crates/:crate_id`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subtree_crates_cnt: Option<i32>,
 }
 
 impl From<Category> for EncodableCategory {
@@ -43,11 +47,41 @@ impl From<Category> for EncodableCategory {
             description,
             created_at,
             crates_cnt,
+            subtree_crates_cnt: None,
             category: category.rsplit("::").collect::<Vec<_>>()[0].to_string(),
         }
     }
 }
 
+impl From<CategoryWithSubtree> for EncodableCategory {
+    fn from(category: CategoryWithSubtree) -> Self {
+        Self {
+            subtree_crates_cnt: Some(category.subtree_crates_cnt),
+            ..category.category.into()
+        }
+    }
+}
+
+/// A category as returned by `GET /categories/assignable`.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EncodableAssignableCategory {
+    #[serde(flatten)]
+    pub category: EncodableCategory,
+    /// How deeply the category is nested, `0` for top-level categories.
+    pub depth: usize,
+}
+
+impl From<AssignableCategory> for EncodableAssignableCategory {
+    fn from(assignable: AssignableCategory) -> Self {
+        let depth = assignable.depth();
+        let category = EncodableCategory {
+            subtree_crates_cnt: Some(assignable.subtree_crates_cnt),
+            ..assignable.category.into()
+        };
+        Self { category, depth }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct EncodableCategoryWithSubcategories {
     pub id: String,
@@ -61,6 +95,21 @@ pub struct EncodableCategoryWithSubcategories {
     pub parent_categories: Vec<EncodableCategory>,
 }
 
+/// A category of a crate, together with its parent categories.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EncodableCrateCategory {
+    pub id: String,
+    pub category: String,
+    pub slug: String,
+    pub description: String,
+    #[serde(with = "rfc3339")]
+    pub created_at: NaiveDateTime,
+    pub crates_cnt: i32,
+    /// The crates in the category and all of its subcategories.
+    pub subtree_crates_cnt: i32,
+    pub parent_categories: Vec<EncodableCategory>,
+}
+
 /// The serialization format for the `CrateOwnerInvitation` model.
 #[derive(Deserialize, Serialize, Debug, PartialEq, Eq)]
 pub struct EncodableCrateOwnerInvitationV1 {
@@ -106,12 +155,58 @@ pub struct EncodableCrateOwnerInvitation {
     pub expires_at: NaiveDateTime,
 }
 
+/// What the authenticated user is allowed to do with a crate, so that the frontend only offers
+/// actions that will succeed.
+#[derive(Serialize, Debug, Copy, Clone)]
+pub struct EncodableCratePermissions {
+    pub rights: Rights,
+    pub can_publish: bool,
+    pub can_yank: bool,
+    pub can_delete: bool,
+    pub can_manage_owners: bool,
+}
+
+impl EncodableCratePermissions {
+    /// Whether the crate can be deleted also depends on the crate itself, e.g. its age and
+    /// downloads, so it is passed in separately.
+    pub fn new(rights: Rights, can_delete: bool) -> Self {
+        Self {
+            rights,
+            can_publish: rights.can_publish(),
+            can_yank: rights.can_yank(),
+            can_delete,
+            can_manage_owners: rights.can_manage_owners(),
+        }
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug, Copy, Clone)]
 pub struct InvitationResponse {
     pub crate_id: i32,
     pub accepted: bool,
 }
 
+/// A webhook of a crate. The secret of the webhook is deliberately not included.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct EncodableCrateWebhook {
+    pub id: i32,
+    pub url: String,
+    pub events: Vec<WebhookEvent>,
+    #[serde(with = "rfc3339")]
+    pub created_at: NaiveDateTime,
+}
+
+impl From<CrateWebhook> for EncodableCrateWebhook {
+    fn from(webhook: CrateWebhook) -> Self {
+        Self {
+            events: webhook.events(),
+            id: webhook.id,
+            url: webhook.url,
+            created_at: webhook.created_at,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct EncodableDependency {
     pub id: i32,
@@ -477,6 +572,8 @@ pub struct EncodableApiTokenWithToken {
     pub created_at: NaiveDateTime,
     #[serde(with = "rfc3339::option")]
     pub last_used_at: Option<NaiveDateTime>,
+    #[serde(with = "rfc3339::option")]
+    pub expired_at: Option<NaiveDateTime>,
 }
 
 impl From<CreatedApiToken> for EncodableApiTokenWithToken {
@@ -488,6 +585,7 @@ impl From<CreatedApiToken> for EncodableApiTokenWithToken {
             revoked: token.model.revoked,
             created_at: token.model.created_at,
             last_used_at: token.model.last_used_at,
+            expired_at: token.model.expired_at,
         }
     }
 }
@@ -550,6 +648,48 @@ impl EncodablePrivateUser {
     }
 }
 
+/// The serialization format for the `User` model in responses for admins.
+/// Same as private user, except for the addition of the account lock fields
+#[derive(Deserialize, Serialize, Debug)]
+pub struct EncodableAdminUser {
+    pub id: i32,
+    pub login: String,
+    pub name: Option<String>,
+    pub email: Option<String>,
+    pub avatar: Option<String>,
+    pub url: Option<String>,
+    pub lock_reason: Option<String>,
+    #[serde(with = "rfc3339::option")]
+    pub lock_until: Option<NaiveDateTime>,
+}
+
+impl EncodableAdminUser {
+    /// Converts this `User` model into an `EncodableAdminUser` for JSON serialization.
+    pub fn from(user: User, email: Option<String>) -> Self {
+        let User {
+            id,
+            name,
+            gh_login,
+            gh_avatar,
+            account_lock_reason,
+            account_lock_until,
+            ..
+        } = user;
+        let url = format!("https://github.com/{gh_login}");
+
+        EncodableAdminUser {
+            id,
+            email,
+            avatar: gh_avatar,
+            login: gh_login,
+            name,
+            url: Some(url),
+            lock_reason: account_lock_reason,
+            lock_until: account_lock_until,
+        }
+    }
+}
+
 /// The serialization format for the `User` model.
 /// Same as private user, except no email field
 #[derive(Deserialize, Serialize, Debug, PartialEq, Eq)]
@@ -613,6 +753,7 @@ pub struct EncodableVersion {
     pub published_by: Option<EncodablePublicUser>,
     pub audit_actions: Vec<EncodableAuditAction>,
     pub checksum: String,
+    pub rust_version: Option<String>,
 }
 
 impl EncodableVersion {
@@ -633,6 +774,7 @@ impl EncodableVersion {
             license,
             crate_size,
             checksum,
+            rust_version,
             ..
         } = version;
 
@@ -657,6 +799,7 @@ impl EncodableVersion {
             links,
             crate_size,
             checksum,
+            rust_version,
             published_by: published_by.map(User::into),
             audit_actions: audit_actions
                 .into_iter()
@@ -704,6 +847,7 @@ mod tests {
             slug: "".to_string(),
             description: "".to_string(),
             crates_cnt: 1,
+            subtree_crates_cnt: None,
             created_at: NaiveDate::from_ymd_opt(2017, 1, 6)
                 .unwrap()
                 .and_hms_opt(14, 23, 11)
@@ -780,6 +924,7 @@ mod tests {
             },
             crate_size: Some(1234),
             checksum: String::new(),
+            rust_version: None,
             published_by: None,
             audit_actions: vec![EncodableAuditAction {
                 action: "publish".to_string(),
@@ -6,8 +6,6 @@ use std::collections::BTreeMap;
 
 use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
-use crate::models::krate::MAX_NAME_LENGTH;
-
 use crate::models::Crate;
 use crate::models::DependencyKind;
 use crate::models::Keyword as CrateKeyword;
@@ -71,13 +69,13 @@ pub struct EncodableCrateDependency {
 impl<'de> Deserialize<'de> for EncodableCrateName {
     fn deserialize<D: Deserializer<'de>>(d: D) -> Result<EncodableCrateName, D::Error> {
         let s = String::deserialize(d)?;
-        if !Crate::valid_name(&s) {
+        // The length of crate names is checked by `validate_crate_name()`, so that the limit
+        // can be configured
+        if !Crate::valid_ident(&s) {
             let value = de::Unexpected::Str(&s);
-            let expected = format!(
-                "a valid crate name to start with a letter, contain only letters, \
-                 numbers, hyphens, or underscores and have at most {MAX_NAME_LENGTH} characters"
-            );
-            Err(de::Error::invalid_value(value, &expected.as_ref()))
+            let expected = "a valid crate name to start with a letter, contain only letters, \
+                 numbers, hyphens, or underscores";
+            Err(de::Error::invalid_value(value, &expected))
         } else {
             Ok(EncodableCrateName(s))
         }
@@ -96,13 +94,13 @@ where
 impl<'de> Deserialize<'de> for EncodableDependencyName {
     fn deserialize<D: Deserializer<'de>>(d: D) -> Result<EncodableDependencyName, D::Error> {
         let s = String::deserialize(d)?;
-        if !Crate::valid_dependency_name(&s) {
+        // The length of dependency names is checked by `validate_dependency_name()`, so that the
+        // limit can be configured
+        if !Crate::valid_dependency_ident(&s) {
             let value = de::Unexpected::Str(&s);
-            let expected = format!(
-                "a valid dependency name to start with a letter or underscore, contain only letters, \
-                 numbers, hyphens, or underscores and have at most {MAX_NAME_LENGTH} characters"
-            );
-            Err(de::Error::invalid_value(value, &expected.as_ref()))
+            let expected = "a valid dependency name to start with a letter or underscore, contain \
+                 only letters, numbers, hyphens, or underscores";
+            Err(de::Error::invalid_value(value, &expected))
         } else {
             Ok(EncodableDependencyName(s))
         }
@@ -194,15 +192,12 @@ impl<'de> Deserialize<'de> for EncodableKeywordList {
     }
 }
 
+/// The number of categories isn't limited here, since the limit is configurable and enforced by
+/// `Category::update_crate`.
 impl<'de> Deserialize<'de> for EncodableCategoryList {
     fn deserialize<D: Deserializer<'de>>(d: D) -> Result<EncodableCategoryList, D::Error> {
         let inner = <Vec<EncodableCategory> as Deserialize<'de>>::deserialize(d)?;
-        if inner.len() > 5 {
-            let expected = "at most 5 categories per crate";
-            Err(de::Error::invalid_length(inner.len(), &expected))
-        } else {
-            Ok(EncodableCategoryList(inner))
-        }
+        Ok(EncodableCategoryList(inner))
     }
 }
 
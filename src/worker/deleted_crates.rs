@@ -0,0 +1,90 @@
+//! Permanently remove crates that were deleted by their owners.
+
+use crate::background_jobs::{Environment, Job};
+use crate::schema::{crate_deletions, crates, versions};
+use crate::swirl::PerformError;
+use crate::worker;
+use chrono::Utc;
+use diesel::prelude::*;
+
+/// Removes crates whose grace period has passed since they were deleted.
+///
+/// The database rows are removed right away, while the files in storage and the index file of
+/// each crate are removed by separate jobs, so that they can be retried independently.
+pub fn perform_purge_deleted_crates(
+    env: &Environment,
+    conn: &mut PgConnection,
+) -> Result<(), PerformError> {
+    let grace_period = chrono::Duration::from_std(env.crate_deletion_grace_period())?;
+    let cutoff = Utc::now().naive_utc() - grace_period;
+
+    let expired: Vec<(i32, String)> = crates::table
+        .filter(crates::deleted_at.lt(cutoff))
+        .select((crates::id, crates::name))
+        .load(conn)?;
+
+    for (crate_id, crate_name) in expired {
+        info!(
+            krate.name = crate_name,
+            "Permanently removing deleted crate"
+        );
+
+        conn.transaction(|conn| {
+            let version_nums: Vec<String> = versions::table
+                .filter(versions::crate_id.eq(crate_id))
+                .select(versions::num)
+                .load(conn)?;
+
+            // The categories of the crate are removed together with it
+            worker::sync_category_feeds_of_crate(conn, crate_id)?;
+
+            // All other rows belonging to the crate are removed by `ON DELETE CASCADE`
+            diesel::delete(crates::table.find(crate_id)).execute(conn)?;
+
+            for version_num in version_nums {
+                worker::delete_version_from_storage(crate_name.clone(), version_num)
+                    .enqueue(conn)?;
+            }
+            worker::remove_crate_from_index(crate_name).enqueue(conn)?;
+
+            Ok::<_, PerformError>(())
+        })?;
+    }
+
+    Ok(())
+}
+
+pub fn purge_deleted_crates() -> Job {
+    Job::PurgeDeletedCrates
+}
+
+/// Removes the user that deleted a crate from the records of deletions that are older than the
+/// retention period.
+///
+/// The crate name and the time of the deletion are kept, so that the records can still be used
+/// for statistics.
+pub fn perform_anonymize_crate_deletions(
+    env: &Environment,
+    conn: &mut PgConnection,
+) -> Result<(), PerformError> {
+    let retention = chrono::Duration::from_std(env.crate_deletion_audit_retention())?;
+    let now = Utc::now().naive_utc();
+    let cutoff = now - retention;
+
+    let anonymized = diesel::update(crate_deletions::table)
+        .filter(crate_deletions::deleted_at.lt(cutoff))
+        .filter(crate_deletions::anonymized_at.is_null())
+        .set((
+            crate_deletions::deleted_by.eq(None::<i32>),
+            crate_deletions::anonymized_at.eq(now),
+        ))
+        .execute(conn)?;
+
+    info!("Anonymized {anonymized} crate deletion records");
+
+    Ok(())
+}
+
+pub fn anonymize_crate_deletions() -> Job {
+    Job::AnonymizeCrateDeletions
+}
@@ -0,0 +1,109 @@
+//! Check that the download counts of crates match the download counts of their versions.
+
+use crate::background_jobs::{Job, VerifyDownloadTotalsJob};
+use crate::schema::{crates, metadata, versions};
+use crate::swirl::PerformError;
+use diesel::dsl::sum;
+use diesel::prelude::*;
+
+/// Compares the download counts of a batch of crates with the sum of the download counts of
+/// their versions, which the `update_downloads` job always increases together.
+///
+/// Differences larger than `threshold` are logged, and if `fix` is set, the download count of
+/// the crate is set to the sum of its versions. Another job is enqueued for the next batch, until
+/// all crates were checked.
+pub fn perform_verify_download_totals(
+    conn: &mut PgConnection,
+    args: &VerifyDownloadTotalsJob,
+) -> Result<(), PerformError> {
+    let totals: Vec<(i32, String, i32, Option<i64>)> = crates::table
+        .left_join(versions::table)
+        .filter(crates::id.gt(args.after_crate_id))
+        .group_by(crates::id)
+        .order(crates::id)
+        .select((
+            crates::id,
+            crates::name,
+            crates::downloads,
+            sum(versions::downloads.nullable()),
+        ))
+        .limit(args.batch_size)
+        .load(conn)?;
+
+    for (crate_id, crate_name, downloads, versions_downloads) in &totals {
+        let versions_downloads = versions_downloads.unwrap_or(0);
+        let difference = versions_downloads - i64::from(*downloads);
+        if difference.abs() <= args.threshold {
+            continue;
+        }
+
+        warn!(
+            krate.name = crate_name,
+            downloads,
+            versions_downloads,
+            fix = args.fix,
+            "Download count of the crate doesn't match its versions"
+        );
+
+        if args.fix {
+            fix_download_total(conn, *crate_id)?;
+        }
+    }
+
+    if totals.len() as i64 == args.batch_size {
+        if let Some(&(last_crate_id, ..)) = totals.last() {
+            let next = VerifyDownloadTotalsJob {
+                after_crate_id: last_crate_id,
+                ..*args
+            };
+            Job::VerifyDownloadTotals(next).enqueue(conn)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Sets the download count of the crate to the sum of its versions, and updates the global
+/// download count accordingly.
+fn fix_download_total(conn: &mut PgConnection, crate_id: i32) -> QueryResult<()> {
+    conn.transaction(|conn| {
+        // The `update_downloads` job updates the version before the crate, so locking the crate
+        // first makes sure that the sum doesn't include downloads that the crate is missing
+        // only because that job's transaction isn't committed yet.
+        let downloads: i32 = crates::table
+            .find(crate_id)
+            .select(crates::downloads)
+            .for_update()
+            .first(conn)?;
+        let versions_downloads: Option<i64> = versions::table
+            .filter(versions::crate_id.eq(crate_id))
+            .select(sum(versions::downloads))
+            .first(conn)?;
+        let versions_downloads = versions_downloads.unwrap_or(0);
+
+        let fixed = i32::try_from(versions_downloads).unwrap_or(i32::MAX);
+        diesel::update(crates::table.find(crate_id))
+            .set(crates::downloads.eq(fixed))
+            .execute(conn)?;
+
+        diesel::update(metadata::table)
+            .set(
+                metadata::total_downloads
+                    .eq(metadata::total_downloads + i64::from(fixed) - i64::from(downloads)),
+            )
+            .execute(conn)?;
+
+        Ok(())
+    })
+}
+
+/// Checks that the download counts of all crates match their versions, in batches of
+/// `batch_size` crates. See `perform_verify_download_totals` for the details.
+pub fn verify_download_totals(batch_size: i64, threshold: i64, fix: bool) -> Job {
+    Job::VerifyDownloadTotals(VerifyDownloadTotalsJob {
+        after_crate_id: 0,
+        batch_size,
+        threshold,
+        fix,
+    })
+}
@@ -0,0 +1,47 @@
+//! Send notification emails that don't need to block the request that triggered them.
+
+use crate::background_jobs::{Environment, Job, SendOwnershipTransferEmailsJob};
+use crate::models::User;
+use crate::swirl::PerformError;
+use diesel::prelude::*;
+
+/// Notifies both parties of an ownership transfer.
+///
+/// Users without a verified email address are skipped.
+pub fn perform_send_ownership_transfer_emails(
+    env: &Environment,
+    conn: &mut PgConnection,
+    crate_name: &str,
+    previous_owner_id: i32,
+    new_owner_id: i32,
+) -> Result<(), PerformError> {
+    let previous_owner = User::find(conn, previous_owner_id)?;
+    let new_owner = User::find(conn, new_owner_id)?;
+
+    for user in [&previous_owner, &new_owner] {
+        if let Some(email) = user.verified_email(conn)? {
+            env.emails()
+                .send_ownership_transfer(
+                    &email,
+                    crate_name,
+                    &previous_owner.gh_login,
+                    &new_owner.gh_login,
+                )
+                .map_err(|err| err.to_string())?;
+        }
+    }
+
+    Ok(())
+}
+
+pub fn send_ownership_transfer_emails(
+    crate_name: String,
+    previous_owner_id: i32,
+    new_owner_id: i32,
+) -> Job {
+    Job::SendOwnershipTransferEmails(SendOwnershipTransferEmailsJob {
+        crate_name,
+        previous_owner_id,
+        new_owner_id,
+    })
+}
@@ -0,0 +1,440 @@
+//! Generate RSS and Atom feeds and upload them to S3.
+
+use crate::swirl::PerformError;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use diesel::dsl::sql;
+use diesel::prelude::*;
+use diesel::sql_types::{Bool, Integer, Text};
+
+use crate::background_jobs::{Environment, Job, SyncCategoryFeedJob, SyncUserFeedJob};
+use crate::schema::{categories, crates, crates_categories, users, versions};
+use crate::swirl::errors::EnqueueError;
+
+/// The number of items included in each category feed.
+const FEED_LENGTH: i64 = 25;
+
+/// A feed, independent of the format it is serialized to.
+struct Feed {
+    title: String,
+    /// The URL of the page that the feed corresponds to.
+    link: String,
+    description: String,
+    items: Vec<FeedItem>,
+}
+
+struct FeedItem {
+    title: String,
+    link: String,
+    description: Option<String>,
+    published_at: NaiveDateTime,
+}
+
+/// A row of `VERSION_COLUMNS`.
+type VersionRow = (String, String, Option<String>, NaiveDateTime, bool);
+
+/// The columns needed for `FeedItem::version()`.
+type VersionColumns = (
+    crates::name,
+    versions::num,
+    crates::description,
+    versions::created_at,
+    versions::yanked,
+);
+
+const VERSION_COLUMNS: VersionColumns = (
+    crates::name,
+    versions::num,
+    crates::description,
+    versions::created_at,
+    versions::yanked,
+);
+
+impl FeedItem {
+    /// Creates an item for a published version.
+    fn version(
+        domain_name: &str,
+        (crate_name, num, description, created_at, yanked): VersionRow,
+    ) -> Self {
+        let title = if yanked {
+            format!("{crate_name} v{num} (yanked)")
+        } else {
+            format!("{crate_name} v{num}")
+        };
+
+        Self {
+            title,
+            link: format!("https://{domain_name}/crates/{crate_name}/{num}"),
+            description,
+            published_at: created_at,
+        }
+    }
+
+    /// Creates an item for a newly published crate.
+    fn krate(
+        domain_name: &str,
+        (name, description, created_at): (String, Option<String>, NaiveDateTime),
+    ) -> Self {
+        Self {
+            link: format!("https://{domain_name}/crates/{name}"),
+            title: name,
+            description,
+            published_at: created_at,
+        }
+    }
+
+    fn published_at(&self) -> DateTime<Utc> {
+        DateTime::from_utc(self.published_at, Utc)
+    }
+}
+
+impl Feed {
+    /// Renders the feed as an RSS 2.0 channel.
+    ///
+    /// The channel deliberately has no `lastBuildDate`, so that regenerating a feed without any
+    /// new items results in an identical file.
+    fn to_rss(&self) -> String {
+        let items = self
+            .items
+            .iter()
+            .map(|item| rss::Item {
+                title: Some(item.title.clone()),
+                link: Some(item.link.clone()),
+                description: item.description.clone(),
+                guid: Some(rss::Guid {
+                    value: item.link.clone(),
+                    permalink: true,
+                }),
+                pub_date: Some(item.published_at().to_rfc2822()),
+                ..Default::default()
+            })
+            .collect();
+
+        let channel = rss::Channel {
+            title: self.title.clone(),
+            link: self.link.clone(),
+            description: self.description.clone(),
+            items,
+            ..Default::default()
+        };
+        channel.to_string()
+    }
+
+    /// Renders the feed as an Atom 1.0 feed.
+    ///
+    /// Atom requires the feed to have an `updated` timestamp. The publish time of the newest item
+    /// is used for it, for the same reason that the RSS channel has no `lastBuildDate`.
+    fn to_atom(&self) -> String {
+        let entries = self
+            .items
+            .iter()
+            .map(|item| atom_syndication::Entry {
+                title: atom_syndication::Text::plain(&item.title),
+                id: item.link.clone(),
+                updated: item.published_at().into(),
+                published: Some(item.published_at().into()),
+                links: vec![atom_syndication::Link {
+                    href: item.link.clone(),
+                    ..Default::default()
+                }],
+                summary: item
+                    .description
+                    .as_deref()
+                    .map(atom_syndication::Text::plain),
+                ..Default::default()
+            })
+            .collect();
+
+        let updated = self
+            .items
+            .iter()
+            .map(FeedItem::published_at)
+            .max()
+            .unwrap_or_default();
+
+        let feed = atom_syndication::Feed {
+            title: atom_syndication::Text::plain(&self.title),
+            id: self.link.clone(),
+            updated: updated.into(),
+            links: vec![atom_syndication::Link {
+                href: self.link.clone(),
+                ..Default::default()
+            }],
+            subtitle: Some(atom_syndication::Text::plain(&self.description)),
+            entries,
+            ..Default::default()
+        };
+        feed.to_string()
+    }
+}
+
+pub fn perform_sync_crates_feeds(
+    env: &Environment,
+    conn: &mut PgConnection,
+) -> Result<(), PerformError> {
+    let domain_name = crate::config::domain_name();
+
+    let new_crates = crates::table
+        .filter(crates::deleted_at.is_null())
+        .order(crates::created_at.desc())
+        .limit(env.feeds().crates_feed_length)
+        .select((crates::name, crates::description, crates::created_at))
+        .load(conn)?
+        .into_iter()
+        .map(|row| FeedItem::krate(&domain_name, row))
+        .collect();
+    let new_crates = Feed {
+        title: "crates.io: newest crates".into(),
+        link: format!("https://{domain_name}/crates?sort=new"),
+        description: "Crates that were recently published for the first time".into(),
+        items: new_crates,
+    };
+
+    let mut updates = versions::table
+        .inner_join(crates::table)
+        .filter(crates::deleted_at.is_null())
+        .into_boxed();
+    if !env.feeds().include_yanked {
+        updates = updates.filter(versions::yanked.eq(false));
+    }
+    let updates = updates
+        .order(versions::created_at.desc())
+        .limit(env.feeds().updates_feed_length)
+        .select(VERSION_COLUMNS)
+        .load(conn)?
+        .into_iter()
+        .map(|row| FeedItem::version(&domain_name, row))
+        .collect();
+    let updates = Feed {
+        title: "crates.io: recent updates".into(),
+        link: format!("https://{domain_name}/crates?sort=recent-updates"),
+        description: "Recently published versions".into(),
+        items: updates,
+    };
+
+    // Both formats are rendered from the same items, so that they never diverge
+    for (name, feed) in [("crates", new_crates), ("updates", updates)] {
+        env.uploader
+            .upload_rss_feed(env.http_client(), name, feed.to_rss())?;
+        env.uploader
+            .upload_atom_feed(env.http_client(), name, feed.to_atom())?;
+    }
+    Ok(())
+}
+
+pub fn sync_crates_feeds() -> Job {
+    Job::SyncCratesFeeds
+}
+
+pub fn perform_sync_category_feed(
+    env: &Environment,
+    conn: &mut PgConnection,
+    slug: &str,
+) -> Result<(), PerformError> {
+    let Some(category) = categories::table
+        .filter(categories::slug.eq(slug))
+        .select((categories::category, categories::description))
+        .first::<(String, String)>(conn)
+        .optional()? else {
+        warn!(slug, "Skipping feed generation for unknown category");
+        return Ok(());
+    };
+    let (category_name, category_description) = category;
+
+    // Include crates in all subcategories, like the crate counts of a category do
+    let category_ids = categories::table.select(categories::id).filter(
+        sql::<Bool>("path <@ (SELECT path FROM categories WHERE slug = ")
+            .bind::<Text, _>(slug)
+            .sql(")"),
+    );
+    let crate_ids = crates_categories::table
+        .select(crates_categories::crate_id)
+        .filter(crates_categories::category_id.eq_any(category_ids));
+
+    let domain_name = crate::config::domain_name();
+    let items = versions::table
+        .inner_join(crates::table)
+        .filter(versions::crate_id.eq_any(crate_ids))
+        .filter(crates::deleted_at.is_null())
+        .filter(versions::yanked.eq(false))
+        .order(versions::created_at.desc())
+        .limit(FEED_LENGTH)
+        .select(VERSION_COLUMNS)
+        .load(conn)?
+        .into_iter()
+        .map(|row| FeedItem::version(&domain_name, row))
+        .collect();
+
+    let feed = Feed {
+        title: format!("crates.io: {category_name}"),
+        link: format!("https://{domain_name}/categories/{slug}"),
+        description: category_description,
+        items,
+    };
+
+    env.uploader.upload_rss_feed(
+        env.http_client(),
+        &format!("categories/{slug}"),
+        feed.to_rss(),
+    )?;
+    Ok(())
+}
+
+pub fn sync_category_feed(slug: String) -> Job {
+    Job::SyncCategoryFeed(SyncCategoryFeedJob { slug })
+}
+
+/// Enqueues `sync_category_feed` for the categories of a crate and their parents, which are all
+/// feeds that the crate can show up in.
+///
+/// This has to be called before the categories of the crate are removed, e.g. when it is
+/// removed permanently.
+pub fn sync_category_feeds_of_crate(
+    conn: &mut PgConnection,
+    crate_id: i32,
+) -> Result<(), EnqueueError> {
+    let slugs: Vec<String> = categories::table
+        .select(categories::slug)
+        .filter(
+            sql::<Bool>(
+                "path @> ANY(SELECT categories.path FROM categories \
+                 INNER JOIN crates_categories ON crates_categories.category_id = categories.id \
+                 WHERE crates_categories.crate_id = ",
+            )
+            .bind::<Integer, _>(crate_id)
+            .sql(")"),
+        )
+        .order(categories::slug)
+        .load(conn)?;
+
+    for slug in slugs {
+        sync_category_feed(slug).enqueue(conn)?;
+    }
+    Ok(())
+}
+
+pub fn perform_sync_user_feed(
+    env: &Environment,
+    conn: &mut PgConnection,
+    user_id: i32,
+) -> Result<(), PerformError> {
+    // The job refers to the user by id, so that the feed moves to the new path when a user
+    // renames their GitHub account
+    let login: String = users::table
+        .find(user_id)
+        .select(users::gh_login)
+        .first(conn)?;
+
+    let domain_name = crate::config::domain_name();
+    let items = versions::table
+        .inner_join(crates::table)
+        .filter(versions::published_by.eq(user_id))
+        .filter(crates::deleted_at.is_null())
+        .filter(versions::yanked.eq(false))
+        .order(versions::created_at.desc())
+        .limit(env.feeds().user_feed_length)
+        .select(VERSION_COLUMNS)
+        .load(conn)?
+        .into_iter()
+        .map(|row| FeedItem::version(&domain_name, row))
+        .collect();
+
+    let feed = Feed {
+        title: format!("crates.io: {login}"),
+        link: format!("https://{domain_name}/users/{login}"),
+        description: format!("Crates published by {login}"),
+        items,
+    };
+
+    env.uploader
+        .upload_rss_feed(env.http_client(), &format!("users/{login}"), feed.to_rss())?;
+    Ok(())
+}
+
+pub fn sync_user_feed(user_id: i32) -> Job {
+    Job::SyncUserFeed(SyncUserFeedJob { user_id })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn feed() -> Feed {
+        let published_at = |day| {
+            NaiveDate::from_ymd_opt(2023, 3, day)
+                .unwrap()
+                .and_hms_opt(12, 0, 0)
+                .unwrap()
+        };
+
+        Feed {
+            title: "crates.io: recent updates".into(),
+            link: "https://crates.io/crates?sort=recent-updates".into(),
+            description: "Recently published versions".into(),
+            items: vec![
+                FeedItem::version(
+                    "crates.io",
+                    ("foo".into(), "1.1.0".into(), None, published_at(2), false),
+                ),
+                FeedItem::version(
+                    "crates.io",
+                    (
+                        "bar".into(),
+                        "0.1.0".into(),
+                        Some("The bar crate".into()),
+                        published_at(1),
+                        false,
+                    ),
+                ),
+            ],
+        }
+    }
+
+    #[test]
+    fn atom_feed_is_well_formed() {
+        let atom: atom_syndication::Feed = feed().to_atom().parse().unwrap();
+
+        assert_eq!(atom.title.as_str(), "crates.io: recent updates");
+        assert_eq!(atom.updated.to_rfc3339(), "2023-03-02T12:00:00+00:00");
+
+        let entries = atom
+            .entries
+            .iter()
+            .map(|entry| (entry.title.as_str(), entry.id.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            entries,
+            [
+                ("foo v1.1.0", "https://crates.io/crates/foo/1.1.0"),
+                ("bar v0.1.0", "https://crates.io/crates/bar/0.1.0"),
+            ]
+        );
+        assert_eq!(atom.entries[0].summary, None);
+        assert_eq!(
+            atom.entries[1]
+                .summary
+                .as_ref()
+                .map(|summary| summary.as_str()),
+            Some("The bar crate")
+        );
+    }
+
+    #[test]
+    fn rss_and_atom_feeds_contain_the_same_items() {
+        let feed = feed();
+        let channel: rss::Channel = feed.to_rss().parse().unwrap();
+        let atom: atom_syndication::Feed = feed.to_atom().parse().unwrap();
+
+        let rss_links = channel
+            .items
+            .iter()
+            .map(|item| item.link.as_deref().unwrap())
+            .collect::<Vec<_>>();
+        let atom_links = atom
+            .entries
+            .iter()
+            .map(|entry| entry.links[0].href.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(rss_links, atom_links);
+    }
+}
@@ -1,6 +1,8 @@
 use crate::background_jobs::{
-    Environment, IndexAddCrateJob, IndexSyncToHttpJob, IndexUpdateYankedJob, Job, NormalizeIndexJob,
+    Environment, IndexAddCrateJob, IndexRemoveCrateJob, IndexSyncToGitJob, IndexSyncToHttpJob,
+    IndexUpdateYankedJob, Job, NormalizeIndexJob,
 };
+use crate::models;
 use crate::schema;
 use crate::swirl::PerformError;
 use anyhow::Context;
@@ -42,17 +44,134 @@ pub fn add_crate(krate: Crate) -> Job {
     Job::IndexAddCrate(IndexAddCrateJob { krate })
 }
 
-#[instrument(skip(env))]
+/// Removes the index file of a crate, after the crate was permanently deleted.
+#[instrument(skip(env, conn))]
+pub fn perform_index_remove_crate(
+    env: &Environment,
+    conn: &mut PgConnection,
+    crate_name: &str,
+) -> Result<(), PerformError> {
+    info!("Removing crate from the index");
+
+    let repo = env.lock_index()?;
+    let dst = repo.index_file(crate_name);
+
+    match fs::remove_file(&dst) {
+        Ok(()) => {
+            let message = format!("Deleting crate `{crate_name}`");
+            repo.commit_and_push(&message, &dst)?;
+        }
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            debug!("Skipping index removal because the crate isn't in the index");
+        }
+        Err(e) => return Err(e.into()),
+    }
+
+    // Queue another background job to update the http-based index as well.
+    update_crate_index(crate_name.to_string()).enqueue(conn)?;
+    Ok(())
+}
+
+pub fn remove_crate_from_index(crate_name: String) -> Job {
+    Job::IndexRemoveCrate(IndexRemoveCrateJob { crate_name })
+}
+
+/// Rewrites the index file of a crate from the versions in the database, or removes it if the
+/// crate doesn't exist anymore.
+///
+/// Crates that are deleted, but haven't been removed permanently yet, keep their index file, like
+/// they do after a regular deletion.
+#[instrument(skip(env, conn))]
+pub fn perform_index_sync_to_git(
+    env: &Environment,
+    conn: &mut PgConnection,
+    crate_name: &str,
+) -> Result<(), PerformError> {
+    info!("Syncing database to git index");
+
+    // Not `Crate::all()`, since deleted crates keep their index file until they are removed
+    let krate: Option<models::Crate> = schema::crates::table
+        .select(models::krate::ALL_COLUMNS)
+        .filter(schema::crates::name.eq(crate_name))
+        .first(conn)
+        .optional()?;
+
+    let contents = match krate {
+        Some(krate) => {
+            let mut contents = Vec::new();
+            for version in krate.index_metadata(conn)? {
+                serde_json::to_writer(&mut contents, &version)?;
+                contents.push(b'\n');
+            }
+            Some(contents).filter(|contents| !contents.is_empty())
+        }
+        None => None,
+    };
+
+    let repo = env.lock_index()?;
+    let dst = repo.index_file(crate_name);
+
+    let existing = match fs::read(&dst) {
+        Ok(existing) => Some(existing),
+        Err(e) if e.kind() == ErrorKind::NotFound => None,
+        Err(e) => return Err(e.into()),
+    };
+
+    if existing == contents {
+        debug!("Skipping git index sync because the index file is up to date");
+    } else {
+        match contents {
+            Some(contents) => {
+                fs::create_dir_all(dst.parent().unwrap())?;
+                fs::write(&dst, contents)?;
+                let message = format!("Syncing crate `{crate_name}` from the database");
+                repo.commit_and_push(&message, &dst)?;
+            }
+            None => {
+                fs::remove_file(&dst)?;
+                let message = format!("Deleting crate `{crate_name}`");
+                repo.commit_and_push(&message, &dst)?;
+            }
+        }
+    }
+
+    // Queue another background job to update the http-based index as well.
+    update_crate_index(crate_name.to_string()).enqueue(conn)?;
+    Ok(())
+}
+
+pub fn sync_to_git_index(crate_name: String) -> Job {
+    Job::IndexSyncToGit(IndexSyncToGitJob { crate_name })
+}
+
+/// Uploads the index file of a crate to the HTTP-based index.
+///
+/// Crates that are deleted, but haven't been removed permanently yet, are still in the git index,
+/// so that they can be restored. They are removed from the HTTP-based index though.
+///
+/// Crates that were removed permanently are removed from the HTTP-based index as well, even if
+/// the job removing them from the git index hasn't run yet. Otherwise a sync running in between
+/// would upload the stale index file again.
+#[instrument(skip(env, conn))]
 pub fn perform_index_sync_to_http(
     env: &Environment,
+    conn: &mut PgConnection,
     crate_name: String,
 ) -> Result<(), PerformError> {
     info!("Syncing git index to HTTP-based index");
 
+    let is_deleted = schema::crates::table
+        .filter(schema::crates::name.eq(&crate_name))
+        .select(schema::crates::deleted_at.is_not_null())
+        .first::<bool>(conn)
+        .optional()?
+        .unwrap_or(true);
+
     let repo = env.lock_index()?;
     let dst = repo.index_file(&crate_name);
 
     let contents = match fs::read_to_string(dst) {
+        _ if is_deleted => None,
         Ok(contents) => Some(contents),
         Err(e) if e.kind() == ErrorKind::NotFound => None,
         Err(e) => return Err(e.into()),
@@ -0,0 +1,101 @@
+//! Check that the index files of crates match the versions in the database.
+
+use crate::background_jobs::{Environment, Job, VerifyIndexConsistencyJob};
+use crate::models::krate::ALL_COLUMNS;
+use crate::models::Crate;
+use crate::schema::crates;
+use crate::swirl::PerformError;
+use crate::worker;
+use diesel::prelude::*;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::ErrorKind;
+
+/// Compares the git index files of a batch of crates with the entries built from the database by
+/// `Crate::index_metadata()`, which is also what the `sync_to_git_index` job writes. The
+/// HTTP-based index is uploaded from the git index, so this covers both indexes.
+///
+/// The set of versions, their yanked flags and their checksums are compared. Mismatches are
+/// logged, and if `fix` is set, index syncs are enqueued for the crate. Another job is enqueued
+/// for the next batch, until all crates were checked.
+pub fn perform_verify_index_consistency(
+    env: &Environment,
+    conn: &mut PgConnection,
+    args: &VerifyIndexConsistencyJob,
+) -> Result<(), PerformError> {
+    // Deleted crates keep their git index file until they are removed, so they are checked too
+    let krates: Vec<Crate> = crates::table
+        .select(ALL_COLUMNS)
+        .filter(crates::id.gt(args.after_crate_id))
+        .order(crates::id)
+        .limit(args.batch_size)
+        .load(conn)?;
+
+    let mut mismatched = Vec::new();
+    {
+        let repo = env.lock_index()?;
+        for krate in &krates {
+            let contents = match fs::read_to_string(repo.index_file(&krate.name)) {
+                Ok(contents) => contents,
+                Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+                Err(e) => return Err(e.into()),
+            };
+
+            let indexed = contents
+                .lines()
+                .filter(|line| !line.is_empty())
+                .map(serde_json::from_str)
+                .collect::<Result<Vec<_>, _>>()?;
+            let indexed = index_summary(indexed);
+            let expected = index_summary(krate.index_metadata(conn)?);
+
+            if indexed != expected {
+                warn!(
+                    krate.name = krate.name,
+                    indexed_versions = indexed.len(),
+                    expected_versions = expected.len(),
+                    fix = args.fix,
+                    "Index file of the crate doesn't match the database"
+                );
+                mismatched.push(krate.name.clone());
+            }
+        }
+    }
+
+    if args.fix {
+        for crate_name in mismatched {
+            // The git index sync also enqueues the sync of the HTTP-based index
+            worker::sync_to_git_index(crate_name).enqueue(conn)?;
+        }
+    }
+
+    if krates.len() as i64 == args.batch_size {
+        if let Some(last) = krates.last() {
+            let next = VerifyIndexConsistencyJob {
+                after_crate_id: last.id,
+                ..*args
+            };
+            Job::VerifyIndexConsistency(next).enqueue(conn)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Maps the version numbers of index entries to their yanked flags and checksums.
+fn index_summary(entries: Vec<cargo_registry_index::Crate>) -> BTreeMap<String, (bool, String)> {
+    entries
+        .into_iter()
+        .map(|entry| (entry.vers, (entry.yanked.unwrap_or(false), entry.cksum)))
+        .collect()
+}
+
+/// Checks that the index files of all crates match the database, in batches of `batch_size`
+/// crates. See `perform_verify_index_consistency` for the details.
+pub fn verify_index_consistency(batch_size: i64, fix: bool) -> Job {
+    Job::VerifyIndexConsistency(VerifyIndexConsistencyJob {
+        after_crate_id: 0,
+        batch_size,
+        fix,
+    })
+}
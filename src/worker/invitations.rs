@@ -0,0 +1,33 @@
+//! Clean up ownership invitations that can't be accepted anymore.
+
+use crate::background_jobs::{Environment, Job};
+use crate::schema::crate_owner_invitations;
+use crate::swirl::PerformError;
+use chrono::Utc;
+use diesel::prelude::*;
+
+/// Deletes ownership invitations that expired, like `CrateOwnerInvitation::is_expired` would
+/// report them.
+///
+/// Expired invitations are already hidden from the invitation lists and can't be accepted, so
+/// this only keeps them from piling up in the database.
+pub fn perform_purge_expired_invitations(
+    env: &Environment,
+    conn: &mut PgConnection,
+) -> Result<(), PerformError> {
+    info!("Purging expired ownership invitations");
+
+    let days = env.ownership_invitations_expiration_days() as i64;
+    let cutoff = Utc::now().naive_utc() - chrono::Duration::days(days);
+
+    let deleted = diesel::delete(crate_owner_invitations::table)
+        .filter(crate_owner_invitations::created_at.le(cutoff))
+        .execute(conn)?;
+
+    info!(deleted, "Finished purging expired ownership invitations");
+    Ok(())
+}
+
+pub fn purge_expired_invitations() -> Job {
+    Job::PurgeExpiredInvitations
+}
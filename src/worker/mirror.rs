@@ -0,0 +1,116 @@
+//! Notify an external mirror about deleted crates, so that it can remove its copy, and about
+//! deleted crates that were restored by an admin.
+
+use chrono::NaiveDateTime;
+use http::header;
+use serde::Serialize;
+
+use super::webhooks::{signature, EVENT_HEADER, SIGNATURE_HEADER};
+use crate::background_jobs::{
+    Environment, Job, NotifyMirrorOfDeletionJob, NotifyMirrorOfRestorationJob,
+};
+use crate::config::MirrorConfig;
+use crate::swirl::PerformError;
+use crate::util::rfc3339;
+
+#[derive(Serialize)]
+struct DeletionPayload<'a> {
+    #[serde(rename = "crate")]
+    crate_name: &'a str,
+    #[serde(with = "rfc3339")]
+    deleted_at: NaiveDateTime,
+    reason: &'a str,
+}
+
+#[derive(Serialize)]
+struct RestorationPayload<'a> {
+    #[serde(rename = "crate")]
+    crate_name: &'a str,
+    #[serde(with = "rfc3339")]
+    restored_at: NaiveDateTime,
+}
+
+/// Sends a signed webhook about a deleted crate to the configured mirror.
+///
+/// Nothing is sent if no mirror is configured. Error responses of the mirror fail the job, so
+/// that it is retried later.
+#[instrument(skip(env))]
+pub fn perform_notify_mirror_of_deletion(
+    env: &Environment,
+    crate_name: &str,
+    deleted_at: NaiveDateTime,
+    reason: &str,
+) -> Result<(), PerformError> {
+    let Some(mirror) = env.mirror() else {
+        info!("Skipping mirror notification, since no mirror is configured");
+        return Ok(());
+    };
+
+    let payload = DeletionPayload {
+        crate_name,
+        deleted_at,
+        reason,
+    };
+    send(env, mirror, "deleted", &payload)
+}
+
+/// Sends a signed webhook about a deleted crate that was restored to the configured mirror.
+///
+/// The webhook is sent to the same URL as deletions, with `restored` in the `X-CratesIo-Event`
+/// header instead of `deleted`.
+#[instrument(skip(env))]
+pub fn perform_notify_mirror_of_restoration(
+    env: &Environment,
+    crate_name: &str,
+    restored_at: NaiveDateTime,
+) -> Result<(), PerformError> {
+    let Some(mirror) = env.mirror() else {
+        info!("Skipping mirror notification, since no mirror is configured");
+        return Ok(());
+    };
+
+    let payload = RestorationPayload {
+        crate_name,
+        restored_at,
+    };
+    send(env, mirror, "restored", &payload)
+}
+
+fn send(
+    env: &Environment,
+    mirror: &MirrorConfig,
+    event: &str,
+    payload: &impl Serialize,
+) -> Result<(), PerformError> {
+    let body = serde_json::to_vec(payload)?;
+
+    env.http_client()
+        .post(&mirror.deletion_webhook_url)
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(EVENT_HEADER, event)
+        .header(SIGNATURE_HEADER, signature(&mirror.webhook_secret, &body))
+        .body(body)
+        .send()?
+        .error_for_status()?;
+
+    Ok(())
+}
+
+pub fn notify_mirror_of_deletion(
+    crate_name: String,
+    deleted_at: NaiveDateTime,
+    reason: String,
+) -> Job {
+    Job::NotifyMirrorOfDeletion(NotifyMirrorOfDeletionJob {
+        crate_name,
+        deleted_at,
+        reason,
+    })
+}
+
+pub fn notify_mirror_of_restoration(crate_name: String, restored_at: NaiveDateTime) -> Job {
+    Job::NotifyMirrorOfRestoration(NotifyMirrorOfRestorationJob {
+        crate_name,
+        restored_at,
+    })
+}
@@ -5,22 +5,62 @@
 
 pub mod cloudfront;
 mod daily_db_maintenance;
+mod deleted_crates;
+mod download_totals;
 pub mod dump_db;
+mod emails;
+mod feeds;
 mod git;
+mod index_consistency;
+mod invitations;
+mod mirror;
 mod readmes;
+mod storage;
+mod tokens;
 mod update_downloads;
+pub mod webhooks;
 
 pub use daily_db_maintenance::daily_db_maintenance;
+pub use deleted_crates::{anonymize_crate_deletions, purge_deleted_crates};
+pub use download_totals::verify_download_totals;
 pub use dump_db::dump_db;
-pub use git::{add_crate, normalize_index, squash_index, sync_yanked};
+pub use emails::send_ownership_transfer_emails;
+pub use feeds::{
+    sync_category_feed, sync_category_feeds_of_crate, sync_crates_feeds, sync_user_feed,
+};
+pub use git::{
+    add_crate, normalize_index, remove_crate_from_index, squash_index, sync_to_git_index,
+    sync_yanked, update_crate_index,
+};
+pub use index_consistency::verify_index_consistency;
+pub use invitations::purge_expired_invitations;
+pub use mirror::{notify_mirror_of_deletion, notify_mirror_of_restoration};
 pub use readmes::render_and_upload_readme;
+pub use storage::{delete_version_from_storage, sweep_orphaned_storage};
+pub use tokens::prune_expired_tokens;
 pub use update_downloads::update_downloads;
+pub use webhooks::dispatch_crate_webhook;
 
 pub(crate) use daily_db_maintenance::perform_daily_db_maintenance;
+pub(crate) use deleted_crates::{perform_anonymize_crate_deletions, perform_purge_deleted_crates};
+pub(crate) use download_totals::perform_verify_download_totals;
 pub(crate) use dump_db::perform_dump_db;
+pub(crate) use emails::perform_send_ownership_transfer_emails;
+pub(crate) use feeds::{
+    perform_sync_category_feed, perform_sync_crates_feeds, perform_sync_user_feed,
+};
 pub(crate) use git::{
-    perform_index_add_crate, perform_index_squash, perform_index_sync_to_http,
-    perform_index_update_yanked, perform_normalize_index,
+    perform_index_add_crate, perform_index_remove_crate, perform_index_squash,
+    perform_index_sync_to_git, perform_index_sync_to_http, perform_index_update_yanked,
+    perform_normalize_index,
+};
+pub(crate) use index_consistency::perform_verify_index_consistency;
+pub(crate) use invitations::perform_purge_expired_invitations;
+pub(crate) use mirror::{
+    perform_notify_mirror_of_deletion, perform_notify_mirror_of_restoration,
 };
 pub(crate) use readmes::perform_render_and_upload_readme;
+pub(crate) use storage::{perform_delete_version_from_storage, perform_sweep_orphaned_storage};
+pub(crate) use tokens::perform_prune_expired_tokens;
 pub(crate) use update_downloads::perform_update_downloads;
+pub(crate) use webhooks::perform_dispatch_crate_webhook;
@@ -0,0 +1,122 @@
+//! Remove uploaded files from storage.
+
+use crate::background_jobs::{
+    DeleteVersionFromStorageJob, Environment, Job, SweepOrphanedStorageJob,
+};
+use crate::schema::{crates, versions};
+use crate::swirl::PerformError;
+use crate::uploaders::{UploadBucket, Uploader};
+use chrono::Utc;
+use diesel::prelude::*;
+use std::collections::HashSet;
+
+/// The prefixes of the files in storage that belong to a single version of a crate.
+const VERSION_FILE_PREFIXES: [&str; 2] = ["crates/", "readmes/"];
+
+/// Deletes the `.crate` file and rendered README of a single crate version, leaving the files of
+/// all other versions intact.
+///
+/// Files that have already been removed are skipped, so the job can safely be run repeatedly.
+#[instrument(skip(env))]
+pub fn perform_delete_version_from_storage(
+    env: &Environment,
+    crate_name: &str,
+    version: &str,
+) -> Result<(), PerformError> {
+    info!("Deleting crate file and readme from storage");
+
+    env.uploader
+        .delete_crate(env.http_client(), crate_name, version)?;
+    env.uploader
+        .delete_readme(env.http_client(), crate_name, version)?;
+
+    Ok(())
+}
+
+pub fn delete_version_from_storage(crate_name: String, version: String) -> Job {
+    Job::DeleteVersionFromStorage(DeleteVersionFromStorageJob {
+        crate_name,
+        version,
+    })
+}
+
+/// Removes `.crate` files and rendered READMEs from storage whose version doesn't exist in the
+/// database, e.g. because a deletion failed halfway through.
+///
+/// Files that were modified less than `min_age_hours` ago are kept, since their version might be
+/// published right now and not be committed to the database yet. With `dry_run`, the orphaned
+/// files are only logged.
+#[instrument(skip(env, conn))]
+pub fn perform_sweep_orphaned_storage(
+    env: &Environment,
+    conn: &mut PgConnection,
+    args: &SweepOrphanedStorageJob,
+) -> Result<(), PerformError> {
+    let cutoff = Utc::now().naive_utc() - chrono::Duration::hours(args.min_age_hours);
+    let mut orphaned = 0;
+
+    for prefix in VERSION_FILE_PREFIXES {
+        let mut continuation_token = None;
+        loop {
+            let (files, next_token) = env.uploader.list_files(
+                env.http_client(),
+                prefix,
+                continuation_token.as_deref(),
+            )?;
+
+            let names = files
+                .iter()
+                .filter_map(|file| Uploader::parse_version_file_path(&file.path))
+                .map(|(name, _)| name)
+                .collect::<HashSet<_>>();
+            let existing: HashSet<(String, String)> = versions::table
+                .inner_join(crates::table)
+                .filter(crates::name.eq_any(names))
+                .select((crates::name, versions::num))
+                .load::<(String, String)>(conn)?
+                .into_iter()
+                .collect();
+
+            for file in files {
+                let Some((name, version)) = Uploader::parse_version_file_path(&file.path) else {
+                    warn!(path = file.path, "Skipping file with an unexpected path");
+                    continue;
+                };
+                if file.last_modified > cutoff
+                    || existing.contains(&(name.to_string(), version.to_string()))
+                {
+                    continue;
+                }
+
+                orphaned += 1;
+                info!(
+                    path = file.path,
+                    dry_run = args.dry_run,
+                    "Found orphaned file"
+                );
+                if !args.dry_run {
+                    env.uploader
+                        .delete(env.http_client(), &file.path, UploadBucket::Default)?;
+                }
+            }
+
+            continuation_token = next_token;
+            if continuation_token.is_none() {
+                break;
+            }
+        }
+    }
+
+    let removed = if args.dry_run { 0 } else { orphaned };
+    info!(orphaned, removed, "Swept orphaned files from storage");
+
+    Ok(())
+}
+
+/// Removes orphaned files from storage, see `perform_sweep_orphaned_storage` for the details.
+pub fn sweep_orphaned_storage(min_age_hours: i64, dry_run: bool) -> Job {
+    Job::SweepOrphanedStorage(SweepOrphanedStorageJob {
+        min_age_hours,
+        dry_run,
+    })
+}
@@ -0,0 +1,34 @@
+//! Clean up API tokens that can't be used anymore.
+
+use crate::background_jobs::Job;
+use crate::schema::{api_tokens, version_owner_actions};
+use crate::swirl::PerformError;
+use diesel::dsl::{exists, not, now, IntervalDsl};
+use diesel::prelude::*;
+
+/// How long expired tokens are kept around, so that users can still see them in their token list
+/// for a while after they expired.
+const RETENTION_DAYS: i32 = 30;
+
+/// Deletes API tokens that expired more than `RETENTION_DAYS` ago.
+///
+/// Tokens that were used to publish or yank a version are kept, since they are still referenced
+/// by the `version_owner_actions` table.
+pub fn perform_prune_expired_tokens(conn: &mut PgConnection) -> Result<(), PerformError> {
+    info!("Pruning expired API tokens");
+
+    let used_by_owner_action = version_owner_actions::table
+        .filter(version_owner_actions::api_token_id.eq(api_tokens::id.nullable()));
+
+    let deleted = diesel::delete(api_tokens::table)
+        .filter(api_tokens::expired_at.lt((now - RETENTION_DAYS.days()).nullable()))
+        .filter(not(exists(used_by_owner_action)))
+        .execute(conn)?;
+
+    info!(deleted, "Finished pruning expired API tokens");
+    Ok(())
+}
+
+pub fn prune_expired_tokens() -> Job {
+    Job::PruneExpiredTokens
+}
@@ -114,6 +114,7 @@ mod test {
             user_id,
             "0000000000000000000000000000000000000000000000000000000000000000".to_string(),
             None,
+            None,
         )
         .unwrap();
         let version = version.save(conn, "someone@example.com").unwrap();
@@ -0,0 +1,97 @@
+//! Send webhooks about events of crates to the URLs that their owners registered.
+
+use diesel::prelude::*;
+use http::header;
+use ring::hmac;
+
+use crate::background_jobs::{DispatchCrateWebhookJob, Environment, Job};
+use crate::models::{CrateWebhook, WebhookEvent};
+use crate::schema::crate_webhooks;
+use crate::swirl::PerformError;
+use crate::util::public_url;
+use url::Url;
+
+/// The header containing the signature of a webhook.
+///
+/// Its value is `sha256=` followed by the hex encoded HMAC-SHA256 of the request body, using
+/// the secret of the webhook as the key.
+pub const SIGNATURE_HEADER: &str = "X-Crates-Io-Signature";
+
+/// The header containing the name of the event that a crate webhook is about.
+pub const EVENT_HEADER: &str = "X-CratesIo-Event";
+
+pub(super) fn signature(secret: &str, body: &[u8]) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    let tag = hmac::sign(&key, body);
+    format!("sha256={}", hex::encode(tag))
+}
+
+/// Sends the payload of a crate event to a webhook.
+///
+/// Webhooks that were removed in the meantime, e.g. because the crate was deleted permanently,
+/// are skipped, and so are webhooks whose hostname resolves to a private address. The request
+/// only connects to the addresses that were checked, and redirects to other hosts aren't
+/// followed. Error responses fail the job, so that it is retried later.
+#[instrument(skip(env, conn, payload))]
+pub fn perform_dispatch_crate_webhook(
+    env: &Environment,
+    conn: &mut PgConnection,
+    webhook_id: i32,
+    event: WebhookEvent,
+    payload: &serde_json::Value,
+) -> Result<(), PerformError> {
+    let Some(webhook) = crate_webhooks::table
+        .find(webhook_id)
+        .first::<CrateWebhook>(conn)
+        .optional()? else {
+        info!("Skipping webhook that doesn't exist anymore");
+        return Ok(());
+    };
+
+    let url = Url::parse(&webhook.url)?;
+    let Some(addrs) = public_url::public_addrs(&url)? else {
+        warn!(
+            url = webhook.url,
+            "Skipping webhook that doesn't point to a public host"
+        );
+        return Ok(());
+    };
+
+    let body = serde_json::to_vec(payload)?;
+
+    env.webhook_client(&url, &addrs)?
+        .post(url)
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(EVENT_HEADER, event.as_str())
+        .header(SIGNATURE_HEADER, signature(&webhook.secret, &body))
+        .body(body)
+        .send()?
+        .error_for_status()?;
+
+    Ok(())
+}
+
+pub fn dispatch_crate_webhook(
+    webhook_id: i32,
+    event: WebhookEvent,
+    payload: serde_json::Value,
+) -> Job {
+    Job::DispatchCrateWebhook(DispatchCrateWebhookJob {
+        webhook_id,
+        event,
+        payload,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signature_is_hmac_sha256_of_the_body() {
+        assert_eq!(
+            signature("secret", br#"{"crate":"foo"}"#),
+            "sha256=ee90c6142ac7d160701b28634a724f500ba27ba79a0823dd6a471e7a4e6f607a"
+        );
+    }
+}